@@ -2,6 +2,7 @@
 
 use chrono::Utc;
 use failure::Fallible;
+use std::cmp;
 use std::convert::AsRef;
 use std::fmt;
 use std::hash;
@@ -11,12 +12,12 @@ use iota_streams_core::{
     sponge::prp::PRP,
     tbits::{
         trinary,
-        word::{BasicTbitWord, SpongosTbitWord, StringTbitWord},
+        word::{BasicTbitWord, PackedTbitWord, SpongosTbitWord, StringTbitWord},
         Tbits,
     },
 };
 use iota_streams_core_mss::signature::mss;
-use iota_streams_protobuf3::{command::*, io, types::*};
+use iota_streams_protobuf3::{command::*, error::Protobuf3Error, io, types::*};
 
 use crate::message::*;
 
@@ -84,6 +85,27 @@ where
 }
 impl<TW> Eq for TangleAddress<TW> where TW: BasicTbitWord {}
 
+/// Addresses are ordered by application instance first and then by message identifier.
+impl<TW> PartialOrd for TangleAddress<TW>
+where
+    TW: PackedTbitWord,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<TW> Ord for TangleAddress<TW>
+where
+    TW: PackedTbitWord,
+{
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.appinst
+            .cmp(&other.appinst)
+            .then_with(|| self.msgid.cmp(&other.msgid))
+    }
+}
+
 impl<TW> TangleAddress<TW> {
     pub fn new(appinst: AppInst<TW>, msgid: MsgId<TW>) -> Self {
         Self { appinst, msgid }
@@ -241,6 +263,25 @@ where
 }
 impl<TW> Eq for AppInst<TW> where TW: BasicTbitWord {}
 
+/// Ordered as the identifier tbits, see `Ord for Tbits`.
+impl<TW> PartialOrd for AppInst<TW>
+where
+    TW: PackedTbitWord,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<TW> Ord for AppInst<TW>
+where
+    TW: PackedTbitWord,
+{
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
 impl<TW> AppInst<TW> {
     pub fn tbits(&self) -> &Tbits<TW> {
         &self.id.0
@@ -291,7 +332,10 @@ where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn sizeof_absorb_external(&self, ctx: &mut sizeof::Context<TW, F>) -> Fallible<()> {
+    fn sizeof_absorb_external(
+        &self,
+        ctx: &mut sizeof::Context<TW, F>,
+    ) -> Result<(), Protobuf3Error> {
         ctx.absorb(External(&self.appinst.id))?
             .absorb(External(&self.msgid.id))?;
         Ok(())
@@ -299,7 +343,7 @@ where
     fn wrap_absorb_external<OS: io::OStream<TW>>(
         &self,
         ctx: &mut wrap::Context<TW, F, OS>,
-    ) -> Fallible<()> {
+    ) -> Result<(), Protobuf3Error> {
         ctx.absorb(External(&self.appinst.id))?
             .absorb(External(&self.msgid.id))?;
         Ok(())
@@ -307,7 +351,7 @@ where
     fn unwrap_absorb_external<IS: io::IStream<TW>>(
         &self,
         ctx: &mut unwrap::Context<TW, F, IS>,
-    ) -> Fallible<()> {
+    ) -> Result<(), Protobuf3Error> {
         ctx.absorb(External(&self.appinst.id))?
             .absorb(External(&self.msgid.id))?;
         Ok(())
@@ -343,6 +387,25 @@ where
 }
 impl<TW> Eq for MsgId<TW> where TW: BasicTbitWord {}
 
+/// Ordered as the identifier tbits, see `Ord for Tbits`.
+impl<TW> PartialOrd for MsgId<TW>
+where
+    TW: PackedTbitWord,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<TW> Ord for MsgId<TW>
+where
+    TW: PackedTbitWord,
+{
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
 impl<TW> MsgId<TW> {
     pub fn tbits(&self) -> &Tbits<TW> {
         &self.id.0
@@ -391,22 +454,55 @@ impl<TW, F> SkipFallback<TW, F> for MsgId<TW>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
-    fn sizeof_skip(&self, ctx: &mut sizeof::Context<TW, F>) -> Fallible<()> {
+    fn sizeof_skip(&self, ctx: &mut sizeof::Context<TW, F>) -> Result<(), Protobuf3Error> {
         ctx.skip(&self.id)?;
         Ok(())
     }
-    fn wrap_skip<OS: io::OStream<TW>>(&self, ctx: &mut wrap::Context<TW, F, OS>) -> Fallible<()> {
+    fn wrap_skip<OS: io::OStream<TW>>(
+        &self,
+        ctx: &mut wrap::Context<TW, F, OS>,
+    ) -> Result<(), Protobuf3Error> {
         ctx.skip(&self.id)?;
         Ok(())
     }
     fn unwrap_skip<IS: io::IStream<TW>>(
         &mut self,
         ctx: &mut unwrap::Context<TW, F, IS>,
-    ) -> Fallible<()> {
+    ) -> Result<(), Protobuf3Error> {
         ctx.skip(&mut self.id)?;
         Ok(())
     }
 }
 
+#[cfg(test)]
+#[test]
+fn test_address_ord() {
+    use iota_streams_core::tbits::trinary::Trit;
+    use std::collections::{BTreeSet, HashSet};
+
+    let addr = |a: usize, m: usize| {
+        let mut appinst = AppInst::<Trit>::default();
+        appinst.id.0.slice_mut().put_usize(a);
+        let mut msgid = MsgId::<Trit>::default();
+        msgid.id.0.slice_mut().put_usize(m);
+        TangleAddress::new(appinst, msgid)
+    };
+    let addrs: Vec<_> = (0..200).map(|i| addr(i % 3, i * 7 % 50)).collect();
+
+    let btree: BTreeSet<_> = addrs.iter().cloned().collect();
+    let hash: HashSet<_> = addrs.iter().cloned().collect();
+    assert_eq!(150, btree.len());
+    assert_eq!(btree.len(), hash.len());
+    assert!(btree.iter().all(|a| hash.contains(a)));
+
+    let sorted: Vec<_> = btree.into_iter().collect();
+    assert!(sorted.windows(2).all(|w| w[0] < w[1]));
+    for a in addrs.iter() {
+        for b in addrs.iter().take(20) {
+            assert_eq!(a == b, a.cmp(b) == cmp::Ordering::Equal);
+        }
+    }
+}
+
 //#[cfg(feature = "tangle")]
 pub mod client;
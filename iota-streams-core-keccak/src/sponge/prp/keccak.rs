@@ -1,4 +1,4 @@
-use iota_streams_core::sponge::prp::{inner, Mode, PRP};
+use iota_streams_core::sponge::prp::{inner, Mode, PrpState, PRP};
 use iota_streams_core::tbits::{
     binary::Byte, convert::*, word::BasicTbitWord, TbitSlice, TbitSliceMut, Tbits,
 };
@@ -38,6 +38,14 @@ impl KeccakF1600T {
     }
 }
 
+/// Volatile write is not optimized away.
+fn keccakf1600_wipe(state: &mut [u64; 25]) {
+    unsafe {
+        std::ptr::write_volatile(state, [0u64; 25]);
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
 fn keccakf1600_transform<TW>(state: &mut [u64; 25], outer: &mut TbitSliceMut<TW>)
 where
     TW: BasicTbitWord + ConvertIso<Byte>,
@@ -56,6 +64,29 @@ where
     }
 }
 
+/// Keccak state is serialized as 25 little-endian lanes.
+const STATE_BYTES: usize = 25 * 8;
+
+fn keccakf1600_to_bytes(state: &[u64; 25], bytes: &mut [u8]) {
+    assert_eq!(STATE_BYTES, bytes.len());
+    for (lane, chunk) in state.iter().zip(bytes.chunks_mut(8)) {
+        chunk.copy_from_slice(&lane.to_le_bytes());
+    }
+}
+
+fn keccakf1600_from_bytes(bytes: &[u8]) -> Option<[u64; 25]> {
+    if bytes.len() != STATE_BYTES {
+        return None;
+    }
+    let mut state = [0_u64; 25];
+    for (lane, chunk) in state.iter_mut().zip(bytes.chunks(8)) {
+        let mut le = [0_u8; 8];
+        le.copy_from_slice(chunk);
+        *lane = u64::from_le_bytes(le);
+    }
+    Some(state)
+}
+
 fn inner_into_keccakf1600(state: &mut [u64; 25], inner: TbitSlice<Byte>) {
     unsafe {
         let state_bytes: &mut [Byte; 25 * 8] = std::mem::transmute(&mut *state);
@@ -129,9 +160,29 @@ where
         keccakf1600_transform(&mut self.state, outer);
     }
 
+    fn wipe(&mut self) {
+        keccakf1600_wipe(&mut self.state);
+    }
+
     type Inner = inner::Inner<Byte, KeccakF1600B>;
 }
 
+impl<TW> PrpState<TW> for KeccakF1600B
+where
+    TW: BasicTbitWord + ConvertIso<Byte>,
+    Byte: ConvertOnto<TW>,
+{
+    const STATE_BYTES: usize = STATE_BYTES;
+
+    fn to_state_bytes(&self, bytes: &mut [u8]) {
+        keccakf1600_to_bytes(&self.state, bytes);
+    }
+
+    fn from_state_bytes(bytes: &[u8]) -> Option<Self> {
+        keccakf1600_from_bytes(bytes).map(|state| Self { state })
+    }
+}
+
 impl<TW> PRP<TW> for KeccakF1600T
 where
     TW: BasicTbitWord + ConvertIso<Byte>,
@@ -141,15 +192,38 @@ where
 
     const CAPACITY: usize = 243;
 
+    /// Trits that fit into 1600-bit Keccak state.
+    const WIDTH: usize = log3e2(1600) as usize - 1;
+
     const MODE: Mode = Mode::XOR;
 
     fn transform(&mut self, outer: &mut TbitSliceMut<TW>) {
         keccakf1600_transform(&mut self.state, outer);
     }
 
+    fn wipe(&mut self) {
+        keccakf1600_wipe(&mut self.state);
+    }
+
     type Inner = inner::Inner<Byte, KeccakF1600T>;
 }
 
+impl<TW> PrpState<TW> for KeccakF1600T
+where
+    TW: BasicTbitWord + ConvertIso<Byte>,
+    Byte: ConvertOnto<TW>,
+{
+    const STATE_BYTES: usize = STATE_BYTES;
+
+    fn to_state_bytes(&self, bytes: &mut [u8]) {
+        keccakf1600_to_bytes(&self.state, bytes);
+    }
+
+    fn from_state_bytes(bytes: &[u8]) -> Option<Self> {
+        keccakf1600_from_bytes(bytes).map(|state| Self { state })
+    }
+}
+
 #[test]
 fn test_keccakf1600t_transform() {
     use iota_streams_core::tbits::{trinary::Trit, Tbits};
@@ -157,3 +231,27 @@ fn test_keccakf1600t_transform() {
     let mut outer = Tbits::<Trit>::zero(<KeccakF1600T as PRP<Trit>>::RATE);
     s.transform(&mut outer.slice_mut());
 }
+
+#[test]
+fn test_keccakf1600_rate_capacity() {
+    use iota_streams_core::sponge::tests::rate_capacity;
+    use iota_streams_core::tbits::{binary::Byte, trinary::Trit};
+    rate_capacity::<Byte, KeccakF1600B>();
+    rate_capacity::<Trit, KeccakF1600T>();
+}
+
+#[test]
+fn test_keccakf1600_squeeze_bytes() {
+    use iota_streams_core::sponge::tests::squeeze_bytes_reproducible;
+    use iota_streams_core::tbits::{binary::Byte, trinary::Trit};
+    squeeze_bytes_reproducible::<Byte, KeccakF1600B>();
+    squeeze_bytes_reproducible::<Trit, KeccakF1600T>();
+}
+
+#[test]
+fn test_keccakf1600_to_from_bytes() {
+    use iota_streams_core::sponge::tests::to_from_bytes;
+    use iota_streams_core::tbits::{binary::Byte, trinary::Trit};
+    to_from_bytes::<Byte, KeccakF1600B>();
+    to_from_bytes::<Trit, KeccakF1600T>();
+}
@@ -42,3 +42,15 @@ fn encrypt_decrypt_keccak_trit() {
     encrypt_decrypt_n::<Trit, KeccakF1600T>(RATE + 28);
     encrypt_decrypt_n::<Trit, KeccakF1600T>(2 * RATE);
 }
+
+#[test]
+fn wipe_secrets_keccak() {
+    wipe_secrets::<Byte, KeccakF1600B>();
+    wipe_secrets::<Trit, KeccakF1600T>();
+}
+
+#[test]
+fn clone_restore_keccak() {
+    clone_restore::<Byte, KeccakF1600B>();
+    clone_restore::<Trit, KeccakF1600T>();
+}
@@ -98,6 +98,20 @@ where
     _phantom: std::marker::PhantomData<P>,
 }
 
+/// PRNG secret keys and nonces are compared in constant time.
+impl<TW, P> PartialEq for SK<TW, P>
+where
+    TW: SpongosTbitWord,
+    P: Parameters<TW>,
+{
+    fn eq(&self, other: &Self) -> bool {
+        // Non-short-circuit `&` so that nonces are compared even if PRNG secrets differ.
+        (self.prng == other.prng)
+            & (self.nonce.size() == other.nonce.size()
+                && self.nonce.slice().equals_ct(&other.nonce.slice()))
+    }
+}
+
 impl<TW, P> SK<TW, P>
 where
     TW: SpongosTbitWord + IntTbitWord,
@@ -148,6 +162,28 @@ where
 //#[cfg(mss_merkle_tree_traversal)]
 //pub type PrivateKey = PrivateKeyMTTraversal;
 
+/// Private keys intentionally avoid early-exit comparison: secret parts (PRNG secret key
+/// and nonce) are compared in constant time, see `TbitSlice::equals_ct`.
+/// Merkle tree is determined by the secret parts and tree height, only its public
+/// height and the current WOTS key number are compared.
+impl<TW, P> PartialEq for PrivateKey<TW, P>
+where
+    TW: SpongosTbitWord,
+    P: Parameters<TW>,
+{
+    fn eq(&self, other: &Self) -> bool {
+        (self.sk == other.sk)
+            & (self.mt.height() == other.mt.height())
+            & (self.mt.skn() == other.mt.skn())
+    }
+}
+impl<TW, P> Eq for PrivateKey<TW, P>
+where
+    TW: SpongosTbitWord,
+    P: Parameters<TW>,
+{
+}
+
 impl<TW, P> PrivateKey<TW, P>
 where
     TW: SpongosTbitWord + IntTbitWord,
@@ -363,7 +399,7 @@ where
     assert!(t.size() == P::SKN_SIZE);
     let height = t.advance(P::SKN_TREE_HEIGHT_SIZE).get_usize();
     let skn = t.advance(P::SKN_KEY_NUMBER_SIZE).get_usize();
-    if skn < max_idx(height) {
+    if height <= P::MAX_D && skn < max_idx(height) {
         Some((height, skn))
     } else {
         None
@@ -31,3 +31,30 @@ where
         }
     }
 }
+
+/// Private keys are compared in constant time (reviewers: see `PartialEq for PrivateKey`),
+/// check the comparison is still correct.
+pub fn private_key_eq<TW, P>()
+where
+    TW: SpongosTbitWord + IntTbitWord,
+    P: Parameters<TW>,
+{
+    let k = Tbits::zero(Prng::<TW, P::PrngG>::KEY_SIZE);
+    let prng = Prng::<TW, P::PrngG>::init(k);
+    let n = Tbits::<TW>::zero(33);
+    let mut n2 = Tbits::<TW>::zero(33);
+    n2.slice_mut().put_usize(1);
+
+    let mut sk = PrivateKey::<TW, P>::gen(&prng, n.slice(), 1);
+    assert!(sk == PrivateKey::<TW, P>::gen(&prng, n.slice(), 1));
+    assert!(sk != PrivateKey::<TW, P>::gen(&prng, n2.slice(), 1));
+    assert!(sk != PrivateKey::<TW, P>::gen(&prng, n.slice(), 2));
+
+    let mut k2 = Tbits::zero(Prng::<TW, P::PrngG>::KEY_SIZE);
+    k2.slice_mut().put_usize(1);
+    let prng2 = Prng::<TW, P::PrngG>::init(k2);
+    assert!(sk != PrivateKey::<TW, P>::gen(&prng2, n.slice(), 1));
+
+    sk.next();
+    assert!(sk != PrivateKey::<TW, P>::gen(&prng, n.slice(), 1));
+}
@@ -68,3 +68,9 @@ fn sign_verify_d2_mttraversal() {
 }
 /*
  */
+
+#[test]
+fn private_key_eq_mtcomplete() {
+    use iota_streams_core::tbits::trinary::Trit;
+    super::tests::private_key_eq::<Trit, ParametersMtComplete<Trit>>();
+}
@@ -175,6 +175,18 @@ pub struct PublicKey<TW, P> {
     _phantom: std::marker::PhantomData<P>,
 }
 
+/// WOTS private keys intentionally avoid early-exit comparison: `Tbits::eq` stops at
+/// the first differing word and would leak its position through timing.
+impl<TW, P> PartialEq for PrivateKey<TW, P>
+where
+    TW: SpongosTbitWord,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.sk.slice().equals_ct(&other.sk.slice())
+    }
+}
+impl<TW, P> Eq for PrivateKey<TW, P> where TW: SpongosTbitWord {}
+
 impl<TW, P> PrivateKey<TW, P>
 where
     TW: SpongosTbitWord,
@@ -186,7 +198,7 @@ where
         G: PRP<TW> + Default,
     {
         let mut sk = Self {
-            sk: Tbits::<TW>::zero(P::PRIVATE_KEY_SIZE),
+            sk: Tbits::<TW>::zero(P::PRIVATE_KEY_SIZE).into_secret(),
             _phantom: std::marker::PhantomData,
         };
         gen_sk(prng, nonces, sk.sk.slice_mut());
@@ -3,7 +3,7 @@ use std::collections::HashSet;
 use std::fmt;
 use std::hash;
 
-use iota_streams_core::prng::Prng;
+use iota_streams_core::prng::{Prng, TbitsRng};
 use iota_streams_core::sponge::{prp::PRP, spongos::Spongos};
 use iota_streams_core::tbits::{
     trinary::TritWord,
@@ -253,6 +253,29 @@ pub fn encrypt_with_pk<TW, F, G>(
     encrypt_with_randomness_mut(s, h, key, encapsulated_key);
 }
 
+/// Encrypt secret key `key` with NTRU public polynomial `h = NTT(pk)` using randomness drawn
+/// from `rng` and spongos instance `s`. Put encrypted key into `encapsulated_key`.
+pub fn encrypt_with_rng<TW, F, R>(
+    s: &mut Spongos<TW, F>,
+    rng: &mut R,
+    h: &Poly,
+    key: TbitSlice<TW>,
+    encapsulated_key: TbitSliceMut<TW>,
+) where
+    TW: TritWord + SpongosTbitWord,
+    F: PRP<TW>,
+    R: TbitsRng<TW> + ?Sized,
+{
+    debug_assert!(key.size() < EKEY_SIZE);
+    debug_assert_eq!(EKEY_SIZE, encapsulated_key.size());
+
+    // Reuse `encapsulated_key` slice for randomness: put it into the first `SK_SIZE` trits.
+    unsafe {
+        rng.gen_tbits(encapsulated_key.clone().take(SK_SIZE));
+    }
+    encrypt_with_randomness_mut(s, h, key, encapsulated_key);
+}
+
 /// Create a public key polynomial `h = NTT(pk)` from tbits `pk` and check it (for invertibility).
 fn pk_from_trits<TW>(pk: TbitSlice<TW>) -> Option<Poly>
 where
@@ -332,6 +355,17 @@ where
     decrypt_with_fo_transform(f, y, fo)
 }
 
+/// Check that encapsulated key `y` of `EKEY_SIZE` trits encodes a polynomial, ie. all
+/// 9-trit coefficients are in range. Decapsulation of a malformed `y` fails with any
+/// private key, while a well-formed `y` only fails with a key it's not encapsulated with.
+pub fn is_valid_ekey<TW>(y: TbitSlice<TW>) -> bool
+where
+    TW: TritWord,
+{
+    debug_assert_eq!(EKEY_SIZE, y.size());
+    Poly::new().from_trits(y)
+}
+
 /// Try to decrypt encapsulated key `y` with private key `sk` using spongos instance `s`.
 /// In case of success `k` contains decrypted secret key.
 pub fn decrypt_with_sk<TW, F>(
@@ -369,6 +403,26 @@ pub struct PrivateKey<TW, F> {
     _phantom: std::marker::PhantomData<F>,
 }
 
+/// Secret trits `sk` wipe themselves, the polynomial `f` is wiped here.
+impl<TW, F> Drop for PrivateKey<TW, F> {
+    fn drop(&mut self) {
+        self.f.wipe();
+    }
+}
+
+/// Private keys intentionally avoid early-exit comparison: secret trits are compared
+/// in constant time, see `TbitSlice::equals_ct`. Polynomial `f` is derived from `sk`
+/// and is not compared.
+impl<TW, F> PartialEq for PrivateKey<TW, F>
+where
+    TW: SpongosTbitWord,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.sk.slice().equals_ct(&other.sk.slice())
+    }
+}
+impl<TW, F> Eq for PrivateKey<TW, F> where TW: SpongosTbitWord {}
+
 /// Public key object, contains trinary representation `pk` of public polynomial
 /// as well as it's NTT form in `h`.
 #[derive(Clone)]
@@ -506,7 +560,7 @@ where
     G: PRP<TW> + Default,
 {
     let mut sk = PrivateKey {
-        sk: Tbits::zero(SK_SIZE),
+        sk: Tbits::zero(SK_SIZE).into_secret(),
         f: Poly::new(),
         _phantom: std::marker::PhantomData,
     };
@@ -655,6 +709,21 @@ where
         encrypt_with_pk(s, prng, self.pk.slice(), &self.h, nonce, k, y);
     }
 
+    /// Encapsulate key `k` with randomness drawn from `rng`, public key `self` using spongos
+    /// instance `s` and put "capsule" into `y`.
+    pub fn encrypt_with_rng<R>(
+        &self,
+        s: &mut Spongos<TW, F>,
+        rng: &mut R,
+        k: TbitSlice<TW>,
+        y: TbitSliceMut<TW>,
+    ) where
+        F: PRP<TW>,
+        R: TbitsRng<TW> + ?Sized,
+    {
+        encrypt_with_rng(s, rng, &self.h, k, y);
+    }
+
     /*
     /// Encapsulate key `k` with `prng`, `nonce`, public key `self` using new spongos instance and put "capsule" into `y`.
     pub fn encrypt_with_troika<G>(&self, prng: &Prng<TW, G>, nonce: TbitSlice<TW>, k: TbitSlice<TW>, y: TbitSliceMut<TW>)
@@ -754,6 +823,21 @@ mod tests {
         assert!(k == dek);
     }
 
+    /// Private keys are compared in constant time, see `PartialEq for PrivateKey`.
+    #[test]
+    fn private_key_eq_troika_b1t1() {
+        let prng = Prng::<Trit, Troika>::init(Tbits::zero(prng::Prng::<Trit, Troika>::KEY_SIZE));
+        let nonce = Tbits::<Trit>::zero(15);
+        let mut nonce2 = Tbits::<Trit>::zero(15);
+        nonce2.slice_mut().put_usize(1);
+        let (sk, _) = gen_keypair::<Trit, Troika, Troika>(&prng, nonce.slice());
+        let (sk1, _) = gen_keypair::<Trit, Troika, Troika>(&prng, nonce.slice());
+        let (sk2, _) = gen_keypair::<Trit, Troika, Troika>(&prng, nonce2.slice());
+        assert!(sk == sk1);
+        assert!(sk == sk.clone());
+        assert!(sk != sk2);
+    }
+
     #[test]
     fn encrypt_decrypt_troika_b1t1() {
         encrypt_decrypt_tbits::<Trit, Troika, Troika>();
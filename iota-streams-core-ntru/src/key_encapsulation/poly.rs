@@ -207,6 +207,14 @@ impl Poly {
             coeffs: [COEFF_ZERO; N],
         }
     }
+    /// Set coefficients to zero, eg. of the private key polynomial when it's dropped.
+    pub fn wipe(&mut self) {
+        unsafe {
+            std::ptr::write_volatile(&mut self.coeffs, [COEFF_ZERO; N]);
+        }
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+
     pub fn round_small(&mut self) {
         for i in 0..N {
             let t = Trint9::from(self.coeffs[i]);
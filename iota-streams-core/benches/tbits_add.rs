@@ -0,0 +1,40 @@
+#[macro_use]
+extern crate criterion;
+
+use criterion::{Benchmark, Criterion};
+use iota_streams_core::tbits::{trinary::B1T5, Tbits};
+use std::time::Duration;
+
+const COUNT: usize = 1_000;
+const N: usize = 2430;
+
+fn add_step(x: &Tbits<B1T5>, y: &mut Tbits<B1T5>, d: usize) {
+    for _ in 0..COUNT {
+        y.slice_mut().drop(d).take(N).add(x.slice().take(N));
+    }
+}
+
+fn tbits_add_benchmark(c: &mut Criterion) {
+    let x = Tbits::<B1T5>::zero(N + 5);
+    let mut y = Tbits::<B1T5>::zero(N + 5);
+    let mut z = Tbits::<B1T5>::zero(N + 5);
+    c.bench(
+        "Add tbits",
+        Benchmark::new(" aligned (1e3 x 2430T)", move |b| {
+            b.iter(|| {
+                add_step(&x, &mut y, 0);
+            })
+        })
+        .with_function(" unaligned (1e3 x 2430T)", move |b| {
+            let x = Tbits::<B1T5>::zero(N + 5);
+            b.iter(|| {
+                add_step(&x, &mut z, 1);
+            })
+        })
+        .sample_size(10)
+        .measurement_time(Duration::from_millis(10000)),
+    );
+}
+
+criterion_group!(benches, tbits_add_benchmark);
+criterion_main!(benches);
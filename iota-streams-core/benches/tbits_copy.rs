@@ -0,0 +1,109 @@
+#[macro_use]
+extern crate criterion;
+
+use criterion::{Benchmark, Criterion};
+use iota_streams_core::tbits::{
+    trinary::{Trit, B1T5},
+    Tbits,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+const COUNT: usize = 1_000_000;
+const N: usize = 9;
+
+/// Number of misaligned copies and comparisons per iteration.
+const MISALIGNED_COUNT: usize = 10_000;
+/// Misaligned slices span several scratch buffers of `copy` and `equals`.
+const MISALIGNED_N: usize = 1000;
+
+/// Counts heap allocations to check that misaligned copies don't allocate.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn copy_step(x: &Tbits<B1T5>, y: &mut Tbits<B1T5>) {
+    // Unaligned slices go through the word-to-tbits conversion path.
+    for i in 0..COUNT {
+        let d = 1 + i % 4;
+        x.slice()
+            .drop(d)
+            .take(N)
+            .copy(&y.slice_mut().drop(d).take(N));
+    }
+}
+
+fn tbits_copy_benchmark(c: &mut Criterion) {
+    let x = Tbits::<B1T5>::zero(20);
+    let mut y = Tbits::<B1T5>::zero(20);
+    c.bench(
+        "Copy tbits",
+        Benchmark::new(" (1e6 x 9T)", move |b| {
+            b.iter(|| {
+                copy_step(&x, &mut y);
+            })
+        })
+        .sample_size(10)
+        .measurement_time(Duration::from_millis(10000)),
+    );
+}
+
+fn misaligned_step(x: &Tbits<B1T5>, y: &mut Tbits<B1T5>) -> bool {
+    // Different offsets within words go through the scratch buffer path.
+    let mut eq = true;
+    for i in 0..MISALIGNED_COUNT {
+        let d = i % 4;
+        let xs = x.slice().drop(d).take(MISALIGNED_N);
+        let ys = y.slice_mut().drop(d + 1).take(MISALIGNED_N);
+        xs.copy(&ys);
+        eq &= xs == unsafe { ys.as_const() };
+    }
+    eq
+}
+
+fn tbits_copy_misaligned_benchmark(c: &mut Criterion) {
+    let ts: Vec<_> = (0..MISALIGNED_N + 5)
+        .map(|i| Trit((i * 7 % 3) as u8))
+        .collect();
+    let x = Tbits::<B1T5>::from_tbits(&ts);
+    let mut y = Tbits::<B1T5>::zero(MISALIGNED_N + 5);
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    assert!(misaligned_step(&x, &mut y));
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!(
+        "Misaligned copy and equals: {} allocations per {} calls.",
+        allocations, MISALIGNED_COUNT
+    );
+    assert_eq!(0, allocations);
+
+    c.bench(
+        "Copy tbits misaligned",
+        Benchmark::new(" (1e4 x 1000T)", move |b| {
+            b.iter(|| misaligned_step(&x, &mut y))
+        })
+        .sample_size(10)
+        .measurement_time(Duration::from_millis(10000)),
+    );
+}
+
+criterion_group!(
+    benches,
+    tbits_copy_benchmark,
+    tbits_copy_misaligned_benchmark
+);
+criterion_main!(benches);
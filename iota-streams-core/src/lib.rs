@@ -1,10 +1,18 @@
+//! Without the default `std` feature only the `tbits::word` traits are available,
+//! they only depend on `core`.
+
 //#![feature(trait_alias)]
 //#![feature(const_fn)]
 //#![feature(const_if_match)]
 #![feature(step_trait)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "std")]
 pub mod hash;
+#[cfg(feature = "std")]
 pub mod prng;
+#[cfg(feature = "std")]
 pub mod psk;
+#[cfg(feature = "std")]
 pub mod sponge;
 pub mod tbits;
@@ -40,6 +40,26 @@ where
     random_tbits::<TW, rand::rngs::ThreadRng>(n, &mut rand::thread_rng())
 }
 
+#[test]
+fn test_spongos_rng() {
+    use crate::sponge::prp::troika::Troika;
+    use crate::tbits::trinary::Trit;
+    let seed = Tbits::<Trit>::cycle_str(243, "SEED");
+    let mut r1 = SpongosRng::<Trit, Troika>::from_seed(seed.slice());
+    let mut r2 = SpongosRng::<Trit, Troika>::from_seed(seed.slice());
+    let mut x1 = Tbits::<Trit>::zero(300);
+    let mut x2 = Tbits::<Trit>::zero(300);
+    r1.gen_tbits(x1.slice_mut());
+    r2.gen_tbits(x2.slice_mut());
+    assert!(x1 == x2, "Equally seeded sources differ");
+    r1.gen_tbits(x2.slice_mut());
+    assert!(x1 != x2, "Source state doesn't change");
+
+    let mut r3 = SpongosRng::<Trit, Troika>::from_entropy();
+    r3.gen_tbits(x2.slice_mut());
+    assert!(x1 != x2, "Entropy-seeded source repeats seeded one");
+}
+
 #[test]
 fn test_random_nonce() {
     use crate::tbits::trinary::Trit;
@@ -49,6 +69,17 @@ fn test_random_nonce() {
     }
 }
 
+/// Secret keys are compared in constant time, see `TbitSlice::equals_ct`.
+impl<TW, G> PartialEq for Prng<TW, G>
+where
+    TW: SpongosTbitWord,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.secret_key.slice().equals_ct(&other.secret_key.slice())
+    }
+}
+impl<TW, G> Eq for Prng<TW, G> where TW: SpongosTbitWord {}
+
 impl<TW, G> Prng<TW, G>
 where
     G: PRP<TW>,
@@ -66,7 +97,7 @@ where
     pub fn init(secret_key: Tbits<TW>) -> Self {
         assert!(secret_key.size() == Self::KEY_SIZE);
         Self {
-            secret_key,
+            secret_key: secret_key.into_secret(),
             _phantom: std::marker::PhantomData,
         }
     }
@@ -127,6 +158,58 @@ where
     }
 }
 
+/// Stateful source of random tbits, eg. a hardware RNG or a deterministic generator
+/// making wrapped messages reproducible in tests.
+///
+/// Unlike `Prng` which derives randomness from the secret key and nonces, a source
+/// changes its state on each call and needs no nonces.
+pub trait TbitsRng<TW> {
+    /// Create a source seeded from system entropy.
+    fn from_entropy() -> Self
+    where
+        Self: Sized;
+
+    /// Fill `out` with random tbits.
+    fn gen_tbits(&mut self, out: TbitSliceMut<TW>);
+}
+
+/// Spongos-based `TbitsRng`, the seed is absorbed into Spongos state and each
+/// `gen_tbits` call squeezes the output and commits.
+pub struct SpongosRng<TW, G> {
+    spongos: Spongos<TW, G>,
+}
+
+impl<TW, G> SpongosRng<TW, G>
+where
+    TW: SpongosTbitWord,
+    G: PRP<TW> + Default,
+{
+    /// Create a source deterministically seeded with `seed`.
+    pub fn from_seed(seed: TbitSlice<TW>) -> Self {
+        let mut spongos = Spongos::<TW, G>::init();
+        spongos.absorb(seed);
+        spongos.commit();
+        Self { spongos }
+    }
+}
+
+impl<TW, G> TbitsRng<TW> for SpongosRng<TW, G>
+where
+    TW: SpongosTbitWord,
+    Byte: ConvertOnto<TW>,
+    G: PRP<TW> + Default,
+{
+    fn from_entropy() -> Self {
+        let seed = random_nonce::<TW>(G::CAPACITY).into_secret();
+        Self::from_seed(seed.slice())
+    }
+
+    fn gen_tbits(&mut self, mut out: TbitSliceMut<TW>) {
+        self.spongos.squeeze(&mut out);
+        self.spongos.commit();
+    }
+}
+
 /*
 pub fn init<'a>(secret_key: TbitSlice<'a>) -> PRNG {
     PRNG::init(secret_key)
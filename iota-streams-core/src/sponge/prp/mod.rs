@@ -87,6 +87,9 @@ where
     /// Capacity -- size of inner part of the Spongos state.
     const CAPACITY: usize = 243;
 
+    /// Full Troika state.
+    const WIDTH: usize = troika::STATE_SIZE;
+
     const MODE: Mode = Mode::OVERWRITE;
 
     fn transform(&mut self, outer: &mut TbitSliceMut<TW>) {
@@ -119,5 +122,35 @@ where
         }
     }
 
+    fn wipe(&mut self) {
+        self.wipe_state();
+    }
+
     type Inner = inner::Inner<Trit, Troika>;
 }
+
+impl<TW> PrpState<TW> for Troika
+where
+    TW: TritWord,
+{
+    /// One byte per trit with value `0..=2`.
+    const STATE_BYTES: usize = troika::STATE_SIZE;
+
+    fn to_state_bytes(&self, bytes: &mut [u8]) {
+        assert_eq!(troika::STATE_SIZE, bytes.len());
+        for (idx, b) in bytes.iter_mut().enumerate() {
+            *b = self.get1(idx);
+        }
+    }
+
+    fn from_state_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != troika::STATE_SIZE || bytes.iter().any(|b| 2 < *b) {
+            return None;
+        }
+        let mut troika = Self::new();
+        for (idx, b) in bytes.iter().enumerate() {
+            troika.set1(idx, *b);
+        }
+        Some(troika)
+    }
+}
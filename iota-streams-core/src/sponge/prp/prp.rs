@@ -14,10 +14,28 @@ pub trait PRP<TW>: Sized {
     /// Other sizes (such as sizes of hash/key/nonce/etc.) are derived from the capacity.
     const CAPACITY: usize;
 
+    /// Size of the full permutation state in tbits used by the sponge, `RATE + CAPACITY`.
+    const WIDTH: usize = Self::RATE + Self::CAPACITY;
+
     const MODE: Mode;
 
     /// Inject outer state, transform full state, eject new outer state.
     fn transform(&mut self, outer: &mut TbitSliceMut<TW>);
 
+    /// Set the full state to zero, the writes must not be optimized away.
+    fn wipe(&mut self);
+
     type Inner: Into<Self> + From<Self>;
 }
+
+/// PRP with a serializable full state, required by `Spongos::to_bytes`.
+pub trait PrpState<TW>: PRP<TW> {
+    /// Size of the full state serialized with `to_state_bytes` in bytes.
+    const STATE_BYTES: usize;
+
+    /// Serialize the full state into `bytes` of `STATE_BYTES` size.
+    fn to_state_bytes(&self, bytes: &mut [u8]);
+
+    /// Deserialize the full state, `None` if `bytes` is not a valid serialized state.
+    fn from_state_bytes(bytes: &[u8]) -> Option<Self>;
+}
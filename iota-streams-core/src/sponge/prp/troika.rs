@@ -35,7 +35,7 @@ const COLUMNS: usize = 9;
 const ROWS: usize = 3;
 const SLICES: usize = 27;
 const SLICESIZE: usize = COLUMNS * ROWS;
-const STATE_SIZE: usize = COLUMNS * ROWS * SLICES;
+pub(crate) const STATE_SIZE: usize = COLUMNS * ROWS * SLICES;
 
 #[derive(Clone, Copy)]
 struct T27(pub u32, pub u32);
@@ -403,6 +403,14 @@ impl Troika {
         }
     }
 
+    /// Set the state to zero, volatile write is not optimized away.
+    pub fn wipe_state(&mut self) {
+        unsafe {
+            std::ptr::write_volatile(&mut self.state, [T27::zero(); SLICESIZE]);
+        }
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+
     //#[inline]
     pub fn set1(&mut self, idx: usize, trit: Trit) {
         self.state[idx % SLICESIZE].set(idx / SLICESIZE, trit);
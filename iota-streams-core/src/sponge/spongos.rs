@@ -1,14 +1,64 @@
 //use std::convert::{AsMut, AsRef, From, TryFrom};
 use std::fmt;
 //use std::hash;
+use std::io;
 
-use super::prp::PRP;
+use super::prp::{PrpState, PRP};
 use crate::hash::Hash;
 use crate::tbits::{
-    word::{BasicTbitWord, SpongosTbitWord},
+    encoding,
+    trinary::{mods3_usize, Trint3, TritWord},
+    word::{BasicTbitWord, IntTbitWord, PackedTbitWord, SpongosTbitWord},
     TbitSlice, TbitSliceMut, Tbits,
 };
 
+/// Number of tbits encoding one byte in `Spongos::absorb_bytes`.
+/// 8 tbits are enough to encode a byte value both with bits and trits, so the same
+/// number is used for binary and trinary words. PB3 byte strings are encoded
+/// differently, with 2 trytes (6 trits) per byte.
+pub const BYTE_TBITS: usize = 8;
+
+/// Number of tbits squeezed for one byte in `Spongos::squeeze_bytes`.
+/// The byte is the lowest 8 bits of the tbits taken as an unsigned integer,
+/// 16 trits keep the bias of trinary bytes below 2^-17.
+pub const SQUEEZE_BYTE_TBITS: usize = 16;
+
+/// Size of the buffer used by `Spongos::absorb_reader` to read data.
+const READER_CHUNK_SIZE: usize = 4096;
+
+/// Size of the position header of `Spongos::to_bytes`.
+const POS_BYTES: usize = 8;
+
+/// Error of `Spongos::from_bytes`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum StateError {
+    /// Serialized state must be `expected` bytes long.
+    Length { expected: usize, actual: usize },
+    /// Position in the outer state must be less than the rate.
+    Position(u64),
+    /// Outer state words are not valid.
+    Outer,
+    /// Permutation state is not valid.
+    Permutation,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::Length { expected, actual } => write!(
+                f,
+                "serialized Spongos state is {} bytes long, expected {}",
+                actual, expected
+            ),
+            StateError::Position(pos) => write!(f, "outer state position {} is out of rate", pos),
+            StateError::Outer => write!(f, "outer state is not valid"),
+            StateError::Permutation => write!(f, "permutation state is not valid"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
 /// Implemented as a separate from `Spongos` struct in order to deal with life-times.
 #[derive(Clone)]
 pub struct Outer<TW> {
@@ -65,18 +115,30 @@ where
     }
 }
 
+/// Duplex mode selecting between `*_overwrite` and `*_xor` methods of `SpongosTbitWord`.
+///
+/// The default mode of a Spongos object is `PRP::MODE`, see `Spongos::set_mode`.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Mode {
+    /// Absorbed tbits overwrite the outer state.
     OVERWRITE,
+    /// Absorbed tbits are added to the outer state (duplex-XOR).
     XOR,
 }
 
 #[derive(Clone)]
 pub struct Spongos<TW, F> {
     /// Spongos transform.
-    s: F,
+    pub(crate) s: F,
     /// Outer state.
     outer: Outer<TW>,
+    /// Duplex mode, `F::MODE` by default.
+    mode: Mode,
+    /// Number of transforms since initialization or `wipe`, see `permutation_count`.
+    #[cfg(feature = "metrics")]
+    permutations: u64,
+    /// Wipe the state on drop, `Drop` can't have `SpongosTbitWord` and `PRP` bounds.
+    wipe: fn(&mut Self),
 }
 
 impl<TW, F> Spongos<TW, F>
@@ -91,6 +153,15 @@ where
 
     /// Sponge fixed MAC size.
     pub const MAC_SIZE: usize = F::CAPACITY;
+
+    /// Sponge rate, ie. the size of the outer state block.
+    pub const RATE: usize = F::RATE;
+
+    /// Sponge capacity, ie. the size of the inner state never exposed by squeeze.
+    pub const CAPACITY: usize = F::CAPACITY;
+
+    /// Size of the full permutation state, `RATE + CAPACITY`.
+    pub const WIDTH: usize = F::WIDTH;
 }
 
 impl<TW, F> Spongos<TW, F>
@@ -99,6 +170,9 @@ where
     F: PRP<TW> + Default,
 {
     /// Create a Spongos object, initialize state with zero trits.
+    ///
+    /// The zero state at position 0 is the canonical starting point before the first
+    /// `absorb`, `Spongos::default()` is the same.
     pub fn init() -> Self {
         Self::init_with_state(F::default())
     }
@@ -121,12 +195,54 @@ where
 {
     /// Create a Spongos object with an explicit state.
     pub fn init_with_state(s: F) -> Self {
+        debug_assert_eq!(F::RATE + F::CAPACITY, F::WIDTH);
         Self {
             s,
             outer: Outer::new(F::RATE),
+            mode: F::MODE,
+            #[cfg(feature = "metrics")]
+            permutations: 0,
+            wipe: Self::wipe,
         }
     }
 
+    /// Current duplex mode.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Switch duplex mode, eg. to match an external Spongos configuration.
+    /// The mode applies to the data processed after the call, the state is kept.
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    /// Wipe the whole state with zeros, the mode is kept.
+    /// The permutation counter is reset with the `metrics` feature.
+    pub fn wipe(&mut self) {
+        self.s.wipe();
+        self.outer.tbits.wipe();
+        self.outer.pos = 0;
+        #[cfg(feature = "metrics")]
+        {
+            self.permutations = 0;
+        }
+    }
+
+    /// Number of transforms performed since initialization or `wipe`.
+    /// Forks and clones start with the count of the original object.
+    #[cfg(feature = "metrics")]
+    pub fn permutation_count(&self) -> u64 {
+        self.permutations
+    }
+
+    /// Overwrite the permutation counter, eg. to keep the count when rolling back
+    /// to a snapshot.
+    #[cfg(feature = "metrics")]
+    pub fn set_permutation_count(&mut self, n: u64) {
+        self.permutations = n;
+    }
+
     pub fn from_inner(inner: F::Inner) -> Self {
         Self::init_with_state(inner.into())
     }
@@ -162,7 +278,7 @@ where
             let mut s = self.outer.slice_min_mut(x.size());
             let n = s.size();
             let x_head = x.advance(n);
-            if F::MODE == Mode::OVERWRITE {
+            if self.mode == Mode::OVERWRITE {
                 s.absorb_overwrite(x_head);
             } else {
                 s.absorb_xor(x_head);
@@ -176,13 +292,34 @@ where
         self.absorb(x.slice())
     }
 
+    /// Absorb tbits yielded by `iter` without collecting the whole input.
+    /// Tbits are buffered up to the free size of the outer state and absorbed by chunks,
+    /// the resulting state is the same as if all the tbits were absorbed with one `absorb` call.
+    pub fn absorb_iter<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = TW::Tbit>,
+    {
+        let mut iter = iter.into_iter();
+        let mut chunk = Vec::with_capacity(F::RATE);
+        let mut buf = Tbits::<TW>::zero(F::RATE);
+        loop {
+            chunk.clear();
+            chunk.extend(iter.by_ref().take(self.outer.size()));
+            if chunk.is_empty() {
+                return;
+            }
+            buf.slice_mut().put_tbits(&chunk[..]);
+            self.absorb(buf.slice().take(chunk.len()));
+        }
+    }
+
     /// Squeeze a trit slice from Spongos object.
     pub fn squeeze(&mut self, y: &mut TbitSliceMut<TW>) {
         while !y.is_empty() {
             let mut s = self.outer.slice_min_mut(y.size());
             let n = s.size();
             let mut head = y.advance(n);
-            if F::MODE == Mode::OVERWRITE {
+            if self.mode == Mode::OVERWRITE {
                 s.squeeze_overwrite(&mut head);
             } else {
                 s.squeeze_xor(&mut head);
@@ -202,7 +339,7 @@ where
             let mut s = self.outer.slice_min_mut(y.size());
             let n = s.size();
             let head = y.advance(n);
-            let eqn = if F::MODE == Mode::OVERWRITE {
+            let eqn = if self.mode == Mode::OVERWRITE {
                 s.squeeze_eq_overwrite(head)
             } else {
                 s.squeeze_eq_xor(head)
@@ -238,7 +375,7 @@ where
             let n = s.size();
             let x_head = x.advance(n);
             let mut y_head = y.advance(n);
-            if F::MODE == Mode::OVERWRITE {
+            if self.mode == Mode::OVERWRITE {
                 s.encrypt_overwrite(x_head, &mut y_head);
             } else {
                 s.encrypt_xor(x_head, &mut y_head);
@@ -257,7 +394,7 @@ where
             let mut s = self.outer.slice_min_mut(xy.size());
             let n = s.size();
             let mut xy_head = xy.advance(n);
-            if F::MODE == Mode::OVERWRITE {
+            if self.mode == Mode::OVERWRITE {
                 s.encrypt_overwrite_mut(&mut xy_head);
             } else {
                 s.encrypt_xor_mut(&mut xy_head);
@@ -295,7 +432,7 @@ where
             let n = s.size();
             let y_head = y.advance(n);
             let mut x_head = x.advance(n);
-            if F::MODE == Mode::OVERWRITE {
+            if self.mode == Mode::OVERWRITE {
                 s.decrypt_overwrite(y_head, &mut x_head);
             } else {
                 s.decrypt_xor(y_head, &mut x_head);
@@ -314,7 +451,7 @@ where
             let mut s = self.outer.slice_min_mut(xy.size());
             let n = s.size();
             let mut xy_head = xy.advance(n);
-            if F::MODE == Mode::OVERWRITE {
+            if self.mode == Mode::OVERWRITE {
                 s.decrypt_overwrite_mut(&mut xy_head);
             } else {
                 s.decrypt_xor_mut(&mut xy_head);
@@ -347,9 +484,19 @@ where
             let mut o = self.outer.slice_mut();
             self.s.transform(&mut o);
             self.outer.pos = 0;
+            #[cfg(feature = "metrics")]
+            {
+                self.permutations += 1;
+            }
         }
     }
 
+    /// Current position in the outer state, ie. the number of tbits processed since the last transform.
+    /// Position is `0` right after `commit`.
+    pub fn pos(&self) -> usize {
+        self.outer.pos
+    }
+
     /// Check whether spongos state is committed.
     pub fn is_committed(&self) -> bool {
         0 == self.outer.pos
@@ -357,9 +504,16 @@ where
 
     /// Join two Spongos objects.
     /// Joiner -- self -- object absorbs data squeezed from joinee.
+    /// With the `metrics` feature the joinee transforms are counted by the joiner too.
     pub fn join(&mut self, joinee: &mut Self) {
         let mut x = Tbits::zero(F::CAPACITY);
+        #[cfg(feature = "metrics")]
+        let joinee_permutations = joinee.permutations;
         joinee.squeeze(&mut x.slice_mut());
+        #[cfg(feature = "metrics")]
+        {
+            self.permutations += joinee.permutations - joinee_permutations;
+        }
         self.absorb(x.slice());
     }
 }
@@ -381,6 +535,12 @@ where
         self.clone()
     }
 
+    /// Roll back to `snapshot` taken earlier with `clone` or `fork`.
+    /// The current state is wiped.
+    pub fn restore(&mut self, snapshot: Self) {
+        *self = snapshot;
+    }
+
     /// Only `inner` part of the state may be serialized.
     /// State should be committed.
     pub fn to_inner(&self) -> F::Inner {
@@ -389,6 +549,173 @@ where
     }
 }
 
+impl<TW, F> Spongos<TW, F>
+where
+    TW: SpongosTbitWord + PackedTbitWord,
+    F: PrpState<TW>,
+{
+    /// Size of the state serialized with `to_bytes` in bytes.
+    pub fn state_bytes() -> usize {
+        POS_BYTES + TW::words(F::RATE) + F::STATE_BYTES
+    }
+
+    /// Serialize the complete state, unlike `to_inner` it needn't be committed.
+    ///
+    /// The layout is the current position as a big-endian `u64`, the outer state
+    /// packed into words (see `tbits::encoding`) and the permutation state
+    /// (see `PrpState::to_state_bytes`). The bytes are secret as the state itself.
+    /// The duplex mode is not part of the state.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = (self.outer.pos as u64).to_be_bytes().to_vec();
+        bytes.extend(encoding::to_words(self.outer.tbits.slice()));
+        let n = bytes.len();
+        bytes.resize(n + F::STATE_BYTES, 0);
+        self.s.to_state_bytes(&mut bytes[n..]);
+        bytes
+    }
+
+    /// Deserialize the state serialized with `to_bytes`.
+    /// The mode is not serialized, it is reset to `F::MODE`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StateError> {
+        let expected = Self::state_bytes();
+        if bytes.len() != expected {
+            return Err(StateError::Length {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        let (pos, bytes) = bytes.split_at(POS_BYTES);
+        let (words, state) = bytes.split_at(bytes.len() - F::STATE_BYTES);
+
+        let mut p = [0_u8; POS_BYTES];
+        p.copy_from_slice(pos);
+        let pos = u64::from_be_bytes(p);
+        if F::RATE as u64 <= pos {
+            return Err(StateError::Position(pos));
+        }
+        let tbits = encoding::from_words(F::RATE, words).ok_or(StateError::Outer)?;
+        let s = F::from_state_bytes(state).ok_or(StateError::Permutation)?;
+
+        let mut spongos = Self::init_with_state(s);
+        spongos.outer = Outer {
+            pos: pos as usize,
+            tbits,
+        };
+        Ok(spongos)
+    }
+}
+
+impl<TW, F> Spongos<TW, F>
+where
+    TW: SpongosTbitWord + IntTbitWord,
+    F: PRP<TW>,
+{
+    /// Absorb bytes, each byte is encoded as an unsigned integer with `BYTE_TBITS` tbits.
+    pub fn absorb_bytes(&mut self, bytes: &[u8]) {
+        let mut x = Tbits::<TW>::zero(BYTE_TBITS * bytes.len());
+        {
+            let mut slice = x.slice_mut();
+            for b in bytes {
+                slice.advance(BYTE_TBITS).put_usize(*b as usize);
+            }
+        }
+        self.absorb_tbits(&x);
+    }
+
+    /// Squeeze `out.len()` bytes, each byte is squeezed with `SQUEEZE_BYTE_TBITS` tbits.
+    /// Tbits are squeezed with one `squeeze` call and are taken in order,
+    /// a byte is `u mod 256` where `u` is the unsigned integer of its tbits
+    /// with the least significant tbit first.
+    pub fn squeeze_bytes(&mut self, out: &mut [u8]) {
+        let x = self.squeeze_tbits(SQUEEZE_BYTE_TBITS * out.len());
+        let mut slice = x.slice();
+        for b in out.iter_mut() {
+            *b = slice.advance(SQUEEZE_BYTE_TBITS).get_usize() as u8;
+        }
+    }
+
+    /// Squeeze a `u64` value, eg. a shard key of a message.
+    /// The value is the 8 bytes squeezed with `squeeze_bytes` in little-endian order,
+    /// it consumes `8 * SQUEEZE_BYTE_TBITS` tbits of the state like any other squeeze.
+    /// Spongos should be committed beforehand so that the value depends on all the absorbed data.
+    pub fn squeeze_u64(&mut self) -> u64 {
+        let mut bytes = [0_u8; 8];
+        self.squeeze_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// Absorb all bytes read from `r` until EOF by chunks.
+    /// The resulting state is the same as if all the bytes were absorbed with one `absorb_bytes` call,
+    /// the last partial chunk is absorbed as is without padding.
+    pub fn absorb_reader<R: io::Read>(&mut self, r: &mut R) -> io::Result<()> {
+        let mut buf = vec![0_u8; READER_CHUNK_SIZE];
+        loop {
+            match r.read(&mut buf[..]) {
+                Ok(0) => return Ok(()),
+                Ok(n) => self.absorb_bytes(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl<TW, F> Spongos<TW, F>
+where
+    TW: SpongosTbitWord + TritWord,
+    F: PRP<TW> + Default,
+{
+    /// Keyed pseudo-random function `out := PRF(key, label, input)`.
+    /// Spongos absorbs `key`, `label` and `input` with commits in between
+    /// and squeezes `out` after the final commit. Each of them is prefixed with its
+    /// length encoded as `size_t` so that eg. an empty label and an empty input
+    /// are not interchangeable.
+    pub fn prf(key: &[Trint3], input: &[Trint3], label: &[Trint3], out: &mut [Trint3]) {
+        let mut s = Self::init();
+        s.absorb_tbits(&sized_trint3s_to_tbits(key));
+        s.commit();
+        s.absorb_tbits(&sized_trint3s_to_tbits(label));
+        s.commit();
+        s.absorb_tbits(&sized_trint3s_to_tbits(input));
+        s.commit();
+        let y = s.squeeze_tbits(3 * out.len());
+        let mut slice = y.slice();
+        for t in out.iter_mut() {
+            *t = slice.advance(3).get3();
+        }
+    }
+}
+
+/// Encode `ts.len()` as PB3 `size_t` (number of trytes `d` followed by `d` trytes
+/// of the length) followed by `ts`.
+fn sized_trint3s_to_tbits<TW>(ts: &[Trint3]) -> Tbits<TW>
+where
+    TW: TritWord,
+{
+    let mut size = Vec::new();
+    let mut n = ts.len();
+    while n != 0 {
+        let (r, q) = mods3_usize(n);
+        size.push(r);
+        n = q;
+    }
+    let mut x = Tbits::<TW>::zero(3 * (1 + size.len() + ts.len()));
+    {
+        let mut slice = x.slice_mut();
+        slice.advance(3).put3(Trint3(size.len() as i8));
+        for t in size.iter().chain(ts) {
+            slice.advance(3).put3(*t);
+        }
+    }
+    x
+}
+
+impl<TW, F> Drop for Spongos<TW, F> {
+    fn drop(&mut self) {
+        (self.wipe)(self);
+    }
+}
+
 impl<TW, F> fmt::Debug for Spongos<TW, F>
 where
     TW: BasicTbitWord,
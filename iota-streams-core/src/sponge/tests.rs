@@ -1,13 +1,20 @@
 use std::fmt;
+use std::io;
 //use std::str::FromStr;
-use super::prp::PRP;
+use super::prp::{PrpState, PRP};
 use super::spongos::*;
-use crate::tbits::{word::SpongosTbitWord, Tbits};
+use crate::tbits::{
+    trinary::{Trint3, TritWord},
+    word::{IntTbitWord, PackedTbitWord, SpongosTbitWord, StringTbitWord},
+    Tbits,
+};
 
 #[cfg(test)]
 use super::prp::troika::Troika;
 #[cfg(test)]
 use crate::tbits::trinary::Trit;
+#[cfg(test)]
+use std::convert::TryFrom;
 
 fn tbits_spongosn<TW, F>(n: usize)
 where
@@ -177,6 +184,369 @@ where
     }
 }
 
+pub fn prf_label_input_key<TW, F>()
+where
+    TW: SpongosTbitWord + TritWord,
+    F: PRP<TW> + Default,
+{
+    let key = [Trint3(1), Trint3(-13), Trint3(13), Trint3(0)];
+    let input = [Trint3(2), Trint3(3)];
+    let label = [Trint3(5)];
+    let mut out = [Trint3(0); 27];
+    Spongos::<TW, F>::prf(&key, &input, &label, &mut out);
+
+    let mut out2 = [Trint3(0); 27];
+    Spongos::<TW, F>::prf(&key, &input, &label, &mut out2);
+    assert_eq!(out, out2, "PRF is not deterministic");
+
+    let mut key2 = key;
+    key2[1] = Trint3(-12);
+    Spongos::<TW, F>::prf(&key2, &input, &label, &mut out2);
+    assert_ne!(out, out2, "PRF output does not depend on key");
+
+    let mut input2 = input;
+    input2[1] = Trint3(4);
+    Spongos::<TW, F>::prf(&key, &input2, &label, &mut out2);
+    assert_ne!(out, out2, "PRF output does not depend on input");
+
+    let label2 = [Trint3(6)];
+    Spongos::<TW, F>::prf(&key, &input, &label2, &mut out2);
+    assert_ne!(out, out2, "PRF output does not depend on label");
+
+    // Moving a tryte from label to input is a different PRF application.
+    Spongos::<TW, F>::prf(&key, &input[1..], &[label[0], input[0]], &mut out2);
+    assert_ne!(out, out2, "PRF label and input are not separated");
+
+    // Empty label and empty input are not interchangeable.
+    Spongos::<TW, F>::prf(&key, &input, &[], &mut out);
+    Spongos::<TW, F>::prf(&key, &[], &input, &mut out2);
+    assert_ne!(
+        out, out2,
+        "PRF empty label and empty input are not separated"
+    );
+}
+
+/// Reader returning at most `n` bytes per `read` call.
+struct ShortReader<'a> {
+    data: &'a [u8],
+    n: usize,
+}
+
+impl<'a> io::Read for ShortReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = std::cmp::min(std::cmp::min(self.n, buf.len()), self.data.len());
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+pub fn squeeze_bytes_reproducible<TW, F>()
+where
+    TW: SpongosTbitWord + IntTbitWord,
+    F: PRP<TW> + Clone + Default,
+{
+    let mut s = Spongos::<TW, F>::init();
+    s.absorb_bytes(b"KEY");
+    s.commit();
+    let mut s2 = s.fork();
+
+    let mut key = [0_u8; 32];
+    let mut key2 = [0_u8; 32];
+    s.squeeze_bytes(&mut key);
+    s2.squeeze_bytes(&mut key2);
+    assert_eq!(key, key2, "Squeezed bytes differ");
+    assert!(key.iter().any(|b| *b != 0), "Squeezed bytes are all zero");
+
+    // Bytes are packed from squeezed tbits.
+    let mut s = Spongos::<TW, F>::init();
+    s.absorb_bytes(b"KEY");
+    s.commit();
+    let x = s.squeeze_tbits(SQUEEZE_BYTE_TBITS * 32);
+    for (i, b) in key.iter().enumerate() {
+        let u = x
+            .slice()
+            .drop(i * SQUEEZE_BYTE_TBITS)
+            .take(SQUEEZE_BYTE_TBITS)
+            .get_usize();
+        assert_eq!(*b, u as u8, "Byte {} is not packed from squeezed tbits", i);
+    }
+}
+
+pub fn squeeze_u64_distribution<TW, F>()
+where
+    TW: SpongosTbitWord + IntTbitWord,
+    F: PRP<TW> + Default,
+{
+    let shard_key = |i: u32| {
+        let mut s = Spongos::<TW, F>::init();
+        s.absorb_bytes(&i.to_be_bytes());
+        s.commit();
+        s.squeeze_u64()
+    };
+    assert_eq!(shard_key(1), shard_key(1), "Squeezed u64 is not stable");
+    assert_ne!(
+        shard_key(1),
+        shard_key(2),
+        "Squeezed u64 doesn't depend on input"
+    );
+
+    // Chi-square statistic of the lowest and the highest 4 bits over 16 shards,
+    // 15 degrees of freedom, the critical value for p = 0.001 is 37.7.
+    const N: u32 = 4096;
+    let mut low = [0_u32; 16];
+    let mut high = [0_u32; 16];
+    for i in 0..N {
+        let u = shard_key(i);
+        low[(u & 15) as usize] += 1;
+        high[(u >> 60) as usize] += 1;
+    }
+    let chi2 = |counts: &[u32; 16]| {
+        let e = N as f64 / 16.0;
+        counts
+            .iter()
+            .map(|c| (*c as f64 - e) * (*c as f64 - e) / e)
+            .sum::<f64>()
+    };
+    assert!(chi2(&low) < 37.7, "Low bits are biased: {:?}", low);
+    assert!(chi2(&high) < 37.7, "High bits are biased: {:?}", high);
+}
+
+pub fn absorb_reader_eq_absorb_bytes<TW, F>()
+where
+    TW: SpongosTbitWord + IntTbitWord,
+    F: PRP<TW> + Default,
+{
+    // Several read chunks with the last one being partial.
+    let data: Vec<u8> = (0..10_000_u32).map(|i| (i * 7 + i / 256) as u8).collect();
+
+    let mut s = Spongos::<TW, F>::init();
+    s.absorb_bytes(&data[..]);
+    s.commit();
+    let h = s.squeeze_tbits(F::CAPACITY);
+
+    let mut s = Spongos::<TW, F>::init();
+    s.absorb_reader(&mut io::Cursor::new(&data[..])).unwrap();
+    s.commit();
+    assert!(h == s.squeeze_tbits(F::CAPACITY), "Streamed absorb differs");
+
+    let mut s = Spongos::<TW, F>::init();
+    s.absorb_reader(&mut ShortReader {
+        data: &data[..],
+        n: 7,
+    })
+    .unwrap();
+    s.commit();
+    assert!(
+        h == s.squeeze_tbits(F::CAPACITY),
+        "Short-read absorb differs"
+    );
+
+    let mut s = Spongos::<TW, F>::init();
+    s.absorb_bytes(&data[..data.len() - 1]);
+    s.commit();
+    assert!(
+        h != s.squeeze_tbits(F::CAPACITY),
+        "Last byte is not absorbed"
+    );
+}
+
+pub fn absorb_iter_eq_absorb<TW, F>()
+where
+    TW: SpongosTbitWord + StringTbitWord,
+    F: PRP<TW> + Default,
+{
+    // Several outer blocks with the last one being partial.
+    let x = Tbits::<TW>::cycle_str(3 * F::RATE + 7, "ABSORBITER");
+
+    let mut s = Spongos::<TW, F>::init();
+    s.absorb_tbits(&x);
+    s.commit();
+    let h = s.squeeze_tbits(F::CAPACITY);
+
+    let mut s = Spongos::<TW, F>::init();
+    s.absorb_iter(x.slice().iter());
+    s.commit();
+    assert!(h == s.squeeze_tbits(F::CAPACITY), "Iterator absorb differs");
+
+    // Misaligned with the outer state.
+    let (x0, x1) = x.slice().split_at(5);
+    let mut s = Spongos::<TW, F>::init();
+    s.absorb(x0);
+    s.absorb_iter(x1.iter());
+    s.commit();
+    assert!(
+        h == s.squeeze_tbits(F::CAPACITY),
+        "Misaligned iterator absorb differs"
+    );
+}
+
+/// Secret tbits and Spongos state are wiped, a wiped Spongos is in the initial state.
+pub fn wipe_secrets<TW, F>()
+where
+    TW: SpongosTbitWord,
+    F: PrpState<TW> + Default,
+{
+    let mut s = Spongos::<TW, F>::init();
+    s.absorb_tbits(&Tbits::zero(F::RATE + 1));
+    s.commit();
+
+    let mut t = s.squeeze_tbits(F::CAPACITY).into_secret();
+    assert!(t != Tbits::zero(F::CAPACITY));
+    assert!(t.clone().is_secret());
+    t.wipe();
+    assert!(t == Tbits::zero(F::CAPACITY));
+
+    let state = |f: &F| {
+        let mut bytes = vec![0_u8; F::STATE_BYTES];
+        f.to_state_bytes(&mut bytes);
+        bytes
+    };
+    let zero = state(&F::default());
+    assert!(state(&s.s) != zero);
+    s.wipe();
+    assert!(state(&s.s) == zero);
+    assert_eq!(0, s.pos());
+    assert!(s.squeeze_tbits(F::CAPACITY) == Spongos::<TW, F>::init().squeeze_tbits(F::CAPACITY));
+}
+
+/// Fresh Spongos objects are in the same zero state at position 0.
+pub fn init_default<TW, F>()
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW> + Default,
+{
+    let mut s = Spongos::<TW, F>::init();
+    let mut t = Spongos::<TW, F>::default();
+    assert_eq!(0, s.pos());
+    assert_eq!(0, t.pos());
+    assert!(s.is_committed() && t.is_committed());
+    assert!(s.squeeze_tbits(F::CAPACITY) == t.squeeze_tbits(F::CAPACITY));
+
+    let mut s = Spongos::<TW, F>::init();
+    let mut t = Spongos::<TW, F>::init();
+    let x = Tbits::<TW>::zero(F::RATE + 1);
+    s.absorb_tbits(&x);
+    t.absorb_tbits(&x);
+    s.commit();
+    t.commit();
+    assert!(s.squeeze_tbits(F::RATE + 1) == t.squeeze_tbits(F::RATE + 1));
+}
+
+/// Rate and capacity constants match the outer state block.
+pub fn rate_capacity<TW, F>()
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW> + Default,
+{
+    assert_eq!(F::RATE, Spongos::<TW, F>::RATE);
+    assert_eq!(F::CAPACITY, Spongos::<TW, F>::CAPACITY);
+    assert_eq!(
+        Spongos::<TW, F>::WIDTH,
+        Spongos::<TW, F>::RATE + Spongos::<TW, F>::CAPACITY
+    );
+
+    let mut s = Spongos::<TW, F>::init();
+    s.absorb_tbits(&Tbits::zero(F::RATE - 1));
+    assert_eq!(F::RATE - 1, s.pos());
+    s.absorb_tbits(&Tbits::zero(1));
+    assert!(s.is_committed(), "Outer state is not `RATE` tbits.");
+}
+
+/// Clones of the state are independent, a restored snapshot continues from the saved state.
+pub fn clone_restore<TW, F>()
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW> + Clone + Default,
+{
+    let mut s = Spongos::<TW, F>::init();
+    s.absorb_tbits(&Tbits::zero(F::RATE + 1));
+    let snapshot = s.clone();
+
+    let mut t = s.clone();
+    s.absorb_tbits(&Tbits::zero(1));
+    s.commit();
+    t.commit();
+    let hs = s.squeeze_tbits(F::CAPACITY);
+    let ht = t.squeeze_tbits(F::CAPACITY);
+    assert!(hs != ht);
+
+    s.restore(snapshot.clone());
+    s.commit();
+    assert!(ht == s.squeeze_tbits(F::CAPACITY));
+
+    let mut u = snapshot;
+    u.commit();
+    assert!(ht == u.squeeze_tbits(F::CAPACITY));
+}
+
+pub fn to_from_bytes<TW, F>()
+where
+    TW: SpongosTbitWord + PackedTbitWord,
+    F: PrpState<TW> + Clone + Default,
+{
+    let mut g = Spongos::<TW, F>::init();
+    g.absorb_tbits(&Tbits::zero(1));
+    g.commit();
+    let x = g.squeeze_tbits(F::RATE + 5);
+
+    let mut s = Spongos::<TW, F>::init();
+    s.absorb_tbits(&x);
+    s.squeeze_tbits(7);
+    assert!(!s.is_committed());
+    let mut t = s.clone();
+
+    let bytes = s.to_bytes();
+    assert_eq!(Spongos::<TW, F>::state_bytes(), bytes.len());
+    drop(s);
+    let mut u = Spongos::<TW, F>::from_bytes(&bytes).unwrap();
+    assert_eq!(t.pos(), u.pos());
+
+    assert!(t.squeeze_tbits(F::RATE + 3) == u.squeeze_tbits(F::RATE + 3));
+    t.absorb_tbits(&x);
+    u.absorb_tbits(&x);
+    t.commit();
+    u.commit();
+    assert!(t.squeeze_tbits(F::CAPACITY) == u.squeeze_tbits(F::CAPACITY));
+
+    let n = bytes.len();
+    assert_eq!(
+        Err(StateError::Length {
+            expected: n,
+            actual: n - 1
+        }),
+        Spongos::<TW, F>::from_bytes(&bytes[1..]).map(|_| ())
+    );
+    let mut bad = bytes.clone();
+    bad[..8].copy_from_slice(&(F::RATE as u64).to_be_bytes());
+    assert_eq!(
+        Err(StateError::Position(F::RATE as u64)),
+        Spongos::<TW, F>::from_bytes(&bad).map(|_| ())
+    );
+}
+
+#[test]
+fn init_default_troika() {
+    init_default::<Trit, Troika>();
+}
+
+#[test]
+fn rate_capacity_troika() {
+    rate_capacity::<Trit, Troika>();
+    let s = Spongos::<Trit, Troika>::init();
+    assert_eq!(Spongos::<Trit, Troika>::CAPACITY, s.to_inner().inner.size());
+}
+
+#[test]
+fn clone_restore_troika() {
+    clone_restore::<Trit, Troika>();
+}
+
+#[test]
+fn wipe_secrets_troika() {
+    wipe_secrets::<Trit, Troika>();
+}
+
 #[test]
 fn tbits_with_size_boundary_cases_troika() {
     tbits_with_size_boundary_cases::<Trit, Troika>();
@@ -197,6 +567,60 @@ fn encrypt_decrypt_troika() {
     encrypt_decrypt_n::<Trit, Troika>(2 * RATE);
 }
 
+#[test]
+fn squeeze_u64_troika() {
+    squeeze_u64_distribution::<Trit, Troika>();
+}
+
+#[test]
+fn absorb_reader_troika() {
+    absorb_reader_eq_absorb_bytes::<Trit, Troika>();
+}
+
+#[test]
+fn absorb_iter_troika() {
+    absorb_iter_eq_absorb::<Trit, Troika>();
+}
+
+#[test]
+fn to_from_bytes_troika() {
+    to_from_bytes::<Trit, Troika>();
+
+    let mut bytes = Spongos::<Trit, Troika>::init().to_bytes();
+    *bytes.last_mut().unwrap() = 3;
+    assert_eq!(
+        Err(StateError::Permutation),
+        Spongos::<Trit, Troika>::from_bytes(&bytes).map(|_| ())
+    );
+}
+
+#[test]
+fn squeeze_bytes_troika() {
+    squeeze_bytes_reproducible::<Trit, Troika>();
+}
+
+#[test]
+fn prf_troika() {
+    prf_label_input_key::<Trit, Troika>();
+
+    let key: Vec<Trint3> = "KEY"
+        .chars()
+        .map(|c| Trint3::try_from(c).unwrap())
+        .collect();
+    let input: Vec<Trint3> = "INPUT"
+        .chars()
+        .map(|c| Trint3::try_from(c).unwrap())
+        .collect();
+    let label: Vec<Trint3> = "LABEL"
+        .chars()
+        .map(|c| Trint3::try_from(c).unwrap())
+        .collect();
+    let mut out = [Trint3(0); 27];
+    Spongos::<Trit, Troika>::prf(&key, &input, &label, &mut out);
+    let out: String = out.iter().map(|t| char::from(*t)).collect();
+    assert_eq!(out, "BVWQDQCKNPOFGMBWESFDUMUFGJB");
+}
+
 /*
 #[test]
 fn inner() {
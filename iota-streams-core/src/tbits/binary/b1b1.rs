@@ -33,3 +33,20 @@ impl SpongosTbitWord for Bit {
 }
 
 //pub trait ByteWord: BasicTbitWord<Tbit = Byte> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tbits::Tbits;
+
+    #[test]
+    fn word_tbits() {
+        // Word conversions go through a buffer of `SIZE` tbits.
+        let t = Tbits::<Bit>::zero(2 * Bit::SIZE + 1);
+        assert!(t.slice().iter().all(|b| b == Bit(0)));
+        assert_eq!(
+            Bit::SIZE,
+            crate::tbits::word::WordTbits::<Bit>::zero().as_ref().len()
+        );
+    }
+}
@@ -2,7 +2,10 @@
 
 use super::defs::*;
 use super::word::BitWord;
-use crate::tbits::word::{BasicTbitWord, IntTbitWord, SpongosTbitWord, StringTbitWord};
+use crate::tbits::word::{
+    BasicTbitWord, ByteWord, IntTbitWord, PackedTbitWord, RangeError, SpongosTbitWord,
+    StringTbitWord,
+};
 
 impl BasicTbitWord for Byte {
     type Tbit = Bit;
@@ -37,6 +40,7 @@ impl BasicTbitWord for Byte {
 
 impl StringTbitWord for Byte {
     const TBITS_PER_CHAR: usize = 4;
+    const REPLACEMENT_CHAR: char = '0';
 
     unsafe fn put_char(mut s: usize, mut d: usize, mut p: *mut Self, c: char) -> bool {
         let mut b8 = if '0' <= c && c <= '9' {
@@ -89,6 +93,22 @@ impl StringTbitWord for Byte {
             (b'A' - 10 + b8) as char
         }
     }
+
+    fn tbit_to_char(t: Bit) -> char {
+        if t.0 == 0 {
+            '0'
+        } else {
+            '1'
+        }
+    }
+
+    fn tbit_from_char(c: char) -> Option<Bit> {
+        match c {
+            '0' => Some(Bit(0)),
+            '1' => Some(Bit(1)),
+            _ => None,
+        }
+    }
 }
 
 #[test]
@@ -126,7 +146,7 @@ impl IntTbitWord for Byte {
                 m += 1;
             }
         });
-        i = i | !(last - 1);
+        i = i | !last.wrapping_sub(1);
         i
     }
 
@@ -168,6 +188,110 @@ impl IntTbitWord for Byte {
         }
         u
     }
+    /// Two's complement, `get_i128` requires `n <= 128`.
+    unsafe fn put_i128(n: usize, d: usize, p: *mut Self, mut i: i128) {
+        <Byte as BasicTbitWord>::unfold_tbits(n, d, p, |xs| {
+            for x in xs.iter_mut() {
+                *x = Bit((i & 1) as u8);
+                i = i >> 1;
+            }
+        });
+    }
+    unsafe fn get_i128(n: usize, d: usize, p: *const Self) -> i128 {
+        let mut m = 0_i128;
+        let mut i = 0_i128;
+        let mut last = 0_i128;
+        <Byte as BasicTbitWord>::fold_tbits(n, d, p, |xs| {
+            for x in xs.iter() {
+                last = ((*x).0 as i128) << m;
+                i += last;
+                m += 1;
+            }
+        });
+        i = i | !last.wrapping_sub(1);
+        i
+    }
+    /// `get_u128` requires `n <= 128`.
+    unsafe fn put_u128(n: usize, d: usize, mut p: *mut Self, mut u: u128) {
+        if n % 8 == 0 && d % 8 == 0 {
+            p = p.add(d / 8);
+            for _ in 0..n / 8 {
+                *p = Byte(u as u8);
+                p = p.add(1);
+                u = u >> 8;
+            }
+        } else {
+            <Byte as BasicTbitWord>::unfold_tbits(n, d, p, |xs| {
+                for x in xs.iter_mut() {
+                    *x = Bit((u & 1) as u8);
+                    u = u >> 1;
+                }
+            });
+        }
+    }
+    unsafe fn get_u128(n: usize, d: usize, mut p: *const Self) -> u128 {
+        let mut u = 0_u128;
+        if n % 8 == 0 && d % 8 == 0 {
+            p = p.add(d / 8);
+            let mut m = 0_usize;
+            for _ in 0..n / 8 {
+                u |= ((*p).0 as u128) << m;
+                p = p.add(1);
+                m += 8;
+            }
+        } else {
+            let mut m = 0_usize;
+            <Byte as BasicTbitWord>::fold_tbits(n, d, p, |xs| {
+                for x in xs.iter() {
+                    u += (x.0 as u128) << m;
+                    m += 1;
+                }
+            });
+        }
+        u
+    }
+
+    /// Two's complement, `-2^(n-1) <= i < 2^(n-1)`.
+    fn fits_isize(n: usize, i: isize) -> bool {
+        if n == 0 {
+            i == 0
+        } else if n >= 8 * std::mem::size_of::<isize>() {
+            true
+        } else {
+            let m = 1_isize << (n - 1);
+            -m <= i && i < m
+        }
+    }
+    /// `u < 2^n`.
+    fn fits_usize(n: usize, u: usize) -> bool {
+        n >= 8 * std::mem::size_of::<usize>() || u >> n == 0
+    }
+
+    fn extract_field(x: Self, offset: usize, width: usize) -> u64 {
+        assert!(offset + width <= 8);
+        let mask = (1_u16 << width) - 1;
+        (((x.0 as u16) >> offset) & mask) as u64
+    }
+    fn insert_field(x: Self, offset: usize, width: usize, value: u64) -> Result<Self, RangeError> {
+        assert!(offset + width <= 8);
+        if value >> width != 0 {
+            return Err(RangeError { n: width });
+        }
+        let mask = ((1_u16 << width) - 1) << offset;
+        Ok(Byte(
+            ((x.0 as u16 & !mask) | (value as u16) << offset) as u8,
+        ))
+    }
+}
+
+/// Bit `i` of the word is bit `i` of the byte, least significant first.
+impl PackedTbitWord for Byte {
+    fn to_byte(self) -> u8 {
+        self.0
+    }
+    fn from_byte(b: u8) -> Option<Self> {
+        Some(Byte(b))
+    }
 }
 
 impl BitWord for Byte {
@@ -201,10 +325,17 @@ impl SpongosTbitWord for Byte {
     fn tbit_sub(x: Self::Tbit, y: Self::Tbit) -> Self::Tbit {
         Bit(x.0 ^ y.0)
     }
+    fn word_add(x: Self, y: Self) -> Self {
+        Byte(x.0 ^ y.0)
+    }
+    fn word_sub(x: Self, y: Self) -> Self {
+        Byte(x.0 ^ y.0)
+    }
     //TODO: Implement other methods.
 }
 
-//pub trait ByteWord: BasicTbitWord<Tbit = Byte> {}
+/// Bit `i` of a word is bit `i` of the byte, ie. bits are packed LSB first.
+unsafe impl ByteWord for Byte {}
 
 #[cfg(test)]
 mod tests {
@@ -212,6 +343,11 @@ mod tests {
     use crate::tbits::*;
     use std::str::FromStr;
 
+    #[test]
+    fn checked_offsets() {
+        crate::tbits::tests::checked_offsets::<Byte>();
+    }
+
     #[test]
     fn copy_exhaustive() {
         let num_loops = 2;
@@ -226,6 +362,80 @@ mod tests {
         crate::tbits::tests::add(&a, &b, &ab);
     }
 
+    #[test]
+    fn add_word_tbits() {
+        let a = Tbits::<Byte>::from_str("0123456789ABCDEF").unwrap();
+        let b = Tbits::<Byte>::from_str("F0E1D2C3B4A59687").unwrap();
+        crate::tbits::tests::add_word_tbits(&a, &b);
+    }
+
+    #[test]
+    fn slice_add_sub() {
+        let a = Tbits::<Byte>::from_str("0123456789ABCDEF").unwrap();
+        let b = Tbits::<Byte>::from_str("F0E1D2C3B4A59687").unwrap();
+        crate::tbits::tests::slice_add_sub(&a, &b);
+    }
+
+    #[test]
+    fn batch_tbits() {
+        let a = Tbits::<Byte>::cycle_str(600, "0123456789ABCDEF");
+        let b = Tbits::<Byte>::cycle_str(600, "F0E1D2C3B4A59687");
+        crate::tbits::tests::batch_tbits(&a, &b);
+    }
+
+    #[test]
+    fn equals_ct() {
+        let a = Tbits::<Byte>::from_str("0123ABCD").unwrap();
+        let b = Tbits::<Byte>::from_str("FEDC5432").unwrap();
+        crate::tbits::tests::equals_ct(&a, &b);
+    }
+
+    #[test]
+    fn copy_strided() {
+        let a = Tbits::<Byte>::from_str("0123456789AB").unwrap();
+        let b = Tbits::<Byte>::from_str("FEDCBA987654").unwrap();
+        crate::tbits::tests::copy_strided(&a, &b, 12);
+    }
+
+    #[test]
+    fn str_lossy() {
+        crate::tbits::tests::str_lossy::<Byte>("", "");
+        crate::tbits::tests::str_lossy::<Byte>("0123456789ABCDEF", "0123456789ABCDEF");
+        crate::tbits::tests::str_lossy::<Byte>("ΑΒCDxyz1", "0CD01");
+
+        // Trailing partial char is padded with zero bits: `F` = 1111, `3` = 0011.
+        let t = Tbits::<Byte>::from_str("FF").unwrap();
+        assert_eq!("F3", t.slice().take(6).to_str());
+    }
+
+    #[test]
+    fn as_bytes_io() {
+        use std::io::{Read, Write};
+
+        let t = Tbits::<Byte>::from_str("0123ABCDEF").unwrap();
+        let mut v: Vec<u8> = Vec::new();
+        v.write_all(t.as_bytes()).unwrap();
+        assert_eq!(t.buf_len(), v.len());
+
+        let mut r = Tbits::<Byte>::zero(t.size());
+        v.as_slice().read_exact(r.as_bytes_mut()).unwrap();
+        assert_eq!(t, r);
+
+        // Bits are packed LSB first.
+        let t = Tbits::<Byte>::from_tbits(&[Bit(1), Bit(0), Bit(1)]);
+        assert_eq!(&[5_u8], t.as_bytes());
+    }
+
+    #[test]
+    fn tbits_str() {
+        crate::tbits::tests::tbits_str::<Byte>("01");
+        let t = Tbits::<Byte>::from_tbits_str("1101").unwrap();
+        assert_eq!(
+            Tbits::<Byte>::from_tbits(&[Bit(1), Bit(1), Bit(0), Bit(1)]),
+            t
+        );
+    }
+
     #[test]
     fn get_put_char() {
         let alphabet = "0123456789ABCDEF";
@@ -249,4 +459,188 @@ mod tests {
             m *= 2;
         }
     }
+
+    #[test]
+    fn split_at() {
+        let a = Tbits::<Byte>::from_str("0123456789ABCDEF").unwrap();
+        let b = Tbits::<Byte>::from_str("FEDCBA9876543210").unwrap();
+        crate::tbits::tests::split_at(&a, &b);
+    }
+
+    #[test]
+    fn copy_within() {
+        let a = Tbits::<Byte>::from_str("0123456789ABCDEF").unwrap();
+        crate::tbits::tests::copy_within(&a);
+    }
+
+    #[test]
+    fn extend_collect() {
+        let a = Tbits::<Byte>::from_str("0123456789ABCDEF").unwrap();
+        crate::tbits::tests::extend_collect(&a);
+        // Non-word-aligned size.
+        crate::tbits::tests::extend_collect(&Tbits::from_slice(a.slice().take(61)));
+    }
+
+    #[test]
+    fn get_put_128() {
+        for &n in [1, 7, 8, 13, 64, 100, 127].iter() {
+            let m = 1_i128 << (n - 1);
+            crate::tbits::tests::get_put_128::<Byte>(n, -m, m - 1, (1_u128 << n) - 1);
+        }
+        crate::tbits::tests::get_put_128::<Byte>(
+            128,
+            std::i128::MIN,
+            std::i128::MAX,
+            std::u128::MAX,
+        );
+    }
+
+    #[test]
+    fn try_put_boundaries() {
+        for &n in [1, 2, 7, 8, 9, 16, 31].iter() {
+            let m = 1_isize << n;
+            crate::tbits::tests::try_put_boundaries::<Byte>(n, -m / 2, m / 2 - 1, m as usize - 1);
+        }
+        let mut t = Tbits::<Byte>::zero(64);
+        assert_eq!(Ok(()), t.slice_mut().try_put_isize(std::isize::MIN));
+        assert_eq!(std::isize::MIN, t.slice().get_isize());
+        assert_eq!(Ok(()), t.slice_mut().try_put_usize(std::usize::MAX));
+        assert_eq!(std::usize::MAX, t.slice().get_usize());
+        assert!(t
+            .slice_mut()
+            .take(63)
+            .try_put_usize(std::usize::MAX)
+            .is_err());
+        assert!(t.slice_mut().take(0).try_put_isize(-1).is_err());
+    }
+
+    #[test]
+    fn to_from_tbits_offsets() {
+        // Pseudo-random bit pattern.
+        let bits: Vec<Bit> = (0..32_usize)
+            .map(|i| Bit(((i * 7 + i / 3) & 1) as u8))
+            .collect();
+        for d in 0..16 {
+            for n in 0..=32 - d {
+                // Bits outside of `(d,n)` range must stay untouched.
+                let mut buf = [MAX_BYTE; 6];
+                let mut bits2 = vec![Bit(0); n];
+                unsafe {
+                    Byte::from_tbits(n, d, buf.as_mut_ptr(), bits.as_ptr());
+                    Byte::to_tbits(n, d, buf.as_ptr(), bits2.as_mut_ptr());
+                }
+                assert_eq!(&bits[..n], &bits2[..]);
+                for i in (0..d).chain(d + n..48) {
+                    assert_eq!(1, (buf[i / 8].0 >> (i % 8)) & 1);
+                }
+                // Least significant bit first.
+                for i in 0..n {
+                    assert_eq!(bits[i].0, (buf[(d + i) / 8].0 >> ((d + i) % 8)) & 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn extract_insert_field() {
+        // Three fields packed into one byte: 3 + 2 + 3 bits.
+        let fields = [(0, 3), (3, 2), (5, 3)];
+        for a in 0..8_u64 {
+            for b in 0..4_u64 {
+                for c in 0..8_u64 {
+                    let mut w = Byte(0);
+                    w = Byte::insert_field(w, fields[0].0, fields[0].1, a).unwrap();
+                    w = Byte::insert_field(w, fields[1].0, fields[1].1, b).unwrap();
+                    w = Byte::insert_field(w, fields[2].0, fields[2].1, c).unwrap();
+                    assert_eq!(w.0 as u64, a | b << 3 | c << 5);
+                    assert_eq!(a, Byte::extract_field(w, fields[0].0, fields[0].1));
+                    assert_eq!(b, Byte::extract_field(w, fields[1].0, fields[1].1));
+                    assert_eq!(c, Byte::extract_field(w, fields[2].0, fields[2].1));
+
+                    // Overwriting the middle field must not bleed into neighbours.
+                    let w2 = Byte::insert_field(w, fields[1].0, fields[1].1, 3 - b).unwrap();
+                    assert_eq!(a, Byte::extract_field(w2, fields[0].0, fields[0].1));
+                    assert_eq!(3 - b, Byte::extract_field(w2, fields[1].0, fields[1].1));
+                    assert_eq!(c, Byte::extract_field(w2, fields[2].0, fields[2].1));
+
+                    // Generic implementation must agree with the specialized one.
+                    unsafe {
+                        assert_eq!(a, <Byte as IntTbitWord>::get_usize(3, 0, &w) as u64);
+                        assert_eq!(c, <Byte as IntTbitWord>::get_usize(3, 5, &w) as u64);
+                    }
+                }
+            }
+        }
+
+        // Oversized values are rejected rather than truncated.
+        let w = Byte(0b1010_0101);
+        assert_eq!(Err(RangeError { n: 2 }), Byte::insert_field(w, 3, 2, 4));
+        assert_eq!(Err(RangeError { n: 0 }), Byte::insert_field(w, 8, 0, 1));
+        assert_eq!(Ok(w), Byte::insert_field(w, 8, 0, 0));
+    }
+
+    #[test]
+    fn split_word_at() {
+        let ts = [1, 0, 1, 1, 0, 0, 1, 1].map(Bit);
+        crate::tbits::tests::split_word_at::<Byte>(&ts);
+        assert_eq!(
+            (Byte(0b0000_0101), Byte(0b0001_1001)),
+            Byte::split_word_at(Byte(0b1100_1101), 3)
+        );
+    }
+
+    #[test]
+    fn encoding_round_trip() {
+        use rand::Rng;
+        let mut rng = rand::thread_rng();
+        let ts: Vec<_> = (0..100).map(|_| Bit(rng.gen_range(0, 2))).collect();
+        crate::tbits::tests::encoding_round_trip::<Byte>(&ts);
+
+        // Layout: big-endian tbit count followed by bytes, least significant bit first.
+        let t = Tbits::<Byte>::from_tbits(&[
+            Bit(1),
+            Bit(1),
+            Bit(0),
+            Bit(1),
+            Bit(0),
+            Bit(0),
+            Bit(0),
+            Bit(0),
+            Bit(1),
+            Bit(1),
+        ]);
+        assert_eq!("000000000000000a0b03", t.to_hex());
+        assert_eq!("AAAAAAAAAAoLAw==", t.to_base64());
+        // Padding bits must be zero.
+        assert_eq!(None, Tbits::<Byte>::from_hex("000000000000000a0b07"));
+    }
+
+    #[test]
+    fn iter() {
+        let a = Tbits::<Byte>::from_str("17E9A5").unwrap();
+        crate::tbits::tests::iter(&a);
+    }
+
+    #[test]
+    fn concat_repeat() {
+        let a = Tbits::<Byte>::from_str("C0C47E").unwrap();
+        crate::tbits::tests::concat_repeat(&a);
+    }
+
+    #[test]
+    fn chunks() {
+        let a = Tbits::<Byte>::from_str("C4B7").unwrap();
+        crate::tbits::tests::chunks(&a);
+    }
+
+    #[test]
+    fn safe_slice_ops() {
+        let a = Tbits::<Byte>::from_str("5AFE51").unwrap();
+        crate::tbits::tests::safe_slice_ops(&a);
+    }
+
+    #[test]
+    fn out_of_range() {
+        crate::tbits::tests::out_of_range::<Byte>(11);
+    }
 }
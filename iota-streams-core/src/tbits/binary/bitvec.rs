@@ -0,0 +1,45 @@
+//! Conversions between `Tbits<Byte>` and `BitVec`.
+//! Tbit order is preserved: `i`-th tbit corresponds to `i`-th bit of `BitVec`.
+
+use ::bitvec::{order::Lsb0, vec::BitVec};
+
+use super::defs::*;
+use crate::tbits::Tbits;
+
+impl From<&Tbits<Byte>> for BitVec<Lsb0, u8> {
+    fn from(tbits: &Tbits<Byte>) -> Self {
+        let mut bits = vec![Bit(0); tbits.size()];
+        tbits.slice().get_tbits(&mut bits[..]);
+        bits.iter().map(|b| b.0 != 0).collect()
+    }
+}
+
+impl From<&BitVec<Lsb0, u8>> for Tbits<Byte> {
+    fn from(bv: &BitVec<Lsb0, u8>) -> Self {
+        let bits: Vec<Bit> = bv.iter().map(|b| Bit(*b as u8)).collect();
+        Tbits::<Byte>::from_tbits(&bits[..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn bitvec_round_trip() {
+        let mut rng = rand::thread_rng();
+        for n in &[0_usize, 1, 7, 8, 9, 15, 17, 63, 65, 100, 1001] {
+            let bits: Vec<Bit> = (0..*n).map(|_| Bit(rng.gen_range(0, 2))).collect();
+            let tbits = Tbits::<Byte>::from_tbits(&bits[..]);
+
+            let bv = BitVec::<Lsb0, u8>::from(&tbits);
+            assert_eq!(*n, bv.len());
+            for (b, t) in bv.iter().zip(bits.iter()) {
+                assert_eq!(*b, t.0 != 0);
+            }
+
+            assert_eq!(tbits, Tbits::<Byte>::from(&bv));
+        }
+    }
+}
@@ -23,6 +23,7 @@ impl hash::Hash for Bit {
 
 /// Unsigned byte type.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug, Default)]
+#[repr(transparent)]
 pub struct Byte(pub u8); //0..255
 pub const MAX_BYTE: Byte = Byte(255);
 pub const MIN_BYTE: Byte = Byte(0);
@@ -2,6 +2,8 @@
 
 mod b1b1;
 mod b1b8;
+#[cfg(feature = "bitvec")]
+mod bitvec;
 mod convert;
 mod defs;
 mod slice;
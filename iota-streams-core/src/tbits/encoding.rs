@@ -0,0 +1,297 @@
+//! Hex and base64 text encodings of tbits.
+//!
+//! Tbits are encoded in their packed word representation rather than tbit by tbit.
+//! The byte layout of `n` tbits with word type `TW` is:
+//!
+//! - 8 bytes: `n` as a big-endian unsigned 64-bit integer;
+//! - `ceil(n / TW::SIZE)` bytes: one byte per word in order, each holding the packed
+//!   word value, see `PackedTbitWord` implementations for words layout.
+//!
+//! Tbits of the last incomplete word not covered by `n` are zero; decoders reject
+//! non-zero padding tbits, so each tbits value has exactly one encoding.
+//!
+//! `Tbits::try_from(&[u8])` unpacks bare words without the header, validating each byte.
+//!
+//! Hex strings use lowercase digits `0-9a-f`, both cases are accepted on input.
+//! Base64 strings use the standard alphabet with `=` padding (RFC 4648).
+//!
+//! With `serde` feature `Tbits` are serialized as a struct with the tbit count `size`
+//! and packed `words` (without the header).
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
+use super::{slice::*, tbits::*, word::*};
+
+/// Size of the tbit count header.
+const HEADER_SIZE: usize = 8;
+
+/// Pack tbits into bytes.
+pub fn to_bytes<TW>(x: TbitSlice<TW>) -> Vec<u8>
+where
+    TW: PackedTbitWord,
+{
+    let mut bytes = (x.size() as u64).to_be_bytes().to_vec();
+    bytes.extend(to_words(x));
+    bytes
+}
+
+/// Unpack tbits from bytes, `None` if `bytes` don't follow the layout.
+pub fn from_bytes<TW>(bytes: &[u8]) -> Option<Tbits<TW>>
+where
+    TW: PackedTbitWord,
+{
+    if bytes.len() < HEADER_SIZE {
+        return None;
+    }
+    let (header, words) = bytes.split_at(HEADER_SIZE);
+    let mut n = [0_u8; HEADER_SIZE];
+    n.copy_from_slice(header);
+    from_words(usize::try_from(u64::from_be_bytes(n)).ok()?, words)
+}
+
+/// Pack tbits into bytes without the tbit count header.
+pub(crate) fn to_words<TW>(x: TbitSlice<TW>) -> Vec<u8>
+where
+    TW: PackedTbitWord,
+{
+    let mut words = Vec::with_capacity(TW::words(x.size()));
+    words.extend(x.chunks(TW::SIZE).map(to_word));
+    words
+}
+
+/// Pack a chunk of at most `TW::SIZE` tbits into a word byte, missing tbits are zero.
+fn to_word<TW>(chunk: TbitSlice<TW>) -> u8
+where
+    TW: PackedTbitWord,
+{
+    let mut ts = WordTbits::<TW>::zero();
+    chunk.get_tbits(&mut ts.as_mut()[..chunk.size()]);
+    let w = unsafe { TW::word_from_tbits(ts.as_ref().as_ptr()) };
+    w.to_byte()
+}
+
+/// Unpack `n` tbits from packed `words`, `None` if the number of words doesn't match `n`
+/// or padding tbits are not zero.
+pub(crate) fn from_words<TW>(n: usize, words: &[u8]) -> Option<Tbits<TW>>
+where
+    TW: PackedTbitWord,
+{
+    if TW::words(n) != words.len() {
+        return None;
+    }
+
+    let mut x = Tbits::<TW>::try_zero(n)?;
+    let mut slice = x.slice_mut();
+    for b in words {
+        let w = TW::from_byte(*b)?;
+        let mut ts = WordTbits::<TW>::zero();
+        unsafe { TW::word_to_tbits(w, ts.as_mut().as_mut_ptr()) };
+        let k = slice.size_min(TW::SIZE);
+        if ts.as_ref()[k..].iter().any(|t| *t != TW::ZERO_TBIT) {
+            return None;
+        }
+        slice.advance(k).put_tbits(&ts.as_ref()[..k]);
+    }
+    Some(x)
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Encode tbits as a hex string.
+pub fn to_hex<TW>(x: TbitSlice<TW>) -> String
+where
+    TW: PackedTbitWord,
+{
+    let bytes = to_bytes(x);
+    let mut s = String::with_capacity(2 * bytes.len());
+    for b in bytes {
+        s.push(HEX_DIGITS[(b >> 4) as usize] as char);
+        s.push(HEX_DIGITS[(b & 15) as usize] as char);
+    }
+    s
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decode tbits from a hex string, `None` if `s` is not a valid encoding.
+pub fn from_hex<TW>(s: &str) -> Option<Tbits<TW>>
+where
+    TW: PackedTbitWord,
+{
+    let s = s.as_bytes();
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(s.len() / 2);
+    for c in s.chunks(2) {
+        bytes.push((hex_digit(c[0])? << 4) | hex_digit(c[1])?);
+    }
+    from_bytes(&bytes)
+}
+
+const BASE64_DIGITS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode tbits as a base64 string.
+pub fn to_base64<TW>(x: TbitSlice<TW>) -> String
+where
+    TW: PackedTbitWord,
+{
+    let bytes = to_bytes(x);
+    let mut s = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for c in bytes.chunks(3) {
+        let u = (c[0] as u32) << 16
+            | (*c.get(1).unwrap_or(&0) as u32) << 8
+            | *c.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= c.len() {
+                s.push(BASE64_DIGITS[(u >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+fn base64_digit(c: u8) -> Option<u32> {
+    match c {
+        b'A'..=b'Z' => Some((c - b'A') as u32),
+        b'a'..=b'z' => Some((c - b'a' + 26) as u32),
+        b'0'..=b'9' => Some((c - b'0' + 52) as u32),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// Decode tbits from a base64 string, `None` if `s` is not a valid encoding.
+pub fn from_base64<TW>(s: &str) -> Option<Tbits<TW>>
+where
+    TW: PackedTbitWord,
+{
+    let s = s.as_bytes();
+    if s.len() % 4 != 0 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(s.len() / 4 * 3);
+    for (i, c) in s.chunks(4).enumerate() {
+        let last = i + 1 == s.len() / 4;
+        let pad = if last {
+            c.iter().rev().take_while(|d| **d == b'=').count()
+        } else {
+            0
+        };
+        if pad > 2 {
+            return None;
+        }
+        let mut u = 0_u32;
+        for d in &c[..4 - pad] {
+            u = u << 6 | base64_digit(*d)?;
+        }
+        u <<= 6 * pad as u32;
+        // Padding bits must be zero.
+        if u & ((1 << (8 * pad)) - 1) != 0 {
+            return None;
+        }
+        bytes.extend_from_slice(&u.to_be_bytes()[1..4 - pad]);
+    }
+    from_bytes(&bytes)
+}
+
+impl<TW> Tbits<TW>
+where
+    TW: PackedTbitWord,
+{
+    /// Encode tbits as a hex string, see `tbits::encoding` for the layout.
+    pub fn to_hex(&self) -> String {
+        to_hex(self.slice())
+    }
+
+    /// Decode tbits from a hex string produced by `to_hex`.
+    pub fn from_hex(s: &str) -> Option<Self> {
+        from_hex(s)
+    }
+
+    /// Encode tbits as a base64 string, see `tbits::encoding` for the layout.
+    pub fn to_base64(&self) -> String {
+        to_base64(self.slice())
+    }
+
+    /// Decode tbits from a base64 string produced by `to_base64`.
+    pub fn from_base64(s: &str) -> Option<Self> {
+        from_base64(s)
+    }
+}
+
+/// Byte is not a valid packed word value.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct WordError {
+    /// Position of the invalid byte.
+    pub index: usize,
+    /// The invalid byte itself.
+    pub byte: u8,
+}
+
+impl fmt::Display for WordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "byte {} at index {} is not a valid packed word",
+            self.byte, self.index
+        )
+    }
+}
+
+impl std::error::Error for WordError {}
+
+/// Unpack tbits from packed words without the header, one word per byte,
+/// the result has `bytes.len() * TW::SIZE` tbits.
+impl<TW> TryFrom<&[u8]> for Tbits<TW>
+where
+    TW: PackedTbitWord,
+{
+    type Error = WordError;
+    fn try_from(bytes: &[u8]) -> Result<Self, WordError> {
+        if let Some(index) = bytes.iter().position(|b| TW::from_byte(*b).is_none()) {
+            return Err(WordError {
+                index,
+                byte: bytes[index],
+            });
+        }
+        // All words are valid and complete, there are no padding tbits.
+        Ok(from_words(bytes.len() * TW::SIZE, bytes).unwrap())
+    }
+}
+
+/// Tbits are ordered by size first and then lexicographically by packed words.
+/// Packed representation is canonical, so the order agrees with `PartialEq`.
+impl<TW> PartialOrd for Tbits<TW>
+where
+    TW: PackedTbitWord,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<TW> Ord for Tbits<TW>
+where
+    TW: PackedTbitWord,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.size().cmp(&other.size()).then_with(|| {
+            let words = self.slice().chunks(TW::SIZE).map(to_word);
+            words.cmp(other.slice().chunks(TW::SIZE).map(to_word))
+        })
+    }
+}
@@ -1,17 +1,29 @@
 //! Trinary & binary slices, buffers, encodings and conversions.
 
+#[cfg(feature = "std")]
 pub mod convert;
+#[cfg(feature = "std")]
+pub mod encoding;
+#[cfg(all(feature = "std", feature = "serde"))]
+mod serde;
+#[cfg(feature = "std")]
 pub mod slice;
+#[cfg(feature = "std")]
 pub mod tbits;
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 pub(crate) mod tests;
 pub mod word;
 
+#[cfg(feature = "std")]
 pub use convert::*;
+#[cfg(feature = "std")]
 pub use slice::*;
+#[cfg(feature = "std")]
 pub use tbits::*;
 
+#[cfg(feature = "std")]
 pub mod binary;
+#[cfg(feature = "std")]
 pub mod trinary;
 
 /*
@@ -0,0 +1,113 @@
+//! Serialization of `Tbits` with `serde`, see `tbits::encoding` for the packed words layout.
+//! Tbits are serialized as a struct with the tbit count `size` and packed `words`.
+
+use ::serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+
+use super::encoding::{from_words, to_words};
+use super::{word::PackedTbitWord, Tbits};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Tbits")]
+struct PackedTbits {
+    size: u64,
+    words: Vec<u8>,
+}
+
+impl<TW> Serialize for Tbits<TW>
+where
+    TW: PackedTbitWord,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        PackedTbits {
+            size: self.size() as u64,
+            words: to_words(self.slice()),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Deserialization fails if the number of packed words doesn't match the tbit count,
+/// or a word or padding tbits are not valid.
+impl<'de, TW> Deserialize<'de> for Tbits<TW>
+where
+    TW: PackedTbitWord,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let packed = PackedTbits::deserialize(deserializer)?;
+        usize::try_from(packed.size)
+            .ok()
+            .and_then(|n| from_words(n, &packed.words))
+            .ok_or_else(|| {
+                de::Error::custom(format!(
+                    "Packed words ({}) are inconsistent with tbits size {}.",
+                    packed.words.len(),
+                    packed.size
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tbits::{binary::*, trinary::*};
+    use rand::Rng;
+    use std::fmt;
+
+    fn round_trip<TW>(ts: &[TW::Tbit])
+    where
+        TW: PackedTbitWord,
+        TW::Tbit: fmt::Display,
+    {
+        for n in 0..=ts.len() {
+            let x = Tbits::<TW>::from_tbits(&ts[..n]);
+            let json = serde_json::to_string(&x).unwrap();
+            assert_eq!(
+                x,
+                serde_json::from_str::<Tbits<TW>>(&json).unwrap(),
+                "n={}",
+                n
+            );
+            let bin = bincode::serialize(&x).unwrap();
+            assert_eq!(
+                x,
+                bincode::deserialize::<Tbits<TW>>(&bin).unwrap(),
+                "n={}",
+                n
+            );
+        }
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let mut rng = rand::thread_rng();
+        let trits: Vec<_> = (0..30).map(|_| Trit(rng.gen_range(0, 3))).collect();
+        round_trip::<Trit>(&trits);
+        round_trip::<B1T5>(&trits);
+        let bits: Vec<_> = (0..30).map(|_| Bit(rng.gen_range(0, 2))).collect();
+        round_trip::<Byte>(&bits);
+    }
+
+    #[test]
+    fn serde_reject_inconsistent() {
+        let t = Tbits::<Byte>::from_tbits(&[Bit(1); 10]);
+        assert_eq!(
+            r#"{"size":10,"words":[255,3]}"#,
+            serde_json::to_string(&t).unwrap()
+        );
+        for json in &[
+            r#"{"size":10,"words":[255]}"#,
+            r#"{"size":10,"words":[255,3,0]}"#,
+            r#"{"size":10,"words":[255,7]}"#,
+            r#"{"size":0,"words":[0]}"#,
+        ] {
+            assert!(
+                serde_json::from_str::<Tbits<Byte>>(json).is_err(),
+                "{}",
+                json
+            );
+        }
+        assert!(serde_json::from_str::<Tbits<Trit>>(r#"{"size":1,"words":[3]}"#).is_err());
+    }
+}
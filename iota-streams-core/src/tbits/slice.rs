@@ -1,8 +1,29 @@
+//! Tbit slices over arrays of tbit words.
+//!
+//! Safe slice operations check offsets and sizes against the slice range and panic
+//! when out of range, so the unsafe `BasicTbitWord` functions taking raw pointers
+//! are not needed outside of the word implementations. The `try_` variants return
+//! `OffsetError` instead of panicking.
+
 use std::fmt;
 use std::hash;
 
 use super::word::*;
 
+/// Slices of sizes `n` and `m` must be of the same size, otherwise the longer one
+/// doesn't fit into the shorter one.
+fn check_same_size(n: usize, m: usize) -> Result<(), OffsetError> {
+    match n.cmp(&m) {
+        std::cmp::Ordering::Equal => Ok(()),
+        std::cmp::Ordering::Less => Err(OffsetError {
+            d: 0,
+            n: m,
+            size: n,
+        }),
+        std::cmp::Ordering::Greater => Err(OffsetError { d: 0, n, size: m }),
+    }
+}
+
 /// Range of the form `[d..n)` where `0 <= d <= n`.
 ///
 /// Safe range operations return a subrange of `self`,
@@ -67,19 +88,39 @@ impl SliceRange {
     /// Return current offset shifted by `n` to the right but no further than right bound.
     #[inline]
     pub fn offset_min(self, n: usize) -> usize {
-        std::cmp::min(self.n, self.d + n)
+        checked_offset(self.d, n).map_or(self.n, |m| std::cmp::min(self.n, m))
+    }
+
+    /// Return current offset shifted by `n` to the right, error if it's out of range.
+    #[inline]
+    pub fn try_offset(self, n: usize) -> Result<usize, OffsetError> {
+        checked_range(self.d, n, self.n)
+    }
+
+    /// Return current offset shifted by `n` to the right, panic if it's out of range.
+    #[inline]
+    fn offset(self, n: usize) -> usize {
+        self.try_offset(n).unwrap_or_else(|e| panic!("{}", e))
     }
 
     /// Take `n` tbits from the current range.
     #[inline]
     pub fn take(self, n: usize) -> Self {
-        assert!(self.n >= self.d + n);
         Self {
-            n: self.d + n,
+            n: self.offset(n),
             d: self.d,
         }
     }
 
+    /// Take `n` tbits from the current range, error if it's out of range.
+    #[inline]
+    pub fn try_take(self, n: usize) -> Result<Self, OffsetError> {
+        Ok(Self {
+            n: self.try_offset(n)?,
+            d: self.d,
+        })
+    }
+
     /// Take no more than `n` tbits from the current range.
     #[inline]
     pub fn take_min(self, n: usize) -> Self {
@@ -92,8 +133,7 @@ impl SliceRange {
     /// Split range at `n` tbits, return head and assign tail to self.
     #[inline]
     pub fn advance(&mut self, n: usize) -> Self {
-        let middle = self.d + n;
-        assert!(self.n >= middle);
+        let middle = self.offset(n);
         let left = self.d;
         self.d = middle;
         Self { n: middle, d: left }
@@ -111,13 +151,21 @@ impl SliceRange {
     /// Drop `n` tbits from the current range.
     #[inline]
     pub fn drop(self, n: usize) -> Self {
-        assert!(self.n >= self.d + n);
         Self {
             n: self.n,
-            d: self.d + n,
+            d: self.offset(n),
         }
     }
 
+    /// Drop `n` tbits from the current range, error if it's out of range.
+    #[inline]
+    pub fn try_drop(self, n: usize) -> Result<Self, OffsetError> {
+        Ok(Self {
+            n: self.n,
+            d: self.try_offset(n)?,
+        })
+    }
+
     /// Drop no more than `n` tbits from the current range.
     #[inline]
     pub fn drop_min(self, n: usize) -> Self {
@@ -130,9 +178,14 @@ impl SliceRange {
     /// Take and drop `n` tbits from the current range.
     #[inline]
     pub fn split_at(self, n: usize) -> (Self, Self) {
-        let middle = self.d + n;
-        assert!(self.n >= middle);
-        (
+        self.try_split_at(n).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Take and drop `n` tbits from the current range, error if it's out of range.
+    #[inline]
+    pub fn try_split_at(self, n: usize) -> Result<(Self, Self), OffsetError> {
+        let middle = self.try_offset(n)?;
+        Ok((
             Self {
                 n: middle,
                 d: self.d,
@@ -141,7 +194,7 @@ impl SliceRange {
                 n: self.n,
                 d: middle,
             },
-        )
+        ))
     }
 
     /// Take and drop no more than `n` tbits from the current range
@@ -302,30 +355,24 @@ where
         }
     }
 
-    /// Split slice into chunks.
-    pub fn chunks(mut self, chunk_size: usize) -> Vec<Self> {
-        // Can't divide by zero.
-        assert!(chunk_size != 0);
-        let chunks_count = (self.size() + chunk_size - 1) / chunk_size;
-        let mut v = Vec::with_capacity(chunks_count);
-        while !self.is_empty() {
-            v.push((&mut self).advance_min(chunk_size));
-        }
-        v
-    }
-
     /// Create slice of `n` tbits pointing to the array slice `t`.
     pub fn from_slice(n: usize, t: &'a [TW]) -> Self {
-        debug_assert!(n <= t.len() * TW::SIZE);
+        debug_assert!(TW::checked_words_range(0, n, t.len()).is_ok());
         Self::from_raw_ptr(n, t.as_ptr())
     }
 
-    /// Copy tbits from `self` slice into `tbits`.
+    /// Copy the first `tbits.len()` tbits from `self` slice into `tbits`.
     pub fn get_tbits(&self, tbits: &mut [TW::Tbit]) {
-        unsafe {
-            assert!(self.size() >= tbits.len());
-            TW::to_tbits(self.size(), self.r.d, self.p, tbits.as_mut_ptr());
-        }
+        self.try_get_tbits(tbits)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Copy the first `tbits.len()` tbits from `self` slice into `tbits`,
+    /// error if `self` is shorter.
+    pub fn try_get_tbits(&self, tbits: &mut [TW::Tbit]) -> Result<(), OffsetError> {
+        self.r.try_offset(tbits.len())?;
+        unsafe { TW::to_tbits(tbits.len(), self.r.d, self.p, tbits.as_mut_ptr()) }
+        Ok(())
     }
 
     /// Check whether `x` slice points to the same tbit in memory as `self`.
@@ -345,9 +392,9 @@ where
     /// It should only be used in debug code.
     pub(crate) unsafe fn is_overlapping(&self, x: &Self) -> bool {
         let begin = self.p.add(self.r.d / TW::SIZE);
-        let end = self.p.add((self.r.n + TW::SIZE - 1) / TW::SIZE);
+        let end = self.p.add(TW::words(self.r.n));
         let x_begin = x.p.add(x.r.d / TW::SIZE);
-        let x_end = x.p.add((x.r.n + TW::SIZE - 1) / TW::SIZE);
+        let x_end = x.p.add(TW::words(x.r.n));
         !(x_end <= begin || end <= x_begin)
     }
 
@@ -357,6 +404,12 @@ where
         self.with_range(self.r.take(n))
     }
 
+    /// Take `n` tbits from the current slice, error if it's out of range.
+    #[inline]
+    pub fn try_take(self, n: usize) -> Result<Self, OffsetError> {
+        Ok(self.with_range(self.r.try_take(n)?))
+    }
+
     /// Take no more than `n` tbits from the current slice.
     #[inline]
     pub fn take_min(self, n: usize) -> Self {
@@ -369,6 +422,12 @@ where
         self.with_range(self.r.drop(n))
     }
 
+    /// Drop `n` tbits from the current slice, error if it's out of range.
+    #[inline]
+    pub fn try_drop(self, n: usize) -> Result<Self, OffsetError> {
+        Ok(self.with_range(self.r.try_drop(n)?))
+    }
+
     /// Drop no more than `n` tbits from the current slice.
     #[inline]
     pub fn drop_min(self, n: usize) -> Self {
@@ -382,6 +441,13 @@ where
         self.with_range2(head, tail)
     }
 
+    /// Take and drop `n` tbits from the current slice, error if it's out of range.
+    #[inline]
+    pub fn try_split_at(self, n: usize) -> Result<(Self, Self), OffsetError> {
+        let (head, tail) = self.r.try_split_at(n)?;
+        Ok(self.with_range2(head, tail))
+    }
+
     /// Take and drop no more than `n` tbits from the current range.
     #[inline]
     pub fn split_at_min(self, n: usize) -> (Self, Self) {
@@ -396,6 +462,13 @@ where
         self.with_range_mut(tail, head)
     }
 
+    /// Advance the current slice by `n` tbits, the slice is left intact if it's out of range.
+    #[inline]
+    pub fn try_advance(&mut self, n: usize) -> Result<Self, OffsetError> {
+        let (head, tail) = self.r.try_split_at(n)?;
+        Ok(self.with_range_mut(tail, head))
+    }
+
     /// Advance the current slice by no more than `n` tbits.
     #[inline]
     pub fn advance_min(&mut self, n: usize) -> Self {
@@ -453,8 +526,13 @@ where
 
     /// Compare two slices of the same size.
     pub fn equals(&self, other: &Self) -> bool {
-        assert_eq!(self.size(), other.size());
-        unsafe { TW::equals(self.size(), self.r.d, self.p, other.r.d, other.p) }
+        self.try_equals(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compare two slices, error if the sizes differ.
+    pub fn try_equals(&self, other: &Self) -> Result<bool, OffsetError> {
+        check_same_size(self.size(), other.size())?;
+        Ok(unsafe { TW::equals(self.size(), self.r.d, self.p, other.r.d, other.p) })
     }
 
     /// Compare two slices.
@@ -465,9 +543,15 @@ where
 
     /// Copy tbits into the slice `to` of equal size.
     pub fn copy(&self, to: &TbitSliceMut<'a, TW>) {
-        assert_eq!(self.size(), to.size());
+        self.try_copy(to).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Copy tbits into the slice `to`, error if the sizes differ.
+    pub fn try_copy(&self, to: &TbitSliceMut<'a, TW>) -> Result<(), OffsetError> {
+        check_same_size(self.size(), to.size())?;
         debug_assert!(unsafe { !self.is_overlapping(&to.as_const()) });
         unsafe { TW::copy(self.size(), self.r.d, self.p, to.r.d, to.p) }
+        Ok(())
     }
 
     /// Copy tbits into the slice `to` of equal size.
@@ -503,6 +587,16 @@ where
         }
     }
 
+    /// Render each tbit as a single char, see `StringTbitWord::tbit_to_char`.
+    pub fn to_tbits_str(&self) -> String {
+        self.display_tbits().to_string()
+    }
+
+    /// Adapter formatting each tbit as a single char, suitable for logs.
+    pub fn display_tbits(self) -> DisplayTbits<'a, TW> {
+        DisplayTbits(self)
+    }
+
     pub fn eq_str(&self, s: &str) -> bool {
         if (self.size() + TW::TBITS_PER_CHAR - 1) / TW::TBITS_PER_CHAR != s.len() {
             return false;
@@ -532,6 +626,94 @@ where
     pub fn get_usize(&self) -> usize {
         unsafe { TW::get_usize(self.size(), self.r.d, self.p) }
     }
+    pub fn get_i128(&self) -> i128 {
+        unsafe { TW::get_i128(self.size(), self.r.d, self.p) }
+    }
+    pub fn get_u128(&self) -> u128 {
+        unsafe { TW::get_u128(self.size(), self.r.d, self.p) }
+    }
+}
+
+impl<'a, TW: 'a> TbitSlice<'a, TW>
+where
+    TW: BasicTbitWord,
+{
+    /// Iterate over tbits of the slice by value.
+    pub fn iter(self) -> TbitIter<'a, TW> {
+        TbitIter(self)
+    }
+
+    /// Iterate over non-overlapping subslices of `block_len` tbits, the last one may be shorter.
+    ///
+    /// Panics if `block_len` is zero.
+    pub fn chunks(self, block_len: usize) -> TbitChunks<'a, TW> {
+        assert!(block_len != 0, "Chunk size must be non-zero.");
+        TbitChunks(self, block_len)
+    }
+}
+
+/// Iterator over constant subslices, see `TbitSlice::chunks`.
+#[derive(Copy, Clone)]
+pub struct TbitChunks<'a, TW: 'a>(TbitSlice<'a, TW>, usize);
+
+impl<'a, TW: 'a> Iterator for TbitChunks<'a, TW>
+where
+    TW: BasicTbitWord,
+{
+    type Item = TbitSlice<'a, TW>;
+    fn next(&mut self) -> Option<TbitSlice<'a, TW>> {
+        if self.0.is_empty() {
+            None
+        } else {
+            let (head, tail) = self.0.split_at_min(self.1);
+            self.0 = tail;
+            Some(head)
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = (self.0.size() + self.1 - 1) / self.1;
+        (n, Some(n))
+    }
+}
+
+impl<'a, TW: 'a> ExactSizeIterator for TbitChunks<'a, TW> where TW: BasicTbitWord {}
+
+/// Iterator over tbits of a constant slice, see `TbitSlice::iter`.
+#[derive(Copy, Clone)]
+pub struct TbitIter<'a, TW: 'a>(TbitSlice<'a, TW>);
+
+impl<'a, TW: 'a> Iterator for TbitIter<'a, TW>
+where
+    TW: BasicTbitWord,
+{
+    type Item = TW::Tbit;
+    fn next(&mut self) -> Option<TW::Tbit> {
+        if self.0.is_empty() {
+            None
+        } else {
+            let t = unsafe { TW::get_tbit(self.0.r.d, self.0.p) };
+            self.0 = self.0.drop(1);
+            Some(t)
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.size(), Some(self.0.size()))
+    }
+}
+
+impl<'a, TW: 'a> ExactSizeIterator for TbitIter<'a, TW> where TW: BasicTbitWord {}
+
+impl<'a, TW: 'a> TbitSlice<'a, TW>
+where
+    TW: SpongosTbitWord,
+{
+    /// Compare two slices in constant time, see `SpongosTbitWord::equals_ct`.
+    /// Only the contents are compared without early return, the size is not secret:
+    /// slices of different sizes are not equal.
+    pub fn equals_ct(&self, other: &Self) -> bool {
+        self.size() == other.size()
+            && unsafe { TW::equals_ct(self.size(), self.r.d, self.p, other.r.d, other.p) }
+    }
 }
 
 impl<'a, TW> PartialEq for TbitSlice<'a, TW>
@@ -571,6 +753,25 @@ where
     }
 }
 
+/// Format tbits of the slice as chars of the tbit alphabet, eg. `-0+` for trits or `01` for bits.
+#[derive(Copy, Clone)]
+pub struct DisplayTbits<'a, TW: 'a>(TbitSlice<'a, TW>);
+
+impl<'a, TW> fmt::Display for DisplayTbits<'a, TW>
+where
+    TW: StringTbitWord,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use std::fmt::Write as _;
+        let mut v = vec![TW::ZERO_TBIT; self.0.size()];
+        self.0.get_tbits(&mut v[..]);
+        for t in v {
+            f.write_char(TW::tbit_to_char(t))?;
+        }
+        Ok(())
+    }
+}
+
 unsafe fn write_tbits<TW>(
     n: usize,
     dx: usize,
@@ -764,7 +965,7 @@ where
 
     /// Create slice of `n` tbits pointing to the array slice `t`.
     pub fn from_slice_mut(n: usize, t: &'a mut [TW]) -> Self {
-        debug_assert!(n <= t.len() * TW::SIZE);
+        debug_assert!(TW::checked_words_range(0, n, t.len()).is_ok());
         Self::from_raw_ptr(n, t.as_mut_ptr())
     }
 
@@ -818,12 +1019,28 @@ where
         }
     }
 
-    /// Copy tbits into `self` slice from `tbits`.
+    /// Copy `tbits` into the first `tbits.len()` tbits of `self` slice.
     pub fn put_tbits(&self, tbits: &[<TW as BasicTbitWord>::Tbit]) {
-        unsafe {
-            assert!(self.size() >= tbits.len());
-            TW::from_tbits(self.size(), self.r.d, self.p, tbits.as_ptr());
-        }
+        self.try_put_tbits(tbits)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Copy `tbits` into the first `tbits.len()` tbits of `self` slice,
+    /// error if `self` is shorter.
+    pub fn try_put_tbits(&self, tbits: &[<TW as BasicTbitWord>::Tbit]) -> Result<(), OffsetError> {
+        self.r.try_offset(tbits.len())?;
+        unsafe { TW::from_tbits(tbits.len(), self.r.d, self.p, tbits.as_ptr()) }
+        Ok(())
+    }
+
+    /// Copy tbits from the slice `from` of equal size, see `TbitSlice::copy`.
+    pub fn copy_from(&self, from: &TbitSlice<'a, TW>) {
+        from.copy(self);
+    }
+
+    /// Copy tbits from the slice `from`, see `TbitSlice::try_copy`.
+    pub fn try_copy_from(&self, from: &TbitSlice<'a, TW>) -> Result<(), OffsetError> {
+        from.try_copy(self)
     }
 
     /// Fill slice with zero tbits.
@@ -851,9 +1068,9 @@ where
     //TODO: Remove `is_overlapping`.
     unsafe fn is_overlapped(&self, x: Self) -> bool {
         let begin = self.p.add(self.r.d / TW::SIZE);
-        let end = self.p.add((self.r.n + TW::SIZE - 1) / TW::SIZE);
+        let end = self.p.add(TW::words(self.r.n));
         let x_begin = x.p.add(x.r.d / TW::SIZE);
-        let x_end = x.p.add((x.r.n + TW::SIZE - 1) / TW::SIZE);
+        let x_end = x.p.add(TW::words(x.r.n));
         !(x_end <= begin || end <= x_begin)
     }
 
@@ -864,6 +1081,13 @@ where
         self.with_range(r)
     }
 
+    /// Take `n` tbits from the current slice, error if it's out of range.
+    #[inline]
+    pub fn try_take(self, n: usize) -> Result<Self, OffsetError> {
+        let r = self.r.try_take(n)?;
+        Ok(self.with_range(r))
+    }
+
     /// Take no more than `n` tbits from the current slice.
     #[inline]
     pub fn take_min(self, n: usize) -> Self {
@@ -878,6 +1102,13 @@ where
         self.with_range(r)
     }
 
+    /// Drop `n` tbits from the current slice, error if it's out of range.
+    #[inline]
+    pub fn try_drop(self, n: usize) -> Result<Self, OffsetError> {
+        let r = self.r.try_drop(n)?;
+        Ok(self.with_range(r))
+    }
+
     /// Drop no more than `n` tbits from the current slice.
     #[inline]
     pub fn drop_min(self, n: usize) -> Self {
@@ -892,6 +1123,13 @@ where
         self.with_range2(head, tail)
     }
 
+    /// Take and drop `n` tbits from the current slice, error if it's out of range.
+    #[inline]
+    pub fn try_split_at(self, n: usize) -> Result<(Self, Self), OffsetError> {
+        let (head, tail) = self.r.try_split_at(n)?;
+        Ok(self.with_range2(head, tail))
+    }
+
     /// Take and drop no more than `n` tbits from the current range.
     #[inline]
     pub fn split_at_min(self, n: usize) -> (Self, Self) {
@@ -899,6 +1137,34 @@ where
         self.with_range2(head, tail)
     }
 
+    /// Copy tbits of `src` range of the slice to offset `dest` within the slice,
+    /// like `[T]::copy_within`. The ranges may overlap.
+    pub fn copy_within(&mut self, src: std::ops::Range<usize>, dest: usize) {
+        assert!(src.start <= src.end && src.end <= self.size());
+        let n = src.end - src.start;
+        assert!(dest <= self.size() - n);
+        unsafe { TW::copy(n, self.r.d + src.start, self.p, self.r.d + dest, self.p) }
+    }
+
+    /// Split the current slice at `n` tbits into non-overlapping `(head, tail)` slices
+    /// borrowed from `self`, like `[T]::split_at_mut`. No tbits are copied.
+    #[inline]
+    pub fn split_at_mut(&mut self, n: usize) -> (TbitSliceMut<TW>, TbitSliceMut<TW>) {
+        let (head, tail) = self.r.split_at(n);
+        (
+            TbitSliceMut {
+                r: head,
+                p: self.p,
+                phantom: std::marker::PhantomData,
+            },
+            TbitSliceMut {
+                r: tail,
+                p: self.p,
+                phantom: std::marker::PhantomData,
+            },
+        )
+    }
+
     /// Advance the current slice by `n` tbits.
     #[inline]
     pub fn advance(&mut self, n: usize) -> Self {
@@ -906,6 +1172,13 @@ where
         self.with_range_mut(tail, head)
     }
 
+    /// Advance the current slice by `n` tbits, the slice is left intact if it's out of range.
+    #[inline]
+    pub fn try_advance(&mut self, n: usize) -> Result<Self, OffsetError> {
+        let (head, tail) = self.r.try_split_at(n)?;
+        Ok(self.with_range_mut(tail, head))
+    }
+
     /// Advance the current slice by no more than `n` tbits.
     #[inline]
     pub fn advance_min(&mut self, n: usize) -> Self {
@@ -965,8 +1238,13 @@ where
 
     /// Compare two slices of the same size.
     pub fn equals(&self, other: &Self) -> bool {
-        assert_eq!(self.size(), other.size());
-        unsafe {
+        self.try_equals(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Compare two slices, error if the sizes differ.
+    pub fn try_equals(&self, other: &Self) -> Result<bool, OffsetError> {
+        check_same_size(self.size(), other.size())?;
+        Ok(unsafe {
             TW::equals(
                 self.size(),
                 self.r.d,
@@ -974,7 +1252,7 @@ where
                 other.r.d,
                 other.p as *const TW,
             )
-        }
+        })
     }
 
     /// Compare two slices.
@@ -1004,6 +1282,23 @@ where
         unsafe { TW::put_char(self.size_min(TW::TBITS_PER_CHAR), self.r.d, self.p, c) }
     }
 
+    /// Parse string `s` of tbit chars and put them at the current offset.
+    /// The number of chars in `s` must be equal to the size of the slice.
+    pub fn from_tbits_str(&mut self, s: &str) -> bool {
+        let mut v = Vec::with_capacity(self.size());
+        for c in s.chars() {
+            match TW::tbit_from_char(c) {
+                Some(t) if v.len() < self.size() => v.push(t),
+                _ => return false,
+            }
+        }
+        if v.len() != self.size() {
+            return false;
+        }
+        self.put_tbits(&v[..]);
+        true
+    }
+
     /// Try to ASCII-convert string `s` to trytes and put them at the current offset.
     /// If the length of `s` exceeds the size of the slice the remaining trits of `s` must be zero.
     pub fn from_str(&mut self, s: &str) -> bool {
@@ -1027,11 +1322,29 @@ impl<'a, TW: 'a> TbitSliceMut<'a, TW>
 where
     TW: IntTbitWord,
 {
+    /// Put signed `i`, the value wraps if it doesn't fit into the slice, see `try_put_isize`.
     pub fn put_isize(&self, i: isize) {
-        unsafe { TW::put_isize(self.r.n, self.r.d, self.p, i) }
+        unsafe { TW::put_isize(self.size(), self.r.d, self.p, i) }
     }
+    /// Put unsigned `u`, the value is truncated if it doesn't fit into the slice, see `try_put_usize`.
     pub fn put_usize(&self, u: usize) {
-        unsafe { TW::put_usize(self.r.n, self.r.d, self.p, u) }
+        unsafe { TW::put_usize(self.size(), self.r.d, self.p, u) }
+    }
+    /// Put signed 128-bit `i`, the value wraps if it doesn't fit into the slice.
+    pub fn put_i128(&self, i: i128) {
+        unsafe { TW::put_i128(self.size(), self.r.d, self.p, i) }
+    }
+    /// Put unsigned 128-bit `u`, the value is truncated if it doesn't fit into the slice.
+    pub fn put_u128(&self, u: u128) {
+        unsafe { TW::put_u128(self.size(), self.r.d, self.p, u) }
+    }
+    /// Put signed `i` if it fits into the slice, otherwise the slice is left intact.
+    pub fn try_put_isize(&self, i: isize) -> Result<(), RangeError> {
+        unsafe { TW::try_put_isize(self.size(), self.r.d, self.p, i) }
+    }
+    /// Put unsigned `u` if it fits into the slice, otherwise the slice is left intact.
+    pub fn try_put_usize(&self, u: usize) -> Result<(), RangeError> {
+        unsafe { TW::try_put_usize(self.size(), self.r.d, self.p, u) }
     }
 }
 
@@ -1039,6 +1352,26 @@ impl<'a, TW: 'a> TbitSliceMut<'a, TW>
 where
     TW: SpongosTbitWord,
 {
+    /// Add `x` to the slice tbit-wise, `self := self + x`, the sizes must be equal.
+    /// Aligned slices are processed word-at-a-time, see `SpongosTbitWord::add`.
+    pub fn add(&mut self, x: TbitSlice<TW>) {
+        let n = self.size();
+        assert_eq!(n, x.size());
+        unsafe {
+            debug_assert!(!x.is_overlapping(&self.as_const()));
+            TW::add(self.r.d, self.p, n, x.r.d, x.p);
+        }
+    }
+    /// Subtract `x` from the slice tbit-wise, `self := self - x`, the sizes must be equal.
+    pub fn sub(&mut self, x: TbitSlice<TW>) {
+        let n = self.size();
+        assert_eq!(n, x.size());
+        unsafe {
+            debug_assert!(!x.is_overlapping(&self.as_const()));
+            TW::sub(self.r.d, self.p, n, x.r.d, x.p);
+        }
+    }
+
     pub fn absorb_overwrite(&mut self, x: TbitSlice<'a, TW>) {
         let n = self.size();
         assert_eq!(n, x.size());
@@ -1143,6 +1476,70 @@ where
     }
 }
 
+impl<'a, TW: 'a> TbitSliceMut<'a, TW>
+where
+    TW: BasicTbitWord,
+{
+    /// Iterate over tbits of the slice by reference, see `TbitRefMut`.
+    pub fn iter_mut(self) -> TbitIterMut<'a, TW> {
+        TbitIterMut(self)
+    }
+}
+
+/// Reference to a single tbit of a mutable slice.
+///
+/// Tbits are packed into words and can't be borrowed directly, the tbit is read
+/// and written with `get` and `set` instead. References to tbits of the same word
+/// are safe to use one after another in the same thread.
+pub struct TbitRefMut<'a, TW: 'a> {
+    d: usize,
+    p: *mut TW,
+    phantom: std::marker::PhantomData<&'a TW>,
+}
+
+impl<'a, TW: 'a> TbitRefMut<'a, TW>
+where
+    TW: BasicTbitWord,
+{
+    /// Read the tbit.
+    pub fn get(&self) -> TW::Tbit {
+        unsafe { TW::get_tbit(self.d, self.p) }
+    }
+
+    /// Overwrite the tbit with `t`.
+    pub fn set(&mut self, t: TW::Tbit) {
+        unsafe { TW::put_tbit(self.d, self.p, t) }
+    }
+}
+
+/// Iterator over tbits of a mutable slice, see `TbitSliceMut::iter_mut`.
+pub struct TbitIterMut<'a, TW: 'a>(TbitSliceMut<'a, TW>);
+
+impl<'a, TW: 'a> Iterator for TbitIterMut<'a, TW>
+where
+    TW: BasicTbitWord,
+{
+    type Item = TbitRefMut<'a, TW>;
+    fn next(&mut self) -> Option<TbitRefMut<'a, TW>> {
+        if self.0.is_empty() {
+            None
+        } else {
+            let t = TbitRefMut {
+                d: self.0.r.d,
+                p: self.0.p,
+                phantom: std::marker::PhantomData,
+            };
+            self.0.r = self.0.r.drop(1);
+            Some(t)
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.size(), Some(self.0.size()))
+    }
+}
+
+impl<'a, TW: 'a> ExactSizeIterator for TbitIterMut<'a, TW> where TW: BasicTbitWord {}
+
 impl<'a, TW: 'a> PartialEq for TbitSliceMut<'a, TW>
 where
     TW: BasicTbitWord,
@@ -1,5 +1,6 @@
 use std::fmt;
 use std::hash;
+use std::mem;
 use std::ops;
 use std::str::FromStr;
 use std::string::ToString;
@@ -12,6 +13,8 @@ use super::{slice::*, word::*};
 pub struct Tbits<TW> {
     n: usize,
     buf: std::vec::Vec<TW>,
+    /// Wipe tbits on drop, set for containers holding secrets, see `into_secret`.
+    wipe: Option<fn(&mut Self)>,
 }
 
 impl<TW> Tbits<TW>
@@ -20,10 +23,16 @@ where
 {
     /// Create a container filled with `n` zero tbits.
     pub fn zero(n: usize) -> Self {
-        Self {
-            n,
-            buf: vec![TW::ZERO_WORD; (n + TW::SIZE - 1) / TW::SIZE],
-        }
+        Self::try_zero(n).expect("Tbits size is too large.")
+    }
+
+    /// Create a container filled with `n` zero tbits, `None` if the buffer can't be allocated.
+    pub fn try_zero(n: usize) -> Option<Self> {
+        let m = TW::words(n);
+        let mut buf = Vec::new();
+        buf.try_reserve_exact(m).ok()?;
+        buf.resize(m, TW::ZERO_WORD);
+        Some(Self { n, buf, wipe: None })
     }
 
     /// Create an empty container.
@@ -31,6 +40,7 @@ where
         Self {
             n: 0,
             buf: Vec::new(),
+            wipe: None,
         }
     }
 
@@ -58,6 +68,31 @@ where
         x
     }
 
+    /// Concatenate slices into a new container, slice boundaries needn't be word-aligned.
+    pub fn concat(ts: &[&TbitSlice<TW>]) -> Self {
+        let size = ts.iter().fold(0, |size, t| size + t.size());
+        let mut x = Self::zero(size);
+        let mut s = x.slice_mut();
+        for t in ts {
+            t.copy(&s.advance(t.size()));
+        }
+        x
+    }
+
+    /// Create a container with `n` copies of `pattern`, ie. of `n * pattern.size()` tbits.
+    pub fn repeat(pattern: TbitSlice<TW>, n: usize) -> Self {
+        let size = pattern
+            .size()
+            .checked_mul(n)
+            .expect("Tbits size is too large.");
+        let mut x = Self::zero(size);
+        let mut s = x.slice_mut();
+        for _ in 0..n {
+            pattern.copy(&s.advance(pattern.size()));
+        }
+        x
+    }
+
     /// Create a container with `n` tbits and cycle `t` to fill it.
     pub fn cycle_tbits(n: usize, t: &Self) -> Self {
         let mut x = Self::zero(n);
@@ -65,20 +100,62 @@ where
         x
     }
 
+    /// Convert into a container of another word type with the same tbit type,
+    /// eg. from loose `Trit` words into packed `B1T5` words.
+    /// Tbits are converted through a buffer of `TW::Tbit`.
+    pub fn convert_to<UW>(&self) -> Tbits<UW>
+    where
+        UW: BasicTbitWord<Tbit = TW::Tbit>,
+    {
+        let mut ts = vec![TW::ZERO_TBIT; self.size()];
+        self.slice().get_tbits(&mut ts);
+        Tbits::<UW>::from_tbits(&ts)
+    }
+
     pub fn set_zero(&mut self) {
         for tw in self.buf.iter_mut() {
             *tw = TW::ZERO_WORD;
         }
     }
 
-    /// Return a constant slice object to the tbits in the container.
-    pub fn slice(&self) -> TbitSlice<TW> {
-        TbitSlice::from_raw_ptr(self.size(), self.buf.as_ptr())
+    /// Borrow the tbits in the container as a constant slice.
+    ///
+    /// The slice is created from the borrowed buffer rather than a raw pointer, so it
+    /// can't outlive the container:
+    ///
+    /// ```compile_fail
+    /// use iota_streams_core::tbits::{trinary::Trit, TbitSlice, Tbits};
+    /// let view: TbitSlice<Trit> = {
+    ///     let tbits = Tbits::<Trit>::zero(3);
+    ///     tbits.view()
+    /// };
+    /// ```
+    pub fn view(&self) -> TbitSlice<'_, TW> {
+        TbitSlice::from_slice(self.size(), &self.buf[..])
+    }
+
+    /// Borrow the tbits in the container as a mutable slice, see `view`.
+    ///
+    /// ```compile_fail
+    /// use iota_streams_core::tbits::{trinary::Trit, TbitSliceMut, Tbits};
+    /// let view: TbitSliceMut<Trit> = {
+    ///     let mut tbits = Tbits::<Trit>::zero(3);
+    ///     tbits.view_mut()
+    /// };
+    /// ```
+    pub fn view_mut(&mut self) -> TbitSliceMut<'_, TW> {
+        let n = self.size();
+        TbitSliceMut::from_slice_mut(n, &mut self.buf[..])
+    }
+
+    /// Return a constant slice object to the tbits in the container, the same as `view`.
+    pub fn slice(&self) -> TbitSlice<'_, TW> {
+        self.view()
     }
 
-    /// Return a mutable slice object to the tbits in the container.
-    pub fn slice_mut(&mut self) -> TbitSliceMut<TW> {
-        TbitSliceMut::from_raw_ptr(self.size(), self.buf.as_mut_ptr())
+    /// Return a mutable slice object to the tbits in the container, the same as `view_mut`.
+    pub fn slice_mut(&mut self) -> TbitSliceMut<'_, TW> {
+        self.view_mut()
     }
 
     /// Return internal buffer length, ie. the number of tbit words.
@@ -107,6 +184,49 @@ where
         Self::cycle_tbits(n, &Self::from_str(s).unwrap_or(Self::zero(0)))
     }
 
+    /// Create container from string `s` replacing each run of chars that can't be
+    /// converted to tbits (eg. non-ASCII chars) with a single `REPLACEMENT_CHAR`.
+    /// Each char of the resulting string takes `TBITS_PER_CHAR` tbits.
+    pub fn from_str_lossy(s: &str) -> Self {
+        let mut chars = Vec::with_capacity(s.len());
+        let mut t = Self::zero(TW::TBITS_PER_CHAR);
+        let mut replaced = false;
+        for c in s.chars() {
+            if t.slice_mut().put_char(c) {
+                chars.push(c);
+                replaced = false;
+            } else if !replaced {
+                chars.push(TW::REPLACEMENT_CHAR);
+                replaced = true;
+            }
+        }
+
+        let mut x = Self::zero(TW::TBITS_PER_CHAR * chars.len());
+        let mut d = 0;
+        for c in chars {
+            let ok = x.slice_mut().drop(d).put_char(c);
+            debug_assert!(ok);
+            d += TW::TBITS_PER_CHAR;
+        }
+        x
+    }
+
+    /// Parse string `s` of tbit chars (eg. `-0+` for trits), each char encoding a single tbit.
+    /// `None` is returned if `s` contains chars outside of the tbit alphabet.
+    pub fn from_tbits_str(s: &str) -> Option<Self> {
+        let mut x = Self::zero(s.chars().count());
+        if x.slice_mut().from_tbits_str(s) {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Render each tbit as a single char, inverse of `from_tbits_str`.
+    pub fn to_tbits_str(&self) -> String {
+        self.slice().to_tbits_str()
+    }
+
     /// Compare to ASCII string.
     pub fn eq_str(&self, s: &str) -> bool {
         self.slice().eq_str(s)
@@ -120,6 +240,36 @@ where
      */
 }
 
+impl<TW> Tbits<TW>
+where
+    TW: SpongosTbitWord,
+{
+    /// Set all tbits to zero, unlike `set_zero` the writes are not optimized away.
+    pub fn wipe(&mut self) {
+        unsafe { TW::wipe(self.n, 0, self.buf.as_mut_ptr()) }
+    }
+
+    /// Mark container as holding a secret, its tbits are wiped when it's dropped.
+    /// Clones of the container are also wiped.
+    pub fn into_secret(mut self) -> Self {
+        self.wipe = Some(Self::wipe);
+        self
+    }
+
+    /// Is container wiped on drop?
+    pub fn is_secret(&self) -> bool {
+        self.wipe.is_some()
+    }
+}
+
+impl<TW> Drop for Tbits<TW> {
+    fn drop(&mut self) {
+        if let Some(wipe) = self.wipe {
+            wipe(self);
+        }
+    }
+}
+
 impl<TW> FromStr for Tbits<TW>
 where
     TW: StringTbitWord,
@@ -141,7 +291,9 @@ impl<TW> ToString for Tbits<TW>
 where
     TW: StringTbitWord,
 {
-    /// ASCII convert trytes.
+    /// ASCII convert trytes, see `TbitSlice::to_str`.
+    /// The last incomplete char if any is padded with zero tbits, so the string has
+    /// `ceil(size / TBITS_PER_CHAR)` chars.
     fn to_string(&self) -> String {
         self.slice().to_str()
     }
@@ -210,14 +362,84 @@ where
 {
     fn add_assign(&mut self, rhs: &Tbits<TW>) {
         let n = self.n;
-        self.n += rhs.n;
-        self.buf
-            .resize((self.n + TW::SIZE - 1) / TW::SIZE, TW::ZERO_WORD);
+        self.n = checked_offset(self.n, rhs.n).expect("Tbits size is too large.");
+        self.buf.resize(TW::words(self.n), TW::ZERO_WORD);
         let mut right = self.slice_mut().drop(n);
         rhs.slice().copy(&mut right);
     }
 }
 
+impl<TW> Tbits<TW>
+where
+    TW: ByteWord,
+{
+    /// Return the packed representation of the tbits, `buf_len()` words as bytes.
+    /// Tbits of the last byte past `size()` are not part of the container.
+    pub fn as_bytes(&self) -> &[u8] {
+        let n = self.buf.len() * mem::size_of::<TW>();
+        unsafe { std::slice::from_raw_parts(self.buf.as_ptr() as *const u8, n) }
+    }
+
+    /// Mutable variant of `as_bytes`.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        let n = self.buf.len() * mem::size_of::<TW>();
+        unsafe { std::slice::from_raw_parts_mut(self.buf.as_mut_ptr() as *mut u8, n) }
+    }
+}
+
+impl<TW> Tbits<TW>
+where
+    TW: BasicTbitWord,
+{
+    /// Append tbits `ts` growing the buffer by whole words.
+    fn push_tbits(&mut self, ts: &[TW::Tbit]) {
+        let n = self.n;
+        self.n = checked_offset(n, ts.len()).expect("Tbits size is too large.");
+        let m = TW::words(self.n);
+        while self.buf.len() < m {
+            self.buf.push(TW::ZERO_WORD);
+        }
+        self.slice_mut().drop(n).put_tbits(ts);
+    }
+}
+
+/// Tbits are appended in chunks of `TW::SIZE`, the buffer grows by whole words with
+/// the amortized doubling of `Vec`, the last word can be partial.
+impl<TW> Extend<TW::Tbit> for Tbits<TW>
+where
+    TW: BasicTbitWord,
+{
+    fn extend<I: IntoIterator<Item = TW::Tbit>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        let m = TW::words(self.n.saturating_add(iter.size_hint().0));
+        self.buf.reserve(m.saturating_sub(self.buf.len()));
+
+        let mut ts_buf = WordTbits::<TW>::zero();
+        let ts = ts_buf.as_mut();
+        let mut k = 0;
+        for t in iter {
+            ts[k] = t;
+            k += 1;
+            if k == TW::SIZE {
+                self.push_tbits(ts);
+                k = 0;
+            }
+        }
+        self.push_tbits(&ts[..k]);
+    }
+}
+
+impl<TW> std::iter::FromIterator<TW::Tbit> for Tbits<TW>
+where
+    TW: BasicTbitWord,
+{
+    fn from_iter<I: IntoIterator<Item = TW::Tbit>>(iter: I) -> Self {
+        let mut x = Self::new();
+        x.extend(iter);
+        x
+    }
+}
+
 impl<'a, TW: 'a> TbitSlice<'a, TW>
 where
     TW: BasicTbitWord,
@@ -142,6 +142,345 @@ where
     assert_eq!(&(&(a + b) + b) + a, abba);
 }
 
+/// Compare `add` against tbit-by-tbit `add_tbits` for various alignments and lengths.
+pub fn add_word_tbits<TW>(a: &Tbits<TW>, b: &Tbits<TW>)
+where
+    TW: SpongosTbitWord,
+    TW::Tbit: fmt::Display,
+{
+    assert_eq!(a.size(), b.size());
+    let d = 2 * TW::SIZE;
+    for ds in 0..d {
+        for dx in 0..d {
+            for n in 0..a.size() - d {
+                let mut s1 = a.clone();
+                let mut s2 = a.clone();
+                unsafe {
+                    TW::add(ds, s1.slice_mut().p, n, dx, b.slice().p);
+                    TW::add_tbits(ds, s2.slice_mut().p, n, dx, b.slice().p);
+                }
+                assert_eq!(s1, s2);
+            }
+        }
+    }
+}
+
+/// Compare `get_tbits_into`/`put_tbits_from` against repeated `get_tbit`/`put_tbit` calls and
+/// the batched `setx_*`/`sety_*` ops against their tbit by tbit definitions for various
+/// alignments and lengths, `a` and `b` must have the same size.
+/// All short lengths are checked, longer ones only around the chunk boundaries of the
+/// `SCRATCH_TBITS` stack buffers, so inputs should be longer than `2 * SCRATCH_TBITS`.
+pub fn batch_tbits<TW>(a: &Tbits<TW>, b: &Tbits<TW>)
+where
+    TW: SpongosTbitWord,
+    TW::Tbit: fmt::Display + fmt::Debug,
+{
+    type MutOp<TW> = unsafe fn(usize, *mut TW, usize, usize, *mut TW);
+    type IoOp<TW> = unsafe fn(usize, *mut TW, usize, usize, *const TW, usize, *mut TW);
+    fn get<TW: BasicTbitWord>(t: &Tbits<TW>, d: usize, n: usize) -> Vec<TW::Tbit> {
+        (0..n)
+            .map(|i| unsafe { TW::get_tbit(d + i, t.slice().p) })
+            .collect()
+    }
+
+    assert_eq!(a.size(), b.size());
+    let d = 2 * TW::SIZE;
+    let m = a.size() - d;
+    let mut lens: Vec<usize> = (0..m.min(64)).collect();
+    lens.extend(
+        [
+            SCRATCH_TBITS - 1,
+            SCRATCH_TBITS,
+            SCRATCH_TBITS + 1,
+            2 * SCRATCH_TBITS + 1,
+            m,
+        ]
+        .iter()
+        .filter(|n| 64 <= **n && **n <= m),
+    );
+    for ds in 0..d {
+        for dx in 0..d {
+            for &n in lens.iter() {
+                let ts = get(a, ds, n);
+                let tx = get(b, dx, n);
+
+                let mut t = vec![TW::ZERO_TBIT; n];
+                unsafe { TW::get_tbits_into(ds, a.slice().p, &mut t) };
+                assert_eq!(ts, t, "ds={} n={}", ds, n);
+
+                let mut s1 = a.clone();
+                let mut s2 = a.clone();
+                unsafe {
+                    TW::put_tbits_from(ds, s1.slice_mut().p, &tx);
+                    for (i, t) in tx.iter().enumerate() {
+                        TW::put_tbit(ds + i, s2.slice_mut().p, *t);
+                    }
+                }
+                assert_eq!(s1, s2, "ds={} n={}", ds, n);
+
+                let add: Vec<_> = ts
+                    .iter()
+                    .zip(tx.iter())
+                    .map(|(s, x)| TW::tbit_add(*x, *s))
+                    .collect();
+                let sub: Vec<_> = ts
+                    .iter()
+                    .zip(tx.iter())
+                    .map(|(s, y)| TW::tbit_sub(*y, *s))
+                    .collect();
+                // Op, expected state and expected output.
+                let mut_ops = [
+                    (TW::setx_add_mut as MutOp<TW>, &tx[..], &add[..]),
+                    (TW::setx_sub_mut, &sub[..], &sub[..]),
+                    (TW::sety_add_mut, &add[..], &add[..]),
+                    (TW::sety_sub_mut, &tx[..], &sub[..]),
+                ];
+                for (op, es, ey) in mut_ops.iter() {
+                    let mut s = a.clone();
+                    let mut x = b.clone();
+                    unsafe { op(ds, s.slice_mut().p, n, dx, x.slice_mut().p) };
+                    assert_eq!(*es, &get(&s, ds, n)[..], "ds={} dx={} n={}", ds, dx, n);
+                    assert_eq!(*ey, &get(&x, dx, n)[..], "ds={} dx={} n={}", ds, dx, n);
+                }
+                let io_ops = [
+                    (TW::setx_add as IoOp<TW>, &tx[..], &add[..]),
+                    (TW::setx_sub, &sub[..], &sub[..]),
+                    (TW::sety_add, &add[..], &add[..]),
+                    (TW::sety_sub, &tx[..], &sub[..]),
+                ];
+                for (op, es, ey) in io_ops.iter() {
+                    let mut s = a.clone();
+                    let mut y = Tbits::<TW>::zero(a.size());
+                    unsafe { op(ds, s.slice_mut().p, n, dx, b.slice().p, ds, y.slice_mut().p) };
+                    assert_eq!(*es, &get(&s, ds, n)[..], "ds={} dx={} n={}", ds, dx, n);
+                    assert_eq!(*ey, &get(&y, ds, n)[..], "ds={} dx={} n={}", ds, dx, n);
+                }
+            }
+        }
+    }
+}
+
+/// Compare slice `add`/`sub` against the scalar `tbit_add`/`tbit_sub` loop
+/// for random alignments and lengths, `a` and `b` must have the same size.
+pub fn slice_add_sub<TW>(a: &Tbits<TW>, b: &Tbits<TW>)
+where
+    TW: SpongosTbitWord,
+    TW::Tbit: fmt::Display + fmt::Debug,
+{
+    use rand::Rng;
+
+    assert_eq!(a.size(), b.size());
+    let mut rng = rand::thread_rng();
+    let d = 2 * TW::SIZE;
+    for _ in 0..200 {
+        let ds = rng.gen_range(0, d);
+        let dx = rng.gen_range(0, d);
+        let n = rng.gen_range(0, a.size() - d);
+
+        let mut ts = vec![TW::ZERO_TBIT; n];
+        let mut tx = vec![TW::ZERO_TBIT; n];
+        a.slice().drop(ds).get_tbits(&mut ts);
+        b.slice().drop(dx).get_tbits(&mut tx);
+        let sum: Vec<_> = ts
+            .iter()
+            .zip(tx.iter())
+            .map(|(s, x)| TW::tbit_add(*s, *x))
+            .collect();
+        let diff: Vec<_> = ts
+            .iter()
+            .zip(tx.iter())
+            .map(|(s, x)| TW::tbit_sub(*s, *x))
+            .collect();
+
+        let mut s = a.clone();
+        s.slice_mut()
+            .drop(ds)
+            .take(n)
+            .add(b.slice().drop(dx).take(n));
+        let mut t = vec![TW::ZERO_TBIT; n];
+        s.slice().drop(ds).get_tbits(&mut t);
+        assert_eq!(sum, t, "ds={} dx={} n={}", ds, dx, n);
+        assert!(s.slice().take(ds) == a.slice().take(ds));
+        assert!(s.slice().drop(ds + n) == a.slice().drop(ds + n));
+
+        s.slice_mut()
+            .drop(ds)
+            .take(n)
+            .sub(b.slice().drop(dx).take(n));
+        assert_eq!(*a, s, "ds={} dx={} n={}", ds, dx, n);
+
+        s.slice_mut()
+            .drop(ds)
+            .take(n)
+            .sub(b.slice().drop(dx).take(n));
+        s.slice().drop(ds).get_tbits(&mut t);
+        assert_eq!(diff, t, "ds={} dx={} n={}", ds, dx, n);
+    }
+}
+
+/// Check `equals_ct` on equal slices and slices differing in a single tbit, `a` and `b` must differ in every tbit.
+pub fn equals_ct<TW>(a: &Tbits<TW>, b: &Tbits<TW>)
+where
+    TW: SpongosTbitWord,
+    TW::Tbit: fmt::Display,
+{
+    assert_eq!(a.size(), b.size());
+    let n = a.size();
+    for d in 0..TW::SIZE {
+        let m = n - d;
+        unsafe {
+            assert!(TW::equals_ct(m, d, a.slice().p, d, a.slice().p));
+            assert!(TW::equals_ct(0, d, a.slice().p, d, b.slice().p));
+        }
+        for i in 0..m {
+            let mut c = a.clone();
+            unsafe {
+                TW::put_tbit(d + i, c.slice_mut().p, TW::get_tbit(d + i, b.slice().p));
+                assert!(!TW::equals_ct(m, d, a.slice().p, d, c.slice().p));
+                assert!(TW::equals_ct(i, d, a.slice().p, d, c.slice().p));
+                assert_eq!(
+                    TW::equals(m, d, a.slice().p, d, c.slice().p),
+                    TW::equals_ct(m, d, a.slice().p, d, c.slice().p)
+                );
+            }
+        }
+    }
+
+    // Slices of different sizes are not equal.
+    assert!(a.slice().equals_ct(&a.slice()));
+    if n > 0 {
+        assert!(!a.slice().equals_ct(&a.slice().drop(1)));
+        assert!(!a.slice().take(n - 1).equals_ct(&a.slice()));
+    }
+}
+
+/// Copy subslices of `a` to different offsets and compare them with `equals`,
+/// including slices not aligned to word boundaries. `a` and `b` must differ in every tbit.
+pub fn copy_equals<TW>(a: &Tbits<TW>, b: &Tbits<TW>)
+where
+    TW: BasicTbitWord,
+    TW::Tbit: fmt::Display,
+{
+    assert_eq!(a.size(), b.size());
+    let d = 2 * TW::SIZE;
+    for dx in 0..d {
+        for dy in 0..d {
+            for n in 0..a.size() - d {
+                let mut c = Tbits::<TW>::zero(a.size());
+                unsafe {
+                    TW::copy(n, dx, a.slice().p, dy, c.slice_mut().p);
+                    assert!(TW::equals(n, dx, a.slice().p, dy, c.slice().p));
+                    if 0 < n {
+                        let i = n / 2;
+                        TW::put_tbit(dy + i, c.slice_mut().p, TW::get_tbit(dx + i, b.slice().p));
+                        assert!(!TW::equals(n, dx, a.slice().p, dy, c.slice().p));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Interleave `a` and `b` element-wise with slice copies, then deinterleave
+/// with `copy_strided` and interleave back.
+pub fn copy_strided<TW>(a: &Tbits<TW>, b: &Tbits<TW>, elem: usize)
+where
+    TW: BasicTbitWord,
+    TW::Tbit: fmt::Display,
+{
+    assert_eq!(a.size(), b.size());
+    assert_eq!(0, a.size() % elem);
+    let count = a.size() / elem;
+
+    for off in 0..2 * TW::SIZE {
+        let mut ab = Tbits::<TW>::zero(off + 2 * a.size());
+        for i in 0..count {
+            let ys = ab.slice_mut().drop(off + 2 * i * elem);
+            a.slice().drop(i * elem).take(elem).copy(&ys.take(elem));
+            let ys = ab.slice_mut().drop(off + 2 * i * elem + elem);
+            b.slice().drop(i * elem).take(elem).copy(&ys.take(elem));
+        }
+
+        let mut a2 = Tbits::<TW>::zero(a.size());
+        let mut b2 = Tbits::<TW>::zero(b.size());
+        let x = ab.slice();
+        unsafe {
+            TW::copy_strided(count, elem, 2 * elem, off, x.p, elem, 0, a2.slice_mut().p);
+            TW::copy_strided(
+                count,
+                elem,
+                2 * elem,
+                off + elem,
+                x.p,
+                elem,
+                0,
+                b2.slice_mut().p,
+            );
+        }
+        assert_eq!(*a, a2);
+        assert_eq!(*b, b2);
+
+        let mut ab2 = Tbits::<TW>::zero(ab.size());
+        let y = ab2.slice_mut();
+        unsafe {
+            TW::copy_strided(count, elem, elem, 0, a2.slice().p, 2 * elem, off, y.p);
+            TW::copy_strided(
+                count,
+                elem,
+                elem,
+                0,
+                b2.slice().p,
+                2 * elem,
+                off + elem,
+                y.p,
+            );
+        }
+        assert_eq!(ab, ab2);
+    }
+}
+
+/// Lossy conversion of `s` must result in `expected` string.
+pub fn str_lossy<TW>(s: &str, expected: &str)
+where
+    TW: StringTbitWord,
+{
+    let t = Tbits::<TW>::from_str_lossy(s);
+    assert_eq!(TW::TBITS_PER_CHAR * expected.chars().count(), t.size());
+    assert_eq!(expected, t.to_string());
+    assert_eq!(t, Tbits::<TW>::from_str(expected).unwrap());
+}
+
+/// Round-trip random strings over tbit `alphabet` through `from_tbits_str`/`to_tbits_str`.
+pub fn tbits_str<TW>(alphabet: &str)
+where
+    TW: StringTbitWord,
+    TW::Tbit: fmt::Display + fmt::Debug,
+{
+    use rand::Rng;
+    let alphabet: Vec<char> = alphabet.chars().collect();
+    let mut rng = rand::thread_rng();
+    for n in 0..100 {
+        let s: String = (0..n)
+            .map(|_| alphabet[rng.gen_range(0, alphabet.len())])
+            .collect();
+        let t = Tbits::<TW>::from_tbits_str(&s).unwrap();
+        assert_eq!(n, t.size());
+        assert_eq!(s, t.to_tbits_str());
+        assert_eq!(s, format!("{}", t.slice().display_tbits()));
+        assert_eq!(t, Tbits::<TW>::from_tbits_str(&t.to_tbits_str()).unwrap());
+
+        // Slices at non-zero offsets.
+        let d = rng.gen_range(0, n + 1);
+        assert_eq!(s[d..], t.slice().drop(d).to_tbits_str()[..]);
+    }
+
+    assert!(Tbits::<TW>::from_tbits_str("9").is_none());
+    let s: String = alphabet.iter().collect();
+    assert!(Tbits::<TW>::from_tbits_str(&(s.clone() + "x")).is_none());
+    let mut t = Tbits::<TW>::zero(alphabet.len() + 1);
+    assert!(!t.slice_mut().from_tbits_str(&s));
+}
+
 pub fn get_put_char<TW>(alphabet_str: &str)
 where
     TW: StringTbitWord,
@@ -197,6 +536,397 @@ where
     }
 }
 
+/// Check that `split_at` halves cover `a` exactly and `split_at_mut` halves don't overlap,
+/// `a` and `b` must have the same size.
+pub fn split_at<TW>(a: &Tbits<TW>, b: &Tbits<TW>)
+where
+    TW: BasicTbitWord,
+    TW::Tbit: fmt::Display + fmt::Debug,
+{
+    let n = a.size();
+    assert_eq!(n, b.size());
+    for d in 0..=n {
+        let (head, tail) = a.slice().split_at(d);
+        assert_eq!(d, head.size(), "d={}", d);
+        assert_eq!(n - d, tail.size(), "d={}", d);
+        assert_eq!(*a, Tbits::from_slices(&[head, tail]), "d={}", d);
+
+        let mut c = Tbits::<TW>::zero(n);
+        {
+            let mut s = c.slice_mut();
+            let (head, tail) = s.split_at_mut(d);
+            a.slice().take(d).copy(&head);
+            b.slice().drop(d).copy(&tail);
+        }
+        assert!(c.slice().take(d) == a.slice().take(d), "d={}", d);
+        assert!(c.slice().drop(d) == b.slice().drop(d), "d={}", d);
+    }
+}
+
+/// Shift `a` left and right within the same buffer with `copy_within`
+/// by distances both multiple and not multiple of `TW::SIZE`.
+pub fn copy_within<TW>(a: &Tbits<TW>)
+where
+    TW: BasicTbitWord,
+    TW::Tbit: fmt::Display + fmt::Debug,
+{
+    let ts: Vec<TW::Tbit> = a.slice().iter().collect();
+    let n = ts.len();
+    let shifts = [1, 2, 3, 7, TW::SIZE, 2 * TW::SIZE, 3 * TW::SIZE + 1];
+    for &shift in shifts.iter() {
+        for &start in [0, 1, TW::SIZE - 1].iter() {
+            if n < start + shift {
+                continue;
+            }
+            let m = n - start - shift;
+            for &(src, dest) in [(start, start + shift), (start + shift, start)].iter() {
+                let mut x = a.clone();
+                x.slice_mut().copy_within(src..src + m, dest);
+                let mut expected = ts.to_vec();
+                expected.copy_within(src..src + m, dest);
+                assert_eq!(
+                    Tbits::<TW>::from_tbits(&expected),
+                    x,
+                    "src={} dest={} m={}",
+                    src,
+                    dest,
+                    m
+                );
+            }
+        }
+    }
+}
+
+/// Check that collecting `a` and extending its prefixes with the rest of `a` tbit by tbit
+/// give the same tbits as putting them one by one.
+pub fn extend_collect<TW>(a: &Tbits<TW>)
+where
+    TW: BasicTbitWord,
+    TW::Tbit: fmt::Display + fmt::Debug,
+{
+    let n = a.size();
+    let mut b = Tbits::<TW>::zero(n);
+    for (i, t) in a.slice().iter().enumerate() {
+        b.slice_mut().drop(i).take(1).put_tbits(&[t]);
+    }
+    assert_eq!(*a, b);
+
+    let ts: Vec<TW::Tbit> = a.slice().iter().collect();
+    assert_eq!(b, ts.iter().cloned().collect::<Tbits<TW>>());
+    for d in 0..=n {
+        let mut x = Tbits::<TW>::from_slice(a.slice().take(d));
+        x.extend(ts[d..].iter().cloned());
+        assert_eq!(b, x, "d={}", d);
+        assert_eq!(TW::words(n), x.buf_len(), "d={}", d);
+
+        // Iterators with no size hint.
+        let mut x = Tbits::<TW>::from_slice(a.slice().take(d));
+        x.extend(ts[d..].iter().cloned().filter(|_| true));
+        assert_eq!(b, x, "d={}", d);
+    }
+}
+
+/// Round-trip boundary and random 128-bit values within `[imin, imax]` and `[0, umax]`
+/// through `n` tbits at non-aligned offsets, directly and via `copy`.
+pub fn get_put_128<TW>(n: usize, imin: i128, imax: i128, umax: u128)
+where
+    TW: IntTbitWord,
+{
+    use rand::Rng;
+
+    let mut rng = rand::thread_rng();
+    let mut is = vec![imin, imax, 0];
+    let mut us = vec![0, umax];
+    for _ in 0..20 {
+        is.push(rng.gen_range(imin, imax));
+        us.push(rng.gen_range(0, umax));
+    }
+
+    let mut t = Tbits::<TW>::zero(n + 7);
+    let mut c = Tbits::<TW>::zero(n + 7);
+    for d in 0..3 {
+        for &i in is.iter() {
+            t.slice_mut().drop(d).take(n).put_i128(i);
+            assert_eq!(i, t.slice().drop(d).take(n).get_i128(), "n={} d={}", n, d);
+            t.slice()
+                .drop(d)
+                .take(n)
+                .copy(&c.slice_mut().drop(d + 5).take(n));
+            assert_eq!(
+                i,
+                c.slice().drop(d + 5).take(n).get_i128(),
+                "n={} d={}",
+                n,
+                d
+            );
+        }
+        for &u in us.iter() {
+            t.slice_mut().drop(d).take(n).put_u128(u);
+            assert_eq!(u, t.slice().drop(d).take(n).get_u128(), "n={} d={}", n, d);
+            t.slice()
+                .drop(d)
+                .take(n)
+                .copy(&c.slice_mut().drop(d + 5).take(n));
+            assert_eq!(
+                u,
+                c.slice().drop(d + 5).take(n).get_u128(),
+                "n={} d={}",
+                n,
+                d
+            );
+        }
+    }
+}
+
+/// Check `try_put_*` accepts values at the boundaries of `n` tbits and rejects values just outside.
+pub fn try_put_boundaries<TW>(n: usize, imin: isize, imax: isize, umax: usize)
+where
+    TW: IntTbitWord,
+    TW::Tbit: fmt::Display + fmt::Debug,
+{
+    let mut t = Tbits::<TW>::zero(n);
+    for &i in [imin, imax].iter() {
+        assert_eq!(Ok(()), t.slice_mut().try_put_isize(i), "n={} i={}", n, i);
+        assert_eq!(i, t.slice().get_isize(), "n={}", n);
+    }
+    for &i in [imin - 1, imax + 1].iter() {
+        assert_eq!(
+            Err(RangeError { n }),
+            t.slice_mut().try_put_isize(i),
+            "n={} i={}",
+            n,
+            i
+        );
+        assert_eq!(imax, t.slice().get_isize(), "n={}", n);
+    }
+
+    for &u in [0, umax].iter() {
+        assert_eq!(Ok(()), t.slice_mut().try_put_usize(u), "n={} u={}", n, u);
+        assert_eq!(u, t.slice().get_usize(), "n={}", n);
+    }
+    assert_eq!(
+        Err(RangeError { n }),
+        t.slice_mut().try_put_usize(umax + 1),
+        "n={}",
+        n
+    );
+    assert_eq!(umax, t.slice().get_usize(), "n={}", n);
+}
+
+/// Check that partial `get_tbits`/`put_tbits` only touch the requested tbits and
+/// `copy_from` is the same as `copy`, `a` must be non-empty.
+pub fn safe_slice_ops<TW>(a: &Tbits<TW>)
+where
+    TW: BasicTbitWord,
+    TW::Tbit: fmt::Display + fmt::Debug,
+{
+    let n = a.size();
+    let mut ts = vec![TW::ZERO_TBIT; n];
+    a.slice().get_tbits(&mut ts);
+    for k in 0..n {
+        let mut us = vec![TW::ZERO_TBIT; k];
+        a.slice().get_tbits(&mut us);
+        assert_eq!(&ts[..k], &us[..]);
+
+        let mut b = Tbits::<TW>::zero(n);
+        b.slice_mut().put_tbits(&ts[..k]);
+        assert_eq!(a.slice().take(k), b.slice().take(k));
+        assert!(b.slice().drop(k).iter().all(|t| t == TW::ZERO_TBIT));
+    }
+
+    let mut b = Tbits::<TW>::zero(n);
+    b.slice_mut().copy_from(&a.slice());
+    assert_eq!(*a, b);
+}
+
+/// Safe slice operations must panic on out-of-range sizes: each op is run on a slice of
+/// `n` tbits with an array of `n + 1` tbits.
+pub fn out_of_range<TW>(n: usize)
+where
+    TW: BasicTbitWord,
+{
+    type Op = (&'static str, fn(usize));
+    let ops: [Op; 7] = [
+        ("take", |n| {
+            Tbits::<TW>::zero(n).slice().take(n + 1);
+        }),
+        ("drop", |n| {
+            Tbits::<TW>::zero(n).slice_mut().drop(n + 1);
+        }),
+        ("copy", |n| {
+            Tbits::<TW>::zero(n)
+                .slice()
+                .copy(&Tbits::<TW>::zero(n + 1).slice_mut())
+        }),
+        ("copy_from", |n| {
+            Tbits::<TW>::zero(n)
+                .slice_mut()
+                .copy_from(&Tbits::<TW>::zero(n + 1).slice())
+        }),
+        ("get_tbits", |n| {
+            Tbits::<TW>::zero(n)
+                .slice()
+                .get_tbits(&mut vec![TW::ZERO_TBIT; n + 1])
+        }),
+        ("put_tbits", |n| {
+            Tbits::<TW>::zero(n)
+                .slice_mut()
+                .put_tbits(&vec![TW::ZERO_TBIT; n + 1])
+        }),
+        ("eq", |n| {
+            Tbits::<TW>::zero(n)
+                .slice()
+                .equals(&Tbits::<TW>::zero(n + 1).slice());
+        }),
+    ];
+    for (name, op) in ops.iter() {
+        assert!(
+            std::panic::catch_unwind(|| op(n)).is_err(),
+            "`{}` must panic on out-of-range size",
+            name
+        );
+    }
+}
+
+/// Check tbit iterators against `get_tbits` and `set_zero`.
+pub fn iter<TW>(a: &Tbits<TW>)
+where
+    TW: BasicTbitWord,
+    TW::Tbit: fmt::Display + fmt::Debug,
+{
+    let mut ts = vec![TW::ZERO_TBIT; a.size()];
+    a.slice().get_tbits(&mut ts);
+    for d in 0..a.size() {
+        let s = a.slice().drop(d);
+        assert_eq!(s.size(), s.iter().count());
+        assert_eq!(s.size(), s.iter().len());
+        assert_eq!(ts[d..], s.iter().collect::<Vec<_>>()[..]);
+
+        let mut b = a.clone();
+        for mut t in b.slice_mut().drop(d).iter_mut() {
+            t.set(TW::ZERO_TBIT);
+        }
+        let mut c = a.clone();
+        c.slice_mut().drop(d).set_zero();
+        assert_eq!(c, b, "d={}", d);
+        assert!(b.slice().drop(d).iter().all(|t| t == TW::ZERO_TBIT));
+        assert!(b
+            .slice_mut()
+            .iter_mut()
+            .zip(ts.iter())
+            .take(d)
+            .all(|(t, u)| t.get() == *u));
+    }
+}
+
+/// Check `Tbits::concat` and `Tbits::repeat` element-wise, `a` must have at least 8 tbits.
+pub fn concat_repeat<TW>(a: &Tbits<TW>)
+where
+    TW: BasicTbitWord,
+    TW::Tbit: fmt::Display + fmt::Debug,
+{
+    let n = a.size();
+    let mut ts = vec![TW::ZERO_TBIT; n];
+    a.slice().get_tbits(&mut ts);
+
+    // Unaligned starts and sizes.
+    let s0 = a.slice().drop(1).take(3);
+    let s1 = a.slice().drop(2).take(n - 3);
+    let s2 = a.slice().drop(5);
+    let c = Tbits::concat(&[&s0, &s1, &s2]);
+    let expected: Vec<_> = ts[1..4]
+        .iter()
+        .chain(ts[2..n - 1].iter())
+        .chain(ts[5..].iter())
+        .cloned()
+        .collect();
+    assert_eq!(expected, c.slice().iter().collect::<Vec<_>>());
+    assert_eq!(Tbits::<TW>::new(), Tbits::concat(&[]));
+
+    let pattern = a.slice().drop(1).take(2);
+    let r = Tbits::repeat(pattern, 100);
+    assert_eq!(200, r.size());
+    for (i, t) in r.slice().iter().enumerate() {
+        assert_eq!(ts[1 + i % 2], t, "i={}", i);
+    }
+    assert_eq!(Tbits::<TW>::new(), Tbits::repeat(pattern, 0));
+    assert_eq!(Tbits::<TW>::new(), Tbits::repeat(a.slice().take(0), 5));
+}
+
+/// Check `TbitSlice::chunks` on a 100-tbit slice built by repeating `a`, `a` must be non-empty.
+pub fn chunks<TW>(a: &Tbits<TW>)
+where
+    TW: BasicTbitWord,
+    TW::Tbit: fmt::Display + fmt::Debug,
+{
+    let r = Tbits::repeat(a.slice(), (100 + a.size() - 1) / a.size());
+    let s = r.slice().take(100);
+
+    let cs = s.chunks(30);
+    assert_eq!(4, cs.len());
+    let sizes: Vec<_> = cs.map(|c| c.size()).collect();
+    assert_eq!(vec![30, 30, 30, 10], sizes);
+
+    let parts: Vec<_> = s.chunks(30).collect();
+    let refs: Vec<_> = parts.iter().collect();
+    assert_eq!(s, Tbits::concat(&refs).slice());
+
+    assert_eq!(1, s.chunks(100).count());
+    assert_eq!(
+        vec![100],
+        s.chunks(1000).map(|c| c.size()).collect::<Vec<_>>()
+    );
+    assert_eq!(0, s.take(0).chunks(30).count());
+}
+
+/// Check that the packed words of the word-aligned prefix of `ts` convert back losslessly.
+pub fn try_from_words<TW>(ts: &[TW::Tbit])
+where
+    TW: PackedTbitWord,
+{
+    use std::convert::TryFrom;
+    let n = ts.len() - ts.len() % TW::SIZE;
+    let x = Tbits::<TW>::from_tbits(&ts[..n]);
+    let words = encoding::to_words(x.slice());
+    assert_eq!(Ok(x), Tbits::<TW>::try_from(&words[..]));
+    assert_eq!(Ok(Tbits::<TW>::zero(0)), Tbits::<TW>::try_from(&[][..]));
+}
+
+/// Check lossless hex and base64 round trips of all prefixes of `ts`.
+pub fn encoding_round_trip<TW>(ts: &[TW::Tbit])
+where
+    TW: PackedTbitWord,
+    TW::Tbit: fmt::Display,
+{
+    for n in 0..=ts.len() {
+        let x = Tbits::<TW>::from_tbits(&ts[..n]);
+        let hex = x.to_hex();
+        assert_eq!(Some(&x), Tbits::<TW>::from_hex(&hex).as_ref(), "n={}", n);
+        assert_eq!(
+            Some(&x),
+            Tbits::<TW>::from_hex(&hex.to_uppercase()).as_ref(),
+            "n={}",
+            n
+        );
+        let b64 = x.to_base64();
+        assert_eq!(Some(&x), Tbits::<TW>::from_base64(&b64).as_ref(), "n={}", n);
+
+        // Truncated encodings are rejected.
+        assert_eq!(
+            None,
+            Tbits::<TW>::from_hex(&hex[..hex.len() - 2]),
+            "n={}",
+            n
+        );
+        assert_eq!(
+            None,
+            Tbits::<TW>::from_base64(&b64[..b64.len() - 4]),
+            "n={}",
+            n
+        );
+    }
+}
+
 /*
 fn f() {
     let mut ts = Tbits::<TW>::zero(15);
@@ -272,6 +1002,156 @@ fn slices() {
 }
  */
 
+/// Check `split_word_at` against the tbits `ts` of a single word.
+pub fn split_word_at<TW>(ts: &[TW::Tbit])
+where
+    TW: BasicTbitWord,
+    TW::Tbit: fmt::Debug,
+{
+    assert_eq!(TW::SIZE, ts.len());
+    let x = unsafe { TW::word_from_tbits(ts.as_ptr()) };
+    for n in 0..=TW::SIZE {
+        let (h, t) = TW::split_word_at(x, n);
+        let mut hs = WordTbits::<TW>::zero();
+        let mut rs = WordTbits::<TW>::zero();
+        unsafe {
+            TW::word_to_tbits(h, hs.as_mut().as_mut_ptr());
+            TW::word_to_tbits(t, rs.as_mut().as_mut_ptr());
+        }
+        assert_eq!(&ts[..n], &hs.as_ref()[..n], "n={}", n);
+        assert!(
+            hs.as_ref()[n..].iter().all(|t| *t == TW::ZERO_TBIT),
+            "n={}",
+            n
+        );
+        assert_eq!(&ts[n..], &rs.as_ref()[..TW::SIZE - n], "n={}", n);
+        assert!(
+            rs.as_ref()[TW::SIZE - n..]
+                .iter()
+                .all(|t| *t == TW::ZERO_TBIT),
+            "n={}",
+            n
+        );
+    }
+}
+
+pub fn checked_offsets<TW>()
+where
+    TW: BasicTbitWord,
+{
+    let max = std::usize::MAX;
+    assert_eq!(Some(max), checked_offset(max - 1, 1));
+    assert_eq!(None, checked_offset(max, 1));
+    assert_eq!(None, checked_offset(1, max));
+
+    assert_eq!(0, TW::words(0));
+    assert_eq!(1, TW::words(1));
+    assert_eq!(2, TW::words(TW::SIZE + 1));
+    assert_eq!(
+        max / TW::SIZE + (max % TW::SIZE != 0) as usize,
+        TW::words(max)
+    );
+    assert_eq!(Some(3 * TW::SIZE), TW::checked_tbits(3));
+    if TW::SIZE > 1 {
+        assert_eq!(None, TW::checked_tbits(max));
+    }
+    assert!(Tbits::<TW>::try_zero(max).is_none());
+
+    // Ranges ending exactly at the end of the buffer are valid.
+    let words = 3;
+    let end = 3 * TW::SIZE;
+    assert_eq!(Ok(end), TW::checked_words_range(0, end, words));
+    assert_eq!(Ok(end), TW::checked_words_range(end - 1, 1, words));
+    assert_eq!(Ok(end), TW::checked_words_range(end, 0, words));
+    assert_eq!(
+        Err(OffsetError {
+            d: end,
+            n: 1,
+            size: end
+        }),
+        TW::checked_words_range(end, 1, words)
+    );
+    assert!(TW::checked_words_range(1, max, words).is_err());
+    assert_eq!(Ok(max), TW::checked_words_range(max - 1, 1, max));
+
+    // Large offsets must not wrap around and pass range checks.
+    let t = Tbits::<TW>::zero(10);
+    let s = t.slice().drop(5);
+    assert!(s.drop_min(max).is_empty());
+    assert_eq!(5, s.take_min(max).size());
+    let r = SliceRange::new(10, 5);
+    assert!(std::panic::catch_unwind(|| r.take(max)).is_err());
+    assert!(std::panic::catch_unwind(|| r.drop(max)).is_err());
+    assert!(std::panic::catch_unwind(|| r.split_at(max - 4)).is_err());
+
+    // Checked variants return errors instead.
+    let err = OffsetError {
+        d: 5,
+        n: max,
+        size: 10,
+    };
+    assert_eq!(Err(err), r.try_take(max).map(|r| r.size()));
+    assert_eq!(Err(err), r.try_drop(max).map(|r| r.size()));
+    assert_eq!(Ok(5), r.try_take(5).map(|r| r.size()));
+    assert_eq!(Ok(0), r.try_drop(5).map(|r| r.size()));
+    assert!(r.try_split_at(max - 4).is_err());
+    assert!(s.try_take(6).is_err());
+    assert!(s.try_drop(6).is_err());
+    assert!(s.try_split_at(6).is_err());
+    assert_eq!(Ok(5), s.try_take(5).map(|s| s.size()));
+
+    let mut u = Tbits::<TW>::zero(6);
+    let mut us = u.slice_mut();
+    assert!(us.try_advance(7).is_err());
+    assert_eq!(6, us.size());
+    assert_eq!(Ok(6), us.try_advance(6).map(|s| s.size()));
+
+    let mut ts = vec![TW::ZERO_TBIT; 6];
+    assert_eq!(
+        Err(OffsetError {
+            d: 5,
+            n: 6,
+            size: 10
+        }),
+        s.try_get_tbits(&mut ts)
+    );
+    let v = Tbits::<TW>::zero(6);
+    assert_eq!(
+        Err(OffsetError {
+            d: 0,
+            n: 6,
+            size: 5
+        }),
+        s.try_copy(&u.slice_mut())
+    );
+    assert_eq!(
+        Err(OffsetError {
+            d: 0,
+            n: 6,
+            size: 5
+        }),
+        u.slice_mut().try_copy_from(&s)
+    );
+    assert_eq!(
+        Err(OffsetError {
+            d: 0,
+            n: 6,
+            size: 5
+        }),
+        s.try_equals(&v.slice())
+    );
+    assert_eq!(
+        Err(OffsetError {
+            d: 1,
+            n: 6,
+            size: 6
+        }),
+        u.slice_mut().drop(1).try_put_tbits(&ts)
+    );
+    assert_eq!(Ok(true), u.slice().try_equals(&v.slice()));
+    assert_eq!(Ok(()), v.slice().try_copy(&u.slice_mut()));
+}
+
 /*
 fn str_tbits<TW>(m: usize, n: usize, ts: &[TW::Tbit])
 where
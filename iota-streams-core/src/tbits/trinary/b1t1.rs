@@ -2,7 +2,9 @@
 
 use super::defs::*;
 use super::word::TritWord;
-use crate::tbits::word::{BasicTbitWord, IntTbitWord, SpongosTbitWord, StringTbitWord};
+use crate::tbits::word::{
+    BasicTbitWord, IntTbitWord, PackedTbitWord, SpongosTbitWord, StringTbitWord,
+};
 use std::convert::TryFrom;
 
 impl BasicTbitWord for Trit {
@@ -49,6 +51,7 @@ impl BasicTbitWord for Trit {
 
 impl StringTbitWord for Trit {
     const TBITS_PER_CHAR: usize = 3;
+    const REPLACEMENT_CHAR: char = '9';
 
     unsafe fn put_char(s: usize, mut d: usize, p: *mut Self, c: char) -> bool {
         if let Ok(t) = Trint3::try_from(c) {
@@ -80,26 +83,51 @@ impl StringTbitWord for Trit {
         }
         char::from(Trint3::from(ts))
     }
+
+    fn tbit_to_char(t: Trit) -> char {
+        match Trint1::from(t).0 {
+            -1 => '-',
+            0 => '0',
+            _ => '+',
+        }
+    }
+
+    fn tbit_from_char(c: char) -> Option<Trit> {
+        match c {
+            '-' => Some(Trint1(-1).into()),
+            '0' => Some(Trint1(0).into()),
+            '+' => Some(Trint1(1).into()),
+            _ => None,
+        }
+    }
+}
+
+/// Balanced ternary digit `r` and quotient `q` such that `i = 3*q + r`.
+/// Unlike `(i - r) / 3` it doesn't overflow at `isize::MIN`.
+fn balanced_div3(i: isize) -> (i8, isize) {
+    match i % 3 {
+        2 => (-1, i / 3 + 1),
+        -2 => (1, i / 3 - 1),
+        r => (r as i8, i / 3),
+    }
 }
 
 impl IntTbitWord for Trit {
     unsafe fn put_isize(n: usize, d: usize, p: *mut Self, mut i: isize) {
         <Trit as BasicTbitWord>::unfold_tbits(n, d, p, |x| {
-            let r = match i % 3 {
-                2 => -1,
-                -2 => 1,
-                r => r,
-            };
-            x[0] = Trint1(r as i8).into();
-            i = (i - r) / 3;
+            let (r, q) = balanced_div3(i);
+            x[0] = Trint1(r).into();
+            i = q;
         });
     }
     unsafe fn get_isize(n: usize, d: usize, p: *const Self) -> isize {
         let mut m = 1_isize;
         let mut i = 0_isize;
+        // Wrapping ops: `m` overflows past the last trit and `i` may overflow in the middle
+        // of the sum near `isize::MIN/MAX`, the result is still exact if it fits `isize`.
         <Trit as BasicTbitWord>::fold_tbits(n, d, p, |x| {
-            i += m * (Trint1::from(x[0])).0 as isize;
-            m *= 3;
+            i = i.wrapping_add(m.wrapping_mul((Trint1::from(x[0])).0 as isize));
+            m = m.wrapping_mul(3);
         });
         i
     }
@@ -113,11 +141,34 @@ impl IntTbitWord for Trit {
         let mut m = 1_usize;
         let mut u = 0_usize;
         <Trit as BasicTbitWord>::fold_tbits(n, d, p, |x| {
-            u += m * x[0].0 as usize;
-            m *= 3;
+            u = u.wrapping_add(m.wrapping_mul(x[0].0 as usize));
+            m = m.wrapping_mul(3);
         });
         u
     }
+    // `put_i128`, `get_i128`, `put_u128` and `get_u128` are the default balanced
+    // implementations, any `i128` or `u128` value fits into 81 trits.
+
+    /// Balanced ternary, `|i| <= (3^n - 1) / 2`.
+    fn fits_isize(n: usize, mut i: isize) -> bool {
+        for _ in 0..n {
+            if i == 0 {
+                break;
+            }
+            i = balanced_div3(i).1;
+        }
+        i == 0
+    }
+    /// `u < 3^n`.
+    fn fits_usize(n: usize, mut u: usize) -> bool {
+        for _ in 0..n {
+            if u == 0 {
+                break;
+            }
+            u = u / 3;
+        }
+        u == 0
+    }
 }
 
 impl SpongosTbitWord for Trit {
@@ -128,6 +179,31 @@ impl SpongosTbitWord for Trit {
     fn tbit_sub(x: Self::Tbit, y: Self::Tbit) -> Self::Tbit {
         Trit((3 + x.0 - y.0) % 3)
     }
+
+    /// Word is a single trit, slices are always aligned.
+    unsafe fn add(ds: usize, s: *mut Self, n: usize, dx: usize, x: *const Self) {
+        let ss = std::slice::from_raw_parts_mut(s.add(ds), n);
+        let xs = std::slice::from_raw_parts(x.add(dx), n);
+        for (ts, tx) in ss.iter_mut().zip(xs.iter()) {
+            // Branch-free reduction modulo 3 allows the loop to be vectorized.
+            let t = ts.0 + tx.0;
+            ts.0 = t - 3 * ((t >= 3) as u8);
+        }
+    }
+}
+
+/// Trit is packed as is, ie. `-1` is stored as `2`.
+impl PackedTbitWord for Trit {
+    fn to_byte(self) -> u8 {
+        self.0
+    }
+    fn from_byte(b: u8) -> Option<Self> {
+        if b <= MAX_TRIT.0 {
+            Some(Trit(b))
+        } else {
+            None
+        }
+    }
 }
 
 impl TritWord for Trit {}
@@ -138,6 +214,11 @@ mod tests {
     use crate::tbits::*;
     use std::str::FromStr;
 
+    #[test]
+    fn checked_offsets() {
+        crate::tbits::tests::checked_offsets::<Trit>();
+    }
+
     #[test]
     fn copy_exhaustive() {
         let num_loops = 11;
@@ -152,6 +233,61 @@ mod tests {
         crate::tbits::tests::add(&a, &b, &ab);
     }
 
+    #[test]
+    fn add_word_tbits() {
+        let a = Tbits::<Trit>::from_str("ADDWORDTBITS9NOPQ").unwrap();
+        let b = Tbits::<Trit>::from_str("ZYXMLKJ9IHGFCBANP").unwrap();
+        crate::tbits::tests::add_word_tbits(&a, &b);
+    }
+
+    #[test]
+    fn slice_add_sub() {
+        let a = Tbits::<Trit>::from_str("ADDWORDTBITS9NOPQ").unwrap();
+        let b = Tbits::<Trit>::from_str("ZYXMLKJ9IHGFCBANP").unwrap();
+        crate::tbits::tests::slice_add_sub(&a, &b);
+    }
+
+    #[test]
+    fn batch_tbits() {
+        let a = Tbits::<Trit>::cycle_str(600, "ADDWORDTBITS9NOPQ");
+        let b = Tbits::<Trit>::cycle_str(600, "ZYXMLKJ9IHGFCBANP");
+        crate::tbits::tests::batch_tbits(&a, &b);
+    }
+
+    #[test]
+    fn equals_ct() {
+        let a = Tbits::<Trit>::from_str("NOPQRSTUV").unwrap();
+        let b = Tbits::<Trit>::from_str("ABCDEFGHI").unwrap();
+        crate::tbits::tests::equals_ct(&a, &b);
+    }
+
+    #[test]
+    fn copy_strided() {
+        let a = Tbits::<Trit>::from_str("ABCDEFGHI").unwrap();
+        let b = Tbits::<Trit>::from_str("NOPQRSTUV").unwrap();
+        crate::tbits::tests::copy_strided(&a, &b, 9);
+    }
+
+    #[test]
+    fn str_lossy() {
+        crate::tbits::tests::str_lossy::<Trit>("", "");
+        crate::tbits::tests::str_lossy::<Trit>("HELLO9WORLD", "HELLO9WORLD");
+        crate::tbits::tests::str_lossy::<Trit>("HÉLLO€€W", "H9LLO9W");
+        crate::tbits::tests::str_lossy::<Trit>("ÄBC-D", "9BC9D");
+
+        // Trailing partial char is padded with zero trits: `M` = (1,1,1), `A` = (1,0,0).
+        let t = Tbits::<Trit>::from_str("MM").unwrap();
+        assert_eq!("MA", t.slice().take(4).to_str());
+        assert_eq!("MA", Tbits::from_slice(t.slice().take(4)).to_string());
+    }
+
+    #[test]
+    fn tbits_str() {
+        crate::tbits::tests::tbits_str::<Trit>("-0+");
+        let t = Tbits::<Trit>::from_tbits_str("+-0").unwrap();
+        assert_eq!(Tbits::<Trit>::from_tbits(&[Trit(1), Trit(2), Trit(0)]), t);
+    }
+
     #[test]
     fn get_put_char() {
         let alphabet = "9ABCDEFGHIJKLMNOPQRSTUVWXYZ";
@@ -175,4 +311,149 @@ mod tests {
             m *= 3;
         }
     }
+
+    #[test]
+    fn split_at() {
+        let a = Tbits::<Trit>::from_str("ABCDEFGHI").unwrap();
+        let b = Tbits::<Trit>::from_str("NOPQRSTUV").unwrap();
+        crate::tbits::tests::split_at(&a, &b);
+    }
+
+    #[test]
+    fn copy_within() {
+        let a = Tbits::<Trit>::from_str("COPYWITHIN9SHIFT").unwrap();
+        crate::tbits::tests::copy_within(&a);
+    }
+
+    #[test]
+    fn extend_collect() {
+        let a = Tbits::<Trit>::from_str("EXTEND9COLLECT").unwrap();
+        crate::tbits::tests::extend_collect(&a);
+    }
+
+    #[test]
+    fn get_put_128() {
+        for &n in [1, 5, 27, 40, 41, 64, 80].iter() {
+            let m = 3_u128.pow(n as u32);
+            let h = ((m - 1) / 2) as i128;
+            crate::tbits::tests::get_put_128::<Trit>(n, -h, h, m - 1);
+        }
+        // Any value fits into 81 trits.
+        crate::tbits::tests::get_put_128::<Trit>(
+            81,
+            std::i128::MIN,
+            std::i128::MAX,
+            std::u128::MAX,
+        );
+    }
+
+    #[test]
+    fn try_put_boundaries() {
+        for &n in [1, 2, 3, 5, 9, 18, 27].iter() {
+            let m = 3_isize.pow(n as u32);
+            crate::tbits::tests::try_put_boundaries::<Trit>(
+                n,
+                -(m - 1) / 2,
+                (m - 1) / 2,
+                m as usize - 1,
+            );
+        }
+        // Any value fits into 41 trits, 3^41 > 2^64.
+        let mut t = Tbits::<Trit>::zero(41);
+        assert_eq!(Ok(()), t.slice_mut().try_put_isize(std::isize::MIN));
+        assert_eq!(std::isize::MIN, t.slice().get_isize());
+        assert_eq!(Ok(()), t.slice_mut().try_put_usize(std::usize::MAX));
+        assert_eq!(std::usize::MAX, t.slice().get_usize());
+        assert!(t
+            .slice_mut()
+            .take(40)
+            .try_put_usize(std::usize::MAX)
+            .is_err());
+    }
+
+    #[test]
+    fn encoding_round_trip() {
+        use rand::Rng;
+        let mut rng = rand::thread_rng();
+        let ts: Vec<_> = (0..100).map(|_| Trit(rng.gen_range(0, 3))).collect();
+        crate::tbits::tests::encoding_round_trip::<Trit>(&ts);
+
+        // Layout: big-endian tbit count followed by a byte per trit.
+        let t = Tbits::<Trit>::from_tbits(&[Trit(1), Trit(2), Trit(0)]);
+        assert_eq!("0000000000000003010200", t.to_hex());
+        assert_eq!("AAAAAAAAAAMBAgA=", t.to_base64());
+        assert_eq!(None, Tbits::<Trit>::from_hex("0000000000000001ff"));
+    }
+
+    #[test]
+    fn try_from_words() {
+        use crate::tbits::encoding::WordError;
+        use rand::Rng;
+        use std::convert::TryFrom;
+        let mut rng = rand::thread_rng();
+        let ts: Vec<_> = (0..100).map(|_| Trit(rng.gen_range(0, 3))).collect();
+        crate::tbits::tests::try_from_words::<Trit>(&ts);
+
+        assert_eq!(
+            Err(WordError { index: 2, byte: 3 }),
+            Tbits::<Trit>::try_from(&[1, 2, 3][..])
+        );
+    }
+
+    #[test]
+    fn iter() {
+        let a = Tbits::<Trit>::from_str("ITERATE9TRITS").unwrap();
+        crate::tbits::tests::iter(&a);
+    }
+
+    #[test]
+    fn concat_repeat() {
+        let a = Tbits::<Trit>::from_str("CONCAT9REPEAT").unwrap();
+        crate::tbits::tests::concat_repeat(&a);
+    }
+
+    #[test]
+    fn chunks() {
+        let a = Tbits::<Trit>::from_str("CHUNKS").unwrap();
+        crate::tbits::tests::chunks(&a);
+    }
+
+    #[test]
+    fn extract_insert_field() {
+        use crate::tbits::word::RangeError;
+        // The default implementation goes through `put_usize` and `get_usize`.
+        for v in 0..3_u64 {
+            let w = Trit::insert_field(Trit(1), 0, 1, v).unwrap();
+            assert_eq!(Trit(v as u8), w);
+            assert_eq!(v, Trit::extract_field(w, 0, 1));
+        }
+        assert_eq!(0, Trit::extract_field(Trit(2), 1, 0));
+
+        // Oversized values are rejected rather than truncated.
+        assert_eq!(
+            Err(RangeError { n: 1 }),
+            Trit::insert_field(Trit(1), 0, 1, 3)
+        );
+        assert_eq!(
+            Err(RangeError { n: 0 }),
+            Trit::insert_field(Trit(1), 1, 0, 1)
+        );
+        assert_eq!(Ok(Trit(1)), Trit::insert_field(Trit(1), 1, 0, 0));
+    }
+
+    #[test]
+    fn split_word_at() {
+        crate::tbits::tests::split_word_at::<Trit>(&[Trit(2)]);
+    }
+
+    #[test]
+    fn safe_slice_ops() {
+        let a = Tbits::<Trit>::from_str("SAFE9SLICE").unwrap();
+        crate::tbits::tests::safe_slice_ops(&a);
+    }
+
+    #[test]
+    fn out_of_range() {
+        crate::tbits::tests::out_of_range::<Trit>(11);
+    }
 }
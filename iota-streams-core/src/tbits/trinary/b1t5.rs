@@ -1,7 +1,7 @@
 //! 5 trits per 1 byte.
 
 use super::defs::*;
-use crate::tbits::word::BasicTbitWord;
+use crate::tbits::word::{BasicTbitWord, PackedTbitWord, SpongosTbitWord};
 
 /// 5 trits packed into a byte. It represents a "network" trinary word.
 ///
@@ -52,9 +52,36 @@ impl BasicTbitWord for B1T5 {
     }
 }
 
+/// Trits are added modulo 3 as `Trit`s, whole words go through the default `word_add`.
+impl SpongosTbitWord for B1T5 {
+    fn tbit_add(x: Self::Tbit, y: Self::Tbit) -> Self::Tbit {
+        Trit((x.0 + y.0) % 3)
+    }
+
+    fn tbit_sub(x: Self::Tbit, y: Self::Tbit) -> Self::Tbit {
+        Trit((3 + x.0 - y.0) % 3)
+    }
+}
+
+/// Trit `i` of the word is the `i`-th least significant base-3 digit of the byte,
+/// only values `0..243` are canonical.
+impl PackedTbitWord for B1T5 {
+    fn to_byte(self) -> u8 {
+        self.0
+    }
+    fn from_byte(b: u8) -> Option<Self> {
+        if b < 243 {
+            Some(B1T5(b))
+        } else {
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tbits::Tbits;
 
     #[test]
     fn copy_exhaustive() {
@@ -70,4 +97,112 @@ mod tests {
             assert_eq!(b, b2);
         }
     }
+
+    #[test]
+    fn packed_words() {
+        for u in 0..243 {
+            let w = B1T5::from_byte(u).unwrap();
+            assert_eq!(u, w.to_byte());
+            assert_eq!(w, B1T5::from_trits(&w.to_trits()));
+        }
+        for u in 243..=255 {
+            assert!(B1T5::from_byte(u).is_none());
+        }
+    }
+
+    fn gen_tbits(n: usize, k: u8) -> Tbits<B1T5> {
+        let ts: Vec<_> = (0..n).map(|i| Trit((i as u8 % 7 + k) % 3)).collect();
+        Tbits::<B1T5>::from_tbits(&ts)
+    }
+
+    #[test]
+    fn convert_to_trit() {
+        for n in 0..23 {
+            let ts: Vec<_> = (0..n).map(|i| Trit((i as u8 * 5 + 1) % 3)).collect();
+            let t = Tbits::<Trit>::from_tbits(&ts);
+            let packed: Tbits<B1T5> = t.convert_to();
+            assert_eq!(n, packed.size());
+            assert_eq!(ts, packed.slice().iter().collect::<Vec<_>>());
+            assert_eq!(t, packed.convert_to::<Trit>());
+        }
+    }
+
+    #[test]
+    fn add_word_tbits() {
+        crate::tbits::tests::add_word_tbits(&gen_tbits(37, 0), &gen_tbits(37, 1));
+    }
+
+    #[test]
+    fn slice_add_sub() {
+        crate::tbits::tests::slice_add_sub(&gen_tbits(47, 0), &gen_tbits(47, 1));
+    }
+
+    #[test]
+    fn batch_tbits() {
+        crate::tbits::tests::batch_tbits(&gen_tbits(600, 0), &gen_tbits(600, 1));
+    }
+
+    #[test]
+    fn equals_ct() {
+        crate::tbits::tests::equals_ct(&gen_tbits(23, 0), &gen_tbits(23, 1));
+    }
+
+    #[test]
+    fn copy_equals() {
+        crate::tbits::tests::copy_equals(&gen_tbits(37, 0), &gen_tbits(37, 2));
+    }
+
+    #[test]
+    fn copy_within() {
+        crate::tbits::tests::copy_within(&gen_tbits(47, 0));
+    }
+
+    #[test]
+    fn extend_collect() {
+        crate::tbits::tests::extend_collect(&gen_tbits(47, 0));
+    }
+
+    #[test]
+    fn split_word_at() {
+        crate::tbits::tests::split_word_at::<B1T5>(&[1, 2, 0, 2, 1].map(Trit));
+    }
+
+    #[test]
+    fn encoding_round_trip() {
+        use rand::Rng;
+        let mut rng = rand::thread_rng();
+        let ts: Vec<_> = (0..100).map(|_| Trit(rng.gen_range(0, 3))).collect();
+        crate::tbits::tests::encoding_round_trip::<B1T5>(&ts);
+
+        // Padding trits must be zero, 5 = 2 + 1*3.
+        assert!(Tbits::<B1T5>::from_hex("000000000000000105").is_none());
+        assert_eq!(
+            Some(Tbits::<B1T5>::from_tbits(&[Trit(2)])),
+            Tbits::<B1T5>::from_hex("000000000000000102")
+        );
+        assert!(Tbits::<B1T5>::from_hex("0000000000000005f3").is_none());
+    }
+
+    #[test]
+    fn try_from_words() {
+        use crate::tbits::encoding::WordError;
+        use rand::Rng;
+        use std::convert::TryFrom;
+        let mut rng = rand::thread_rng();
+        let ts: Vec<_> = (0..100).map(|_| Trit(rng.gen_range(0, 3))).collect();
+        crate::tbits::tests::try_from_words::<B1T5>(&ts);
+
+        // 243 = 3^5 doesn't encode any 5 trits.
+        assert_eq!(
+            Err(WordError {
+                index: 1,
+                byte: 243
+            }),
+            Tbits::<B1T5>::try_from(&[0, 243, 255][..])
+        );
+        assert_eq!(
+            Ok(Tbits::<B1T5>::from_tbits(&[Trit(2); 5])),
+            Tbits::<B1T5>::try_from(&[242][..])
+        );
+    }
 }
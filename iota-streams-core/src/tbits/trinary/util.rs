@@ -31,6 +31,17 @@ pub fn mods3_usize(t: usize) -> (Trint3, usize) {
     }
     (Trint3(r), q)
 }
+/// Remainder `r` and quotient `q` of `t` `mods 3^3` where
+/// `t == q * 3^3 + r` and `-13 <= r <= 13`.
+pub fn mods3_u64(t: u64) -> (Trint3, u64) {
+    let mut r = (t % 27) as i8;
+    let mut q = t / 27;
+    if 13 < r {
+        r -= 27;
+        q += 1;
+    }
+    (Trint3(r), q)
+}
 
 /// Remainder `r` and quotient `q` of `t` `mods 3^1` where
 /// `t == q * 3^1 + r` and `-1 <= r <= 1`.
@@ -1,4 +1,77 @@
-use std::fmt;
+use core::convert::TryFrom;
+use core::fmt;
+
+/// Tbit offset `d + n`, `None` on overflow.
+pub fn checked_offset(d: usize, n: usize) -> Option<usize> {
+    d.checked_add(n)
+}
+
+/// Tbit offset `d + n` if it doesn't exceed `size`.
+pub fn checked_range(d: usize, n: usize, size: usize) -> Result<usize, OffsetError> {
+    match checked_offset(d, n) {
+        Some(m) if m <= size => Ok(m),
+        _ => Err(OffsetError { d, n, size }),
+    }
+}
+
+/// Tbit range `[d..d+n)` doesn't fit into `size` tbits or its end overflows.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct OffsetError {
+    /// The range offset.
+    pub d: usize,
+    /// The range size.
+    pub n: usize,
+    /// The available number of tbits.
+    pub size: usize,
+}
+
+impl fmt::Display for OffsetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Offset {} + {} is out of range {}.",
+            self.d, self.n, self.size
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OffsetError {}
+
+/// Max number of tbits per word, `BasicTbitWord::SIZE` must not exceed it.
+/// Tbits of a single word are converted through stack buffers of this size.
+pub const MAX_WORD_TBITS: usize = 64;
+
+/// Stack buffer holding `TW::SIZE` tbits of a single word.
+pub struct WordTbits<TW: BasicTbitWord>([TW::Tbit; MAX_WORD_TBITS]);
+
+impl<TW: BasicTbitWord> WordTbits<TW> {
+    /// All-zero tbits of a single word.
+    pub fn zero() -> Self {
+        assert!(TW::SIZE <= MAX_WORD_TBITS, "Tbit word is too large.");
+        Self([TW::ZERO_TBIT; MAX_WORD_TBITS])
+    }
+}
+
+impl<TW: BasicTbitWord> Clone for WordTbits<TW> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<TW: BasicTbitWord> Copy for WordTbits<TW> {}
+
+impl<TW: BasicTbitWord> AsRef<[TW::Tbit]> for WordTbits<TW> {
+    fn as_ref(&self) -> &[TW::Tbit] {
+        &self.0[..TW::SIZE]
+    }
+}
+
+impl<TW: BasicTbitWord> AsMut<[TW::Tbit]> for WordTbits<TW> {
+    fn as_mut(&mut self) -> &mut [TW::Tbit] {
+        &mut self.0[..TW::SIZE]
+    }
+}
 
 /// Abstraction for a binary/trinary word containing one or several tbits (bits/trits).
 /// The size and encoding of the word is defined by the implementation.
@@ -15,23 +88,69 @@ pub trait BasicTbitWord: Sized + Copy + PartialEq {
     /// All-zero tbits word.
     const ZERO_WORD: Self;
 
+    /// Number of words needed to store `n` tbits, doesn't overflow.
+    fn words(n: usize) -> usize {
+        n / Self::SIZE + (n % Self::SIZE != 0) as usize
+    }
+    /// Number of tbits stored in `n` words, `None` on overflow.
+    fn checked_tbits(n: usize) -> Option<usize> {
+        n.checked_mul(Self::SIZE)
+    }
+    /// Tbit offset `d + n` if the range `[d..d+n)` fits into `words` words,
+    /// ie. `d + n <= words * SIZE`.
+    fn checked_words_range(d: usize, n: usize, words: usize) -> Result<usize, OffsetError> {
+        checked_range(d, n, Self::checked_tbits(words).unwrap_or(usize::MAX))
+    }
+
     /// Convert word to SIZE tbits.
     unsafe fn word_to_tbits(x: Self, ts: *mut Self::Tbit);
     /// Convert word from SIZE tbits.
     unsafe fn word_from_tbits(ts: *const Self::Tbit) -> Self;
 
+    /// Split word `x` at `n` tbits into `(head, tail)`: `head` holds the first `n` tbits
+    /// of `x`, `tail` holds the remaining `SIZE - n` tbits moved to the start of the word,
+    /// the rest of the tbits are zero. Requires `n <= SIZE`.
+    fn split_word_at(x: Self, n: usize) -> (Self, Self) {
+        assert!(n <= Self::SIZE, "Split offset {} is out of word range.", n);
+        let mut ts = WordTbits::<Self>::zero();
+        let mut hs = WordTbits::<Self>::zero();
+        let mut rs = WordTbits::<Self>::zero();
+        unsafe { Self::word_to_tbits(x, ts.as_mut().as_mut_ptr()) };
+        hs.as_mut()[..n].copy_from_slice(&ts.as_ref()[..n]);
+        rs.as_mut()[..Self::SIZE - n].copy_from_slice(&ts.as_ref()[n..]);
+        unsafe {
+            (
+                Self::word_from_tbits(hs.as_ref().as_ptr()),
+                Self::word_from_tbits(rs.as_ref().as_ptr()),
+            )
+        }
+    }
+
     unsafe fn put_tbit(d: usize, p: *mut Self, t: Self::Tbit) {
-        let mut ts = vec![Self::ZERO_TBIT; Self::SIZE];
+        let mut ts_buf = WordTbits::<Self>::zero();
+        let ts = ts_buf.as_mut();
         Self::word_to_tbits(*p.add(d / Self::SIZE), ts.as_mut_ptr());
         ts[d % Self::SIZE] = t;
         *p.add(d / Self::SIZE) = Self::word_from_tbits(ts.as_ptr());
     }
     unsafe fn get_tbit(d: usize, p: *const Self) -> Self::Tbit {
-        let mut ts = vec![Self::ZERO_TBIT; Self::SIZE];
+        let mut ts_buf = WordTbits::<Self>::zero();
+        let ts = ts_buf.as_mut();
         Self::word_to_tbits(*p.add(d / Self::SIZE), ts.as_mut_ptr());
         ts[d % Self::SIZE]
     }
 
+    /// Get `out.len()` adjacent tbits of `(d,p)` slice, each touched word is converted once
+    /// unlike with repeated `get_tbit` calls.
+    unsafe fn get_tbits_into(d: usize, p: *const Self, out: &mut [Self::Tbit]) {
+        Self::to_tbits(out.len(), d, p, out.as_mut_ptr());
+    }
+    /// Put `ts.len()` adjacent tbits into `(d,p)` slice, each touched word is converted once
+    /// unlike with repeated `put_tbit` calls.
+    unsafe fn put_tbits_from(d: usize, p: *mut Self, ts: &[Self::Tbit]) {
+        Self::from_tbits(ts.len(), d, p, ts.as_ptr());
+    }
+
     unsafe fn fold_tbits<F>(n: usize, dx: usize, x: *const Self, mut f: F)
     where
         F: FnMut(&[Self::Tbit]),
@@ -40,15 +159,15 @@ pub trait BasicTbitWord: Sized + Copy + PartialEq {
             return;
         }
 
-        // Primitive array `[Self::Tbit; Self::SIZE]` is not supported yet.
-        let mut v = vec![Self::ZERO_TBIT; Self::SIZE];
+        let mut v_buf = WordTbits::<Self>::zero();
+        let v = v_buf.as_mut();
         let rx = dx % Self::SIZE;
         let mut xx = x.add(dx / Self::SIZE);
         let mut nn = n;
         let mut d;
 
         if rx != 0 {
-            d = std::cmp::min(n, Self::SIZE - rx);
+            d = core::cmp::min(n, Self::SIZE - rx);
             Self::word_to_tbits(*xx, v.as_mut_ptr());
             f(&v[rx..rx + d]);
             nn -= d;
@@ -77,15 +196,15 @@ pub trait BasicTbitWord: Sized + Copy + PartialEq {
             return;
         }
 
-        // Primitive array `[Self::Tbit; Self::SIZE]` is not supported yet.
-        let mut v = vec![Self::ZERO_TBIT; Self::SIZE];
+        let mut v_buf = WordTbits::<Self>::zero();
+        let v = v_buf.as_mut();
         let rx = dx % Self::SIZE;
         let mut xx = x.add(dx / Self::SIZE);
         let mut nn = n;
         let mut d;
 
         if rx != 0 {
-            d = std::cmp::min(n, Self::SIZE - rx);
+            d = core::cmp::min(n, Self::SIZE - rx);
             Self::word_to_tbits(*xx, v.as_mut_ptr());
             f(&mut v[rx..rx + d]);
             *xx = Self::word_from_tbits(v.as_ptr());
@@ -117,15 +236,15 @@ pub trait BasicTbitWord: Sized + Copy + PartialEq {
             return;
         }
 
-        // Primitive array `[Self::Tbit; Self::SIZE]` is not supported yet.
-        let mut v = vec![Self::ZERO_TBIT; Self::SIZE];
+        let mut v_buf = WordTbits::<Self>::zero();
+        let v = v_buf.as_mut();
         let rx = dx % Self::SIZE;
         let mut xx = x.add(dx / Self::SIZE);
         let mut nn = n;
         let mut d;
 
         if rx != 0 {
-            d = std::cmp::min(n, Self::SIZE - rx);
+            d = core::cmp::min(n, Self::SIZE - rx);
             Self::word_to_tbits(*xx, v.as_mut_ptr());
             f(&mut v[rx..rx + d]);
             *xx = Self::word_from_tbits(v.as_ptr());
@@ -150,63 +269,90 @@ pub trait BasicTbitWord: Sized + Copy + PartialEq {
 
     unsafe fn to_tbits(n: usize, dx: usize, x: *const Self, mut ts: *mut Self::Tbit) {
         Self::fold_tbits(n, dx, x, |tx| {
-            std::ptr::copy(tx.as_ptr(), ts, tx.len());
+            core::ptr::copy(tx.as_ptr(), ts, tx.len());
             ts = ts.add(tx.len());
         });
     }
 
     unsafe fn from_tbits(n: usize, dx: usize, x: *mut Self, mut ts: *const Self::Tbit) {
         Self::unfold_tbits(n, dx, x, |tx| {
-            std::ptr::copy(ts, tx.as_mut_ptr(), tx.len());
+            core::ptr::copy(ts, tx.as_mut_ptr(), tx.len());
             ts = ts.add(tx.len());
         });
     }
 
     /// Copy `n` tbits from `(dx,x)` slice into `(dy,y)`.
+    ///
+    /// Slices may overlap, the result is the same as if `(dx,x)` were copied into
+    /// a temporary buffer first, like `core::ptr::copy`. Words are copied backwards
+    /// if `(dy,y)` starts after `(dx,x)` so that source tbits are read before they
+    /// are overwritten.
     unsafe fn copy(n: usize, dx: usize, x: *const Self, dy: usize, y: *mut Self) {
         if n == 0 {
             return;
         }
 
         let rx = dx % Self::SIZE;
-        let mut xx = x.add(dx / Self::SIZE);
+        let xx = x.add(dx / Self::SIZE);
         let ry = dy % Self::SIZE;
-        let mut yy = y.add(dy / Self::SIZE);
-        let mut nn = n;
+        let yy = y.add(dy / Self::SIZE);
 
         if rx == ry {
-            let mut xs = vec![Self::ZERO_TBIT; Self::SIZE];
-            let mut ys = vec![Self::ZERO_TBIT; Self::SIZE];
-
-            if rx != 0 {
-                Self::word_to_tbits(*xx, xs.as_mut_ptr());
-                Self::word_to_tbits(*yy, ys.as_mut_ptr());
-                let d = std::cmp::min(n, Self::SIZE - rx);
-                ys[ry..ry + d].copy_from_slice(&xs[rx..rx + d]);
-                *yy = Self::word_from_tbits(ys.as_ptr());
-
-                nn -= d;
-                xx = xx.add(1);
-                yy = yy.add(1);
-            }
+            // Partial head word, full middle words and partial tail word.
+            let d = if rx != 0 {
+                core::cmp::min(n, Self::SIZE - rx)
+            } else {
+                0
+            };
+            let h = (d != 0) as usize;
+            let m = (n - d) / Self::SIZE;
+            let t = (n - d) % Self::SIZE;
 
-            std::ptr::copy(xx, yy, nn / Self::SIZE);
-
-            xx = xx.add(nn / Self::SIZE);
-            yy = yy.add(nn / Self::SIZE);
-            nn = nn % Self::SIZE;
+            let copy_head = || {
+                if d != 0 {
+                    copy_in_word::<Self>(d, rx, xx, yy);
+                }
+            };
+            let copy_tail = || {
+                if t != 0 {
+                    copy_in_word::<Self>(t, 0, xx.add(h + m), yy.add(h + m));
+                }
+            };
 
-            if nn != 0 {
-                Self::word_to_tbits(*xx, xs.as_mut_ptr());
-                Self::word_to_tbits(*yy, ys.as_mut_ptr());
-                ys[0..nn].copy_from_slice(&xs[0..nn]);
-                *yy = Self::word_from_tbits(ys.as_ptr());
+            if (yy as *const Self) > xx {
+                copy_tail();
+                core::ptr::copy(xx.add(h), yy.add(h), m);
+                copy_head();
+            } else {
+                copy_head();
+                core::ptr::copy(xx.add(h), yy.add(h), m);
+                copy_tail();
             }
         } else {
             // Rare case, just convert via tbits.
-            let mut ts = vec![Self::ZERO_TBIT; n];
-            Self::to_tbits(n, dx, x, ts.as_mut_ptr());
-            Self::from_tbits(n, dy, y, ts.as_ptr());
+            copy_via_tbits::<Self, SCRATCH_TBITS>(n, dx, x, dy, y);
+        }
+    }
+
+    /// Copy `count` elements of `elem` tbits each from `(dx,x)` slice into `(dy,y)`.
+    /// Source and destination offsets are advanced by `src_stride` and `dst_stride` tbits
+    /// respectively after each element, ie. gather (or scatter) over fixed strides.
+    /// Elements are copied in order with `copy`, so only each element copy is overlap-safe.
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn copy_strided(
+        count: usize,
+        elem: usize,
+        src_stride: usize,
+        mut dx: usize,
+        x: *const Self,
+        dst_stride: usize,
+        mut dy: usize,
+        y: *mut Self,
+    ) {
+        for _ in 0..count {
+            Self::copy(elem, dx, x, dy, y);
+            dx += src_stride;
+            dy += dst_stride;
         }
     }
 
@@ -216,14 +362,15 @@ pub trait BasicTbitWord: Sized + Copy + PartialEq {
             return;
         }
 
-        let mut v = vec![Self::ZERO_TBIT; Self::SIZE];
+        let mut v_buf = WordTbits::<Self>::zero();
+        let v = v_buf.as_mut();
         let rx = dx % Self::SIZE;
         let mut xx = x.add(dx / Self::SIZE);
         let mut nn = n;
         let mut d;
 
         if rx != 0 {
-            d = std::cmp::min(n, Self::SIZE - rx);
+            d = core::cmp::min(n, Self::SIZE - rx);
             Self::word_to_tbits(*xx, v.as_mut_ptr());
             for i in rx..rx + d {
                 *v.as_mut_ptr().add(i) = Self::ZERO_TBIT;
@@ -262,13 +409,15 @@ pub trait BasicTbitWord: Sized + Copy + PartialEq {
         let mut nn = n;
 
         if rx == ry {
-            let mut xs = vec![Self::ZERO_TBIT; Self::SIZE];
-            let mut ys = vec![Self::ZERO_TBIT; Self::SIZE];
+            let mut xs_buf = WordTbits::<Self>::zero();
+            let xs = xs_buf.as_mut();
+            let mut ys_buf = WordTbits::<Self>::zero();
+            let ys = ys_buf.as_mut();
 
             if rx != 0 {
                 Self::word_to_tbits(*xx, xs.as_mut_ptr());
                 Self::word_to_tbits(*yy, ys.as_mut_ptr());
-                let d = std::cmp::min(n, Self::SIZE - rx);
+                let d = core::cmp::min(n, Self::SIZE - rx);
                 if ys[ry..ry + d] != xs[rx..rx + d] {
                     return false;
                 }
@@ -302,28 +451,284 @@ pub trait BasicTbitWord: Sized + Copy + PartialEq {
             true
         } else {
             // Rare case, just convert via tbits.
-            let mut xs = vec![Self::ZERO_TBIT; n];
-            let mut ys = vec![Self::ZERO_TBIT; n];
-            Self::to_tbits(n, dx, x, xs.as_mut_ptr());
-            Self::to_tbits(n, dy, y, ys.as_mut_ptr());
-            xs == ys
+            equals_via_tbits::<Self, SCRATCH_TBITS>(n, dx, x, dy, y)
         }
     }
 }
 
+/// Size of the stack buffers used by `copy` and `equals` for slices with different
+/// offsets within words.
+pub(crate) const SCRATCH_TBITS: usize = 243;
+
+/// Copy `n` tbits at offset `r` within word `*x` into the same position within word `*y`.
+unsafe fn copy_in_word<TW>(n: usize, r: usize, x: *const TW, y: *mut TW)
+where
+    TW: BasicTbitWord,
+{
+    let mut xs_buf = WordTbits::<TW>::zero();
+    let xs = xs_buf.as_mut();
+    let mut ys_buf = WordTbits::<TW>::zero();
+    let ys = ys_buf.as_mut();
+    TW::word_to_tbits(*x, xs.as_mut_ptr());
+    TW::word_to_tbits(*y, ys.as_mut_ptr());
+    ys[r..r + n].copy_from_slice(&xs[r..r + n]);
+    *y = TW::word_from_tbits(ys.as_ptr());
+}
+
+/// Copy `n` tbits from `(dx,x)` slice into `(dy,y)` through a stack buffer of `N` tbits.
+/// Slices may overlap, chunks are copied backwards if `(dy,y)` starts after `(dx,x)`.
+unsafe fn copy_via_tbits<TW, const N: usize>(
+    n: usize,
+    dx: usize,
+    x: *const TW,
+    dy: usize,
+    y: *mut TW,
+) where
+    TW: BasicTbitWord,
+{
+    let mut ts = [TW::ZERO_TBIT; N];
+    let x_begin = (x.add(dx / TW::SIZE) as usize, dx % TW::SIZE);
+    let y_begin = (y.add(dy / TW::SIZE) as usize, dy % TW::SIZE);
+    let chunks = (n + N - 1) / N;
+    for i in 0..chunks {
+        let j = if y_begin > x_begin { chunks - 1 - i } else { i };
+        let d = j * N;
+        let k = core::cmp::min(N, n - d);
+        TW::to_tbits(k, dx + d, x, ts.as_mut_ptr());
+        TW::from_tbits(k, dy + d, y, ts.as_ptr());
+    }
+}
+
+/// Compare `n` tbits from `(dx,x)` and `(dy,y)` slices through stack buffers of `N` tbits.
+unsafe fn equals_via_tbits<TW, const N: usize>(
+    n: usize,
+    dx: usize,
+    x: *const TW,
+    dy: usize,
+    y: *const TW,
+) -> bool
+where
+    TW: BasicTbitWord,
+{
+    let mut xs = [TW::ZERO_TBIT; N];
+    let mut ys = [TW::ZERO_TBIT; N];
+    let mut d = 0;
+    while d < n {
+        let k = core::cmp::min(N, n - d);
+        TW::to_tbits(k, dx + d, x, xs.as_mut_ptr());
+        TW::to_tbits(k, dy + d, y, ys.as_mut_ptr());
+        if xs[..k] != ys[..k] {
+            return false;
+        }
+        d += k;
+    }
+    true
+}
+
+/// Process `n` tbits of state `(ds,s)` and input `(dx,x)` slices through stack buffers of
+/// `N` tbits, so each touched word is converted once per chunk rather than once per tbit.
+/// `f(ts, tx)` updates state tbit `ts` and returns the output tbit which is put into
+/// `(dy,y)` slice if any. The output slice may be the input one, the chunk of input tbits
+/// is read before the output tbits are put.
+#[allow(clippy::too_many_arguments)]
+unsafe fn duplex_via_tbits<TW, F, const N: usize>(
+    n: usize,
+    ds: usize,
+    s: *mut TW,
+    dx: usize,
+    x: *const TW,
+    y: Option<(usize, *mut TW)>,
+    mut f: F,
+) where
+    TW: BasicTbitWord,
+    F: FnMut(&mut TW::Tbit, TW::Tbit) -> TW::Tbit,
+{
+    let mut ss = [TW::ZERO_TBIT; N];
+    let mut xs = [TW::ZERO_TBIT; N];
+    let mut d = 0;
+    while d < n {
+        let k = core::cmp::min(N, n - d);
+        TW::get_tbits_into(ds + d, s, &mut ss[..k]);
+        TW::get_tbits_into(dx + d, x, &mut xs[..k]);
+        for (ts, tx) in ss[..k].iter_mut().zip(xs[..k].iter_mut()) {
+            *tx = f(ts, *tx);
+        }
+        TW::put_tbits_from(ds + d, s, &ss[..k]);
+        if let Some((dy, y)) = y {
+            TW::put_tbits_from(dy + d, y, &xs[..k]);
+        }
+        d += k;
+    }
+}
+
 pub trait StringTbitWord: BasicTbitWord {
     const TBITS_PER_CHAR: usize;
+    /// Char encoding zero tbits, it replaces undecodable chars in lossy conversions.
+    const REPLACEMENT_CHAR: char;
     unsafe fn put_char(s: usize, d: usize, p: *mut Self, c: char) -> bool;
     unsafe fn get_char(s: usize, d: usize, p: *const Self) -> char;
+
+    /// Char representing a single tbit, eg. one of `-0+` for trits.
+    fn tbit_to_char(t: Self::Tbit) -> char;
+    /// Parse a single tbit char, `None` if `c` is not in the tbit alphabet.
+    fn tbit_from_char(c: char) -> Option<Self::Tbit>;
+}
+
+/// Value doesn't fit into the requested number of tbits.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RangeError {
+    /// The number of tbits requested.
+    pub n: usize,
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Value doesn't fit into {} tbits.", self.n)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RangeError {}
+
+/// Number of values of a single tbit, ie. 2 for bits and 3 for trits.
+fn tbit_radix<TW: IntTbitWord>() -> usize {
+    (2..).find(|u| !TW::fits_usize(1, *u)).unwrap()
 }
 
 pub trait IntTbitWord: BasicTbitWord {
+    /// Put signed `i` into `n` tbits, higher tbits of `i` that don't fit are silently dropped
+    /// (the value wraps), see `try_put_isize`.
     unsafe fn put_isize(n: usize, d: usize, p: *mut Self, i: isize);
     unsafe fn get_isize(n: usize, d: usize, p: *const Self) -> isize;
+    /// Put unsigned `u` into `n` tbits, higher tbits of `u` that don't fit are silently dropped
+    /// (the value is truncated), see `try_put_usize`.
     unsafe fn put_usize(n: usize, d: usize, p: *mut Self, u: usize);
     unsafe fn get_usize(n: usize, d: usize, p: *const Self) -> usize;
+    /// Put signed 128-bit `i` into `n` tbits, higher tbits of `i` that don't fit are silently
+    /// dropped (the value wraps), eg. for 128-bit identifiers and nonces.
+    ///
+    /// The default implementation puts one tbit at a time with `put_isize` or `put_usize`,
+    /// the encoding is balanced if a single tbit can hold `1` and `-1` (eg. trits)
+    /// and two's complement otherwise (eg. bits).
+    unsafe fn put_i128(n: usize, d: usize, p: *mut Self, mut i: i128) {
+        let r = tbit_radix::<Self>() as i128;
+        let balanced = Self::fits_isize(1, 1);
+        for k in 0..n {
+            let mut t = i.rem_euclid(r);
+            i = i.div_euclid(r);
+            if balanced && 2 * t > r {
+                t -= r;
+                i += 1;
+            }
+            Self::put_isize(1, d + k, p, t as isize);
+        }
+    }
+    /// The default implementation gets one tbit at a time, see `put_i128`.
+    unsafe fn get_i128(n: usize, d: usize, p: *const Self) -> i128 {
+        let r = tbit_radix::<Self>() as i128;
+        let balanced = Self::fits_isize(1, 1);
+        let mut m = 1_i128;
+        let mut i = 0_i128;
+        for k in 0..n {
+            // Only the most significant bit is signed in two's complement.
+            let t = if balanced || k + 1 == n {
+                Self::get_isize(1, d + k, p) as i128
+            } else {
+                Self::get_usize(1, d + k, p) as i128
+            };
+            i = i.wrapping_add(m.wrapping_mul(t));
+            m = m.wrapping_mul(r);
+        }
+        i
+    }
+    /// Put unsigned 128-bit `u` into `n` tbits, higher tbits of `u` that don't fit are silently
+    /// dropped (the value is truncated).
+    ///
+    /// The default implementation puts one tbit at a time with `put_usize`.
+    unsafe fn put_u128(n: usize, d: usize, p: *mut Self, mut u: u128) {
+        let r = tbit_radix::<Self>() as u128;
+        for k in 0..n {
+            Self::put_usize(1, d + k, p, (u % r) as usize);
+            u /= r;
+        }
+    }
+    /// The default implementation gets one tbit at a time with `get_usize`.
+    unsafe fn get_u128(n: usize, d: usize, p: *const Self) -> u128 {
+        let r = tbit_radix::<Self>() as u128;
+        let mut m = 1_u128;
+        let mut u = 0_u128;
+        for k in 0..n {
+            u = u.wrapping_add(m.wrapping_mul(Self::get_usize(1, d + k, p) as u128));
+            m = m.wrapping_mul(r);
+        }
+        u
+    }
+
+    /// Can signed `i` be represented with `n` tbits?
+    fn fits_isize(n: usize, i: isize) -> bool;
+    /// Can unsigned `u` be represented with `n` tbits?
+    fn fits_usize(n: usize, u: usize) -> bool;
+
+    /// Put signed `i` into `n` tbits, tbits are left intact if `i` doesn't fit.
+    unsafe fn try_put_isize(n: usize, d: usize, p: *mut Self, i: isize) -> Result<(), RangeError> {
+        if Self::fits_isize(n, i) {
+            Self::put_isize(n, d, p, i);
+            Ok(())
+        } else {
+            Err(RangeError { n })
+        }
+    }
+
+    /// Put unsigned `u` into `n` tbits, tbits are left intact if `u` doesn't fit.
+    unsafe fn try_put_usize(n: usize, d: usize, p: *mut Self, u: usize) -> Result<(), RangeError> {
+        if Self::fits_usize(n, u) {
+            Self::put_usize(n, d, p, u);
+            Ok(())
+        } else {
+            Err(RangeError { n })
+        }
+    }
+
+    /// Extract unsigned value of `width` tbits at `offset` within word `x`.
+    /// Requires `offset + width <= SIZE`.
+    fn extract_field(x: Self, offset: usize, width: usize) -> u64 {
+        assert!(offset + width <= Self::SIZE);
+        unsafe { Self::get_usize(width, offset, &x) as u64 }
+    }
+
+    /// Return word `x` with `width` tbits at `offset` replaced with unsigned `value`.
+    /// Other tbits of `x` are left intact, error if `value` doesn't fit into `width` tbits.
+    /// Requires `offset + width <= SIZE`.
+    fn insert_field(x: Self, offset: usize, width: usize, value: u64) -> Result<Self, RangeError> {
+        assert!(offset + width <= Self::SIZE);
+        let mut y = x;
+        match usize::try_from(value) {
+            Ok(u) if Self::fits_usize(width, u) => {
+                unsafe { Self::put_usize(width, offset, &mut y, u) };
+                Ok(y)
+            }
+            _ => Err(RangeError { n: width }),
+        }
+    }
 }
 
+/// Tbit word packed into a single byte, used by text encodings (see `tbits::encoding`).
+pub trait PackedTbitWord: BasicTbitWord {
+    /// Byte value of the word.
+    fn to_byte(self) -> u8;
+    /// Word from byte value `b`, `None` if `b` is not a valid (canonical) word value.
+    fn from_byte(b: u8) -> Option<Self>;
+}
+
+/// Tbit word stored as whole bytes holding its tbits in packed form, so that a buffer of
+/// words can be accessed as bytes directly, see `Tbits::as_bytes`.
+///
+/// # Safety
+///
+/// The implementing type must have the layout of `[u8; SIZE / 8]` (eg. `#[repr(transparent)]`
+/// over `u8`) with `SIZE` being a multiple of 8, and every byte value must be a valid word.
+pub unsafe trait ByteWord: BasicTbitWord {}
+
 pub trait SpongosTbitWord: BasicTbitWord {
     // Spongos-related utils
 
@@ -332,150 +737,195 @@ pub trait SpongosTbitWord: BasicTbitWord {
     /// x-y
     fn tbit_sub(x: Self::Tbit, y: Self::Tbit) -> Self::Tbit;
 
+    /// x+y for all tbits of the words.
+    fn word_add(x: Self, y: Self) -> Self {
+        let mut xs = WordTbits::<Self>::zero();
+        let mut ys = WordTbits::<Self>::zero();
+        unsafe {
+            Self::word_to_tbits(x, xs.as_mut().as_mut_ptr());
+            Self::word_to_tbits(y, ys.as_mut().as_mut_ptr());
+        }
+        for (tx, ty) in xs.as_mut().iter_mut().zip(ys.as_ref().iter()) {
+            *tx = Self::tbit_add(*tx, *ty);
+        }
+        unsafe { Self::word_from_tbits(xs.as_ref().as_ptr()) }
+    }
+
+    /// x-y for all tbits of the words.
+    fn word_sub(x: Self, y: Self) -> Self {
+        let mut xs = WordTbits::<Self>::zero();
+        let mut ys = WordTbits::<Self>::zero();
+        unsafe {
+            Self::word_to_tbits(x, xs.as_mut().as_mut_ptr());
+            Self::word_to_tbits(y, ys.as_mut().as_mut_ptr());
+        }
+        for (tx, ty) in xs.as_mut().iter_mut().zip(ys.as_ref().iter()) {
+            *tx = Self::tbit_sub(*tx, *ty);
+        }
+        unsafe { Self::word_from_tbits(xs.as_ref().as_ptr()) }
+    }
+
     /// s:=s+x
-    unsafe fn add(mut ds: usize, s: *mut Self, n: usize, mut dx: usize, x: *const Self) {
-        for _ in 0..n {
-            let ts = Self::get_tbit(ds, s);
-            let tx = Self::get_tbit(dx, x);
-            let ty = Self::tbit_add(ts, tx);
-            Self::put_tbit(ds, s, ty);
-            dx += 1;
-            ds += 1;
+    /// Whole words are added with `word_add` if both slices are aligned to the word boundary,
+    /// otherwise falls back to `add_tbits`.
+    unsafe fn add(ds: usize, s: *mut Self, n: usize, dx: usize, x: *const Self) {
+        if ds % Self::SIZE == 0 && dx % Self::SIZE == 0 {
+            let ss = s.add(ds / Self::SIZE);
+            let xx = x.add(dx / Self::SIZE);
+            let m = n / Self::SIZE;
+            for i in 0..m {
+                *ss.add(i) = Self::word_add(*ss.add(i), *xx.add(i));
+            }
+            let k = m * Self::SIZE;
+            Self::add_tbits(ds + k, s, n - k, dx + k, x);
+        } else {
+            Self::add_tbits(ds, s, n, dx, x);
         }
     }
 
-    /// y:=x+s, s:=x, x:=y
-    unsafe fn setx_add_mut(mut ds: usize, s: *mut Self, n: usize, mut dx: usize, x: *mut Self) {
-        for _ in 0..n {
-            let ts = Self::get_tbit(ds, s);
-            let tx = Self::get_tbit(dx, x);
-            let ty = Self::tbit_add(tx, ts);
-            Self::put_tbit(ds, s, tx);
-            Self::put_tbit(dx, x, ty);
-            dx += 1;
-            ds += 1;
+    /// s:=s+x, tbit by tbit through stack buffers, see `duplex_via_tbits`.
+    unsafe fn add_tbits(ds: usize, s: *mut Self, n: usize, dx: usize, x: *const Self) {
+        duplex_via_tbits::<Self, _, SCRATCH_TBITS>(n, ds, s, dx, x, None, |ts, tx| {
+            *ts = Self::tbit_add(*ts, tx);
+            *ts
+        });
+    }
+
+    /// s:=s-x
+    /// Whole words are subtracted with `word_sub` if both slices are aligned to the word boundary,
+    /// otherwise falls back to `sub_tbits`.
+    unsafe fn sub(ds: usize, s: *mut Self, n: usize, dx: usize, x: *const Self) {
+        if ds % Self::SIZE == 0 && dx % Self::SIZE == 0 {
+            let ss = s.add(ds / Self::SIZE);
+            let xx = x.add(dx / Self::SIZE);
+            let m = n / Self::SIZE;
+            for i in 0..m {
+                *ss.add(i) = Self::word_sub(*ss.add(i), *xx.add(i));
+            }
+            let k = m * Self::SIZE;
+            Self::sub_tbits(ds + k, s, n - k, dx + k, x);
+        } else {
+            Self::sub_tbits(ds, s, n, dx, x);
         }
     }
+
+    /// s:=s-x, tbit by tbit through stack buffers, see `duplex_via_tbits`.
+    unsafe fn sub_tbits(ds: usize, s: *mut Self, n: usize, dx: usize, x: *const Self) {
+        duplex_via_tbits::<Self, _, SCRATCH_TBITS>(n, ds, s, dx, x, None, |ts, tx| {
+            *ts = Self::tbit_sub(*ts, tx);
+            *ts
+        });
+    }
+
+    /// y:=x+s, s:=x, x:=y
+    unsafe fn setx_add_mut(ds: usize, s: *mut Self, n: usize, dx: usize, x: *mut Self) {
+        duplex_via_tbits::<Self, _, SCRATCH_TBITS>(n, ds, s, dx, x, Some((dx, x)), |ts, tx| {
+            let ty = Self::tbit_add(tx, *ts);
+            *ts = tx;
+            ty
+        });
+    }
     /// x:=y-s, s:=x, y:=x
-    unsafe fn setx_sub_mut(mut ds: usize, s: *mut Self, n: usize, mut dy: usize, y: *mut Self) {
-        for _ in 0..n {
-            let ts = Self::get_tbit(ds, s);
-            let ty = Self::get_tbit(dy, y);
-            let tx = Self::tbit_sub(ty, ts);
-            Self::put_tbit(ds, s, tx);
-            Self::put_tbit(dy, y, tx);
-            dy += 1;
-            ds += 1;
-        }
+    unsafe fn setx_sub_mut(ds: usize, s: *mut Self, n: usize, dy: usize, y: *mut Self) {
+        duplex_via_tbits::<Self, _, SCRATCH_TBITS>(n, ds, s, dy, y, Some((dy, y)), |ts, ty| {
+            let tx = Self::tbit_sub(ty, *ts);
+            *ts = tx;
+            tx
+        });
     }
     /// y:=x+s, s:=x
     unsafe fn setx_add(
-        mut ds: usize,
+        ds: usize,
         s: *mut Self,
         n: usize,
-        mut dx: usize,
+        dx: usize,
         x: *const Self,
-        mut dy: usize,
+        dy: usize,
         y: *mut Self,
     ) {
-        for _ in 0..n {
-            let ts = Self::get_tbit(ds, s);
-            let tx = Self::get_tbit(dx, x);
-            let ty = Self::tbit_add(tx, ts);
-            Self::put_tbit(ds, s, tx);
-            Self::put_tbit(dy, y, ty);
-            dx += 1;
-            ds += 1;
-            dy += 1;
-        }
+        duplex_via_tbits::<Self, _, SCRATCH_TBITS>(n, ds, s, dx, x, Some((dy, y)), |ts, tx| {
+            let ty = Self::tbit_add(tx, *ts);
+            *ts = tx;
+            ty
+        });
     }
     /// x:=y-s, s:=x
     unsafe fn setx_sub(
-        mut ds: usize,
+        ds: usize,
         s: *mut Self,
         n: usize,
-        mut dy: usize,
+        dy: usize,
         y: *const Self,
-        mut dx: usize,
+        dx: usize,
         x: *mut Self,
     ) {
-        for _ in 0..n {
-            let ts = Self::get_tbit(ds, s);
-            let ty = Self::get_tbit(dy, y);
-            let tx = Self::tbit_sub(ty, ts);
-            Self::put_tbit(ds, s, tx);
-            Self::put_tbit(dx, x, tx);
-            dx += 1;
-            ds += 1;
-            dy += 1;
-        }
+        duplex_via_tbits::<Self, _, SCRATCH_TBITS>(n, ds, s, dy, y, Some((dx, x)), |ts, ty| {
+            let tx = Self::tbit_sub(ty, *ts);
+            *ts = tx;
+            tx
+        });
     }
 
     /// y:=x+s, s:=y, x:=y
-    unsafe fn sety_add_mut(mut ds: usize, s: *mut Self, n: usize, mut dx: usize, x: *mut Self) {
-        for _ in 0..n {
-            let ts = Self::get_tbit(ds, s);
-            let tx = Self::get_tbit(dx, x);
-            let ty = Self::tbit_add(tx, ts);
-            Self::put_tbit(ds, s, ty);
-            Self::put_tbit(dx, x, ty);
-            dx += 1;
-            ds += 1;
-        }
+    unsafe fn sety_add_mut(ds: usize, s: *mut Self, n: usize, dx: usize, x: *mut Self) {
+        duplex_via_tbits::<Self, _, SCRATCH_TBITS>(n, ds, s, dx, x, Some((dx, x)), |ts, tx| {
+            let ty = Self::tbit_add(tx, *ts);
+            *ts = ty;
+            ty
+        });
     }
     /// x:=y-s, s:=y, y:=x
-    unsafe fn sety_sub_mut(mut ds: usize, s: *mut Self, n: usize, mut dy: usize, y: *mut Self) {
-        for _ in 0..n {
-            let ts = Self::get_tbit(ds, s);
-            let ty = Self::get_tbit(dy, y);
-            let tx = Self::tbit_sub(ty, ts);
-            Self::put_tbit(ds, s, ty);
-            Self::put_tbit(dy, y, tx);
-            dy += 1;
-            ds += 1;
-        }
+    unsafe fn sety_sub_mut(ds: usize, s: *mut Self, n: usize, dy: usize, y: *mut Self) {
+        duplex_via_tbits::<Self, _, SCRATCH_TBITS>(n, ds, s, dy, y, Some((dy, y)), |ts, ty| {
+            let tx = Self::tbit_sub(ty, *ts);
+            *ts = ty;
+            tx
+        });
     }
     /// y:=x+s, s:=y
     unsafe fn sety_add(
-        mut ds: usize,
+        ds: usize,
         s: *mut Self,
         n: usize,
-        mut dx: usize,
+        dx: usize,
         x: *const Self,
-        mut dy: usize,
+        dy: usize,
         y: *mut Self,
     ) {
-        for _ in 0..n {
-            let tx = Self::get_tbit(dx, x);
-            let ts = Self::get_tbit(ds, s);
-            let ty = Self::tbit_add(tx, ts);
-            Self::put_tbit(ds, s, ty);
-            Self::put_tbit(dy, y, ty);
-            dx += 1;
-            ds += 1;
-            dy += 1;
-        }
+        duplex_via_tbits::<Self, _, SCRATCH_TBITS>(n, ds, s, dx, x, Some((dy, y)), |ts, tx| {
+            let ty = Self::tbit_add(tx, *ts);
+            *ts = ty;
+            ty
+        });
     }
     /// x:=y-s, s:=y
     unsafe fn sety_sub(
-        mut ds: usize,
+        ds: usize,
         s: *mut Self,
         n: usize,
-        mut dy: usize,
+        dy: usize,
         y: *const Self,
-        mut dx: usize,
+        dx: usize,
         x: *mut Self,
     ) {
-        for _ in 0..n {
-            let ty = Self::get_tbit(dy, y);
-            let ts = Self::get_tbit(ds, s);
-            let tx = Self::tbit_sub(ty, ts);
-            Self::put_tbit(ds, s, ty);
-            Self::put_tbit(dx, x, tx);
-            dx += 1;
-            ds += 1;
-            dy += 1;
+        duplex_via_tbits::<Self, _, SCRATCH_TBITS>(n, ds, s, dy, y, Some((dx, x)), |ts, ty| {
+            let tx = Self::tbit_sub(ty, *ts);
+            *ts = ty;
+            tx
+        });
+    }
+
+    /// Set `n` tbits in `(dx,x)` slice to zero and make sure the writes are not optimized away.
+    /// Used to wipe secrets right before the memory is freed.
+    unsafe fn wipe(n: usize, dx: usize, x: *mut Self) {
+        if n == 0 {
+            return;
+        }
+        Self::set_zero(n, dx, x);
+        for i in dx / Self::SIZE..(dx + n + Self::SIZE - 1) / Self::SIZE {
+            core::ptr::write_volatile(x.add(i), core::ptr::read(x.add(i)));
         }
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
     }
 
     /// Absorb plain tbits `x` into state `s`, OVERWRITE mode.
@@ -497,6 +947,18 @@ pub trait SpongosTbitWord: BasicTbitWord {
         Self::copy(n, ds, s, dy, y);
     }
 
+    /// Compare `n` tbits from `(dx,x)` slice and `(dy,y)` in constant time.
+    /// Unlike `equals` all tbits are compared without early return, use it for secret values like MACs.
+    /// The accumulator goes through `black_box` so that the loop is not turned into early return.
+    unsafe fn equals_ct(n: usize, dx: usize, x: *const Self, dy: usize, y: *const Self) -> bool {
+        let mut diff = 0_u8;
+        for i in 0..n {
+            diff |= (Self::get_tbit(dx + i, x) != Self::get_tbit(dy + i, y)) as u8;
+            diff = core::hint::black_box(diff);
+        }
+        diff == 0
+    }
+
     /// Squeeze tbits `y` from state `s`, OVERWRITE mode.
     unsafe fn squeeze_eq_overwrite(
         ds: usize,
@@ -505,13 +967,13 @@ pub trait SpongosTbitWord: BasicTbitWord {
         dy: usize,
         y: *const Self,
     ) -> bool {
-        let r = Self::equals(n, ds, s as *const Self, dy, y);
+        let r = Self::equals_ct(n, ds, s as *const Self, dy, y);
         Self::set_zero(n, ds, s);
         r
     }
     /// Squeeze tbits `y` from state `s`, ADD/XOR mode.
     unsafe fn squeeze_eq_xor(ds: usize, s: *mut Self, n: usize, dy: usize, y: *const Self) -> bool {
-        Self::equals(n, ds, s as *const Self, dy, y)
+        Self::equals_ct(n, ds, s as *const Self, dy, y)
     }
 
     /// Encrypt tbits `x` into `y` with state `s`, OVERWRITE mode.
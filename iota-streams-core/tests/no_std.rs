@@ -0,0 +1,99 @@
+//! Check that `tbits::word` traits and their default methods are usable without `std`:
+//! `cargo test --no-default-features --test no_std` run in the `iota-streams-core` directory.
+//! The test crate itself is `no_std`, so any `std` item the traits need fails to compile,
+//! only the test harness links `std`.
+
+#![no_std]
+
+use core::fmt;
+use iota_streams_core::tbits::word::BasicTbitWord;
+
+/// Bit.
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct B(u8);
+
+impl fmt::Display for B {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// 3 bits per 1 byte.
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct B3(u8);
+
+impl BasicTbitWord for B3 {
+    type Tbit = B;
+    const SIZE: usize = 3;
+    const ZERO_WORD: B3 = B3(0);
+    const ZERO_TBIT: B = B(0);
+
+    unsafe fn word_to_tbits(x: Self, ts: *mut Self::Tbit) {
+        *ts.add(0) = B(1 & x.0);
+        *ts.add(1) = B(1 & (x.0 >> 1));
+        *ts.add(2) = B(1 & (x.0 >> 2));
+    }
+
+    unsafe fn word_from_tbits(ts: *const Self::Tbit) -> Self {
+        B3((*ts.add(0)).0 | (*ts.add(1)).0 << 1 | (*ts.add(2)).0 << 2)
+    }
+}
+
+const N: usize = 1000;
+const WORDS: usize = (N + 2) / 3;
+
+fn gen_tbits(seed: usize) -> [B; N] {
+    let mut ts = [B(0); N];
+    for (i, t) in ts.iter_mut().enumerate() {
+        *t = B(((i * 7 + seed) % 5 % 2) as u8);
+    }
+    ts
+}
+
+#[test]
+fn copy_equals_unaligned() {
+    let ts = gen_tbits(1);
+    let mut x = [B3(0); WORDS];
+    let mut y = [B3(0); WORDS];
+    let mut ys = [B(0); N];
+    unsafe {
+        B3::from_tbits(N, 0, x.as_mut_ptr(), ts.as_ptr());
+
+        // Different offsets within words go through stack scratch buffers.
+        for &(dx, dy) in [(0, 1), (2, 1), (1, 0), (5, 7)].iter() {
+            let n = N - 7;
+            B3::set_zero(N, 0, y.as_mut_ptr());
+            B3::copy(n, dx, x.as_ptr(), dy, y.as_mut_ptr());
+            assert!(B3::equals(n, dx, x.as_ptr(), dy, y.as_ptr()));
+            B3::to_tbits(n, dy, y.as_ptr(), ys.as_mut_ptr());
+            assert_eq!(&ts[dx..dx + n], &ys[..n]);
+
+            B3::set_zero(1, dy + n / 2, y.as_mut_ptr());
+            assert_eq!(
+                ts[dx + n / 2] == B(0),
+                B3::equals(n, dx, x.as_ptr(), dy, y.as_ptr())
+            );
+        }
+    }
+}
+
+#[test]
+fn copy_overlapping() {
+    let ts = gen_tbits(3);
+    let mut x = [B3(0); WORDS];
+    let mut xs = [B(0); N];
+    unsafe {
+        B3::from_tbits(N, 0, x.as_mut_ptr(), ts.as_ptr());
+        B3::copy(N - 400, 0, x.as_ptr(), 400, x.as_mut_ptr());
+        B3::to_tbits(N - 400, 400, x.as_ptr(), xs.as_mut_ptr());
+    }
+    assert_eq!(&ts[..N - 400], &xs[..N - 400]);
+
+    // Same offsets within words, the destination starts after the source.
+    unsafe {
+        B3::from_tbits(N, 0, x.as_mut_ptr(), ts.as_ptr());
+        B3::copy(N - 403, 1, x.as_ptr(), 403, x.as_mut_ptr());
+        B3::to_tbits(N - 403, 403, x.as_ptr(), xs.as_mut_ptr());
+    }
+    assert_eq!(&ts[1..N - 402], &xs[..N - 403]);
+}
@@ -0,0 +1,106 @@
+#[macro_use]
+extern crate criterion;
+
+use criterion::{Benchmark, Criterion};
+use iota_streams_core::{
+    sponge::prp::troika::Troika,
+    tbits::{trinary::Trit, TbitSliceMut, Tbits},
+};
+use iota_streams_protobuf3::{command::*, error::Protobuf3Error, types::*};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Counts heap allocations in order to compare a fresh context per message with `reset`.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Small message, a typical high-throughput publisher case.
+const SIZE: usize = 243;
+
+/// Number of messages per iteration.
+const MESSAGES: usize = 1000;
+
+fn wrap_message<'a>(
+    ctx: &mut wrap::Context<Trit, Troika, TbitSliceMut<'a, Trit>>,
+    payload: &Trytes<Trit>,
+) -> Result<(), Protobuf3Error> {
+    ctx.absorb(payload)?.commit()?.squeeze(&Mac(243))?;
+    Ok(())
+}
+
+fn wrap_fresh(payload: &Trytes<Trit>, buf: &mut Tbits<Trit>) -> Result<(), Protobuf3Error> {
+    for _ in 0..MESSAGES {
+        wrap_message(
+            &mut wrap::Context::<Trit, Troika, _>::new(buf.slice_mut()),
+            payload,
+        )?;
+    }
+    Ok(())
+}
+
+fn wrap_reset(payload: &Trytes<Trit>, buf: &mut Tbits<Trit>) -> Result<(), Protobuf3Error> {
+    let mut ctx = wrap::Context::<Trit, Troika, _>::new(buf.slice_mut());
+    for _ in 0..MESSAGES {
+        ctx.reset()?;
+        wrap_message(&mut ctx, payload)?;
+    }
+    Ok(())
+}
+
+fn count_allocations<F: FnOnce()>(f: F) -> usize {
+    let n = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - n
+}
+
+fn context_reset_benchmark(c: &mut Criterion) {
+    let payload = Trytes(Tbits::<Trit>::cycle_str(SIZE, "PAYLOAD"));
+    let buf_size = sizeof::Context::<Trit, Troika>::new()
+        .absorb(&payload)
+        .unwrap()
+        .commit()
+        .unwrap()
+        .squeeze(&Mac(243))
+        .unwrap()
+        .get_size();
+    let mut buf = Tbits::<Trit>::zero(buf_size);
+    let mut buf2 = Tbits::<Trit>::zero(buf_size);
+
+    println!(
+        "Allocations per {} messages: fresh context {}, reset context {}",
+        MESSAGES,
+        count_allocations(|| wrap_fresh(&payload, &mut buf).unwrap()),
+        count_allocations(|| wrap_reset(&payload, &mut buf2).unwrap()),
+    );
+
+    let payload2 = payload.clone();
+    c.bench(
+        "Fresh vs reset context",
+        Benchmark::new("fresh (1000 messages)", move |b| {
+            b.iter(|| wrap_fresh(&payload, &mut buf).unwrap())
+        })
+        .with_function("reset (1000 messages)", move |b| {
+            b.iter(|| wrap_reset(&payload2, &mut buf2).unwrap())
+        })
+        .sample_size(10)
+        .measurement_time(Duration::from_millis(10000)),
+    );
+}
+
+criterion_group!(benches, context_reset_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,49 @@
+#[macro_use]
+extern crate criterion;
+
+use criterion::{Benchmark, Criterion};
+use iota_streams_core::{
+    sponge::prp::troika::Troika,
+    tbits::{trinary::Trit, Tbits},
+};
+use iota_streams_protobuf3::{command::*, error::Protobuf3Error, types::*};
+use std::time::Duration;
+
+/// 1 MiB message, one tryte encodes about a byte.
+const SIZE: usize = 3 * 1024 * 1024;
+
+fn sizeof_message(payload: &Trytes<Trit>) -> Result<usize, Protobuf3Error> {
+    Ok(sizeof::Context::<Trit, Troika>::new()
+        .absorb(payload)?
+        .commit()?
+        .squeeze(&Mac(243))?
+        .size())
+}
+
+fn wrap_message(payload: &Trytes<Trit>, buf: &mut Tbits<Trit>) -> Result<(), Protobuf3Error> {
+    wrap::Context::<Trit, Troika, _>::new(buf.slice_mut())
+        .absorb(payload)?
+        .commit()?
+        .squeeze(&Mac(243))?;
+    Ok(())
+}
+
+fn sizeof_wrap_benchmark(c: &mut Criterion) {
+    let payload = Trytes(Tbits::<Trit>::cycle_str(SIZE, "PAYLOAD"));
+    let mut buf = Tbits::<Trit>::zero(sizeof_message(&payload).unwrap());
+    let payload2 = payload.clone();
+    c.bench(
+        "Sizeof vs wrap",
+        Benchmark::new("sizeof (1MiB)", move |b| {
+            b.iter(|| sizeof_message(&payload).unwrap())
+        })
+        .with_function("wrap (1MiB)", move |b| {
+            b.iter(|| wrap_message(&payload2, &mut buf).unwrap())
+        })
+        .sample_size(10)
+        .measurement_time(Duration::from_millis(10000)),
+    );
+}
+
+criterion_group!(benches, sizeof_wrap_benchmark);
+criterion_main!(benches);
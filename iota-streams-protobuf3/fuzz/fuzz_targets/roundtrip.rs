@@ -0,0 +1,108 @@
+//! Round-trip `Absorb`, `Mask` and `Squeeze` commands of arbitrary lengths,
+//! run with `cargo fuzz run roundtrip` from `iota-streams-protobuf3`.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use iota_streams_core::{
+    sponge::prp::troika::Troika,
+    tbits::{trinary::Trit, Tbits},
+};
+use iota_streams_protobuf3::{
+    command::{sizeof, unwrap, wrap, Absorb, Commit, Mask, Squeeze},
+    error::Protobuf3Error,
+    io,
+    testkit::{roundtrip, Message},
+    types::{Mac, NTrytes, Size, Trytes},
+};
+
+#[derive(PartialEq)]
+struct Fuzzed {
+    bytes: Vec<u8>,
+    trytes: Trytes<Trit>,
+    /// Size of `masked` in trytes.
+    masked_size: Size,
+    masked: NTrytes<Trit>,
+    text: String,
+    /// MAC size in trytes.
+    mac_size: Size,
+}
+
+impl Default for Fuzzed {
+    fn default() -> Self {
+        Self {
+            bytes: Vec::new(),
+            trytes: Trytes::default(),
+            masked_size: Size(0),
+            masked: NTrytes::zero(0),
+            text: String::new(),
+            mac_size: Size(0),
+        }
+    }
+}
+
+/// Split `input` into fields, the first bytes select the field lengths.
+fn fuzzed(input: &[u8]) -> Fuzzed {
+    let len = |i: usize| input.get(i).map_or(0, |b| *b as usize);
+    let rest = input.get(3..).unwrap_or_default();
+    let (bytes, rest) = rest.split_at(len(0).min(rest.len()));
+    let (trits, text) = rest.split_at(len(1).min(rest.len()));
+    let tbits = |ts: &[u8]| -> Tbits<Trit> { ts.iter().map(|b| Trit(b % 3)).collect() };
+    let trytes = tbits(&trits[..trits.len() / 3 * 3]);
+    let masked = tbits(&bytes[..bytes.len() / 3 * 3]);
+    Fuzzed {
+        bytes: bytes.to_vec(),
+        trytes: Trytes(trytes),
+        masked_size: Size(masked.size() / 3),
+        masked: NTrytes(masked),
+        text: String::from_utf8_lossy(text).into_owned(),
+        mac_size: Size(len(2)),
+    }
+}
+
+impl Message<Trit, Troika> for Fuzzed {
+    fn sizeof<'c>(
+        &self,
+        ctx: &'c mut sizeof::Context<Trit, Troika>,
+    ) -> Result<&'c mut sizeof::Context<Trit, Troika>, Protobuf3Error> {
+        ctx.absorb(&self.bytes[..])?
+            .absorb(&self.trytes)?
+            .absorb(&self.masked_size)?
+            .mask(&self.masked)?
+            .mask(self.text.as_str())?
+            .mask(&self.mac_size)?
+            .commit()?
+            .squeeze(&Mac(3 * self.mac_size.0))
+    }
+    fn wrap<'c, OS: io::OStream<Trit>>(
+        &self,
+        ctx: &'c mut wrap::Context<Trit, Troika, OS>,
+    ) -> Result<&'c mut wrap::Context<Trit, Troika, OS>, Protobuf3Error> {
+        ctx.absorb(&self.bytes[..])?
+            .absorb(&self.trytes)?
+            .absorb(&self.masked_size)?
+            .mask(&self.masked)?
+            .mask(self.text.as_str())?
+            .mask(&self.mac_size)?
+            .commit()?
+            .squeeze(&Mac(3 * self.mac_size.0))
+    }
+    fn unwrap<'c, IS: io::IStream<Trit>>(
+        &mut self,
+        ctx: &'c mut unwrap::Context<Trit, Troika, IS>,
+    ) -> Result<&'c mut unwrap::Context<Trit, Troika, IS>, Protobuf3Error> {
+        ctx.absorb(&mut self.bytes)?
+            .absorb(&mut self.trytes)?
+            .absorb(&mut self.masked_size)?;
+        self.masked = NTrytes(Tbits::zero(3 * self.masked_size.0));
+        ctx.mask(&mut self.masked)?
+            .mask(&mut self.text)?
+            .mask(&mut self.mac_size)?
+            .commit()?
+            .squeeze(&Mac(3 * self.mac_size.0))
+    }
+}
+
+fuzz_target!(|input: &[u8]| {
+    roundtrip::<Trit, Troika, _, _>(fuzzed, input).unwrap();
+});
@@ -21,37 +21,94 @@
 //!
 //! Command traits are implemented in modules `sizeof`, `wrap`, `unwrap`.
 
-use failure::Fallible;
+use crate::error::Protobuf3Error;
+
+/// Duplex mode of Spongos used by wrap and unwrap contexts, see `wrap::Context::set_spongos_mode`.
+/// Both sides must use the same mode, otherwise masked fields and MACs don't match.
+pub use iota_streams_core::sponge::spongos::Mode as SpongosMode;
 
 /// Absorb command. Trinary representation of the field is absorbed into Spongos state.
 /// External fields are not encoded in the trinary stream. Non-trinary field is
 /// an input argument in Wrap command and an output argument in Unwrap command.
 pub trait Absorb<Type> {
-    fn absorb(&mut self, field: Type) -> Fallible<&mut Self>;
+    fn absorb(&mut self, field: Type) -> Result<&mut Self, Protobuf3Error>;
 }
 
 /// Squeeze command. Trinary representation of the field is squeezed from Spongos state.
 /// The command supports fields of `tryte [n]` type (`NTryte`) and is usually used as
 /// MAC or externally stored hash value to be signed.
 pub trait Squeeze<Type> {
-    fn squeeze(&mut self, field: Type) -> Fallible<&mut Self>;
+    fn squeeze(&mut self, field: Type) -> Result<&mut Self, Protobuf3Error>;
 }
 
 /// Mask command. Trinary representation is encrypted in Wrap command and decrypted
 /// in Unwrap command using Spongos.
 /// Formatted fields (eg. of `size_t` type or `oneof`) are checked after decryption.
 pub trait Mask<Type> {
-    fn mask(&mut self, field: Type) -> Fallible<&mut Self>;
+    fn mask(&mut self, field: Type) -> Result<&mut Self, Protobuf3Error>;
+}
+
+/// Element of a fixed-size array masked with `Mask<&[T; N]>`, implemented for any `T`
+/// that context `C` can mask by reference. The array impls are bound on this trait rather
+/// than on `C: Mask<&T>` directly so that trait resolution doesn't recurse into nested arrays.
+pub trait MaskElement<C> {
+    fn mask_element<'c>(&self, ctx: &'c mut C) -> Result<&'c mut C, Protobuf3Error>;
+}
+
+impl<C, T> MaskElement<C> for T
+where
+    for<'a> C: Mask<&'a T>,
+{
+    fn mask_element<'c>(&self, ctx: &'c mut C) -> Result<&'c mut C, Protobuf3Error> {
+        ctx.mask(self)
+    }
+}
+
+/// Element of a fixed-size array unmasked with `Mask<&mut [T; N]>`, see `MaskElement`.
+pub trait UnmaskElement<C> {
+    fn unmask_element<'c>(&mut self, ctx: &'c mut C) -> Result<&'c mut C, Protobuf3Error>;
+}
+
+impl<C, T> UnmaskElement<C> for T
+where
+    for<'a> C: Mask<&'a mut T>,
+{
+    fn unmask_element<'c>(&mut self, ctx: &'c mut C) -> Result<&'c mut C, Protobuf3Error> {
+        ctx.mask(self)
+    }
+}
+
+/// Size in trits of the authentication tag appended by `SealedMask`.
+pub const SEALED_MASK_TAG_SIZE: usize = 81;
+
+/// SealedMask command. The field is masked, Spongos state is committed and a tag of
+/// `SEALED_MASK_TAG_SIZE` trits is squeezed, ie. `mask(field)?.commit()?.squeeze(&Mac(..))`
+/// in one command so that the MAC can't be forgotten.
+/// Unwrap command decrypts the field into a copy of the output argument and compares the tag
+/// in constant time, the output argument is only overwritten if the tag matches,
+/// otherwise `Protobuf3Error::BadMac` is returned and the plaintext is wiped, see `Wipe`.
+/// The tag can't be checked by `UnwrapValidate` contexts, they reject sealed fields.
+pub trait SealedMask<Type> {
+    fn sealed_mask(&mut self, field: Type) -> Result<&mut Self, Protobuf3Error>;
 }
 
 /// Skip command. Trinary representation is just encoded/decoded and is not processed with Spongos.
 pub trait Skip<Type> {
-    fn skip(&mut self, field: Type) -> Fallible<&mut Self>;
+    fn skip(&mut self, field: Type) -> Result<&mut Self, Protobuf3Error>;
+}
+
+/// PadTo command. Zero tbits are skipped so that the message reaches `total` tbits, eg. to
+/// make all messages of a channel the same size regardless of the payload.
+/// Padding is not processed with Spongos, Unwrap command checks that it is zero and
+/// returns `Protobuf3Error::OutOfRange` otherwise.
+/// `Protobuf3Error::PadOverflow` is returned if the message is already longer than `total`.
+pub trait PadTo {
+    fn pad_to(&mut self, total: usize) -> Result<&mut Self, Protobuf3Error>;
 }
 
 /// Commit command. Commit Spongos state.
 pub trait Commit {
-    fn commit(&mut self) -> Fallible<&mut Self>;
+    fn commit(&mut self) -> Result<&mut Self, Protobuf3Error>;
 }
 
 /// Mssig command. Sign/verify hash value. The signature is processed implicitly and is
@@ -62,24 +119,78 @@ pub trait Commit {
 /// against an expected one. It can supported with this trait for Unwrap operation and
 /// the two cases are distinguished by the `Key` type: a mutable reference to a MSS
 /// public key refers to the recovered public key and an immutable reference to a MSS
-/// public key referes to the expected public key.
+/// public key referes to the expected public key. The recovered public key is written
+/// into the caller's buffer, `unwrap::Context::mssig_recover` returns it by value.
+///
+/// The `Hash` type selects the signed value: `MssHashSig` squeezes it from Spongos and
+/// `&External<NTrytes>` takes a `P::HASH_SIZE` hash supplied by the caller, eg. a hash of
+/// an off-chain blob. An external hash is neither absorbed nor encoded, only the signature
+/// is, so Unwrap must be given the same hash to verify the signature.
 pub trait Mssig<Key, Hash> {
-    fn mssig(&mut self, key: Key, hash: Hash) -> Fallible<&mut Self>;
+    fn mssig(&mut self, key: Key, hash: Hash) -> Result<&mut Self, Protobuf3Error>;
+
+    /// Process consecutive signatures, one per `(key, hash)` pair, in order.
+    /// Processing stops at the first failed signature and the error reports its index
+    /// (see `Protobuf3Error::MssigBatch`).
+    ///
+    /// Signatures are encoded independently in the stream and each one has its own
+    /// Merkle authentication path, so there is no recomputation to share between them.
+    fn mssig_batch(&mut self, pairs: &[(Key, Hash)]) -> Result<&mut Self, Protobuf3Error>
+    where
+        Key: Clone,
+        Hash: Clone,
+    {
+        for (index, (key, hash)) in pairs.iter().enumerate() {
+            if let Err(e) = self.mssig(key.clone(), hash.clone()) {
+                bail!(Protobuf3Error::MssigBatch {
+                    index,
+                    reason: e.to_string(),
+                });
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Ed25519sig command. Sign/verify hash value with Ed25519, the signature is processed
+/// implicitly as with `Mssig`. Wrap operation takes a key pair and Unwrap operation
+/// takes the signer's public key: unlike MSS, an Ed25519 public key can't be recovered
+/// from a signature, it's usually absorbed in the message beforehand.
+#[cfg(feature = "ed25519-dalek")]
+pub trait Ed25519Sig<Key, Hash> {
+    fn ed25519sig(&mut self, key: Key, hash: Hash) -> Result<&mut Self, Protobuf3Error>;
 }
 
 /// Ntrukem command. Secret is encapsulated with NTRU key. For Wrap operation
-/// PRNG, nonce and NTRU public key are passed as tuple in `key` argument.
+/// PRNG, nonce and NTRU public key are passed as tuple in `key` argument,
+/// or NTRU public key only and randomness is drawn from the RNG of the context
+/// (see `wrap::Context::set_rng`).
 /// For Unwrap operation NTRU private key is passed in `key` argument.
 pub trait Ntrukem<Key, Secret> {
-    fn ntrukem(&mut self, key: Key, secret: Secret) -> Fallible<&mut Self>;
+    fn ntrukem(&mut self, key: Key, secret: Secret) -> Result<&mut Self, Protobuf3Error>;
 }
 
 /// Fork command. Fork Spongos state and continue processing `cont` commands.
 /// After the fork is finished the resulting Spongos state is discarded and
 /// field processing continues using the saved current Spongos state.
-/// The trait can be implemented for functions `Fn(&mut self) -> Fallible<&mut Self>`.
+/// The trait can be implemented for functions `Fn(&mut self) -> Result<&mut Self, Protobuf3Error>`.
+/// Boxed closures `DynFork` are functions too, so forks assembled at runtime
+/// can be collected and processed in sequence.
 pub trait Fork<F> {
-    fn fork(&mut self, cont: F) -> Fallible<&mut Self>;
+    fn fork(&mut self, cont: F) -> Result<&mut Self, Protobuf3Error>;
+}
+
+/// Type-erased fork continuation for context `Ctx`.
+pub type DynFork<'c, Ctx> =
+    Box<dyn for<'a> FnMut(&'a mut Ctx) -> Result<&'a mut Ctx, Protobuf3Error> + 'c>;
+
+/// Box fork continuation `cont`, closures passed here directly get the required
+/// higher-ranked signature, which is not inferred for them in `Box::new`.
+pub fn dyn_fork<'c, Ctx, C>(cont: C) -> DynFork<'c, Ctx>
+where
+    C: for<'a> FnMut(&'a mut Ctx) -> Result<&'a mut Ctx, Protobuf3Error> + 'c,
+{
+    Box::new(cont)
 }
 
 /// Join command. Spongos state for the linked message is retrieved from the context
@@ -89,26 +200,155 @@ pub trait Fork<F> {
 /// Although it may be non-trivial to locate a link in the middle of a message,
 /// links are usually inserted at the start of message content (after header of course).
 pub trait Join<L, S> {
-    fn join(&mut self, store: S, link: L) -> Fallible<&mut Self>;
+    fn join(&mut self, store: S, link: L) -> Result<&mut Self, Protobuf3Error>;
+}
+
+/// Join command verifying the linked Spongos state before joining.
+///
+/// The store is trusted by `Join`. `JoinVerified` squeezes a commitment from a fork of
+/// the retrieved state and compares it with `expected_mac` (see `types::link_commitment`)
+/// in constant time, a tampered store is rejected with `Protobuf3Error::BadLinkCommitment`.
+pub trait JoinVerified<L, S, M> {
+    fn join_verified(
+        &mut self,
+        store: S,
+        link: L,
+        expected_mac: M,
+    ) -> Result<&mut Self, Protobuf3Error>;
 }
 
 /// Repeated modifier.
 pub trait Repeated<I, F> {
     /// `values_iter` provides some iterated values or counter.
     /// `value_handler` handles one item.
-    fn repeated(&mut self, values_iter: I, value_handle: F) -> Fallible<&mut Self>;
+    fn repeated(&mut self, values_iter: I, value_handle: F) -> Result<&mut Self, Protobuf3Error>;
+}
+
+/// Guard command. Check an invariant of the decoded fields inline in the command chain,
+/// eg. `ctx.absorb(&mut n)?.guard(n.0 <= MAX, "n is too large")?`.
+/// Unwrap operation fails with `err` message if `cond` is false.
+/// Wrap and sizeof operations process trusted values and ignore the `cond`.
+pub trait Guard {
+    fn guard(&mut self, cond: bool, err: &'static str) -> Result<&mut Self, Protobuf3Error>;
+}
+
+/// Timestamp command. A 64-bit Unix timestamp is absorbed as a fixed-width `u64` field
+/// of `SIZEOF_U64` trits, so the message size doesn't depend on the value.
+/// The timestamp is an input argument in Wrap command and an output argument in Unwrap command,
+/// unwrap only decodes it and doesn't check it against the current time.
+pub trait Timestamp<T> {
+    fn absorb_timestamp(&mut self, ts: T) -> Result<&mut Self, Protobuf3Error>;
+}
+
+/// Repeated modifier with a runtime count `n`, eg. just unwrapped `size_t` value.
+/// `value_handle` is called with index `0..n`. Unwrap operation rejects counts
+/// exceeding the ceiling set with `unwrap::Context::set_repeated_max`.
+pub trait RepeatedN<F> {
+    fn repeated_n(&mut self, n: usize, value_handle: F) -> Result<&mut Self, Protobuf3Error>;
 }
 
-/// Dump context info into stdout.
+/// Dump context info into stdout or into the `DumpSink` of the context.
 /// Use it like this: `ctx.dump(format_args!("checkpoint"))`
 pub trait Dump {
-    fn dump<'a>(&mut self, args: std::fmt::Arguments<'a>) -> Fallible<&mut Self> {
-        //std::io::_print(args);
-        println!("{}", args);
+    fn dump<'a>(&mut self, args: std::fmt::Arguments<'a>) -> Result<&mut Self, Protobuf3Error> {
+        dump_default(args);
         Ok(self)
     }
 }
 
+/// Emit dump line as a `tracing` debug event, only with `tracing` feature.
+#[cfg(feature = "tracing")]
+fn dump_default(args: std::fmt::Arguments) {
+    tracing::debug!("{}", args);
+}
+
+/// Print dump line into stdout, only with `dump-stdout` feature (enabled by default).
+/// Without the feature dump lines are dropped unless a `DumpSink` is set.
+#[cfg(all(feature = "dump-stdout", not(feature = "tracing")))]
+fn dump_default(args: std::fmt::Arguments) {
+    println!("{}", args);
+}
+
+#[cfg(not(any(feature = "dump-stdout", feature = "tracing")))]
+fn dump_default(_args: std::fmt::Arguments) {}
+
+type DumpFn = dyn FnMut(std::fmt::Arguments) + Send;
+
+/// Output sink of `Dump` command, stdout or `tracing` events by default.
+#[derive(Default)]
+pub struct DumpSink(Option<Box<DumpFn>>);
+
+impl DumpSink {
+    /// Redirect dump output into `sink`, it's `Send` so that contexts can be moved
+    /// across threads.
+    pub fn new<S>(sink: S) -> Self
+    where
+        S: FnMut(std::fmt::Arguments) + Send + 'static,
+    {
+        Self(Some(Box::new(sink)))
+    }
+
+    /// Write dump line into the sink.
+    pub fn dump<'a>(&mut self, args: std::fmt::Arguments<'a>) {
+        match &mut self.0 {
+            Some(sink) => sink(args),
+            None => dump_default(args),
+        }
+    }
+}
+
+impl std::fmt::Debug for DumpSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "DumpSink({})",
+            if self.0.is_some() { "custom" } else { "stdout" }
+        )
+    }
+}
+
+/// Run command `$cmd`, a closure taking the context, within a `tracing` debug span `$name`
+/// recording the type of the `field`, the stream `offset` of the command, the `size` of
+/// the command in tbits and the `error` if the command fails. The span is only emitted
+/// with `tracing` feature, otherwise the command is just run.
+macro_rules! traced {
+    ($ctx:expr, $name:literal, $cmd:expr) => {
+        traced!($ctx, $name, "", $cmd)
+    };
+    ($ctx:expr, $name:literal, $field:expr, $cmd:expr) => {{
+        #[cfg(feature = "tracing")]
+        {
+            let offset = $ctx.position();
+            let span = tracing::debug_span!(
+                $name,
+                field = $field,
+                offset,
+                size = tracing::field::Empty,
+                error = tracing::field::Empty
+            );
+            let _entered = span.enter();
+            let r = run_command($ctx, $cmd).map(|_| ());
+            span.record("size", &($ctx.position() - offset));
+            if let Err(e) = &r {
+                span.record("error", &tracing::field::display(e));
+            }
+            r.map(|_| $ctx)
+        }
+        #[cfg(not(feature = "tracing"))]
+        {
+            run_command($ctx, $cmd)
+        }
+    }};
+}
+
+/// Helper for `traced!` fixing the signature of the command closure.
+fn run_command<Ctx, C>(ctx: &mut Ctx, cmd: C) -> Result<&mut Ctx, Protobuf3Error>
+where
+    C: FnOnce(&mut Ctx) -> Result<&mut Ctx, Protobuf3Error>,
+{
+    cmd(ctx)
+}
+
 /// Implementation of command traits for message size calculation.
 pub mod sizeof;
 
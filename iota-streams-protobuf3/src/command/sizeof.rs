@@ -1,20 +1,28 @@
 //! Implementation of command traits for calculating the size for output buffer in Wrap operation.
-use failure::ensure;
 use std::iter;
 
-use iota_streams_core::tbits::word::{BasicTbitWord, IntTbitWord, SpongosTbitWord};
+use iota_streams_core::tbits::{
+    trinary,
+    word::{BasicTbitWord, IntTbitWord, SpongosTbitWord},
+};
 use iota_streams_core_mss::signature::mss;
 use iota_streams_core_ntru::key_encapsulation::ntru;
 
 use crate::command::*;
+use crate::error::Protobuf3Error;
 use crate::types::*;
-use failure::Fallible;
 
 /// Message size counting context.
+///
+/// Sizing only accumulates the number of trits each command encodes, there's no Spongos
+/// state here and no permutations are run, so the cost is linear in the number of commands
+/// rather than in the message length. `F` is not required to be a `PRP`.
 #[derive(Debug)]
 pub struct Context<TW, F> {
     /// The current message size in trits.
     size: usize,
+    /// Output sink of `Dump` command.
+    dump_sink: DumpSink,
     _phantom: std::marker::PhantomData<(TW, F)>,
 }
 
@@ -23,18 +31,107 @@ impl<TW, F> Context<TW, F> {
     pub fn new() -> Self {
         Self {
             size: 0,
+            dump_sink: DumpSink::default(),
             _phantom: std::marker::PhantomData,
         }
     }
+    /// Redirect output of `Dump` command into `sink`.
+    pub fn set_dump_sink<S>(&mut self, sink: S)
+    where
+        S: FnMut(std::fmt::Arguments) + Send + 'static,
+    {
+        self.dump_sink = DumpSink::new(sink);
+    }
+
     /// Returns calculated message size.
     pub fn get_size(&self) -> usize {
         self.size
     }
+
+    /// Returns calculated message size in trits, same as `get_size`.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl<TW, F> Context<TW, F> {
+    /// Message sequence counter has fixed-size encoding.
+    pub fn absorb_counter(&mut self, _counter: u64) -> Result<&mut Self, Protobuf3Error> {
+        self.size += SIZEOF_U64;
+        Ok(self)
+    }
+
+    /// Schema identifier is external and is not encoded.
+    pub fn bind_schema(&mut self, _schema_id: &[Trint3]) -> Result<&mut Self, Protobuf3Error> {
+        Ok(self)
+    }
+}
+
+impl<TW, F> Context<TW, F>
+where
+    TW: BasicTbitWord,
+{
+    /// Tolerant `oneof` field: variant tag and body size are absorbed, the body is sized by `cont`.
+    /// `tag` must be less than `known_max`, see `wrap::Context::oneof_tolerant`.
+    pub fn oneof_tolerant<C>(
+        &mut self,
+        tag: Size,
+        known_max: Size,
+        size: Size,
+        cont: C,
+    ) -> Result<&mut Self, Protobuf3Error>
+    where
+        C: for<'a> FnMut(&'a mut Self) -> Result<&'a mut Self, Protobuf3Error>,
+    {
+        ensure!(
+            tag < known_max,
+            Protobuf3Error::OutOfRange(format!(
+                "Oneof tag {} is not less than the number of known variants {}.",
+                tag.0, known_max.0
+            ))
+        );
+        self.absorb(&tag)?.absorb(&size)?.fork(cont)
+    }
+}
+
+impl<TW, F> Context<TW, F>
+where
+    TW: IntTbitWord + SpongosTbitWord,
+{
+    /// Only the signature is encoded, message identifier is external.
+    pub fn finalize_with_signature_and_id<P>(
+        &mut self,
+        sk: &mss::PrivateKey<TW, P>,
+        _id: &NTrytes<TW>,
+    ) -> Result<&mut Self, Protobuf3Error>
+    where
+        P: mss::Parameters<TW>,
+    {
+        self.mssig(sk, MssHashSig)
+    }
+}
+
+impl<TW, F> Context<TW, F>
+where
+    TW: BasicTbitWord,
+{
+    /// The number of recipients and a capsule per recipient are encoded.
+    pub fn ntrukem_multi(
+        &mut self,
+        keys: &[&ntru::PublicKey<TW, F>],
+        secret: &NTrytes<TW>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        self.absorb(Size(keys.len()))?;
+        for pk in keys {
+            self.ntrukem(*pk, secret)?;
+        }
+        Ok(self)
+    }
 }
 
 /// All Trint3 values are encoded with 3 trits.
 impl<TW, F> Absorb<&Trint3> for Context<TW, F> {
-    fn absorb(&mut self, _trint3: &Trint3) -> Fallible<&mut Self> {
+    fn absorb(&mut self, _trint3: &Trint3) -> Result<&mut Self, Protobuf3Error> {
         self.size += 3;
         Ok(self)
     }
@@ -42,7 +139,7 @@ impl<TW, F> Absorb<&Trint3> for Context<TW, F> {
 
 /// Size has var-size encoding.
 impl<TW, F> Absorb<&Size> for Context<TW, F> {
-    fn absorb(&mut self, size: &Size) -> Fallible<&mut Self> {
+    fn absorb(&mut self, size: &Size) -> Result<&mut Self, Protobuf3Error> {
         self.size += sizeof_sizet(size.0);
         Ok(self)
     }
@@ -50,25 +147,85 @@ impl<TW, F> Absorb<&Size> for Context<TW, F> {
 
 /// All Trint3 values are encoded with 3 trits.
 impl<TW, F> Absorb<Trint3> for Context<TW, F> {
-    fn absorb(&mut self, trint3: Trint3) -> Fallible<&mut Self> {
+    fn absorb(&mut self, trint3: Trint3) -> Result<&mut Self, Protobuf3Error> {
         self.absorb(&trint3)
     }
 }
 
 /// Size has var-size encoding.
 impl<TW, F> Absorb<Size> for Context<TW, F> {
-    fn absorb(&mut self, size: Size) -> Fallible<&mut Self> {
+    fn absorb(&mut self, size: Size) -> Result<&mut Self, Protobuf3Error> {
         self.absorb(&size)
     }
 }
 
+/// Ratio has fixed-size encoding.
+impl<TW, F> Absorb<&Ratio> for Context<TW, F> {
+    fn absorb(&mut self, _ratio: &Ratio) -> Result<&mut Self, Protobuf3Error> {
+        self.size += SIZEOF_RATIO;
+        Ok(self)
+    }
+}
+
+/// Ratio has fixed-size encoding.
+impl<TW, F> Absorb<Ratio> for Context<TW, F> {
+    fn absorb(&mut self, ratio: Ratio) -> Result<&mut Self, Protobuf3Error> {
+        self.absorb(&ratio)
+    }
+}
+
+/// Byte string is encoded with `size_t` length followed by the bytes.
+impl<TW, F> Absorb<&[u8]> for Context<TW, F> {
+    fn absorb(&mut self, bytes: &[u8]) -> Result<&mut Self, Protobuf3Error> {
+        self.size += sizeof_bytes(bytes.len());
+        Ok(self)
+    }
+}
+
+/// Presence flag followed by the inner field if present.
+impl<'a, TW, F, T: 'a> Absorb<Option<&'a T>> for Context<TW, F>
+where
+    Self: Absorb<&'a T>,
+{
+    fn absorb(&mut self, x: Option<&'a T>) -> Result<&mut Self, Protobuf3Error> {
+        self.size += SIZEOF_FLAG;
+        match x {
+            Some(x) => self.absorb(x),
+            None => Ok(self),
+        }
+    }
+}
+
+/// Interval has fixed-size encoding.
+impl<TW, F> Absorb<&Interval> for Context<TW, F> {
+    fn absorb(&mut self, _interval: &Interval) -> Result<&mut Self, Protobuf3Error> {
+        self.size += SIZEOF_INTERVAL;
+        Ok(self)
+    }
+}
+
+/// Interval has fixed-size encoding.
+impl<TW, F> Absorb<Interval> for Context<TW, F> {
+    fn absorb(&mut self, interval: Interval) -> Result<&mut Self, Protobuf3Error> {
+        self.absorb(&interval)
+    }
+}
+
+/// Timestamp has fixed-size encoding.
+impl<TW, F> Timestamp<u64> for Context<TW, F> {
+    fn absorb_timestamp(&mut self, _ts: u64) -> Result<&mut Self, Protobuf3Error> {
+        self.size += SIZEOF_U64;
+        Ok(self)
+    }
+}
+
 /*
 /// External values are not encoded in the trinary stream.
 impl<'a, TW, F, T: 'a> Absorb<&'a External<T>> for Context<TW, F>
 where
     Self: Absorb<T>,
 {
-    fn absorb(&mut self, _external: &'a External<T>) -> Fallible<&mut Self> {
+    fn absorb(&mut self, _external: &'a External<T>) -> Result<&mut Self, Protobuf3Error> {
         Ok(self)
     }
 }
@@ -78,7 +235,7 @@ impl<'a, TW, F, T: 'a> Absorb<External<&'a T>> for Context<TW, F>
 where
 //Self: Absorb<&'a T>,
 {
-    fn absorb(&mut self, _external: External<&'a T>) -> Fallible<&mut Self> {
+    fn absorb(&mut self, _external: External<&'a T>) -> Result<&mut Self, Protobuf3Error> {
         Ok(self)
     }
 }
@@ -89,7 +246,10 @@ impl<'a, TW, F> Absorb<External<&'a NTrytes<TW>>> for Context<TW, F>
 where
 //Self: Absorb<&'a T>,
 {
-    fn absorb(&mut self, _external: External<&'a NTrytes<TW>>) -> Fallible<&mut Self> {
+    fn absorb(
+        &mut self,
+        _external: External<&'a NTrytes<TW>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
         Ok(self)
     }
 }
@@ -99,7 +259,7 @@ impl<'a, TW, F> Absorb<&'a Trytes<TW>> for Context<TW, F>
 where
     TW: BasicTbitWord,
 {
-    fn absorb(&mut self, trytes: &'a Trytes<TW>) -> Fallible<&mut Self> {
+    fn absorb(&mut self, trytes: &'a Trytes<TW>) -> Result<&mut Self, Protobuf3Error> {
         ensure!(
             (trytes.0).size() % 3 == 0,
             "Trit size of `trytes` must be a multiple of 3."
@@ -114,17 +274,45 @@ impl<TW, F> Absorb<Trytes<TW>> for Context<TW, F>
 where
     TW: BasicTbitWord,
 {
-    fn absorb(&mut self, trytes: Trytes<TW>) -> Fallible<&mut Self> {
+    fn absorb(&mut self, trytes: Trytes<TW>) -> Result<&mut Self, Protobuf3Error> {
         self.absorb(&trytes)
     }
 }
 
+/// TLV stream is encoded as the number of records followed by the records.
+impl<'a, TW, F> Absorb<&'a TlvStream<TW>> for Context<TW, F>
+where
+    TW: BasicTbitWord,
+{
+    fn absorb(&mut self, tlvs: &'a TlvStream<TW>) -> Result<&mut Self, Protobuf3Error> {
+        self.absorb(Size(tlvs.records.len()))?;
+        for tlv in &tlvs.records {
+            self.absorb(&tlv.tlv_type)?.absorb(&tlv.value)?;
+        }
+        Ok(self)
+    }
+}
+
+impl<TW, F> Context<TW, F> {
+    /// Size of `size_t` number of elements followed by the elements, see `wrap::Context::absorb_many`.
+    pub fn absorb_many<'b, T>(&mut self, xs: &'b [T]) -> Result<&mut Self, Protobuf3Error>
+    where
+        Self: Absorb<&'b T>,
+    {
+        <Self as Absorb<Size>>::absorb(self, Size(xs.len()))?;
+        for x in xs {
+            self.absorb(x)?;
+        }
+        Ok(self)
+    }
+}
+
 /// `tryte [n]` is fixed-size and is encoded with `3 * n` trits.
 impl<'a, TW, F> Absorb<&'a NTrytes<TW>> for Context<TW, F>
 where
     TW: BasicTbitWord,
 {
-    fn absorb(&mut self, ntrytes: &'a NTrytes<TW>) -> Fallible<&mut Self> {
+    fn absorb(&mut self, ntrytes: &'a NTrytes<TW>) -> Result<&mut Self, Protobuf3Error> {
         ensure!(
             (ntrytes.0).size() % 3 == 0,
             "Trit size of `tryte [n]` must be a multiple of 3."
@@ -139,18 +327,35 @@ impl<TW, F> Absorb<NTrytes<TW>> for Context<TW, F>
 where
     TW: BasicTbitWord,
 {
-    fn absorb(&mut self, ntrytes: NTrytes<TW>) -> Fallible<&mut Self> {
+    fn absorb(&mut self, ntrytes: NTrytes<TW>) -> Result<&mut Self, Protobuf3Error> {
         self.absorb(&ntrytes)
     }
 }
 
+/// `ntryte` is encoded with exactly `N * 3` trits.
+impl<'a, TW, F, const N: usize> Absorb<&'a NTryte<TW, N>> for Context<TW, F>
+where
+    TW: BasicTbitWord,
+{
+    fn absorb(&mut self, ntryte: &'a NTryte<TW, N>) -> Result<&mut Self, Protobuf3Error> {
+        ensure!(
+            (ntryte.0).size() == NTryte::<TW, N>::SIZE,
+            "Trit size of `ntryte` must be {}: {}.",
+            NTryte::<TW, N>::SIZE,
+            (ntryte.0).size()
+        );
+        self.size += NTryte::<TW, N>::SIZE;
+        Ok(self)
+    }
+}
+
 /// MSS public key has fixed size.
 impl<'a, TW, F, P> Absorb<&'a mss::PublicKey<TW, P>> for Context<TW, F>
 where
     TW: BasicTbitWord,
     P: mss::Parameters<TW>,
 {
-    fn absorb(&mut self, pk: &'a mss::PublicKey<TW, P>) -> Fallible<&mut Self> {
+    fn absorb(&mut self, pk: &'a mss::PublicKey<TW, P>) -> Result<&mut Self, Protobuf3Error> {
         ensure!(pk.tbits().size() == P::PUBLIC_KEY_SIZE);
         self.size += P::PUBLIC_KEY_SIZE;
         Ok(self)
@@ -162,7 +367,7 @@ impl<'a, TW, F> Absorb<&'a ntru::PublicKey<TW, F>> for Context<TW, F>
 where
     TW: BasicTbitWord,
 {
-    fn absorb(&mut self, pk: &'a ntru::PublicKey<TW, F>) -> Fallible<&mut Self> {
+    fn absorb(&mut self, pk: &'a ntru::PublicKey<TW, F>) -> Result<&mut Self, Protobuf3Error> {
         ensure!(pk.tbits().size() == ntru::PUBLIC_KEY_SIZE);
         self.size += ntru::PUBLIC_KEY_SIZE;
         Ok(self)
@@ -174,21 +379,44 @@ impl<'a, TW, F> Squeeze<&'a External<NTrytes<TW>>> for Context<TW, F>
 where
     TW: BasicTbitWord,
 {
-    fn squeeze(&mut self, _external_ntrytes: &'a External<NTrytes<TW>>) -> Fallible<&mut Self> {
+    fn squeeze(
+        &mut self,
+        _external_ntrytes: &'a External<NTrytes<TW>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        Ok(self)
+    }
+}
+
+/// External values are not encoded.
+impl<'a, TW, F, const N: usize> Squeeze<&'a External<NTryte<TW, N>>> for Context<TW, F> {
+    fn squeeze(
+        &mut self,
+        _external_ntryte: &'a External<NTryte<TW, N>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        Ok(self)
+    }
+}
+
+/// External values are not encoded.
+impl<'a, TW, F, const N: usize> Squeeze<&'a mut External<NTryte<TW, N>>> for Context<TW, F> {
+    fn squeeze(
+        &mut self,
+        _external_ntryte: &'a mut External<NTryte<TW, N>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
         Ok(self)
     }
 }
 
 /// External values are not encoded.
 impl<TW, F> Squeeze<&External<Mac>> for Context<TW, F> {
-    fn squeeze(&mut self, _mac: &External<Mac>) -> Fallible<&mut Self> {
+    fn squeeze(&mut self, _mac: &External<Mac>) -> Result<&mut Self, Protobuf3Error> {
         Ok(self)
     }
 }
 
 /// Mac is just like NTrytes.
 impl<TW, F> Squeeze<&Mac> for Context<TW, F> {
-    fn squeeze(&mut self, mac: &Mac) -> Fallible<&mut Self> {
+    fn squeeze(&mut self, mac: &Mac) -> Result<&mut Self, Protobuf3Error> {
         ensure!(
             mac.0 % 3 == 0,
             "Trit size of `mac` must be a multiple of 3: {}.",
@@ -201,14 +429,14 @@ impl<TW, F> Squeeze<&Mac> for Context<TW, F> {
 
 /// Mac is just like NTrytes.
 impl<TW, F> Squeeze<Mac> for Context<TW, F> {
-    fn squeeze(&mut self, val: Mac) -> Fallible<&mut Self> {
+    fn squeeze(&mut self, val: Mac) -> Result<&mut Self, Protobuf3Error> {
         self.squeeze(&val)
     }
 }
 
 /// Mask Trint3.
 impl<TW, F> Mask<&Trint3> for Context<TW, F> {
-    fn mask(&mut self, _val: &Trint3) -> Fallible<&mut Self> {
+    fn mask(&mut self, _val: &Trint3) -> Result<&mut Self, Protobuf3Error> {
         self.size += 3;
         Ok(self)
     }
@@ -216,14 +444,14 @@ impl<TW, F> Mask<&Trint3> for Context<TW, F> {
 
 /// Mask Trint3.
 impl<TW, F> Mask<Trint3> for Context<TW, F> {
-    fn mask(&mut self, val: Trint3) -> Fallible<&mut Self> {
+    fn mask(&mut self, val: Trint3) -> Result<&mut Self, Protobuf3Error> {
         self.mask(&val)
     }
 }
 
 /// Mask Size.
 impl<TW, F> Mask<&Size> for Context<TW, F> {
-    fn mask(&mut self, val: &Size) -> Fallible<&mut Self> {
+    fn mask(&mut self, val: &Size) -> Result<&mut Self, Protobuf3Error> {
         self.size += sizeof_sizet(val.0);
         Ok(self)
     }
@@ -231,7 +459,89 @@ impl<TW, F> Mask<&Size> for Context<TW, F> {
 
 /// Mask Size.
 impl<TW, F> Mask<Size> for Context<TW, F> {
-    fn mask(&mut self, val: Size) -> Fallible<&mut Self> {
+    fn mask(&mut self, val: Size) -> Result<&mut Self, Protobuf3Error> {
+        self.mask(&val)
+    }
+}
+
+/// Mask string, the length is absorbed and the UTF-8 bytes are masked.
+impl<TW, F> Mask<&str> for Context<TW, F> {
+    fn mask(&mut self, s: &str) -> Result<&mut Self, Protobuf3Error> {
+        self.size += sizeof_bytes(s.len());
+        Ok(self)
+    }
+}
+
+/// Mask bool flag.
+impl<TW, F> Mask<&bool> for Context<TW, F> {
+    fn mask(&mut self, _val: &bool) -> Result<&mut Self, Protobuf3Error> {
+        self.size += SIZEOF_FLAG;
+        Ok(self)
+    }
+}
+
+/// Mask bool flag.
+impl<TW, F> Mask<bool> for Context<TW, F> {
+    fn mask(&mut self, val: bool) -> Result<&mut Self, Protobuf3Error> {
+        self.mask(&val)
+    }
+}
+
+/// Mask trit.
+impl<TW, F> Mask<&trinary::Trit> for Context<TW, F> {
+    fn mask(&mut self, _val: &trinary::Trit) -> Result<&mut Self, Protobuf3Error> {
+        self.size += 1;
+        Ok(self)
+    }
+}
+
+/// Mask trit.
+impl<TW, F> Mask<trinary::Trit> for Context<TW, F> {
+    fn mask(&mut self, val: trinary::Trit) -> Result<&mut Self, Protobuf3Error> {
+        self.mask(&val)
+    }
+}
+
+/// Mask Ratio.
+impl<TW, F> Mask<&Ratio> for Context<TW, F> {
+    fn mask(&mut self, _val: &Ratio) -> Result<&mut Self, Protobuf3Error> {
+        self.size += SIZEOF_RATIO;
+        Ok(self)
+    }
+}
+
+/// Mask Ratio.
+impl<TW, F> Mask<Ratio> for Context<TW, F> {
+    fn mask(&mut self, val: Ratio) -> Result<&mut Self, Protobuf3Error> {
+        self.mask(&val)
+    }
+}
+
+/// Masked presence flag followed by the masked inner field if present.
+impl<'a, TW, F, T: 'a> Mask<Option<&'a T>> for Context<TW, F>
+where
+    Self: Mask<&'a T>,
+{
+    fn mask(&mut self, x: Option<&'a T>) -> Result<&mut Self, Protobuf3Error> {
+        self.size += SIZEOF_FLAG;
+        match x {
+            Some(x) => self.mask(x),
+            None => Ok(self),
+        }
+    }
+}
+
+/// Mask Interval.
+impl<TW, F> Mask<&Interval> for Context<TW, F> {
+    fn mask(&mut self, _val: &Interval) -> Result<&mut Self, Protobuf3Error> {
+        self.size += SIZEOF_INTERVAL;
+        Ok(self)
+    }
+}
+
+/// Mask Interval.
+impl<TW, F> Mask<Interval> for Context<TW, F> {
+    fn mask(&mut self, val: Interval) -> Result<&mut Self, Protobuf3Error> {
         self.mask(&val)
     }
 }
@@ -241,18 +551,48 @@ impl<TW, F> Mask<&NTrytes<TW>> for Context<TW, F>
 where
     TW: BasicTbitWord,
 {
-    fn mask(&mut self, val: &NTrytes<TW>) -> Fallible<&mut Self> {
+    fn mask(&mut self, val: &NTrytes<TW>) -> Result<&mut Self, Protobuf3Error> {
         self.size += (val.0).size();
         Ok(self)
     }
 }
 
+/// Mask `N` trytes.
+impl<TW, F, const N: usize> Mask<&NTryte<TW, N>> for Context<TW, F>
+where
+    TW: BasicTbitWord,
+{
+    fn mask(&mut self, val: &NTryte<TW, N>) -> Result<&mut Self, Protobuf3Error> {
+        ensure!(
+            (val.0).size() == NTryte::<TW, N>::SIZE,
+            "Trit size of `ntryte` must be {}: {}.",
+            NTryte::<TW, N>::SIZE,
+            (val.0).size()
+        );
+        self.size += NTryte::<TW, N>::SIZE;
+        Ok(self)
+    }
+}
+
+/// Mask `N` elements of a fixed-size array, there's no count prefix.
+impl<'a, TW, F, T: 'a, const N: usize> Mask<&'a [T; N]> for Context<TW, F>
+where
+    T: MaskElement<Self>,
+{
+    fn mask(&mut self, xs: &'a [T; N]) -> Result<&mut Self, Protobuf3Error> {
+        for x in xs.iter() {
+            x.mask_element(self)?;
+        }
+        Ok(self)
+    }
+}
+
 /// Mask trytes, the size prefixed before the content trytes is also masked.
 impl<TW, F> Mask<&Trytes<TW>> for Context<TW, F>
 where
     TW: BasicTbitWord,
 {
-    fn mask(&mut self, trytes: &Trytes<TW>) -> Fallible<&mut Self> {
+    fn mask(&mut self, trytes: &Trytes<TW>) -> Result<&mut Self, Protobuf3Error> {
         ensure!(
             (trytes.0).size() % 3 == 0,
             "Trit size of `trytes` must be a multiple of 3: {}.",
@@ -269,7 +609,7 @@ impl<TW, F> Mask<&ntru::PublicKey<TW, F>> for Context<TW, F>
 where
     TW: BasicTbitWord,
 {
-    fn mask(&mut self, ntru_pk: &ntru::PublicKey<TW, F>) -> Fallible<&mut Self> {
+    fn mask(&mut self, ntru_pk: &ntru::PublicKey<TW, F>) -> Result<&mut Self, Protobuf3Error> {
         ensure!(ntru_pk.tbits().size() == ntru::PUBLIC_KEY_SIZE);
         self.size += ntru::PUBLIC_KEY_SIZE;
         Ok(self)
@@ -281,7 +621,7 @@ where
     TW: BasicTbitWord,
     P: mss::Parameters<TW>,
 {
-    fn mask(&mut self, mss_pk: &mss::PublicKey<TW, P>) -> Fallible<&mut Self> {
+    fn mask(&mut self, mss_pk: &mss::PublicKey<TW, P>) -> Result<&mut Self, Protobuf3Error> {
         ensure!(mss_pk.tbits().size() == P::PUBLIC_KEY_SIZE);
         self.size += P::PUBLIC_KEY_SIZE;
         Ok(self)
@@ -291,7 +631,7 @@ where
 /// Skipped values are just encoded.
 /// All Trint3 values are encoded with 3 trits.
 impl<TW, F> Skip<&Trint3> for Context<TW, F> {
-    fn skip(&mut self, _trint3: &Trint3) -> Fallible<&mut Self> {
+    fn skip(&mut self, _trint3: &Trint3) -> Result<&mut Self, Protobuf3Error> {
         self.size += 3;
         Ok(self)
     }
@@ -299,14 +639,14 @@ impl<TW, F> Skip<&Trint3> for Context<TW, F> {
 
 /// All Trint3 values are encoded with 3 trits.
 impl<TW, F> Skip<Trint3> for Context<TW, F> {
-    fn skip(&mut self, trint3: Trint3) -> Fallible<&mut Self> {
+    fn skip(&mut self, trint3: Trint3) -> Result<&mut Self, Protobuf3Error> {
         self.skip(&trint3)
     }
 }
 
 /// Size has var-size encoding.
 impl<TW, F> Skip<&Size> for Context<TW, F> {
-    fn skip(&mut self, size: &Size) -> Fallible<&mut Self> {
+    fn skip(&mut self, size: &Size) -> Result<&mut Self, Protobuf3Error> {
         self.size += sizeof_sizet(size.0);
         Ok(self)
     }
@@ -314,7 +654,7 @@ impl<TW, F> Skip<&Size> for Context<TW, F> {
 
 /// Size has var-size encoding.
 impl<TW, F> Skip<Size> for Context<TW, F> {
-    fn skip(&mut self, size: Size) -> Fallible<&mut Self> {
+    fn skip(&mut self, size: Size) -> Result<&mut Self, Protobuf3Error> {
         self.skip(&size)
     }
 }
@@ -324,7 +664,7 @@ impl<'a, TW, F> Skip<&'a Trytes<TW>> for Context<TW, F>
 where
     TW: BasicTbitWord,
 {
-    fn skip(&mut self, trytes: &'a Trytes<TW>) -> Fallible<&mut Self> {
+    fn skip(&mut self, trytes: &'a Trytes<TW>) -> Result<&mut Self, Protobuf3Error> {
         ensure!(
             (trytes.0).size() % 3 == 0,
             "Trit size of `trytes` must be a multiple of 3."
@@ -339,7 +679,7 @@ impl<TW, F> Skip<Trytes<TW>> for Context<TW, F>
 where
     TW: BasicTbitWord,
 {
-    fn skip(&mut self, trytes: Trytes<TW>) -> Fallible<&mut Self> {
+    fn skip(&mut self, trytes: Trytes<TW>) -> Result<&mut Self, Protobuf3Error> {
         self.skip(&trytes)
     }
 }
@@ -349,7 +689,7 @@ impl<'a, TW, F> Skip<&'a NTrytes<TW>> for Context<TW, F>
 where
     TW: BasicTbitWord,
 {
-    fn skip(&mut self, ntrytes: &'a NTrytes<TW>) -> Fallible<&mut Self> {
+    fn skip(&mut self, ntrytes: &'a NTrytes<TW>) -> Result<&mut Self, Protobuf3Error> {
         ensure!(
             (ntrytes.0).size() % 3 == 0,
             "Trit size of `tryte [n]` must be a multiple of 3."
@@ -364,14 +704,22 @@ impl<TW, F> Skip<NTrytes<TW>> for Context<TW, F>
 where
     TW: BasicTbitWord,
 {
-    fn skip(&mut self, ntrytes: NTrytes<TW>) -> Fallible<&mut Self> {
+    fn skip(&mut self, ntrytes: NTrytes<TW>) -> Result<&mut Self, Protobuf3Error> {
         self.skip(&ntrytes)
     }
 }
 
+/// Skipped byte string is encoded with `size_t` length followed by the bytes.
+impl<TW, F> Skip<&[u8]> for Context<TW, F> {
+    fn skip(&mut self, bytes: &[u8]) -> Result<&mut Self, Protobuf3Error> {
+        self.size += sizeof_bytes(bytes.len());
+        Ok(self)
+    }
+}
+
 /// Commit costs nothing in the trinary stream.
 impl<TW, F> Commit for Context<TW, F> {
-    fn commit(&mut self) -> Fallible<&mut Self> {
+    fn commit(&mut self) -> Result<&mut Self, Protobuf3Error> {
         Ok(self)
     }
 }
@@ -386,7 +734,7 @@ where
         &mut self,
         sk: &mss::PrivateKey<TW, P>,
         hash: &External<NTrytes<TW>>,
-    ) -> Fallible<&mut Self> {
+    ) -> Result<&mut Self, Protobuf3Error> {
         ensure!(
             P::HASH_SIZE == ((hash.0).0).size(),
             "Trit size of `external tryte hash[n]` to be signed with MSS must be equal {} trits.",
@@ -403,7 +751,11 @@ where
     TW: IntTbitWord + SpongosTbitWord,
     P: mss::Parameters<TW>,
 {
-    fn mssig(&mut self, sk: &mss::PrivateKey<TW, P>, hash: &External<Mac>) -> Fallible<&mut Self> {
+    fn mssig(
+        &mut self,
+        sk: &mss::PrivateKey<TW, P>,
+        hash: &External<Mac>,
+    ) -> Result<&mut Self, Protobuf3Error> {
         ensure!(
             P::HASH_SIZE == (hash.0).0,
             "Trit size of `external tryte hash[n]` to be signed with MSS must be equal {} trits.",
@@ -420,13 +772,88 @@ where
     TW: IntTbitWord + SpongosTbitWord,
     P: mss::Parameters<TW>,
 {
-    fn mssig(&mut self, sk: &mss::PrivateKey<TW, P>, _hash: MssHashSig) -> Fallible<&mut Self> {
+    fn mssig(
+        &mut self,
+        sk: &mss::PrivateKey<TW, P>,
+        _hash: MssHashSig,
+    ) -> Result<&mut Self, Protobuf3Error> {
         // Squeeze external and commit cost nothing in the stream.
         self.size += P::signature_size(sk.height());
         Ok(self)
     }
 }
 
+#[cfg(feature = "net")]
+impl<'a, TW, F> Absorb<&'a std::net::Ipv4Addr> for Context<TW, F> {
+    fn absorb(&mut self, _ip: &'a std::net::Ipv4Addr) -> Result<&mut Self, Protobuf3Error> {
+        self.size += SIZEOF_IPV4_ADDR;
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "net")]
+impl<'a, TW, F> Absorb<&'a std::net::Ipv6Addr> for Context<TW, F> {
+    fn absorb(&mut self, _ip: &'a std::net::Ipv6Addr) -> Result<&mut Self, Protobuf3Error> {
+        self.size += SIZEOF_IPV6_ADDR;
+        Ok(self)
+    }
+}
+
+/// Size of socket address depends on the IP version.
+#[cfg(feature = "net")]
+impl<'a, TW, F> Absorb<&'a std::net::SocketAddr> for Context<TW, F> {
+    fn absorb(&mut self, addr: &'a std::net::SocketAddr) -> Result<&mut Self, Protobuf3Error> {
+        self.size += sizeof_socket_addr(addr);
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "net")]
+impl<'a, TW, F> Mask<&'a std::net::Ipv4Addr> for Context<TW, F> {
+    fn mask(&mut self, _ip: &'a std::net::Ipv4Addr) -> Result<&mut Self, Protobuf3Error> {
+        self.size += SIZEOF_IPV4_ADDR;
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "net")]
+impl<'a, TW, F> Mask<&'a std::net::Ipv6Addr> for Context<TW, F> {
+    fn mask(&mut self, _ip: &'a std::net::Ipv6Addr) -> Result<&mut Self, Protobuf3Error> {
+        self.size += SIZEOF_IPV6_ADDR;
+        Ok(self)
+    }
+}
+
+/// Size of socket address depends on the IP version.
+#[cfg(feature = "net")]
+impl<'a, TW, F> Mask<&'a std::net::SocketAddr> for Context<TW, F> {
+    fn mask(&mut self, addr: &'a std::net::SocketAddr) -> Result<&mut Self, Protobuf3Error> {
+        self.size += sizeof_socket_addr(addr);
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "ed25519-dalek")]
+impl<'a, TW, F> Absorb<&'a Ed25519PublicKey> for Context<TW, F> {
+    fn absorb(&mut self, _pk: &'a Ed25519PublicKey) -> Result<&mut Self, Protobuf3Error> {
+        self.size += SIZEOF_ED25519_PUBLIC_KEY;
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "ed25519-dalek")]
+impl<'a, TW, F> Ed25519Sig<&'a Ed25519Keypair, Ed25519HashSig> for Context<TW, F> {
+    fn ed25519sig(
+        &mut self,
+        _key: &'a Ed25519Keypair,
+        _hash: Ed25519HashSig,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        // Squeeze and commit cost nothing in the stream.
+        self.size += SIZEOF_ED25519_SIGNATURE;
+        Ok(self)
+    }
+}
+
 /// Sizeof encapsulated secret is fixed.
 impl<TW, F> Ntrukem<&ntru::PublicKey<TW, F>, &NTrytes<TW>> for Context<TW, F>
 where
@@ -436,7 +863,7 @@ where
         &mut self,
         _key: &ntru::PublicKey<TW, F>,
         _secret: &NTrytes<TW>,
-    ) -> Fallible<&mut Self> {
+    ) -> Result<&mut Self, Protobuf3Error> {
         //TODO: Ensure key is valid.
         //TODO: ensure!(ntru::KEY_SIZE == (secret.0).size(), "Trit size of `external tryte secret[n]` to be encapsulated with NTRU must be equal {} trits.", ntru::KEY_SIZE);
         self.size += ntru::EKEY_SIZE;
@@ -447,9 +874,9 @@ where
 /// Forks cost nothing in the trinary stream.
 impl<TW, F, C> Fork<C> for Context<TW, F>
 where
-    C: for<'a> FnMut(&'a mut Self) -> Fallible<&'a mut Self>,
+    C: for<'a> FnMut(&'a mut Self) -> Result<&'a mut Self, Protobuf3Error>,
 {
-    fn fork(&mut self, mut cont: C) -> Fallible<&mut Self> {
+    fn fork(&mut self, mut cont: C) -> Result<&mut Self, Protobuf3Error> {
         cont(self)
     }
 }
@@ -459,22 +886,46 @@ where
 impl<TW, F, I, C> Repeated<I, C> for Context<TW, F>
 where
     I: iter::Iterator,
-    C: for<'a> FnMut(&'a mut Self, <I as iter::Iterator>::Item) -> Fallible<&'a mut Self>,
+    C: for<'a> FnMut(
+        &'a mut Self,
+        <I as iter::Iterator>::Item,
+    ) -> Result<&'a mut Self, Protobuf3Error>,
 {
-    fn repeated(&mut self, values_iter: I, mut value_handle: C) -> Fallible<&mut Self> {
-        values_iter.fold(Ok(self), |rctx, item| -> Fallible<&mut Self> {
-            match rctx {
-                Ok(ctx) => value_handle(ctx, item),
-                Err(e) => Err(e),
-            }
-        })
+    fn repeated(
+        &mut self,
+        values_iter: I,
+        mut value_handle: C,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        values_iter.fold(
+            Ok(self),
+            |rctx, item| -> Result<&mut Self, Protobuf3Error> {
+                match rctx {
+                    Ok(ctx) => value_handle(ctx, item),
+                    Err(e) => Err(e),
+                }
+            },
+        )
+    }
+}
+
+/// Repeated modifier. The actual number of repetitions must be wrapped
+/// (absorbed/masked/skipped) explicitly.
+impl<TW, F, C> RepeatedN<C> for Context<TW, F>
+where
+    C: for<'a> FnMut(&'a mut Self, usize) -> Result<&'a mut Self, Protobuf3Error>,
+{
+    fn repeated_n(&mut self, n: usize, mut value_handle: C) -> Result<&mut Self, Protobuf3Error> {
+        for i in 0..n {
+            value_handle(self, i)?;
+        }
+        Ok(self)
     }
 }
 
 /*
 /// It's the size of the link.
 impl<'a, TW, F, L: Link> Absorb<&'a L> for Context<TW, F> {
-    fn absorb(&mut self, link: &'a L) -> Fallible<&mut Self> {
+    fn absorb(&mut self, link: &'a L) -> Result<&mut Self, Protobuf3Error> {
         self.size += link.size();
         Ok(self)
     }
@@ -483,7 +934,7 @@ impl<'a, TW, F, L: Link> Absorb<&'a L> for Context<TW, F> {
 /*
 /// It's the size of the link.
 impl<'a, L: Link, S: LinkStore<L>> Join<&'a L, &'a S> for Context<TW, F> {
-    fn join(&mut self, store: &'a S, link: &'a L) -> Fallible<&mut Self> {
+    fn join(&mut self, store: &'a S, link: &'a L) -> Result<&mut Self, Protobuf3Error> {
         self.size += link.size();
         Ok(self)
     }
@@ -492,7 +943,7 @@ impl<'a, L: Link, S: LinkStore<L>> Join<&'a L, &'a S> for Context<TW, F> {
 
 /// It's the size of the link.
 impl<'a, TW, F, T: 'a + AbsorbFallback<TW, F>> Absorb<&'a Fallback<T>> for Context<TW, F> {
-    fn absorb(&mut self, val: &'a Fallback<T>) -> Fallible<&mut Self> {
+    fn absorb(&mut self, val: &'a Fallback<T>) -> Result<&mut Self, Protobuf3Error> {
         (val.0).sizeof_absorb(self)?;
         Ok(self)
     }
@@ -500,13 +951,13 @@ impl<'a, TW, F, T: 'a + AbsorbFallback<TW, F>> Absorb<&'a Fallback<T>> for Conte
 impl<'a, TW, F, T: 'a + AbsorbExternalFallback<TW, F>> Absorb<External<Fallback<&'a T>>>
     for Context<TW, F>
 {
-    fn absorb(&mut self, val: External<Fallback<&'a T>>) -> Fallible<&mut Self> {
+    fn absorb(&mut self, val: External<Fallback<&'a T>>) -> Result<&mut Self, Protobuf3Error> {
         ((val.0).0).sizeof_absorb_external(self)?;
         Ok(self)
     }
 }
 impl<'a, TW, F, T: 'a + SkipFallback<TW, F>> Skip<&'a Fallback<T>> for Context<TW, F> {
-    fn skip(&mut self, val: &'a Fallback<T>) -> Fallible<&mut Self> {
+    fn skip(&mut self, val: &'a Fallback<T>) -> Result<&mut Self, Protobuf3Error> {
         (val.0).sizeof_skip(self)?;
         Ok(self)
     }
@@ -516,7 +967,22 @@ impl<'a, TW, F, T: 'a + SkipFallback<TW, F>> Skip<&'a Fallback<T>> for Context<T
 impl<'a, TW, F, L: SkipFallback<TW, F>, S: LinkStore<TW, F, L>> Join<&'a L, &'a S>
     for Context<TW, F>
 {
-    fn join(&mut self, _store: &'a S, link: &'a L) -> Fallible<&mut Self> {
+    fn join(&mut self, _store: &'a S, link: &'a L) -> Result<&mut Self, Protobuf3Error> {
+        link.sizeof_skip(self)?;
+        Ok(self)
+    }
+}
+
+/// The commitment is not encoded, it's the size of the link.
+impl<'a, TW, F, L: SkipFallback<TW, F>, S: LinkStore<TW, F, L>>
+    JoinVerified<&'a L, &'a S, &'a NTrytes<TW>> for Context<TW, F>
+{
+    fn join_verified(
+        &mut self,
+        _store: &'a S,
+        link: &'a L,
+        _expected_mac: &'a NTrytes<TW>,
+    ) -> Result<&mut Self, Protobuf3Error> {
         link.sizeof_skip(self)?;
         Ok(self)
     }
@@ -525,15 +991,48 @@ impl<'a, TW, F, L: SkipFallback<TW, F>, S: LinkStore<TW, F, L>> Join<&'a L, &'a
 impl<'a, TW, F, L, S: LinkStore<TW, F, L>> Join<&'a L, &'a S> for Context<TW, F> where
     Self: Skip<&'a L>
 {
-    fn join(&mut self, _store: &'a S, link: &'a L) -> Fallible<&mut Self> {
+    fn join(&mut self, _store: &'a S, link: &'a L) -> Result<&mut Self, Protobuf3Error> {
         self.skip(link)
     }
 }
 */
 
+/// Guards are not encoded.
+impl<TW, F> Guard for Context<TW, F> {
+    fn guard(&mut self, _cond: bool, _err: &'static str) -> Result<&mut Self, Protobuf3Error> {
+        Ok(self)
+    }
+}
+
+impl<'a, TW, F, T: 'a + ?Sized> SealedMask<&'a T> for Context<TW, F>
+where
+    Self: Mask<&'a T>,
+{
+    fn sealed_mask(&mut self, field: &'a T) -> Result<&mut Self, Protobuf3Error> {
+        self.mask(field)?
+            .commit()?
+            .squeeze(&Mac(SEALED_MASK_TAG_SIZE))
+    }
+}
+
+impl<TW, F> PadTo for Context<TW, F> {
+    fn pad_to(&mut self, total: usize) -> Result<&mut Self, Protobuf3Error> {
+        ensure!(
+            self.size <= total,
+            Protobuf3Error::PadOverflow {
+                position: self.size,
+                total,
+            }
+        );
+        self.size = total;
+        Ok(self)
+    }
+}
+
 impl<TW, F> Dump for Context<TW, F> {
-    fn dump<'a>(&mut self, args: std::fmt::Arguments<'a>) -> Fallible<&mut Self> {
-        println!("{}: size=[{}]", args, self.size);
+    fn dump<'a>(&mut self, args: std::fmt::Arguments<'a>) -> Result<&mut Self, Protobuf3Error> {
+        self.dump_sink
+            .dump(format_args!("{}: size=[{}]", args, self.size));
         Ok(self)
     }
 }
@@ -1,7 +1,7 @@
 //! Implementation of command traits for unwrapping.
 
-use failure::{ensure, Fallible};
 use std::convert::AsMut;
+use std::iter;
 use std::mem;
 
 use iota_streams_core::{
@@ -15,31 +15,167 @@ use iota_streams_core::{
 use iota_streams_core_mss::signature::{mss, wots::Parameters as _};
 use iota_streams_core_ntru::key_encapsulation::ntru;
 
-use super::wrap::{wrap_size, Wrap};
+use super::wrap::{wrap_schema, wrap_size, Wrap};
 use crate::command::*;
+use crate::error::Protobuf3Error;
 use crate::io;
 use crate::types::*;
 
+/// Default max total size in trits claimed by length prefixes, see
+/// `Context::set_max_message_size`; `3^15` trits is far above the size of any message
+/// of the channel application.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 14_348_907;
+
 //#[derive(Debug)]
 pub struct Context<TW, F, IS> {
     pub spongos: Spongos<TW, F>,
     pub stream: IS,
+    /// Position of `stream` when the context was created, `reset` rewinds the stream back to it.
+    start: usize,
+    /// Output sink of `Dump` command.
+    dump_sink: DumpSink,
+    /// Max number of repetitions accepted by `RepeatedN` command.
+    repeated_max: usize,
+    /// Range of MSS tree heights accepted by `Mssig` command, see `set_mss_height_range`.
+    mss_height: (usize, usize),
+    /// Max total size in trits claimed by length prefixes, see `set_max_message_size`.
+    max_message_size: usize,
+    /// Total size in trits claimed by length prefixes so far.
+    claimed_size: usize,
+    /// Validate message structure only, see `new_validate`.
+    validate: bool,
+    /// A masked field has been skipped in validation mode and Spongos state
+    /// no longer matches the state of the wrapping side.
+    desynced: bool,
 }
 
 impl<TW, F, IS> Context<TW, F, IS>
 where
     TW: SpongosTbitWord,
     F: PRP<TW> + Default,
+    IS: io::IStream<TW>,
 {
     pub fn new(stream: IS) -> Self {
         Self {
             spongos: Spongos::<TW, F>::init(),
+            start: stream.position(),
             stream: stream,
+            dump_sink: DumpSink::default(),
+            repeated_max: std::usize::MAX,
+            mss_height: (0, std::usize::MAX),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            claimed_size: 0,
+            validate: false,
+            desynced: false,
         }
     }
+
+    /// Create `UnwrapValidate` context which checks that a message is well-formed
+    /// without decrypting masked fields, eg. on a gateway that holds no decryption keys.
+    ///
+    /// `Mask` behaves like `Skip`: masked trits are advanced over and returned as is (encrypted).
+    /// Only fixed-size fields can be masked as formatted fields (`size_t`, `oneof`, etc.)
+    /// can't be decoded without decryption and are rejected.
+    /// `Absorb`, `Squeeze`, `Commit` and `Mssig` run as usual until the first masked field.
+    /// Spongos state after a skipped masked field depends on the masked content, so the
+    /// following MACs and expected MSS public keys are only parsed and not checked.
+    /// Integrity of masked content is therefore not validated and still depends on
+    /// the MAC checked by the recipient with a regular unwrap context.
+    pub fn new_validate(stream: IS) -> Self {
+        let mut ctx = Self::new(stream);
+        ctx.validate = true;
+        ctx
+    }
+}
+
+impl<TW, F, IS> Context<TW, F, IS>
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW>,
+{
+    /// Duplex mode of Spongos, `PRP::MODE` by default.
+    pub fn spongos_mode(&self) -> SpongosMode {
+        self.spongos.mode()
+    }
+
+    /// Select overwrite or duplex-XOR mode of Spongos used by the following commands,
+    /// eg. to match an external Spongos configuration. The mode is kept by `reset`.
+    pub fn set_spongos_mode(&mut self, mode: SpongosMode) -> &mut Self {
+        self.spongos.set_mode(mode);
+        self
+    }
+
+    /// Number of Spongos permutations performed since `new` or `reset`, including the ones
+    /// in `Fork` continuations, eg. to find message formats that commit too often.
+    #[cfg(feature = "metrics")]
+    pub fn permutation_count(&self) -> u64 {
+        self.spongos.permutation_count()
+    }
+}
+
+impl<TW, F, IS> Context<TW, F, IS> {
+    /// Redirect output of `Dump` command into `sink`.
+    pub fn set_dump_sink<S>(&mut self, sink: S)
+    where
+        S: FnMut(std::fmt::Arguments) + Send + 'static,
+    {
+        self.dump_sink = DumpSink::new(sink);
+    }
+
+    /// Set the max number of repetitions accepted by `RepeatedN` command, not limited by default.
+    /// Counts are usually decoded from the message and must be limited when handling untrusted input.
+    pub fn set_repeated_max(&mut self, max: usize) {
+        self.repeated_max = max;
+    }
+
+    /// Set the range `[min, max]` of MSS tree heights accepted by `Mssig` command,
+    /// heights up to `mss::Parameters::MAX_D` are accepted by default.
+    /// The height is encoded in the signature and determines its size, so the signature
+    /// is consumed without knowing the height in advance. Signatures of trees with heights
+    /// out of the range are rejected with `Protobuf3Error::OutOfRange`.
+    pub fn set_mss_height_range(&mut self, min: usize, max: usize) {
+        self.mss_height = (min, max);
+    }
+
+    /// Set the max total size in trits of length-prefixed fields (trytes, byte strings,
+    /// `oneof` bodies, one trit per `absorb_many` element), `DEFAULT_MAX_MESSAGE_SIZE`
+    /// by default, `usize::MAX` disables the limit.
+    /// Lengths are decoded from the message and buffers for the fields are allocated
+    /// before their content is read, so a forged length could request a huge allocation.
+    /// Claimed sizes are accumulated over the message and a field whose length prefix
+    /// exceeds the limit is rejected with `Protobuf3Error::MessageTooLarge` before allocating.
+    pub fn set_max_message_size(&mut self, n: usize) {
+        self.max_message_size = n;
+    }
+
+    /// Account `n` trits claimed by the length prefix of `field`.
+    fn claim(&mut self, field: &'static str, n: usize) -> Result<(), Protobuf3Error> {
+        let claimed = self.claimed_size.saturating_add(n);
+        ensure!(
+            claimed <= self.max_message_size,
+            Protobuf3Error::MessageTooLarge {
+                field,
+                claimed,
+                max: self.max_message_size,
+            }
+        );
+        self.claimed_size = claimed;
+        Ok(())
+    }
+
+    /// Whether this is an `UnwrapValidate` context created with `new_validate`.
+    pub fn is_validate(&self) -> bool {
+        self.validate
+    }
+
+    /// Whether Spongos state depends on masked fields skipped in validation mode
+    /// and MACs and signatures can't be checked anymore.
+    pub fn is_desynced(&self) -> bool {
+        self.desynced
+    }
 }
 impl<TW, F, IS: io::IStream<TW>> Context<TW, F, IS> {
-    pub fn drop(&mut self, n: Size) -> Fallible<&mut Self> {
+    pub fn drop(&mut self, n: Size) -> Result<&mut Self, Protobuf3Error> {
         self.stream.try_advance(n.0)?;
         Ok(self)
         //<IS as io::IStream<TW>>::try_advance(&mut self.stream, n)
@@ -48,8 +184,8 @@ impl<TW, F, IS: io::IStream<TW>> Context<TW, F, IS> {
 
 /// Helper trait for unwrapping (decoding/absorbing) trint3s.
 pub(crate) trait Unwrap<TW> {
-    fn unwrap3(&mut self, trint3: &mut Trint3) -> Fallible<&mut Self>;
-    fn unwrapn(&mut self, trits: TbitSliceMut<TW>) -> Fallible<&mut Self>
+    fn unwrap3(&mut self, trint3: &mut Trint3) -> Result<&mut Self, Protobuf3Error>;
+    fn unwrapn(&mut self, trits: TbitSliceMut<TW>) -> Result<&mut Self, Protobuf3Error>
     where
         TW: BasicTbitWord;
 }
@@ -58,14 +194,13 @@ pub(crate) trait Unwrap<TW> {
 pub(crate) fn unwrap_size<'a, TW, Ctx: Unwrap<TW>>(
     ctx: &'a mut Ctx,
     size: &mut Size,
-) -> Fallible<&'a mut Ctx> where
+) -> Result<&'a mut Ctx, Protobuf3Error> where
 {
     let mut d = Trint3(0);
     ctx.unwrap3(&mut d)?;
     ensure!(
         Trint3(0) <= d && d <= Trint3(13),
-        "Invalid size of `size_t`: {}.",
-        d
+        Protobuf3Error::OutOfRange(format!("Invalid size of `size_t`: {}.", d))
     );
 
     let mut m: i64 = 0;
@@ -83,16 +218,18 @@ pub(crate) fn unwrap_size<'a, TW, Ctx: Unwrap<TW>>(
             m += r * t.0 as i64;
         }
 
+        ensure!(t != Trint3(0), Protobuf3Error::NonCanonicalSizeT);
         ensure!(
             Trint3(0) < t,
-            "The last most significant trint3 is `size_t` can't be 0 or negative: {}.",
-            t
+            Protobuf3Error::OutOfRange(format!(
+                "The last most significant trint3 of `size_t` can't be negative: {}.",
+                t
+            ))
         );
 
         ensure!(
-            SIZE_MAX >= m as usize,
-            "`size_t` value is overflown: {}.",
-            m
+            m as usize <= SIZE_MAX,
+            Protobuf3Error::OutOfRange(format!("`size_t` value is overflown: {}.", m))
         );
     }
 
@@ -100,6 +237,191 @@ pub(crate) fn unwrap_size<'a, TW, Ctx: Unwrap<TW>>(
     Ok(ctx)
 }
 
+/// Helper function for unwrapping (decoding/absorbing) fixed-width `u64` values.
+pub(crate) fn unwrap_u64<'a, TW, Ctx: Unwrap<TW>>(
+    ctx: &'a mut Ctx,
+    u: &mut u64,
+) -> Result<&'a mut Ctx, Protobuf3Error> {
+    let mut m: i128 = 0;
+    let mut r: i128 = 1;
+    let mut t = Trint3(0);
+    for _ in 0..U64_TRYTES {
+        ctx.unwrap3(&mut t)?;
+        m += r * t.0 as i128;
+        r *= 27;
+    }
+
+    ensure!(
+        (0..=u64::MAX as i128).contains(&m),
+        Protobuf3Error::OutOfRange(format!("`u64` value is out of range: {}.", m))
+    );
+    *u = m as u64;
+    Ok(ctx)
+}
+
+/// Helper function for unwrapping (decoding/absorbing) byte strings of at most `max` bytes.
+pub(crate) fn unwrap_bytes<'a, TW, Ctx: Unwrap<TW>>(
+    ctx: &'a mut Ctx,
+    bytes: &mut Vec<u8>,
+    max: usize,
+) -> Result<&'a mut Ctx, Protobuf3Error> {
+    let mut n = Size(0);
+    unwrap_size(ctx, &mut n)?;
+    ensure!(
+        n.0 <= max,
+        Protobuf3Error::OutOfRange(format!(
+            "Byte string length {} exceeds maximum {}.",
+            n.0, max
+        ))
+    );
+
+    unwrap_bytes_body(ctx, n.0, bytes)
+}
+
+/// Helper function for unwrapping (decoding/absorbing) `n` bytes without the length.
+pub(crate) fn unwrap_bytes_body<'a, TW, Ctx: Unwrap<TW>>(
+    ctx: &'a mut Ctx,
+    n: usize,
+    bytes: &mut Vec<u8>,
+) -> Result<&'a mut Ctx, Protobuf3Error> {
+    // Bytes are decoded one by one, so that a bogus length is caught
+    // by the exhausted stream rather than by a huge allocation.
+    bytes.clear();
+    let mut t = Trint3(0);
+    for _ in 0..n {
+        let mut m: i32 = 0;
+        let mut r: i32 = 1;
+        for _ in 0..BYTE_TRYTES {
+            ctx.unwrap3(&mut t)?;
+            m += r * t.0 as i32;
+            r *= 27;
+        }
+        ensure!(
+            (0..=255).contains(&m),
+            Protobuf3Error::OutOfRange(format!("Byte value is out of range: {}.", m))
+        );
+        bytes.push(m as u8);
+    }
+    Ok(ctx)
+}
+
+/// Helper function for unwrapping (decoding/absorbing) ratio values.
+pub(crate) fn unwrap_ratio<'a, TW, Ctx: Unwrap<TW>>(
+    ctx: &'a mut Ctx,
+    ratio: &mut Ratio,
+) -> Result<&'a mut Ctx, Protobuf3Error> {
+    let mut m: i32 = 0;
+    let mut r: i32 = 1;
+    let mut t = Trint3(0);
+    for _ in 0..SIZEOF_RATIO / 3 {
+        ctx.unwrap3(&mut t)?;
+        m += r * t.0 as i32;
+        r *= 27;
+    }
+
+    ensure!(
+        (0..=RATIO_SCALE).contains(&m),
+        Protobuf3Error::OutOfRange(format!("`ratio` value is out of range: {}.", m))
+    );
+    *ratio = Ratio::from_fixed(m);
+    Ok(ctx)
+}
+
+/// Helper function for unwrapping (decoding/absorbing) interval values.
+pub(crate) fn unwrap_interval<'a, TW, Ctx: Unwrap<TW>>(
+    ctx: &'a mut Ctx,
+    interval: &mut Interval,
+) -> Result<&'a mut Ctx, Protobuf3Error> {
+    unwrap_u64(ctx, &mut interval.start_ms)?;
+    unwrap_u64(ctx, &mut interval.end_ms)?;
+    ensure!(
+        interval.start_ms <= interval.end_ms,
+        Protobuf3Error::OutOfRange(format!(
+            "Invalid interval: end {} is before start {}.",
+            interval.end_ms, interval.start_ms
+        ))
+    );
+    Ok(ctx)
+}
+
+/// Helper function for unwrapping (decoding/absorbing) `N` bytes without length,
+/// eg. octets of IP addresses.
+#[cfg(feature = "net")]
+pub(crate) fn unwrap_octets<'a, TW, Ctx: Unwrap<TW>, const N: usize>(
+    ctx: &'a mut Ctx,
+    octets: &mut [u8; N],
+) -> Result<&'a mut Ctx, Protobuf3Error> {
+    let mut bytes = Vec::with_capacity(N);
+    unwrap_bytes_body(ctx, N, &mut bytes)?;
+    octets.copy_from_slice(&bytes);
+    Ok(ctx)
+}
+
+/// Helper function for unwrapping (decoding/absorbing) socket addresses, see `wrap_socket_addr`.
+/// The IP version must be `4` or `6`.
+#[cfg(feature = "net")]
+pub(crate) fn unwrap_socket_addr<'a, TW, Ctx: Unwrap<TW>>(
+    ctx: &'a mut Ctx,
+    addr: &mut std::net::SocketAddr,
+) -> Result<&'a mut Ctx, Protobuf3Error> {
+    let mut version = Trint3(0);
+    ctx.unwrap3(&mut version)?;
+    let mut port = [0_u8; 2];
+    *addr = match version.0 {
+        4 => {
+            let mut octets = [0_u8; 4];
+            unwrap_octets(ctx, &mut octets)?;
+            unwrap_octets(ctx, &mut port)?;
+            std::net::SocketAddr::from((octets, u16::from_be_bytes(port)))
+        }
+        6 => {
+            let mut octets = [0_u8; 16];
+            let mut flowinfo = [0_u8; 4];
+            let mut scope_id = [0_u8; 4];
+            unwrap_octets(ctx, &mut octets)?;
+            unwrap_octets(ctx, &mut port)?;
+            unwrap_octets(ctx, &mut flowinfo)?;
+            unwrap_octets(ctx, &mut scope_id)?;
+            std::net::SocketAddr::V6(std::net::SocketAddrV6::new(
+                octets.into(),
+                u16::from_be_bytes(port),
+                u32::from_be_bytes(flowinfo),
+                u32::from_be_bytes(scope_id),
+            ))
+        }
+        v => {
+            bail!(Protobuf3Error::OutOfRange(format!(
+                "Invalid IP version of socket address: {}.",
+                v
+            )))
+        }
+    };
+    Ok(ctx)
+}
+
+/// Helper function for unwrapping (decoding/absorbing) the presence flag of `Option` fields.
+pub(crate) fn unwrap_flag<'a, TW, Ctx: Unwrap<TW>>(
+    ctx: &'a mut Ctx,
+    present: &mut bool,
+) -> Result<&'a mut Ctx, Protobuf3Error>
+where
+    TW: trinary::TritWord,
+{
+    let mut flag = Tbits::<TW>::zero(SIZEOF_FLAG);
+    ctx.unwrapn(flag.slice_mut())?;
+    match flag.slice().get_trit() {
+        trinary::Trit(0) => *present = false,
+        trinary::Trit(1) => *present = true,
+        t => {
+            bail!(Protobuf3Error::OutOfRange(format!(
+                "Invalid presence flag: {}.",
+                t
+            )))
+        }
+    }
+    Ok(ctx)
+}
+
 struct AbsorbContext<TW, F, IS> {
     ctx: Context<TW, F, IS>,
 }
@@ -123,13 +445,13 @@ where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn unwrap3(&mut self, trint3: &mut Trint3) -> Fallible<&mut Self> {
+    fn unwrap3(&mut self, trint3: &mut Trint3) -> Result<&mut Self, Protobuf3Error> {
         let slice = self.ctx.stream.try_advance(3)?;
         *trint3 = slice.get3();
         self.ctx.spongos.absorb(slice);
         Ok(self)
     }
-    fn unwrapn(&mut self, trits: TbitSliceMut<TW>) -> Fallible<&mut Self> {
+    fn unwrapn(&mut self, trits: TbitSliceMut<TW>) -> Result<&mut Self, Protobuf3Error> {
         let slice = self.ctx.stream.try_advance(trits.size())?;
         slice.copy(&trits);
         self.ctx.spongos.absorb(unsafe { trits.as_const() });
@@ -140,7 +462,7 @@ where
 fn unwrap_absorb_trint3<'a, TW, F, IS: io::IStream<TW>>(
     ctx: &'a mut AbsorbContext<TW, F, IS>,
     trint3: &mut Trint3,
-) -> Fallible<&'a mut AbsorbContext<TW, F, IS>>
+) -> Result<&'a mut AbsorbContext<TW, F, IS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
@@ -150,17 +472,102 @@ where
 fn unwrap_absorb_size<'a, TW, F, IS: io::IStream<TW>>(
     ctx: &'a mut AbsorbContext<TW, F, IS>,
     size: &mut Size,
-) -> Fallible<&'a mut AbsorbContext<TW, F, IS>>
+) -> Result<&'a mut AbsorbContext<TW, F, IS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
     unwrap_size(ctx, size)
 }
+fn unwrap_absorb_ratio<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut AbsorbContext<TW, F, IS>,
+    ratio: &mut Ratio,
+) -> Result<&'a mut AbsorbContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    unwrap_ratio(ctx, ratio)
+}
+fn unwrap_absorb_bytes<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut AbsorbContext<TW, F, IS>,
+    bytes: &mut Vec<u8>,
+    max: usize,
+) -> Result<&'a mut AbsorbContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    unwrap_bytes(ctx, bytes, max)
+}
+#[cfg(feature = "ed25519-dalek")]
+fn unwrap_absorb_bytes_body<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut AbsorbContext<TW, F, IS>,
+    n: usize,
+    bytes: &mut Vec<u8>,
+) -> Result<&'a mut AbsorbContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    unwrap_bytes_body(ctx, n, bytes)
+}
+#[cfg(feature = "net")]
+fn unwrap_absorb_octets<'a, TW, F, IS: io::IStream<TW>, const N: usize>(
+    ctx: &'a mut AbsorbContext<TW, F, IS>,
+    octets: &mut [u8; N],
+) -> Result<&'a mut AbsorbContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    unwrap_octets(ctx, octets)
+}
+#[cfg(feature = "net")]
+fn unwrap_absorb_socket_addr<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut AbsorbContext<TW, F, IS>,
+    addr: &mut std::net::SocketAddr,
+) -> Result<&'a mut AbsorbContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    unwrap_socket_addr(ctx, addr)
+}
+fn unwrap_absorb_u64<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut AbsorbContext<TW, F, IS>,
+    u: &mut u64,
+) -> Result<&'a mut AbsorbContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    unwrap_u64(ctx, u)
+}
+fn unwrap_absorb_interval<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut AbsorbContext<TW, F, IS>,
+    interval: &mut Interval,
+) -> Result<&'a mut AbsorbContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    unwrap_interval(ctx, interval)
+}
+fn unwrap_absorb_flag<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut AbsorbContext<TW, F, IS>,
+    present: &mut bool,
+) -> Result<&'a mut AbsorbContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    unwrap_flag(ctx, present)
+}
 fn unwrap_absorb_trits<'a, TW, F, IS: io::IStream<TW>>(
     ctx: &'a mut AbsorbContext<TW, F, IS>,
     trits: TbitSliceMut<TW>,
-) -> Fallible<&'a mut AbsorbContext<TW, F, IS>>
+) -> Result<&'a mut AbsorbContext<TW, F, IS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
@@ -168,13 +575,223 @@ where
     ctx.unwrapn(trits)
 }
 
+impl<TW, F, IS: io::IStream<TW>> Context<TW, F, IS>
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW>,
+{
+    /// Rewind the input stream to the position the context was created at and reset Spongos
+    /// to the initial zero state, so that the context can unwrap another message from the same
+    /// buffer without reallocation.
+    /// Dump sink, Spongos mode, `RepeatedN` limit, MSS height range and validation mode are kept.
+    pub fn reset(&mut self) -> Result<&mut Self, Protobuf3Error> {
+        let n = self.stream.position() - self.start;
+        self.stream.rewind(n)?;
+        self.spongos.wipe();
+        self.desynced = false;
+        self.claimed_size = 0;
+        Ok(self)
+    }
+}
+
+impl<TW, F, IS: io::IStream<TW>> Context<TW, F, IS> {
+    /// Offset in tbits of the next unwrapped field relative to the position of the stream
+    /// when the context was created, ie. the size of the message unwrapped so far.
+    pub fn position(&self) -> usize {
+        self.stream.position() - self.start
+    }
+}
+
+impl<TW, F, IS: io::IStream<TW>> Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    /// Absorb message sequence counter and check it against the `last` accepted one.
+    /// Counter that is not greater than `last` indicates a replayed message and is rejected.
+    /// Otherwise `last` is updated and the number of skipped counter values
+    /// (ie. dropped messages) is returned.
+    pub fn check_counter(&mut self, last: &mut u64) -> Result<u64, Protobuf3Error> {
+        let mut counter = 0_u64;
+        unwrap_u64(
+            AsMut::<AbsorbContext<TW, F, IS>>::as_mut(self),
+            &mut counter,
+        )?;
+        ensure!(
+            *last < counter,
+            Protobuf3Error::OutOfRange(format!(
+                "Replayed message: counter {} is not greater than the last one {}.",
+                counter, *last
+            ))
+        );
+        let gap = counter - *last - 1;
+        *last = counter;
+        Ok(gap)
+    }
+
+    /// Absorb byte string of at most `max` bytes, longer strings are rejected.
+    pub fn absorb_bytes(
+        &mut self,
+        bytes: &mut Vec<u8>,
+        max: usize,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        unwrap_absorb_bytes(AsMut::<AbsorbContext<TW, F, IS>>::as_mut(self), bytes, max)?;
+        // Bytes are not preallocated, the length is accounted once they are decoded.
+        self.claim("bytes", 3 * BYTE_TRYTES * bytes.len())?;
+        Ok(self)
+    }
+
+    /// Bind message to a schema version, see `wrap::Context::bind_schema`.
+    pub fn bind_schema(&mut self, schema_id: &[Trint3]) -> Result<&mut Self, Protobuf3Error> {
+        Ok(wrap_schema(
+            AsMut::<AbsorbExternalContext<TW, F, IS>>::as_mut(self),
+            schema_id,
+        )?
+        .as_mut())
+    }
+}
+
+impl<TW, F, IS: io::IStream<TW>> Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW> + Clone,
+{
+    /// Tolerant `oneof` field, see `wrap::Context::oneof_tolerant`.
+    /// Variants with tags less than `known_max` are unwrapped by `cont` in a forked Spongos state
+    /// and `oneof` is set to `Oneof::Known(tag)`, `cont` must consume exactly the encoded size
+    /// of the body. The body of a variant with an unknown tag is skipped and `oneof` is set to
+    /// `Oneof::Unknown(tag, body)`. In both cases the raw body trits are then absorbed, so that
+    /// the Spongos state doesn't depend on whether the variant is known. Known variants require
+    /// an input stream that can be rewound.
+    pub fn oneof_tolerant<C>(
+        &mut self,
+        oneof: &mut Oneof<TW>,
+        known_max: Size,
+        mut cont: C,
+    ) -> Result<&mut Self, Protobuf3Error>
+    where
+        C: for<'a> FnMut(&'a mut Self, Size) -> Result<&'a mut Self, Protobuf3Error>,
+    {
+        let mut tag = Size(0);
+        let mut size = Size(0);
+        self.absorb(&mut tag)?.absorb(&mut size)?;
+        if tag < known_max {
+            let start = self.stream.position();
+            self.fork(|ctx| cont(ctx, tag))?;
+            let consumed = self.stream.position() - start;
+            ensure!(
+                consumed == size.0,
+                Protobuf3Error::OutOfRange(format!(
+                    "Oneof body of {} trits doesn't match its encoded size {}.",
+                    consumed, size.0
+                ))
+            );
+            self.stream.rewind(size.0)?;
+            let body = self.stream.try_advance(size.0)?;
+            self.spongos.absorb(body);
+            *oneof = Oneof::Known(tag);
+        } else {
+            self.claim("oneof", size.0)?;
+            let body = self.stream.try_advance(size.0)?;
+            self.spongos.absorb(body);
+            *oneof = Oneof::Unknown(tag, Tbits::from_slice(body));
+        }
+        Ok(self)
+    }
+}
+
+impl<TW, F, IS: io::IStream<TW>> Context<TW, F, IS>
+where
+    TW: IntTbitWord + SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW> + Clone,
+{
+    /// Try to decapsulate a secret of `n` trits with `sk` as `Ntrukem` does and tell
+    /// "not a recipient" from a corrupted capsule: `Some(secret)` -- decapsulated,
+    /// `None` -- the capsule is well-formed but is not encapsulated with `sk`,
+    /// `Protobuf3Error::NtruMalformedCapsule` -- the capsule is not a valid encoding.
+    ///
+    /// The capsule is consumed in all cases. Spongos state after `None` differs from
+    /// the wrapped one, capsules of other recipients are usually tried in a `Fork`.
+    pub fn try_ntrukem(
+        &mut self,
+        sk: &ntru::PrivateKey<TW, F>,
+        n: usize,
+    ) -> Result<Option<NTrytes<TW>>, Protobuf3Error> {
+        let mut secret = NTrytes::zero(n);
+        match self.ntrukem(sk, &mut secret).map(|_| ()) {
+            Ok(()) => Ok(Some(secret)),
+            Err(Protobuf3Error::NtruDecapsulation) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Decapsulate `secret` with `sk` from one of the capsules wrapped with
+    /// `wrap::Context::ntrukem_multi`. Capsules are tried in order, the rest of them
+    /// are only checked to be well-formed once the secret is decapsulated.
+    /// `Protobuf3Error::NtruDecapsulation` is returned if none of the capsules decapsulates,
+    /// `Protobuf3Error::NtruMalformedCapsule` if any capsule is malformed, so the result
+    /// doesn't depend on the position of the matching capsule.
+    pub fn ntrukem_multi(
+        &mut self,
+        sk: &ntru::PrivateKey<TW, F>,
+        secret: &mut NTrytes<TW>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        let mut n = Size(0);
+        self.absorb(&mut n)?;
+        let mut found = None;
+        for _ in 0..n.0 {
+            if found.is_some() {
+                let ekey_slice = self.stream.try_advance(ntru::EKEY_SIZE)?;
+                ensure!(
+                    ntru::is_valid_ekey(ekey_slice),
+                    Protobuf3Error::NtruMalformedCapsule
+                );
+                continue;
+            }
+            let key_size = (secret.0).size();
+            self.fork(|ctx| {
+                found = ctx.try_ntrukem(sk, key_size)?;
+                Ok(ctx)
+            })?;
+        }
+        match found {
+            Some(key) => *secret = key,
+            None => bail!(Protobuf3Error::NtruDecapsulation),
+        }
+        Ok(self)
+    }
+}
+
+impl<TW, F, IS: io::IStream<TW>> Context<TW, F, IS>
+where
+    TW: IntTbitWord + SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    /// Verify message signature with `pk` and derive message identifier `id`,
+    /// see `wrap::Context::finalize_with_signature_and_id`.
+    pub fn verify_signature_and_id<P>(
+        &mut self,
+        pk: &mss::PublicKey<TW, P>,
+        id: &mut NTrytes<TW>,
+    ) -> Result<&mut Self, Protobuf3Error>
+    where
+        P: mss::Parameters<TW>,
+    {
+        self.commit()?.mssig(pk, MssHashSig)?.commit()?;
+        self.spongos.squeeze(&mut (id.0).slice_mut());
+        Ok(self)
+    }
+}
+
 impl<TW, F, IS: io::IStream<TW>> Absorb<&mut Trint3> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, trint3: &mut Trint3) -> Fallible<&mut Self> {
-        Ok(unwrap_absorb_trint3(self.as_mut(), trint3)?.as_mut())
+    fn absorb(&mut self, trint3: &mut Trint3) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Trint3", |ctx| {
+            Ok(unwrap_absorb_trint3(ctx.as_mut(), trint3)?.as_mut())
+        })
     }
 }
 
@@ -183,340 +800,1035 @@ where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, size: &mut Size) -> Fallible<&mut Self> {
-        Ok(unwrap_absorb_size(self.as_mut(), size)?.as_mut())
+    fn absorb(&mut self, size: &mut Size) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Size", |ctx| {
+            Ok(unwrap_absorb_size(ctx.as_mut(), size)?.as_mut())
+        })
     }
 }
 
-impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut NTrytes<TW>> for Context<TW, F, IS>
+impl<TW, F, IS: io::IStream<TW>> Absorb<&mut Ratio> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, ntrytes: &'a mut NTrytes<TW>) -> Fallible<&mut Self> {
-        Ok(unwrap_absorb_trits(self.as_mut(), (ntrytes.0).slice_mut())?.as_mut())
+    fn absorb(&mut self, ratio: &mut Ratio) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Ratio", |ctx| {
+            Ok(unwrap_absorb_ratio(ctx.as_mut(), ratio)?.as_mut())
+        })
     }
 }
 
-impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut Trytes<TW>> for Context<TW, F, IS>
+/// Byte string length is limited by `SIZE_MAX` only, use `absorb_bytes` to limit it explicitly.
+impl<TW, F, IS: io::IStream<TW>> Absorb<&mut Vec<u8>> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, trytes: &'a mut Trytes<TW>) -> Fallible<&mut Self> {
-        let mut size = Size(0);
-        self.absorb(&mut size)?;
-        trytes.0 = Tbits::<TW>::zero(3 * size.0);
-        Ok(unwrap_absorb_trits(self.as_mut(), (trytes.0).slice_mut())?.as_mut())
+    fn absorb(&mut self, bytes: &mut Vec<u8>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Vec<u8>", |ctx| {
+            ctx.absorb_bytes(bytes, SIZE_MAX)
+        })
     }
 }
 
-impl<'a, TW, F, IS: io::IStream<TW>, P> Absorb<&'a mut mss::PublicKey<TW, P>> for Context<TW, F, IS>
+/// Presence flag followed by the inner field if present, the inner value is
+/// default-initialized if the option was `None`.
+fn unwrap_absorb_option<'a, TW, F, IS: io::IStream<TW>, T: Default>(
+    ctx: &'a mut Context<TW, F, IS>,
+    x: &mut Option<T>,
+) -> Result<&'a mut Context<TW, F, IS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
-    P: mss::Parameters<TW>,
+    for<'b> Context<TW, F, IS>: Absorb<&'b mut T>,
 {
-    fn absorb(&mut self, pk: &'a mut mss::PublicKey<TW, P>) -> Fallible<&mut Self> {
-        ensure!(pk.tbits().size() == P::PUBLIC_KEY_SIZE);
-        Ok(unwrap_absorb_trits(self.as_mut(), pk.tbits_mut().slice_mut())?.as_mut())
+    let mut present = false;
+    unwrap_absorb_flag(ctx.as_mut(), &mut present)?;
+    if present {
+        ctx.absorb(x.get_or_insert_with(T::default))
+    } else {
+        *x = None;
+        Ok(ctx)
     }
 }
 
-impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut ntru::PublicKey<TW, F>> for Context<TW, F, IS>
+/// Masked presence flag followed by the masked inner field if present.
+fn unwrap_mask_option<'a, TW, F, IS: io::IStream<TW>, T: Default>(
+    ctx: &'a mut Context<TW, F, IS>,
+    x: &mut Option<T>,
+) -> Result<&'a mut Context<TW, F, IS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
+    for<'b> Context<TW, F, IS>: Mask<&'b mut T>,
 {
-    fn absorb(&mut self, pk: &'a mut ntru::PublicKey<TW, F>) -> Fallible<&mut Self> {
-        ensure!(pk.tbits().size() == ntru::PUBLIC_KEY_SIZE);
-        unwrap_absorb_trits(self.as_mut(), pk.tbits_mut().slice_mut())?;
-        ensure!(pk.validate(), "NTRU public key is not valid.");
-        Ok(self)
+    ensure!(
+        !ctx.validate,
+        "Masked presence flag can't be validated without decryption."
+    );
+    let mut present = false;
+    unwrap_mask_flag(ctx.as_mut(), &mut present)?;
+    if present {
+        ctx.mask(x.get_or_insert_with(T::default))
+    } else {
+        *x = None;
+        Ok(ctx)
     }
 }
 
-struct AbsorbExternalContext<TW, F, IS> {
-    ctx: Context<TW, F, IS>,
+/// `Option` fields are implemented per inner type, a blanket impl over `T` bounded by
+/// `Self: Absorb<&mut T>` sends the trait solver into infinite `Option<Option<..>>` recursion.
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut Option<Trint3>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, x: &'a mut Option<Trint3>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Option<Trint3>", |ctx| {
+            unwrap_absorb_option(ctx, x)
+        })
+    }
 }
-impl<TW, F, IS> AsMut<AbsorbExternalContext<TW, F, IS>> for Context<TW, F, IS>
+
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut Option<Size>> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn as_mut<'a>(&'a mut self) -> &'a mut AbsorbExternalContext<TW, F, IS> {
-        unsafe {
-            mem::transmute::<&'a mut Context<TW, F, IS>, &'a mut AbsorbExternalContext<TW, F, IS>>(
-                self,
-            )
-        }
+    fn absorb(&mut self, x: &'a mut Option<Size>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Option<Size>", |ctx| {
+            unwrap_absorb_option(ctx, x)
+        })
     }
 }
-impl<TW, F, IS> AsMut<Context<TW, F, IS>> for AbsorbExternalContext<TW, F, IS> {
-    fn as_mut<'a>(&'a mut self) -> &'a mut Context<TW, F, IS> {
-        unsafe {
-            mem::transmute::<&'a mut AbsorbExternalContext<TW, F, IS>, &'a mut Context<TW, F, IS>>(
-                self,
-            )
-        }
+
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut Option<Ratio>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, x: &'a mut Option<Ratio>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Option<Ratio>", |ctx| {
+            unwrap_absorb_option(ctx, x)
+        })
     }
 }
 
-impl<TW, F, IS: io::IStream<TW>> Wrap<TW> for AbsorbExternalContext<TW, F, IS>
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut Option<Interval>> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn wrap3(&mut self, trint3: Trint3) -> Fallible<&mut Self> {
-        let mut buf = [BasicTbitWord::ZERO_WORD; 3];
-        let mut t3 = TbitSliceMut::<TW>::from_slice_mut(3, &mut buf);
-        t3.put3(trint3);
-        self.ctx.spongos.absorb(unsafe { t3.as_const() });
-        Ok(self)
+    fn absorb(&mut self, x: &'a mut Option<Interval>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Option<Interval>", |ctx| {
+            unwrap_absorb_option(ctx, x)
+        })
     }
-    fn wrapn(&mut self, trits: TbitSlice<TW>) -> Fallible<&mut Self> {
-        self.ctx.spongos.absorb(trits);
-        Ok(self)
+}
+
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut Option<Trytes<TW>>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, x: &'a mut Option<Trytes<TW>>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Option<Trytes>", |ctx| {
+            unwrap_absorb_option(ctx, x)
+        })
     }
 }
-
-fn wrap_absorb_external_trint3<'a, TW, F, IS: io::IStream<TW>>(
-    ctx: &'a mut AbsorbExternalContext<TW, F, IS>,
-    trint3: Trint3,
-) -> Fallible<&'a mut AbsorbExternalContext<TW, F, IS>>
+
+impl<TW, F, IS: io::IStream<TW>> Absorb<&mut Interval> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, interval: &mut Interval) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Interval", |ctx| {
+            Ok(unwrap_absorb_interval(ctx.as_mut(), interval)?.as_mut())
+        })
+    }
+}
+
+impl<TW, F, IS: io::IStream<TW>> Timestamp<&mut u64> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb_timestamp(&mut self, ts: &mut u64) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb_timestamp", "u64", |ctx| {
+            Ok(unwrap_absorb_u64(ctx.as_mut(), ts)?.as_mut())
+        })
+    }
+}
+
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut NTrytes<TW>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, ntrytes: &'a mut NTrytes<TW>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "NTrytes", |ctx| {
+            Ok(unwrap_absorb_trits(ctx.as_mut(), (ntrytes.0).slice_mut())?.as_mut())
+        })
+    }
+}
+
+/// Exactly `N * 3` trits are consumed, `ntryte` is reallocated if it has a wrong size.
+impl<'a, TW, F, IS: io::IStream<TW>, const N: usize> Absorb<&'a mut NTryte<TW, N>>
+    for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, ntryte: &'a mut NTryte<TW, N>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "NTryte", |ctx| {
+            if (ntryte.0).size() != NTryte::<TW, N>::SIZE {
+                ntryte.0 = Tbits::<TW>::zero(NTryte::<TW, N>::SIZE);
+            }
+            Ok(unwrap_absorb_trits(ctx.as_mut(), (ntryte.0).slice_mut())?.as_mut())
+        })
+    }
+}
+
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut Trytes<TW>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, trytes: &'a mut Trytes<TW>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Trytes", |ctx| {
+            let mut size = Size(0);
+            ctx.absorb(&mut size)?;
+            ctx.claim("trytes", size.0.saturating_mul(3))?;
+            trytes.0 = Tbits::<TW>::zero(3 * size.0);
+            Ok(unwrap_absorb_trits(ctx.as_mut(), (trytes.0).slice_mut())?.as_mut())
+        })
+    }
+}
+
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut TlvStream<TW>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, tlvs: &'a mut TlvStream<TW>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "TlvStream", |ctx| {
+            let mut count = Size(0);
+            ctx.absorb(&mut count)?;
+            ensure!(
+                count.0 <= tlvs.max_records,
+                Protobuf3Error::OutOfRange(format!(
+                    "Too many TLV records: {} > {}.",
+                    count.0, tlvs.max_records
+                ))
+            );
+
+            tlvs.records.clear();
+            tlvs.skipped = 0;
+            for _ in 0..count.0 {
+                let mut tlv = Tlv {
+                    tlv_type: Size(0),
+                    value: Trytes::default(),
+                };
+                let mut size = Size(0);
+                ctx.absorb(&mut tlv.tlv_type)?.absorb(&mut size)?;
+                ensure!(
+                    size.0 <= tlvs.max_value_size,
+                    Protobuf3Error::OutOfRange(format!(
+                        "TLV record is too long: {} > {} trytes.",
+                        size.0, tlvs.max_value_size
+                    ))
+                );
+                ctx.claim("trytes", size.0.saturating_mul(3))?;
+                tlv.value.0 = Tbits::<TW>::zero(3 * size.0);
+                unwrap_absorb_trits(ctx.as_mut(), tlv.value.0.slice_mut())?;
+                if tlvs.known.contains(&tlv.tlv_type) {
+                    tlvs.records.push(tlv);
+                } else {
+                    tlvs.skipped += 1;
+                }
+            }
+            Ok(ctx)
+        })
+    }
+}
+
+impl<TW, F, IS: io::IStream<TW>> Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    /// Absorb `size_t` number of elements followed by the elements into `xs`,
+    /// see `wrap::Context::absorb_many`. The number of elements is limited the same way as
+    /// `RepeatedN` (see `set_repeated_max`). Each element takes at least one trit, so the number
+    /// is also charged as trits against `set_max_message_size` before `xs` is preallocated.
+    pub fn absorb_many<T>(&mut self, xs: &mut Vec<T>) -> Result<&mut Self, Protobuf3Error>
+    where
+        T: Default,
+        for<'b> Self: Absorb<&'b mut T>,
+    {
+        let mut count = Size(0);
+        <Self as Absorb<&mut Size>>::absorb(self, &mut count)?;
+        ensure!(
+            count.0 <= self.repeated_max,
+            Protobuf3Error::OutOfRange(format!(
+                "Number of elements {} exceeds maximum {}.",
+                count.0, self.repeated_max
+            ))
+        );
+        self.claim("absorb_many", count.0)?;
+
+        xs.clear();
+        xs.reserve(count.0);
+        for _ in 0..count.0 {
+            let mut x = T::default();
+            self.absorb(&mut x)?;
+            xs.push(x);
+        }
+        Ok(self)
+    }
+}
+
+impl<'a, TW, F, IS: io::IStream<TW>, P> Absorb<&'a mut mss::PublicKey<TW, P>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+    P: mss::Parameters<TW>,
+{
+    fn absorb(&mut self, pk: &'a mut mss::PublicKey<TW, P>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "mss::PublicKey", |ctx| {
+            ensure!(pk.tbits().size() == P::PUBLIC_KEY_SIZE);
+            Ok(unwrap_absorb_trits(ctx.as_mut(), pk.tbits_mut().slice_mut())?.as_mut())
+        })
+    }
+}
+
+#[cfg(feature = "net")]
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut std::net::Ipv4Addr> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, ip: &'a mut std::net::Ipv4Addr) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Ipv4Addr", |ctx| {
+            let mut octets = [0_u8; 4];
+            unwrap_absorb_octets(ctx.as_mut(), &mut octets)?;
+            *ip = std::net::Ipv4Addr::from(octets);
+            Ok(ctx)
+        })
+    }
+}
+
+#[cfg(feature = "net")]
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut std::net::Ipv6Addr> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, ip: &'a mut std::net::Ipv6Addr) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Ipv6Addr", |ctx| {
+            let mut octets = [0_u8; 16];
+            unwrap_absorb_octets(ctx.as_mut(), &mut octets)?;
+            *ip = std::net::Ipv6Addr::from(octets);
+            Ok(ctx)
+        })
+    }
+}
+
+#[cfg(feature = "net")]
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut std::net::SocketAddr> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, addr: &'a mut std::net::SocketAddr) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "SocketAddr", |ctx| {
+            Ok(unwrap_absorb_socket_addr(ctx.as_mut(), addr)?.as_mut())
+        })
+    }
+}
+
+#[cfg(feature = "ed25519-dalek")]
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut Ed25519PublicKey> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, pk: &'a mut Ed25519PublicKey) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Ed25519PublicKey", |ctx| {
+            let mut bytes = Vec::with_capacity(ed25519_dalek::PUBLIC_KEY_LENGTH);
+            unwrap_absorb_bytes_body(ctx.as_mut(), ed25519_dalek::PUBLIC_KEY_LENGTH, &mut bytes)?;
+            *pk = match Ed25519PublicKey::from_bytes(&bytes) {
+                Ok(pk) => pk,
+                Err(e) => {
+                    bail!(Protobuf3Error::OutOfRange(format!(
+                        "Invalid Ed25519 public key: {}.",
+                        e
+                    )))
+                }
+            };
+            Ok(ctx)
+        })
+    }
+}
+
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<&'a mut ntru::PublicKey<TW, F>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, pk: &'a mut ntru::PublicKey<TW, F>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "ntru::PublicKey", |ctx| {
+            ensure!(pk.tbits().size() == ntru::PUBLIC_KEY_SIZE);
+            unwrap_absorb_trits(ctx.as_mut(), pk.tbits_mut().slice_mut())?;
+            ensure!(pk.validate(), "NTRU public key is not valid.");
+            Ok(ctx)
+        })
+    }
+}
+
+struct AbsorbExternalContext<TW, F, IS> {
+    ctx: Context<TW, F, IS>,
+}
+impl<TW, F, IS> AsMut<AbsorbExternalContext<TW, F, IS>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn as_mut<'a>(&'a mut self) -> &'a mut AbsorbExternalContext<TW, F, IS> {
+        unsafe {
+            mem::transmute::<&'a mut Context<TW, F, IS>, &'a mut AbsorbExternalContext<TW, F, IS>>(
+                self,
+            )
+        }
+    }
+}
+impl<TW, F, IS> AsMut<Context<TW, F, IS>> for AbsorbExternalContext<TW, F, IS> {
+    fn as_mut<'a>(&'a mut self) -> &'a mut Context<TW, F, IS> {
+        unsafe {
+            mem::transmute::<&'a mut AbsorbExternalContext<TW, F, IS>, &'a mut Context<TW, F, IS>>(
+                self,
+            )
+        }
+    }
+}
+
+impl<TW, F, IS: io::IStream<TW>> Wrap<TW> for AbsorbExternalContext<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn wrap3(&mut self, trint3: Trint3) -> Result<&mut Self, Protobuf3Error> {
+        let mut buf = [BasicTbitWord::ZERO_WORD; 3];
+        let mut t3 = TbitSliceMut::<TW>::from_slice_mut(3, &mut buf);
+        t3.put3(trint3);
+        self.ctx.spongos.absorb(unsafe { t3.as_const() });
+        Ok(self)
+    }
+    fn wrapn(&mut self, trits: TbitSlice<TW>) -> Result<&mut Self, Protobuf3Error> {
+        self.ctx.spongos.absorb(trits);
+        Ok(self)
+    }
+}
+
+fn wrap_absorb_external_trint3<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut AbsorbExternalContext<TW, F, IS>,
+    trint3: Trint3,
+) -> Result<&'a mut AbsorbExternalContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    ctx.wrap3(trint3)
+}
+fn wrap_absorb_external_size<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut AbsorbExternalContext<TW, F, IS>,
+    size: Size,
+) -> Result<&'a mut AbsorbExternalContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    wrap_size(ctx, size)
+}
+fn wrap_absorb_external_trits<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut AbsorbExternalContext<TW, F, IS>,
+    trits: TbitSlice<TW>,
+) -> Result<&'a mut AbsorbExternalContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    ctx.wrapn(trits)
+}
+
+impl<'a, T: 'a, TW, F, IS: io::IStream<TW>> Absorb<&'a External<T>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+    Self: Absorb<External<&'a T>>,
+{
+    fn absorb(&mut self, external: &'a External<T>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<T>", |ctx| {
+            ctx.absorb(External(&external.0))
+        })
+    }
+}
+
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<External<&'a Trint3>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, trint3: External<&'a Trint3>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<Trint3>", |ctx| {
+            Ok(wrap_absorb_external_trint3(ctx.as_mut(), *trint3.0)?.as_mut())
+        })
+    }
+}
+
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<External<&'a Size>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, size: External<&'a Size>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<Size>", |ctx| {
+            Ok(wrap_absorb_external_size(ctx.as_mut(), *size.0)?.as_mut())
+        })
+    }
+}
+
+impl<TW, F, IS: io::IStream<TW>> Absorb<External<Size>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, size: External<Size>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<Size>", |ctx| {
+            ctx.absorb(&size)
+        })
+    }
+}
+
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<External<&'a NTrytes<TW>>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(
+        &mut self,
+        external_ntrytes: External<&'a NTrytes<TW>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<NTrytes>", |ctx| {
+            Ok(
+                wrap_absorb_external_trits(ctx.as_mut(), ((external_ntrytes.0).0).slice())?
+                    .as_mut(),
+            )
+        })
+    }
+}
+
+impl<'a, TW, F, IS: io::IStream<TW>, P> Absorb<External<&'a mss::PublicKey<TW, P>>>
+    for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+    P: mss::Parameters<TW>,
+{
+    fn absorb(
+        &mut self,
+        pk: External<&'a mss::PublicKey<TW, P>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<mss::PublicKey>", |ctx| {
+            ensure!((pk.0).tbits().size() == P::PUBLIC_KEY_SIZE);
+            Ok(wrap_absorb_external_trits(ctx.as_mut(), (pk.0).tbits().slice())?.as_mut())
+        })
+    }
+}
+
+impl<'a, TW, F, IS: io::IStream<TW>> Absorb<External<&'a ntru::PublicKey<TW, F>>>
+    for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(
+        &mut self,
+        pk: External<&'a ntru::PublicKey<TW, F>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<ntru::PublicKey>", |ctx| {
+            ensure!((pk.0).tbits().size() == ntru::PUBLIC_KEY_SIZE);
+            Ok(wrap_absorb_external_trits(ctx.as_mut(), (pk.0).tbits().slice())?.as_mut())
+        })
+    }
+}
+
+/// This is just an external tag or hash value to-be-signed.
+impl<'a, TW, F, IS> Squeeze<&'a mut External<NTrytes<TW>>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn squeeze(&mut self, val: &'a mut External<NTrytes<TW>>) -> Result<&mut Self, Protobuf3Error> {
+        self.spongos.squeeze(&mut ((val.0).0).slice_mut());
+        Ok(self)
+    }
+}
+
+/// Squeeze `N` trytes into the caller's buffer, eg. a hash value to be verified externally.
+impl<'a, TW, F, IS, const N: usize> Squeeze<&'a mut External<NTryte<TW, N>>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn squeeze(
+        &mut self,
+        val: &'a mut External<NTryte<TW, N>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        self.squeeze(External(&mut val.0))
+    }
+}
+
+/// Squeeze `N` trytes into the caller's buffer, eg. a hash value to be verified externally.
+impl<'a, TW, F, IS, const N: usize> Squeeze<External<&'a mut NTryte<TW, N>>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn squeeze(
+        &mut self,
+        val: External<&'a mut NTryte<TW, N>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        let ntryte = val.0;
+        if (ntryte.0).size() != NTryte::<TW, N>::SIZE {
+            ntryte.0 = Tbits::<TW>::zero(NTryte::<TW, N>::SIZE);
+        }
+        self.spongos.squeeze(&mut (ntryte.0).slice_mut());
+        Ok(self)
+    }
+}
+
+/// External values are not encoded. Squeeze and compare tag trits.
+impl<'a, TW, F, IS: io::IStream<TW>> Squeeze<&'a Mac> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn squeeze(&mut self, val: &'a Mac) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "squeeze", "Mac", |ctx| {
+            let slice = ctx.stream.try_advance(val.0)?;
+            if ctx.desynced {
+                return Ok(ctx);
+            }
+            ensure!(ctx.spongos.squeeze_eq(slice), Protobuf3Error::BadMac);
+            Ok(ctx)
+        })
+    }
+}
+
+struct MaskContext<TW, F, IS> {
+    ctx: Context<TW, F, IS>,
+}
+impl<TW, F, IS> AsMut<MaskContext<TW, F, IS>> for Context<TW, F, IS> {
+    fn as_mut<'a>(&'a mut self) -> &'a mut MaskContext<TW, F, IS> {
+        unsafe {
+            mem::transmute::<&'a mut Context<TW, F, IS>, &'a mut MaskContext<TW, F, IS>>(self)
+        }
+    }
+}
+impl<TW, F, IS> AsMut<Context<TW, F, IS>> for MaskContext<TW, F, IS> {
+    fn as_mut<'a>(&'a mut self) -> &'a mut Context<TW, F, IS> {
+        unsafe {
+            mem::transmute::<&'a mut MaskContext<TW, F, IS>, &'a mut Context<TW, F, IS>>(self)
+        }
+    }
+}
+
+impl<TW, F, IS: io::IStream<TW>> Unwrap<TW> for MaskContext<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn unwrap3(&mut self, trint3: &mut Trint3) -> Result<&mut Self, Protobuf3Error> {
+        ensure!(
+            !self.ctx.validate,
+            "Masked formatted fields can't be validated without decryption."
+        );
+        // 3 words should be enough to encode trint3 for any TE.
+        let mut buf = [BasicTbitWord::ZERO_WORD; 3];
+        let slice = self.ctx.stream.try_advance(3)?;
+        {
+            let mut t3 = TbitSliceMut::<TW>::from_slice_mut(3, &mut buf);
+            self.ctx.spongos.decrypt(slice, &mut t3);
+        }
+        {
+            let t3 = TbitSlice::<TW>::from_slice(3, &buf);
+            *trint3 = t3.get3();
+        }
+        Ok(self)
+    }
+    fn unwrapn(&mut self, mut trits: TbitSliceMut<TW>) -> Result<&mut Self, Protobuf3Error> {
+        let slice = self.ctx.stream.try_advance(trits.size())?;
+        slice.copy(&trits);
+        if self.ctx.validate {
+            self.ctx.desynced = true;
+        } else {
+            self.ctx.spongos.decrypt_mut(&mut trits);
+        }
+        Ok(self)
+    }
+}
+
+fn unwrap_mask_trint3<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, IS>,
+    trint3: &mut Trint3,
+) -> Result<&'a mut MaskContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    ctx.unwrap3(trint3)
+}
+fn unwrap_mask_size<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, IS>,
+    size: &mut Size,
+) -> Result<&'a mut MaskContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    unwrap_size(ctx, size)
+}
+fn unwrap_mask_ratio<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, IS>,
+    ratio: &mut Ratio,
+) -> Result<&'a mut MaskContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    unwrap_ratio(ctx, ratio)
+}
+fn unwrap_mask_interval<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, IS>,
+    interval: &mut Interval,
+) -> Result<&'a mut MaskContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    unwrap_interval(ctx, interval)
+}
+fn unwrap_mask_flag<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, IS>,
+    present: &mut bool,
+) -> Result<&'a mut MaskContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    unwrap_flag(ctx, present)
+}
+fn unwrap_mask_bytes_body<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, IS>,
+    n: usize,
+    bytes: &mut Vec<u8>,
+) -> Result<&'a mut MaskContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    unwrap_bytes_body(ctx, n, bytes)
+}
+#[cfg(feature = "net")]
+fn unwrap_mask_octets<'a, TW, F, IS: io::IStream<TW>, const N: usize>(
+    ctx: &'a mut MaskContext<TW, F, IS>,
+    octets: &mut [u8; N],
+) -> Result<&'a mut MaskContext<TW, F, IS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    ctx.wrap3(trint3)
+    unwrap_octets(ctx, octets)
 }
-fn wrap_absorb_external_size<'a, TW, F, IS: io::IStream<TW>>(
-    ctx: &'a mut AbsorbExternalContext<TW, F, IS>,
-    size: Size,
-) -> Fallible<&'a mut AbsorbExternalContext<TW, F, IS>>
+#[cfg(feature = "net")]
+fn unwrap_mask_socket_addr<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, IS>,
+    addr: &mut std::net::SocketAddr,
+) -> Result<&'a mut MaskContext<TW, F, IS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    wrap_size(ctx, size)
+    unwrap_socket_addr(ctx, addr)
 }
-fn wrap_absorb_external_trits<'a, TW, F, IS: io::IStream<TW>>(
-    ctx: &'a mut AbsorbExternalContext<TW, F, IS>,
-    trits: TbitSlice<TW>,
-) -> Fallible<&'a mut AbsorbExternalContext<TW, F, IS>>
+fn unwrap_mask_trits<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, IS>,
+    trits: TbitSliceMut<TW>,
+) -> Result<&'a mut MaskContext<TW, F, IS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    ctx.wrapn(trits)
+    ctx.unwrapn(trits)
 }
 
-impl<'a, T: 'a, TW, F, IS: io::IStream<TW>> Absorb<&'a External<T>> for Context<TW, F, IS>
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut Trint3> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
-    Self: Absorb<External<&'a T>>,
 {
-    fn absorb(&mut self, external: &'a External<T>) -> Fallible<&mut Self> {
-        self.absorb(External(&external.0))
+    fn mask(&mut self, trint3: &'a mut Trint3) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Trint3", |ctx| {
+            Ok(unwrap_mask_trint3(ctx.as_mut(), trint3)?.as_mut())
+        })
     }
 }
 
-impl<'a, TW, F, IS: io::IStream<TW>> Absorb<External<&'a Trint3>> for Context<TW, F, IS>
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut Size> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, trint3: External<&'a Trint3>) -> Fallible<&mut Self> {
-        Ok(wrap_absorb_external_trint3(self.as_mut(), *trint3.0)?.as_mut())
+    fn mask(&mut self, size: &'a mut Size) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Size", |ctx| {
+            Ok(unwrap_mask_size(ctx.as_mut(), size)?.as_mut())
+        })
     }
 }
 
-impl<'a, TW, F, IS: io::IStream<TW>> Absorb<External<&'a Size>> for Context<TW, F, IS>
+/// String is decoded as `Mask<&str>` encodes it. Decrypted bytes out of range or
+/// not forming valid UTF-8 are rejected with `Protobuf3Error::InvalidUtf8`.
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut String> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, size: External<&'a Size>) -> Fallible<&mut Self> {
-        Ok(wrap_absorb_external_size(self.as_mut(), *size.0)?.as_mut())
+    fn mask(&mut self, s: &'a mut String) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "String", |ctx| {
+            let mut n = Size(0);
+            ctx.absorb(&mut n)?;
+            ctx.claim("string", n.0.saturating_mul(3 * BYTE_TRYTES))?;
+            let mut bytes = Vec::new();
+            if let Err(e) = unwrap_mask_bytes_body(ctx.as_mut(), n.0, &mut bytes) {
+                return match e {
+                    Protobuf3Error::OutOfRange(_) => Err(Protobuf3Error::InvalidUtf8),
+                    _ => Err(e),
+                };
+            }
+            *s = String::from_utf8(bytes).map_err(|_| Protobuf3Error::InvalidUtf8)?;
+            Ok(ctx)
+        })
     }
 }
 
-impl<TW, F, IS: io::IStream<TW>> Absorb<External<Size>> for Context<TW, F, IS>
+/// Decrypted flag trit other than `0` or `1` is rejected. Flags are formatted fields
+/// and can't be checked in validation mode.
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut bool> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, size: External<Size>) -> Fallible<&mut Self> {
-        self.absorb(&size)
+    fn mask(&mut self, flag: &'a mut bool) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "bool", |ctx| {
+            ensure!(
+                !ctx.validate,
+                "Masked formatted fields can't be validated without decryption."
+            );
+            Ok(unwrap_mask_flag(ctx.as_mut(), flag)?.as_mut())
+        })
     }
 }
 
-impl<'a, TW, F, IS: io::IStream<TW>> Absorb<External<&'a NTrytes<TW>>> for Context<TW, F, IS>
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut trinary::Trit> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, external_ntrytes: External<&'a NTrytes<TW>>) -> Fallible<&mut Self> {
-        Ok(wrap_absorb_external_trits(self.as_mut(), ((external_ntrytes.0).0).slice())?.as_mut())
+    fn mask(&mut self, trit: &'a mut trinary::Trit) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "trinary::Trit", |ctx| {
+            let mut t = Tbits::<TW>::zero(1);
+            unwrap_mask_trits(ctx.as_mut(), t.slice_mut())?;
+            *trit = t.slice().get_trit();
+            Ok(ctx)
+        })
     }
 }
 
-impl<'a, TW, F, IS: io::IStream<TW>, P> Absorb<External<&'a mss::PublicKey<TW, P>>>
-    for Context<TW, F, IS>
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut Ratio> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
-    P: mss::Parameters<TW>,
 {
-    fn absorb(&mut self, pk: External<&'a mss::PublicKey<TW, P>>) -> Fallible<&mut Self> {
-        ensure!((pk.0).tbits().size() == P::PUBLIC_KEY_SIZE);
-        Ok(wrap_absorb_external_trits(self.as_mut(), (pk.0).tbits().slice())?.as_mut())
+    fn mask(&mut self, ratio: &'a mut Ratio) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Ratio", |ctx| {
+            Ok(unwrap_mask_ratio(ctx.as_mut(), ratio)?.as_mut())
+        })
     }
 }
 
-impl<'a, TW, F, IS: io::IStream<TW>> Absorb<External<&'a ntru::PublicKey<TW, F>>>
-    for Context<TW, F, IS>
+#[cfg(feature = "net")]
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut std::net::Ipv4Addr> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, pk: External<&'a ntru::PublicKey<TW, F>>) -> Fallible<&mut Self> {
-        ensure!((pk.0).tbits().size() == ntru::PUBLIC_KEY_SIZE);
-        Ok(wrap_absorb_external_trits(self.as_mut(), (pk.0).tbits().slice())?.as_mut())
+    fn mask(&mut self, ip: &'a mut std::net::Ipv4Addr) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Ipv4Addr", |ctx| {
+            let mut octets = [0_u8; 4];
+            unwrap_mask_octets(ctx.as_mut(), &mut octets)?;
+            *ip = std::net::Ipv4Addr::from(octets);
+            Ok(ctx)
+        })
     }
 }
 
-/// This is just an external tag or hash value to-be-signed.
-impl<'a, TW, F, IS> Squeeze<&'a mut External<NTrytes<TW>>> for Context<TW, F, IS>
+#[cfg(feature = "net")]
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut std::net::Ipv6Addr> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn squeeze(&mut self, val: &'a mut External<NTrytes<TW>>) -> Fallible<&mut Self> {
-        self.spongos.squeeze(&mut ((val.0).0).slice_mut());
-        Ok(self)
+    fn mask(&mut self, ip: &'a mut std::net::Ipv6Addr) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Ipv6Addr", |ctx| {
+            let mut octets = [0_u8; 16];
+            unwrap_mask_octets(ctx.as_mut(), &mut octets)?;
+            *ip = std::net::Ipv6Addr::from(octets);
+            Ok(ctx)
+        })
     }
 }
 
-/// External values are not encoded. Squeeze and compare tag trits.
-impl<'a, TW, F, IS: io::IStream<TW>> Squeeze<&'a Mac> for Context<TW, F, IS>
+#[cfg(feature = "net")]
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut std::net::SocketAddr> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn squeeze(&mut self, val: &'a Mac) -> Fallible<&mut Self> {
-        ensure!(
-            self.spongos.squeeze_eq(self.stream.try_advance(val.0)?),
-            "Integrity is violated, bad MAC."
-        );
-        Ok(self)
+    fn mask(&mut self, addr: &'a mut std::net::SocketAddr) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "SocketAddr", |ctx| {
+            Ok(unwrap_mask_socket_addr(ctx.as_mut(), addr)?.as_mut())
+        })
     }
 }
 
-struct MaskContext<TW, F, IS> {
-    ctx: Context<TW, F, IS>,
-}
-impl<TW, F, IS> AsMut<MaskContext<TW, F, IS>> for Context<TW, F, IS> {
-    fn as_mut<'a>(&'a mut self) -> &'a mut MaskContext<TW, F, IS> {
-        unsafe {
-            mem::transmute::<&'a mut Context<TW, F, IS>, &'a mut MaskContext<TW, F, IS>>(self)
-        }
-    }
-}
-impl<TW, F, IS> AsMut<Context<TW, F, IS>> for MaskContext<TW, F, IS> {
-    fn as_mut<'a>(&'a mut self) -> &'a mut Context<TW, F, IS> {
-        unsafe {
-            mem::transmute::<&'a mut MaskContext<TW, F, IS>, &'a mut Context<TW, F, IS>>(self)
-        }
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut Interval> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn mask(&mut self, interval: &'a mut Interval) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Interval", |ctx| {
+            Ok(unwrap_mask_interval(ctx.as_mut(), interval)?.as_mut())
+        })
     }
 }
 
-impl<TW, F, IS: io::IStream<TW>> Unwrap<TW> for MaskContext<TW, F, IS>
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut Option<Trint3>> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn unwrap3(&mut self, trint3: &mut Trint3) -> Fallible<&mut Self> {
-        // 3 words should be enough to encode trint3 for any TE.
-        let mut buf = [BasicTbitWord::ZERO_WORD; 3];
-        let slice = self.ctx.stream.try_advance(3)?;
-        {
-            let mut t3 = TbitSliceMut::<TW>::from_slice_mut(3, &mut buf);
-            self.ctx.spongos.decrypt(slice, &mut t3);
-        }
-        {
-            let t3 = TbitSlice::<TW>::from_slice(3, &buf);
-            *trint3 = t3.get3();
-        }
-        Ok(self)
-    }
-    fn unwrapn(&mut self, mut trits: TbitSliceMut<TW>) -> Fallible<&mut Self> {
-        let slice = self.ctx.stream.try_advance(trits.size())?;
-        slice.copy(&trits);
-        self.ctx.spongos.decrypt_mut(&mut trits);
-        Ok(self)
+    fn mask(&mut self, x: &'a mut Option<Trint3>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Option<Trint3>", |ctx| {
+            unwrap_mask_option(ctx, x)
+        })
     }
 }
 
-fn unwrap_mask_trint3<'a, TW, F, IS: io::IStream<TW>>(
-    ctx: &'a mut MaskContext<TW, F, IS>,
-    trint3: &mut Trint3,
-) -> Fallible<&'a mut MaskContext<TW, F, IS>>
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut Option<Size>> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    ctx.unwrap3(trint3)
+    fn mask(&mut self, x: &'a mut Option<Size>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Option<Size>", |ctx| {
+            unwrap_mask_option(ctx, x)
+        })
+    }
 }
-fn unwrap_mask_size<'a, TW, F, IS: io::IStream<TW>>(
-    ctx: &'a mut MaskContext<TW, F, IS>,
-    size: &mut Size,
-) -> Fallible<&'a mut MaskContext<TW, F, IS>>
+
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut Option<Ratio>> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    unwrap_size(ctx, size)
+    fn mask(&mut self, x: &'a mut Option<Ratio>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Option<Ratio>", |ctx| {
+            unwrap_mask_option(ctx, x)
+        })
+    }
 }
-fn unwrap_mask_trits<'a, TW, F, IS: io::IStream<TW>>(
-    ctx: &'a mut MaskContext<TW, F, IS>,
-    trits: TbitSliceMut<TW>,
-) -> Fallible<&'a mut MaskContext<TW, F, IS>>
+
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut Option<Interval>> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    ctx.unwrapn(trits)
+    fn mask(&mut self, x: &'a mut Option<Interval>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Option<Interval>", |ctx| {
+            unwrap_mask_option(ctx, x)
+        })
+    }
 }
 
-impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut Trint3> for Context<TW, F, IS>
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut Option<Trytes<TW>>> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn mask(&mut self, trint3: &'a mut Trint3) -> Fallible<&mut Self> {
-        Ok(unwrap_mask_trint3(self.as_mut(), trint3)?.as_mut())
+    fn mask(&mut self, x: &'a mut Option<Trytes<TW>>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Option<Trytes>", |ctx| {
+            unwrap_mask_option(ctx, x)
+        })
     }
 }
 
-impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut Size> for Context<TW, F, IS>
+impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut NTrytes<TW>> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn mask(&mut self, size: &'a mut Size) -> Fallible<&mut Self> {
-        Ok(unwrap_mask_size(self.as_mut(), size)?.as_mut())
+    fn mask(&mut self, ntrytes: &'a mut NTrytes<TW>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "NTrytes", |ctx| {
+            Ok(unwrap_mask_trits(ctx.as_mut(), (ntrytes.0).slice_mut())?.as_mut())
+        })
     }
 }
 
-impl<'a, TW, F, IS: io::IStream<TW>> Mask<&'a mut NTrytes<TW>> for Context<TW, F, IS>
+/// Exactly `N * 3` trits are consumed, `ntryte` is reallocated if it has a wrong size.
+impl<'a, TW, F, IS: io::IStream<TW>, const N: usize> Mask<&'a mut NTryte<TW, N>>
+    for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn mask(&mut self, ntrytes: &'a mut NTrytes<TW>) -> Fallible<&mut Self> {
-        Ok(unwrap_mask_trits(self.as_mut(), (ntrytes.0).slice_mut())?.as_mut())
+    fn mask(&mut self, ntryte: &'a mut NTryte<TW, N>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "NTryte", |ctx| {
+            if (ntryte.0).size() != NTryte::<TW, N>::SIZE {
+                ntryte.0 = Tbits::<TW>::zero(NTryte::<TW, N>::SIZE);
+            }
+            Ok(unwrap_mask_trits(ctx.as_mut(), (ntryte.0).slice_mut())?.as_mut())
+        })
+    }
+}
+
+/// Unmask `N` elements of a fixed-size array one by one, the count is not encoded.
+impl<'a, TW, F, T: 'a, IS: io::IStream<TW>, const N: usize> Mask<&'a mut [T; N]>
+    for Context<TW, F, IS>
+where
+    T: UnmaskElement<Self>,
+{
+    fn mask(&mut self, xs: &'a mut [T; N]) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "[T; N]", |ctx| {
+            for x in xs.iter_mut() {
+                x.unmask_element(ctx)?;
+            }
+            Ok(ctx)
+        })
     }
 }
 
@@ -525,11 +1837,14 @@ where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn mask(&mut self, trytes: &'a mut Trytes<TW>) -> Fallible<&mut Self> {
-        let mut size = Size(0);
-        self.mask(&mut size)?;
-        trytes.0 = Tbits::<TW>::zero(size.0 * 3);
-        Ok(unwrap_mask_trits(self.as_mut(), (trytes.0).slice_mut())?.as_mut())
+    fn mask(&mut self, trytes: &'a mut Trytes<TW>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Trytes", |ctx| {
+            let mut size = Size(0);
+            ctx.mask(&mut size)?;
+            ctx.claim("trytes", size.0.saturating_mul(3))?;
+            trytes.0 = Tbits::<TW>::zero(size.0 * 3);
+            Ok(unwrap_mask_trits(ctx.as_mut(), (trytes.0).slice_mut())?.as_mut())
+        })
     }
 }
 
@@ -538,11 +1853,19 @@ where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn mask(&mut self, ntru_pk: &'a mut ntru::PublicKey<TW, F>) -> Fallible<&mut Self> {
-        ensure!(ntru_pk.tbits().size() == ntru::PUBLIC_KEY_SIZE);
-        unwrap_mask_trits(self.as_mut(), ntru_pk.tbits_mut().slice_mut())?;
-        ensure!(ntru_pk.validate(), "Unmasked NTRU public key is not valid.");
-        Ok(self)
+    fn mask(
+        &mut self,
+        ntru_pk: &'a mut ntru::PublicKey<TW, F>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "ntru::PublicKey", |ctx| {
+            ensure!(ntru_pk.tbits().size() == ntru::PUBLIC_KEY_SIZE);
+            unwrap_mask_trits(ctx.as_mut(), ntru_pk.tbits_mut().slice_mut())?;
+            ensure!(
+                ctx.validate || ntru_pk.validate(),
+                "Unmasked NTRU public key is not valid."
+            );
+            Ok(ctx)
+        })
     }
 }
 
@@ -552,9 +1875,11 @@ where
     F: PRP<TW>,
     P: mss::Parameters<TW>,
 {
-    fn mask(&mut self, mss_pk: &'a mut mss::PublicKey<TW, P>) -> Fallible<&mut Self> {
-        ensure!(mss_pk.tbits().size() == P::PUBLIC_KEY_SIZE);
-        Ok(unwrap_mask_trits(self.as_mut(), mss_pk.tbits_mut().slice_mut())?.as_mut())
+    fn mask(&mut self, mss_pk: &'a mut mss::PublicKey<TW, P>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "mss::PublicKey", |ctx| {
+            ensure!(mss_pk.tbits().size() == P::PUBLIC_KEY_SIZE);
+            Ok(unwrap_mask_trits(ctx.as_mut(), mss_pk.tbits_mut().slice_mut())?.as_mut())
+        })
     }
 }
 
@@ -580,12 +1905,12 @@ impl<TW, F, IS: io::IStream<TW>> Unwrap<TW> for SkipContext<TW, F, IS>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
-    fn unwrap3(&mut self, trint3: &mut Trint3) -> Fallible<&mut Self> {
+    fn unwrap3(&mut self, trint3: &mut Trint3) -> Result<&mut Self, Protobuf3Error> {
         let slice = self.ctx.stream.try_advance(3)?;
         *trint3 = slice.get3();
         Ok(self)
     }
-    fn unwrapn(&mut self, trits: TbitSliceMut<TW>) -> Fallible<&mut Self> {
+    fn unwrapn(&mut self, trits: TbitSliceMut<TW>) -> Result<&mut Self, Protobuf3Error> {
         let slice = self.ctx.stream.try_advance(trits.size())?;
         slice.copy(&trits);
         Ok(self)
@@ -595,7 +1920,7 @@ where
 fn unwrap_skip_trint3<'a, TW, F, IS: io::IStream<TW>>(
     ctx: &'a mut SkipContext<TW, F, IS>,
     trint3: &mut Trint3,
-) -> Fallible<&'a mut SkipContext<TW, F, IS>>
+) -> Result<&'a mut SkipContext<TW, F, IS>, Protobuf3Error>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
@@ -604,7 +1929,7 @@ where
 fn unwrap_skip_size<'a, TW, F, IS: io::IStream<TW>>(
     ctx: &'a mut SkipContext<TW, F, IS>,
     size: &mut Size,
-) -> Fallible<&'a mut SkipContext<TW, F, IS>>
+) -> Result<&'a mut SkipContext<TW, F, IS>, Protobuf3Error>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
@@ -613,18 +1938,55 @@ where
 fn unwrap_skip_trits<'a, TW, F, IS: io::IStream<TW>>(
     ctx: &'a mut SkipContext<TW, F, IS>,
     trits: TbitSliceMut<TW>,
-) -> Fallible<&'a mut SkipContext<TW, F, IS>>
+) -> Result<&'a mut SkipContext<TW, F, IS>, Protobuf3Error>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
     ctx.unwrapn(trits)
 }
+#[cfg(feature = "ed25519-dalek")]
+fn unwrap_skip_bytes_body<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut SkipContext<TW, F, IS>,
+    n: usize,
+    bytes: &mut Vec<u8>,
+) -> Result<&'a mut SkipContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: BasicTbitWord + trinary::TritWord,
+{
+    unwrap_bytes_body(ctx, n, bytes)
+}
+fn unwrap_skip_bytes<'a, TW, F, IS: io::IStream<TW>>(
+    ctx: &'a mut SkipContext<TW, F, IS>,
+    bytes: &mut Vec<u8>,
+    max: usize,
+) -> Result<&'a mut SkipContext<TW, F, IS>, Protobuf3Error>
+where
+    TW: BasicTbitWord + trinary::TritWord,
+{
+    unwrap_bytes(ctx, bytes, max)
+}
+
+impl<TW, F, IS: io::IStream<TW>> Context<TW, F, IS>
+where
+    TW: BasicTbitWord + trinary::TritWord,
+{
+    /// Skip byte string of at most `max` bytes, longer strings are rejected.
+    pub fn skip_bytes(
+        &mut self,
+        bytes: &mut Vec<u8>,
+        max: usize,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        unwrap_skip_bytes(self.as_mut(), bytes, max)?;
+        self.claim("bytes", 3 * BYTE_TRYTES * bytes.len())?;
+        Ok(self)
+    }
+}
 
 impl<'a, TW, F, IS: io::IStream<TW>> Skip<&'a mut Trint3> for Context<TW, F, IS>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
-    fn skip(&mut self, trint3: &'a mut Trint3) -> Fallible<&mut Self> {
+    fn skip(&mut self, trint3: &'a mut Trint3) -> Result<&mut Self, Protobuf3Error> {
         Ok(unwrap_skip_trint3(self.as_mut(), trint3)?.as_mut())
     }
 }
@@ -633,7 +1995,7 @@ impl<'a, TW, F, IS: io::IStream<TW>> Skip<&'a mut Size> for Context<TW, F, IS>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
-    fn skip(&mut self, size: &'a mut Size) -> Fallible<&mut Self> {
+    fn skip(&mut self, size: &'a mut Size) -> Result<&mut Self, Protobuf3Error> {
         Ok(unwrap_skip_size(self.as_mut(), size)?.as_mut())
     }
 }
@@ -642,7 +2004,7 @@ impl<'a, TW, F, IS: io::IStream<TW>> Skip<&'a mut NTrytes<TW>> for Context<TW, F
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
-    fn skip(&mut self, ntrytes: &'a mut NTrytes<TW>) -> Fallible<&mut Self> {
+    fn skip(&mut self, ntrytes: &'a mut NTrytes<TW>) -> Result<&mut Self, Protobuf3Error> {
         Ok(unwrap_skip_trits(self.as_mut(), (ntrytes.0).slice_mut())?.as_mut())
     }
 }
@@ -651,29 +2013,59 @@ impl<'a, TW, F, IS: io::IStream<TW>> Skip<&'a mut Trytes<TW>> for Context<TW, F,
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
-    fn skip(&mut self, trytes: &'a mut Trytes<TW>) -> Fallible<&mut Self> {
+    fn skip(&mut self, trytes: &'a mut Trytes<TW>) -> Result<&mut Self, Protobuf3Error> {
         let mut size = Size(0);
         self.skip(&mut size)?;
+        self.claim("trytes", size.0.saturating_mul(3))?;
         trytes.0 = Tbits::<TW>::zero(size.0 * 3);
         Ok(unwrap_skip_trits(self.as_mut(), (trytes.0).slice_mut())?.as_mut())
     }
 }
 
+/// Byte string length is limited by `SIZE_MAX` only, use `skip_bytes` to limit it explicitly.
+impl<'a, TW, F, IS: io::IStream<TW>> Skip<&'a mut Vec<u8>> for Context<TW, F, IS>
+where
+    TW: BasicTbitWord + trinary::TritWord,
+{
+    fn skip(&mut self, bytes: &'a mut Vec<u8>) -> Result<&mut Self, Protobuf3Error> {
+        self.skip_bytes(bytes, SIZE_MAX)
+    }
+}
+
 /// Commit Spongos.
 impl<TW, F, IS> Commit for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn commit(&mut self) -> Fallible<&mut Self> {
+    fn commit(&mut self) -> Result<&mut Self, Protobuf3Error> {
         self.spongos.commit();
         Ok(self)
     }
 }
 
-/// Recover public key.
-impl<'a, TW, F, IS: io::IStream<TW>, P>
-    Mssig<&'a mut mss::PublicKey<TW, P>, &'a External<NTrytes<TW>>> for Context<TW, F, IS>
+impl<TW, F, IS> Context<TW, F, IS>
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW>,
+{
+    /// Current position of Spongos in the outer state block, `0 <= pos < Spongos::RATE`.
+    pub fn spongos_position(&self) -> usize {
+        self.spongos.pos()
+    }
+
+    /// Commit Spongos state as `commit` does and return the number of tbits
+    /// the incomplete outer state block is implicitly padded with, `0` if already committed.
+    pub fn commit_padding(&mut self) -> Result<usize, Protobuf3Error> {
+        let pos = self.spongos.pos();
+        self.spongos.commit();
+        Ok(if pos == 0 { 0 } else { F::RATE - pos })
+    }
+}
+
+/// Recover public key into `apk`, the recovered key is not checked (see `mssig_recover`).
+impl<'a, 'b, TW, F, IS: io::IStream<TW>, P>
+    Mssig<&'a mut mss::PublicKey<TW, P>, &'b External<NTrytes<TW>>> for Context<TW, F, IS>
 where
     TW: IntTbitWord + SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
@@ -682,41 +2074,51 @@ where
     fn mssig(
         &mut self,
         apk: &'a mut mss::PublicKey<TW, P>,
-        hash: &'a External<NTrytes<TW>>,
-    ) -> Fallible<&mut Self> {
-        ensure!(
-            P::HASH_SIZE == ((hash.0).0).size(),
-            "Trit size of `external tryte hash[n]` to be signed with MSS must be equal {} trits.",
-            P::HASH_SIZE
-        );
-        ensure!(
-            P::PUBLIC_KEY_SIZE == apk.tbits().size(),
-            "Trit size of MSS public key must be equal {} trits.",
-            P::PUBLIC_KEY_SIZE
-        );
+        hash: &'b External<NTrytes<TW>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mssig", "mss::PublicKey", |ctx| {
+            ensure!(
+                P::HASH_SIZE == ((hash.0).0).size(),
+                "Trit size of `external tryte hash[n]` to be signed with MSS must be equal {} trits.",
+                P::HASH_SIZE
+            );
+            ensure!(
+                P::PUBLIC_KEY_SIZE == apk.tbits().size(),
+                "Trit size of MSS public key must be equal {} trits.",
+                P::PUBLIC_KEY_SIZE
+            );
 
-        let skn_slice = self.stream.try_advance(P::SKN_SIZE)?;
-        let d_skn = mss::parse_skn::<TW, P>(skn_slice);
-        ensure!(
-            d_skn.is_some(),
-            "Failed to parse MSS signature skn: {:?}.",
-            skn_slice
-        );
-        let (d, skn) = d_skn.unwrap();
-        let n = P::apath_size(d);
-        let wotsig_apath_slice = self
-            .stream
-            .try_advance(P::WotsParameters::SIGNATURE_SIZE + n)?;
-        let (wotsig, apath) = wotsig_apath_slice.split_at(P::WotsParameters::SIGNATURE_SIZE);
-        mss::recover_apk::<TW, P>(
-            d,
-            skn,
-            ((hash.0).0).slice(),
-            wotsig,
-            apath,
-            apk.tbits_mut().slice_mut(),
-        );
-        Ok(self)
+            let skn_slice = ctx.stream.try_advance(P::SKN_SIZE)?;
+            let d_skn = mss::parse_skn::<TW, P>(skn_slice);
+            ensure!(
+                d_skn.is_some(),
+                "Failed to parse MSS signature skn: {:?}.",
+                skn_slice
+            );
+            let (d, skn) = d_skn.unwrap();
+            let (min, max) = ctx.mss_height;
+            ensure!(
+                min <= d && d <= max,
+                Protobuf3Error::OutOfRange(format!(
+                    "MSS tree height {} is out of range [{}, {}].",
+                    d, min, max
+                ))
+            );
+            let n = P::apath_size(d);
+            let wotsig_apath_slice = ctx
+                .stream
+                .try_advance(P::WotsParameters::SIGNATURE_SIZE + n)?;
+            let (wotsig, apath) = wotsig_apath_slice.split_at(P::WotsParameters::SIGNATURE_SIZE);
+            mss::recover_apk::<TW, P>(
+                d,
+                skn,
+                ((hash.0).0).slice(),
+                wotsig,
+                apath,
+                apk.tbits_mut().slice_mut(),
+            );
+            Ok(ctx)
+        })
     }
 }
 
@@ -731,11 +2133,13 @@ where
         &mut self,
         pk: &'a mss::PublicKey<TW, P>,
         hash: &'a External<NTrytes<TW>>,
-    ) -> Fallible<&mut Self> {
-        let mut apk = mss::PublicKey::<TW, P>::default();
-        self.mssig(&mut apk, hash)?;
-        ensure!(apk == *pk, "Authenticity is violated, bad signature.");
-        Ok(self)
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mssig", "mss::PublicKey", |ctx| {
+            let mut apk = mss::PublicKey::<TW, P>::default();
+            ctx.mssig(&mut apk, hash)?;
+            ensure!(ctx.desynced || apk == *pk, Protobuf3Error::MssVerify);
+            Ok(ctx)
+        })
     }
 }
 
@@ -750,9 +2154,11 @@ where
         &mut self,
         apk: &'a mut mss::PublicKey<TW, P>,
         _hash: MssHashSig,
-    ) -> Fallible<&mut Self> {
-        let mut hash = External(NTrytes::<TW>(Tbits::<TW>::zero(P::HASH_SIZE)));
-        self.squeeze(&mut hash)?.commit()?.mssig(apk, &hash)
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mssig", "mss::PublicKey", |ctx| {
+            let mut hash = External(NTrytes::<TW>(Tbits::<TW>::zero(P::HASH_SIZE)));
+            ctx.squeeze(&mut hash)?.commit()?.mssig(apk, &hash)
+        })
     }
 }
 
@@ -763,9 +2169,66 @@ where
     F: PRP<TW>,
     P: mss::Parameters<TW>,
 {
-    fn mssig(&mut self, pk: &'a mss::PublicKey<TW, P>, _hash: MssHashSig) -> Fallible<&mut Self> {
-        let mut hash = External(NTrytes::<TW>(Tbits::<TW>::zero(P::HASH_SIZE)));
-        self.squeeze(&mut hash)?.commit()?.mssig(pk, &hash)
+    fn mssig(
+        &mut self,
+        pk: &'a mss::PublicKey<TW, P>,
+        _hash: MssHashSig,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mssig", "mss::PublicKey", |ctx| {
+            let mut hash = External(NTrytes::<TW>(Tbits::<TW>::zero(P::HASH_SIZE)));
+            ctx.squeeze(&mut hash)?.commit()?.mssig(pk, &hash)
+        })
+    }
+}
+
+impl<TW, F, IS: io::IStream<TW>> Context<TW, F, IS>
+where
+    TW: IntTbitWord + SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    /// Verify MSS signature of `hash` (`&External<NTrytes>` or `MssHashSig`) by recovering
+    /// the signer's public key and return the recovered key, eg. to be pinned on first use.
+    ///
+    /// The recovered key is not checked against anything and a corrupted signature just
+    /// recovers a different key, use `Mssig<&mss::PublicKey, Hash>` to check a known key.
+    pub fn mssig_recover<P, H>(&mut self, hash: H) -> Result<mss::PublicKey<TW, P>, Protobuf3Error>
+    where
+        P: mss::Parameters<TW>,
+        for<'a> Self: Mssig<&'a mut mss::PublicKey<TW, P>, H>,
+    {
+        let mut apk = mss::PublicKey::<TW, P>::default();
+        self.mssig(&mut apk, hash)?;
+        Ok(apk)
+    }
+}
+
+/// Verify the signature with the signer's public key `pk`.
+#[cfg(feature = "ed25519-dalek")]
+impl<'a, TW, F, IS: io::IStream<TW>> Ed25519Sig<&'a Ed25519PublicKey, Ed25519HashSig>
+    for Context<TW, F, IS>
+where
+    TW: IntTbitWord + SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn ed25519sig(
+        &mut self,
+        pk: &'a Ed25519PublicKey,
+        _hash: Ed25519HashSig,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "ed25519sig", "Ed25519PublicKey", |ctx| {
+            use ed25519_dalek::Verifier;
+
+            let mut hash = [0_u8; ED25519_HASH_BYTES];
+            ctx.spongos.squeeze_bytes(&mut hash);
+            ctx.spongos.commit();
+            let mut bytes = Vec::with_capacity(ed25519_dalek::SIGNATURE_LENGTH);
+            unwrap_skip_bytes_body(ctx.as_mut(), ed25519_dalek::SIGNATURE_LENGTH, &mut bytes)?;
+            let verified = Ed25519Signature::from_bytes(&bytes)
+                .and_then(|signature| pk.verify(&hash, &signature))
+                .is_ok();
+            ensure!(ctx.desynced || verified, Protobuf3Error::Ed25519Verify);
+            Ok(ctx)
+        })
     }
 }
 
@@ -779,15 +2242,21 @@ where
         &mut self,
         sk: &'a ntru::PrivateKey<TW, F>,
         secret: &'a mut NTrytes<TW>,
-    ) -> Fallible<&mut Self> {
-        //TODO: ensure!(ntru::KEY_SIZE == (secret.0).size(), "Trit size of `external tryte secret[n]` to be encapsulated with NTRU must be equal {} trits.", ntru::KEY_SIZE);
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "ntrukem", "ntru::PrivateKey", |ctx| {
+            //TODO: ensure!(ntru::KEY_SIZE == (secret.0).size(), "Trit size of `external tryte secret[n]` to be encapsulated with NTRU must be equal {} trits.", ntru::KEY_SIZE);
 
-        let ekey_slice = self.stream.try_advance(ntru::EKEY_SIZE)?;
-        ensure!(
-            sk.decrypt_with_spongos(&mut self.spongos, ekey_slice, (secret.0).slice_mut()),
-            "Failed to decapsulate secret."
-        );
-        Ok(self)
+            let ekey_slice = ctx.stream.try_advance(ntru::EKEY_SIZE)?;
+            ensure!(
+                ntru::is_valid_ekey(ekey_slice),
+                Protobuf3Error::NtruMalformedCapsule
+            );
+            ensure!(
+                sk.decrypt_with_spongos(&mut ctx.spongos, ekey_slice, (secret.0).slice_mut()),
+                Protobuf3Error::NtruDecapsulation
+            );
+            Ok(ctx)
+        })
     }
 }
 
@@ -795,13 +2264,100 @@ impl<C, TW, F, IS: io::IStream<TW>> Fork<C> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW> + Clone,
-    C: for<'a> FnMut(&'a mut Self) -> Fallible<&'a mut Self>,
+    C: for<'a> FnMut(&'a mut Self) -> Result<&'a mut Self, Protobuf3Error>,
+{
+    fn fork(&mut self, mut cont: C) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "fork", |ctx| {
+            let saved_fork = ctx.spongos.fork();
+            let desynced = ctx.desynced;
+            cont(ctx)?;
+            #[cfg(feature = "metrics")]
+            let permutations = ctx.spongos.permutation_count();
+            ctx.spongos = saved_fork;
+            #[cfg(feature = "metrics")]
+            ctx.spongos.set_permutation_count(permutations);
+            ctx.desynced = desynced;
+            Ok(ctx)
+        })
+    }
+}
+
+impl<TW, F, IS: io::IStream<TW>> Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW> + Clone,
 {
-    fn fork(&mut self, mut cont: C) -> Fallible<&mut Self> {
+    /// Fork Spongos state and continue processing `cont` commands as `fork` does,
+    /// but return the resulting forked Spongos state instead of discarding it,
+    /// eg. to squeeze a per-fork commitment. The current Spongos state is not affected.
+    pub fn fork_capture<C>(&mut self, mut cont: C) -> Result<Spongos<TW, F>, Protobuf3Error>
+    where
+        C: for<'a> FnMut(&'a mut Self) -> Result<&'a mut Self, Protobuf3Error>,
+    {
         let saved_fork = self.spongos.fork();
+        let desynced = self.desynced;
         cont(self)?;
-        self.spongos = saved_fork;
-        Ok(self)
+        self.desynced = desynced;
+        #[cfg(feature = "metrics")]
+        let permutations = self.spongos.permutation_count();
+        let forked = mem::replace(&mut self.spongos, saved_fork);
+        #[cfg(feature = "metrics")]
+        self.spongos.set_permutation_count(permutations);
+        Ok(forked)
+    }
+}
+
+impl<TW, F, IS: io::IStream<TW>> Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW> + Clone,
+{
+    /// Process items with `value_handle` as `repeated` does, but the handler can pause
+    /// processing after an item. In that case the returned `ResumeToken` captures Spongos
+    /// state and the number of remaining items and `None` is returned once all items are done.
+    ///
+    /// To continue pass the same `values_iter` (its processed items are skipped) and the token,
+    /// Spongos state is restored from the token, so the context can be a new one,
+    /// eg. with a different input buffer.
+    pub fn repeated_resumable<I, C>(
+        &mut self,
+        values_iter: I,
+        resume: Option<ResumeToken<TW, F>>,
+        mut value_handle: C,
+    ) -> Result<Option<ResumeToken<TW, F>>, Protobuf3Error>
+    where
+        I: iter::ExactSizeIterator,
+        C: for<'a> FnMut(
+            &'a mut Self,
+            <I as iter::Iterator>::Item,
+        ) -> Result<RepeatedStep, Protobuf3Error>,
+    {
+        let total = values_iter.len();
+        let mut processed = 0;
+        if let Some(token) = resume {
+            ensure!(
+                token.processed + token.remaining == total,
+                "Resumed {} + {} items, but {} items are given.",
+                token.processed,
+                token.remaining,
+                total
+            );
+            self.spongos = token.spongos;
+            processed = token.processed;
+        }
+
+        for item in values_iter.skip(processed) {
+            let step = value_handle(self, item)?;
+            processed += 1;
+            if step == RepeatedStep::Pause && processed < total {
+                return Ok(Some(ResumeToken {
+                    spongos: self.spongos.fork(),
+                    processed,
+                    remaining: total - processed,
+                }));
+            }
+        }
+        Ok(None)
     }
 }
 
@@ -809,9 +2365,9 @@ impl<C, TW, F, IS: io::IStream<TW>> Repeated<Size, C> for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
-    C: for<'a> FnMut(&'a mut Self) -> Fallible<&'a mut Self>,
+    C: for<'a> FnMut(&'a mut Self) -> Result<&'a mut Self, Protobuf3Error>,
 {
-    fn repeated(&mut self, n: Size, mut value_handle: C) -> Fallible<&mut Self> {
+    fn repeated(&mut self, n: Size, mut value_handle: C) -> Result<&mut Self, Protobuf3Error> {
         for _ in 0..(n.0) {
             value_handle(self)?;
         }
@@ -819,26 +2375,51 @@ where
     }
 }
 
+impl<C, TW, F, IS: io::IStream<TW>> RepeatedN<C> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+    C: for<'a> FnMut(&'a mut Self, usize) -> Result<&'a mut Self, Protobuf3Error>,
+{
+    fn repeated_n(&mut self, n: usize, mut value_handle: C) -> Result<&mut Self, Protobuf3Error> {
+        ensure!(
+            n <= self.repeated_max,
+            Protobuf3Error::OutOfRange(format!(
+                "Number of repetitions {} exceeds maximum {}.",
+                n, self.repeated_max
+            ))
+        );
+        for i in 0..n {
+            value_handle(self, i)?;
+        }
+        Ok(self)
+    }
+}
+
 impl<'a, TW, F, T: 'a + AbsorbFallback<TW, F>, IS: io::IStream<TW>> Absorb<&'a mut Fallback<T>>
     for Context<TW, F, IS>
 {
-    fn absorb(&mut self, val: &'a mut Fallback<T>) -> Fallible<&mut Self> {
-        (val.0).unwrap_absorb(self)?;
-        Ok(self)
+    fn absorb(&mut self, val: &'a mut Fallback<T>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Fallback<T>", |ctx| {
+            (val.0).unwrap_absorb(ctx)?;
+            Ok(ctx)
+        })
     }
 }
 impl<'a, TW, F, T: 'a + AbsorbExternalFallback<TW, F>, IS: io::IStream<TW>>
     Absorb<External<Fallback<&'a T>>> for Context<TW, F, IS>
 {
-    fn absorb(&mut self, val: External<Fallback<&'a T>>) -> Fallible<&mut Self> {
-        ((val.0).0).unwrap_absorb_external(self)?;
-        Ok(self)
+    fn absorb(&mut self, val: External<Fallback<&'a T>>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<Fallback<T>>", |ctx| {
+            ((val.0).0).unwrap_absorb_external(ctx)?;
+            Ok(ctx)
+        })
     }
 }
 impl<'a, TW, F, T: 'a + SkipFallback<TW, F>, IS: io::IStream<TW>> Skip<&'a mut Fallback<T>>
     for Context<TW, F, IS>
 {
-    fn skip(&mut self, val: &'a mut Fallback<T>) -> Fallible<&mut Self> {
+    fn skip(&mut self, val: &'a mut Fallback<T>) -> Result<&mut Self, Protobuf3Error> {
         (val.0).unwrap_skip(self)?;
         Ok(self)
     }
@@ -850,17 +2431,41 @@ where
     TW: SpongosTbitWord,
     F: PRP<TW>,
 {
-    fn join(&mut self, store: &S, link: &'a mut L) -> Fallible<&mut Self> {
-        //TODO: Move `skip` out of `join` and `skip` links explicitly.
-        // That way it's easier to handle the case when the link is not found
-        // and calling function can try to fetch and parse message for the link.
-        //TODO: Implement a strategy (depth of recursion or max number of retries)
-        // for such cases.
-        link.unwrap_skip(self)?;
-        //TODO: Return and use info.
-        let (mut s, _i) = store.lookup(link)?;
-        self.spongos.join(&mut s);
-        Ok(self)
+    fn join(&mut self, store: &S, link: &'a mut L) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "join", std::any::type_name::<L>(), |ctx| {
+            //TODO: Move `skip` out of `join` and `skip` links explicitly.
+            // That way it's easier to handle the case when the link is not found
+            // and calling function can try to fetch and parse message for the link.
+            //TODO: Implement a strategy (depth of recursion or max number of retries)
+            // for such cases.
+            link.unwrap_skip(ctx)?;
+            //TODO: Return and use info.
+            let (mut s, _i) = store.lookup(link)?;
+            ctx.spongos.join(&mut s);
+            Ok(ctx)
+        })
+    }
+}
+
+impl<'a, TW, F, L: SkipFallback<TW, F>, S: LinkStore<TW, F, L>, IS: io::IStream<TW>>
+    JoinVerified<&'a mut L, &S, &NTrytes<TW>> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW> + Clone,
+{
+    fn join_verified(
+        &mut self,
+        store: &S,
+        link: &'a mut L,
+        expected_mac: &NTrytes<TW>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "join_verified", std::any::type_name::<L>(), |ctx| {
+            link.unwrap_skip(ctx)?;
+            let (mut s, _i) = store.lookup(link)?;
+            check_link_commitment(&s, expected_mac)?;
+            ctx.spongos.join(&mut s);
+            Ok(ctx)
+        })
     }
 }
 
@@ -868,7 +2473,7 @@ where
 impl<'a, L, S: LinkStore<L>, IS: io::IStream<TW>> Join<&'a mut L, &S> for Context<TW, F, IS> where
     Self: Skip<&'a mut L>,
 {
-    fn join(&mut self, store: &S, link: &'a mut L) -> Fallible<&mut Self> {
+    fn join(&mut self, store: &S, link: &'a mut L) -> Result<&mut Self, Protobuf3Error> {
         self.skip(link)?;
         let (mut s, i) = store.lookup(link)?;
         self.spongos.join(&mut s);
@@ -877,17 +2482,76 @@ impl<'a, L, S: LinkStore<L>, IS: io::IStream<TW>> Join<&'a mut L, &S> for Contex
 }
  */
 
+impl<TW, F, IS> Guard for Context<TW, F, IS> {
+    fn guard(&mut self, cond: bool, err: &'static str) -> Result<&mut Self, Protobuf3Error> {
+        ensure!(cond, "{}", err);
+        Ok(self)
+    }
+}
+
+/// The field is unmasked into a clone of `field` so that its preset size (eg. of `NTrytes`)
+/// is kept and `field` is untouched if the tag doesn't match, the clone is wiped then.
+/// The tag can't be checked in validation mode, so the command fails instead of returning
+/// an unauthenticated value.
+impl<'a, TW, F, IS: io::IStream<TW>, T: Clone + Wipe> SealedMask<&'a mut T> for Context<TW, F, IS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+    for<'b> Self: Mask<&'b mut T>,
+{
+    fn sealed_mask(&mut self, field: &'a mut T) -> Result<&mut Self, Protobuf3Error> {
+        ensure!(
+            !self.validate && !self.desynced,
+            "Sealed mask tag can't be checked in validation mode."
+        );
+        let mut plain = field.clone();
+        let res = self
+            .mask(&mut plain)
+            .and_then(|ctx| ctx.commit())
+            .and_then(|ctx| ctx.squeeze(&Mac(SEALED_MASK_TAG_SIZE)))
+            .map(|_| ());
+        if let Err(e) = res {
+            plain.wipe();
+            return Err(e);
+        }
+        *field = plain;
+        Ok(self)
+    }
+}
+
+/// Padding must be zero, otherwise the message would be malleable as padding is not
+/// absorbed into Spongos.
+impl<TW, F, IS: io::IStream<TW>> PadTo for Context<TW, F, IS>
+where
+    TW: BasicTbitWord,
+{
+    fn pad_to(&mut self, total: usize) -> Result<&mut Self, Protobuf3Error> {
+        let position = self.position();
+        ensure!(
+            position <= total,
+            Protobuf3Error::PadOverflow { position, total }
+        );
+        let n = total - position;
+        let padding = self.stream.try_advance(n)?;
+        ensure!(
+            padding.equals(&Tbits::<TW>::zero(n).slice()),
+            Protobuf3Error::OutOfRange("Padding is not zero.".into())
+        );
+        Ok(self)
+    }
+}
+
 impl<TW, F, IS: io::IStream<TW>> Dump for Context<TW, F, IS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
 {
-    fn dump<'a>(&mut self, args: std::fmt::Arguments<'a>) -> Fallible<&mut Self> {
-        println!(
+    fn dump<'a>(&mut self, args: std::fmt::Arguments<'a>) -> Result<&mut Self, Protobuf3Error> {
+        self.dump_sink.dump(format_args!(
             "{}: istream=[{}] spongos=[{:?}]",
             args,
             self.stream.dump(),
             self.spongos
-        );
+        ));
         Ok(self)
     }
 }
@@ -1,9 +1,9 @@
 //! Implementation of command traits for wrapping.
 
-use failure::{ensure, Fallible};
 use std::convert::AsMut;
 use std::iter;
 use std::mem;
+use std::ops::Range;
 
 use iota_streams_core::{
     prng,
@@ -18,6 +18,7 @@ use iota_streams_core_mss::signature::mss;
 use iota_streams_core_ntru::key_encapsulation::ntru;
 
 use crate::command::*;
+use crate::error::Protobuf3Error;
 use crate::io;
 use crate::types::*;
 
@@ -25,32 +26,442 @@ use crate::types::*;
 pub struct Context<TW, F, OS> {
     pub spongos: Spongos<TW, F>,
     pub stream: OS,
+    /// Position of `stream` when the context was created, `reset` rewinds the stream back to it.
+    start: usize,
+    /// Output sink of `Dump` command.
+    dump_sink: DumpSink,
+    /// Regions recorded since `track_regions`.
+    regions: Option<RegionTracker<TW>>,
+    /// Source of randomness of `Ntrukem` command with a public key only, see `set_rng`.
+    rng: Option<Box<dyn prng::TbitsRng<TW> + Send>>,
 }
 
 impl<TW, F, OS> Context<TW, F, OS>
 where
     TW: SpongosTbitWord,
     F: PRP<TW> + Default,
+    OS: io::OStream<TW>,
 {
     pub fn new(stream: OS) -> Self {
         Self {
             spongos: Spongos::<TW, F>::init(),
+            start: stream.position(),
             stream: stream,
+            dump_sink: DumpSink::default(),
+            regions: None,
+            rng: None,
         }
     }
 }
 
+impl<TW, F, OS> Context<TW, F, OS>
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW>,
+{
+    /// Duplex mode of Spongos, `PRP::MODE` by default.
+    pub fn spongos_mode(&self) -> SpongosMode {
+        self.spongos.mode()
+    }
+
+    /// Select overwrite or duplex-XOR mode of Spongos used by the following commands,
+    /// eg. to match an external Spongos configuration. The mode is kept by `reset`.
+    pub fn set_spongos_mode(&mut self, mode: SpongosMode) -> &mut Self {
+        self.spongos.set_mode(mode);
+        self
+    }
+
+    /// Number of Spongos permutations performed since `new` or `reset`, including the ones
+    /// in `Fork` continuations, eg. to find message formats that commit too often.
+    #[cfg(feature = "metrics")]
+    pub fn permutation_count(&self) -> u64 {
+        self.spongos.permutation_count()
+    }
+}
+
+impl<TW, F, OS> Context<TW, F, OS> {
+    /// Redirect output of `Dump` command into `sink`.
+    pub fn set_dump_sink<S>(&mut self, sink: S)
+    where
+        S: FnMut(std::fmt::Arguments) + Send + 'static,
+    {
+        self.dump_sink = DumpSink::new(sink);
+    }
+
+    /// Draw NTRU encapsulation randomness of the following `ntrukem(&pk, &secret)` commands
+    /// from `rng` instead of deriving it with `prng::Prng` and a nonce, eg. from a hardware RNG
+    /// or from a deterministic `prng::SpongosRng` making wrapped messages reproducible.
+    pub fn set_rng<R>(&mut self, rng: R)
+    where
+        R: prng::TbitsRng<TW> + Send + 'static,
+    {
+        self.rng = Some(Box::new(rng));
+    }
+}
+
+/// Region of the output stream or Spongos operation recorded by the wrap context,
+/// see `Context::track_regions`. Stream ranges are relative to the stream position
+/// the tracking was started at.
+#[derive(Clone)]
+pub enum Region<TW> {
+    /// Absorbed trits.
+    Absorb(Range<usize>),
+    /// External trits, absorbed but not encoded.
+    External(Tbits<TW>),
+    /// Masked trits.
+    Mask(Range<usize>),
+    /// Skipped trits.
+    Skip(Range<usize>),
+    /// `Mac` squeezed into the stream.
+    Mac(Range<usize>),
+    /// Number of trits squeezed into an external buffer.
+    SqueezeExternal(usize),
+    /// Spongos commit.
+    Commit,
+    /// Spongos state is forked, it is restored at the matching `Unfork`.
+    Fork,
+    /// End of the fork.
+    Unfork,
+    /// Command that can't be replayed, eg. `mssig` or `join`.
+    Opaque(&'static str),
+}
+
+impl<TW> Region<TW> {
+    /// Extend the region with the adjacent `next` one of the same kind.
+    fn extend(&mut self, next: &Region<TW>) -> bool {
+        match (self, next) {
+            (Region::Absorb(r), Region::Absorb(s))
+            | (Region::Mask(r), Region::Mask(s))
+            | (Region::Skip(r), Region::Skip(s))
+                if r.end == s.start =>
+            {
+                r.end = s.end;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+struct RegionTracker<TW> {
+    pos: usize,
+    regions: Vec<Region<TW>>,
+}
+
+impl<TW, F, OS> Context<TW, F, OS> {
+    /// Start recording regions of the output stream and Spongos operations,
+    /// previously recorded regions are discarded.
+    ///
+    /// The recorded regions allow to re-mask the wrapped message with a different
+    /// Spongos state, see `remask`.
+    pub fn track_regions(&mut self) {
+        self.regions = Some(RegionTracker {
+            pos: 0,
+            regions: Vec::new(),
+        });
+    }
+
+    /// Stop recording and return the regions recorded since `track_regions`.
+    pub fn take_regions(&mut self) -> Option<Vec<Region<TW>>> {
+        self.regions.take().map(|t| t.regions)
+    }
+
+    /// Record `n` trits of the stream, `region` is only evaluated when tracking.
+    fn track<R>(&mut self, n: usize, region: R)
+    where
+        R: FnOnce(Range<usize>) -> Region<TW>,
+    {
+        if let Some(t) = self.regions.as_mut() {
+            let next = region(t.pos..t.pos + n);
+            t.pos += n;
+            let extended = match t.regions.last_mut() {
+                Some(last) => last.extend(&next),
+                None => false,
+            };
+            if !extended {
+                t.regions.push(next);
+            }
+        }
+    }
+}
+
+/// Re-mask a message wrapped with `regions` recorded (see `Context::track_regions`)
+/// in place: masked regions are decrypted with the `old` Spongos state and encrypted
+/// with the `new` one, `Mac`s are checked with the `old` state and squeezed with
+/// the `new` one. Absorbed and skipped trits are left untouched.
+///
+/// `buf` must start at the stream position the tracking was started at, `old` and
+/// `new` are Spongos states at that position, eg. after absorbing the old and new keys.
+pub fn remask<TW, F>(
+    mut buf: TbitSliceMut<TW>,
+    regions: &[Region<TW>],
+    mut old: Spongos<TW, F>,
+    mut new: Spongos<TW, F>,
+) -> Result<(), Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW> + Clone,
+{
+    // Fail before the buffer is modified.
+    for region in regions {
+        if let Region::Opaque(cmd) = region {
+            bail!(Protobuf3Error::NotRemaskable(cmd));
+        }
+    }
+
+    let mut pos = 0;
+    let mut forks = Vec::new();
+    for region in regions {
+        let mut advance = |r: &Range<usize>| -> Result<TbitSliceMut<TW>, Protobuf3Error> {
+            ensure!(
+                pos <= r.start && r.start <= r.end && r.end - pos <= buf.size(),
+                "Region {:?} is out of the buffer.",
+                r
+            );
+            buf.advance(r.start - pos);
+            pos = r.end;
+            Ok(buf.advance(r.end - r.start))
+        };
+        match region {
+            Region::Absorb(r) => {
+                let slice = advance(r)?;
+                let trits = unsafe { slice.as_const() };
+                old.absorb(trits);
+                new.absorb(trits);
+            }
+            Region::External(trits) => {
+                old.absorb(trits.slice());
+                new.absorb(trits.slice());
+            }
+            Region::Mask(r) => {
+                let mut slice = advance(r)?;
+                let mut plain = Tbits::from_slice(unsafe { slice.as_const() });
+                old.decrypt_mut(&mut plain.slice_mut());
+                new.encrypt(plain.slice(), &mut slice);
+                plain.wipe();
+            }
+            Region::Skip(r) => {
+                advance(r)?;
+            }
+            Region::Mac(r) => {
+                let mut slice = advance(r)?;
+                ensure!(
+                    old.squeeze_eq(unsafe { slice.as_const() }),
+                    Protobuf3Error::BadMac
+                );
+                new.squeeze(&mut slice);
+            }
+            Region::SqueezeExternal(n) => {
+                old.squeeze_tbits(*n);
+                new.squeeze_tbits(*n);
+            }
+            Region::Commit => {
+                old.commit();
+                new.commit();
+            }
+            Region::Fork => {
+                forks.push((old.fork(), new.fork()));
+            }
+            Region::Unfork => match forks.pop() {
+                Some((o, n)) => {
+                    old = o;
+                    new = n;
+                }
+                None => bail!("Unbalanced fork regions."),
+            },
+            Region::Opaque(cmd) => bail!(Protobuf3Error::NotRemaskable(cmd)),
+        }
+    }
+    Ok(())
+}
+
+/// Wrap context together with the key material used by signing and encapsulation
+/// commands, see `ContextBuilder`.
+pub struct KeyedContext<'k, TW, F, G, P, OS>
+where
+    P: mss::Parameters<TW>,
+{
+    pub ctx: Context<TW, F, OS>,
+    pub prng: Option<&'k prng::Prng<TW, G>>,
+    pub secret_key: Option<&'k mut mss::PrivateKey<TW, P>>,
+    pub trusted_pubkeys: Vec<&'k ntru::PublicKey<TW, F>>,
+}
+
+impl<'k, TW, F, G, P, OS: io::OStream<TW>> KeyedContext<'k, TW, F, G, P, OS>
+where
+    TW: IntTbitWord + SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+    G: PRP<TW> + Clone + Default,
+    P: mss::Parameters<TW>,
+{
+    /// Reset the inner context for the next message, key material is kept.
+    pub fn reset(&mut self) -> Result<&mut Self, Protobuf3Error> {
+        self.ctx.reset()?;
+        Ok(self)
+    }
+
+    /// Encapsulate `secret` with each of the trusted public keys in order,
+    /// each capsule is wrapped in a separate fork as in `Keyload` message.
+    pub fn encapsulate(
+        &mut self,
+        nonce: &Tbits<TW>,
+        secret: &NTrytes<TW>,
+    ) -> Result<&mut Self, Protobuf3Error>
+    where
+        F: Clone,
+    {
+        let prng = match self.prng {
+            Some(prng) => prng,
+            None => bail!("PRNG is not set."),
+        };
+        for pk in self.trusted_pubkeys.iter() {
+            self.ctx
+                .fork(|ctx| ctx.ntrukem((*pk, prng, nonce), secret))?;
+        }
+        Ok(self)
+    }
+
+    /// Encapsulate `secret` with each of the trusted public keys as `encapsulate` does,
+    /// but with randomness drawn from the context RNG (see `ContextBuilder::rng`) instead
+    /// of the PRNG, so no nonce is needed.
+    pub fn encapsulate_random(&mut self, secret: &NTrytes<TW>) -> Result<&mut Self, Protobuf3Error>
+    where
+        F: Clone,
+    {
+        for pk in self.trusted_pubkeys.iter() {
+            self.ctx.fork(|ctx| ctx.ntrukem(*pk, secret))?;
+        }
+        Ok(self)
+    }
+
+    /// Commit and sign the committed hash with the secret key (see `MssHashSig`).
+    pub fn sign(&mut self) -> Result<&mut Self, Protobuf3Error> {
+        match self.secret_key {
+            Some(ref mut sk) => {
+                self.ctx.commit()?.mssig(&mut **sk, MssHashSig)?;
+            }
+            None => bail!("Secret key is not set."),
+        }
+        Ok(self)
+    }
+}
+
+/// Builder of `KeyedContext`.
+/// The output buffer is required, Spongos state is initialized with zero trits
+/// if not set, PRNG or RNG is required if trusted public keys are set.
+pub struct ContextBuilder<'k, TW, F, G, P, OS>
+where
+    P: mss::Parameters<TW>,
+{
+    stream: Option<OS>,
+    spongos: Option<Spongos<TW, F>>,
+    prng: Option<&'k prng::Prng<TW, G>>,
+    rng: Option<Box<dyn prng::TbitsRng<TW> + Send>>,
+    secret_key: Option<&'k mut mss::PrivateKey<TW, P>>,
+    trusted_pubkeys: Vec<&'k ntru::PublicKey<TW, F>>,
+}
+
+impl<'k, TW, F, G, P, OS> Default for ContextBuilder<'k, TW, F, G, P, OS>
+where
+    P: mss::Parameters<TW>,
+{
+    fn default() -> Self {
+        Self {
+            stream: None,
+            spongos: None,
+            prng: None,
+            rng: None,
+            secret_key: None,
+            trusted_pubkeys: Vec::new(),
+        }
+    }
+}
+
+impl<'k, TW, F, G, P, OS> ContextBuilder<'k, TW, F, G, P, OS>
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW> + Default,
+    P: mss::Parameters<TW>,
+    OS: io::OStream<TW>,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Output buffer.
+    pub fn buffer(mut self, stream: OS) -> Self {
+        self.stream = Some(stream);
+        self
+    }
+
+    /// Initial Spongos state, eg. joined with a linked message.
+    pub fn spongos(mut self, spongos: Spongos<TW, F>) -> Self {
+        self.spongos = Some(spongos);
+        self
+    }
+
+    /// PRNG used for NTRU encapsulation.
+    pub fn prng(mut self, prng: &'k prng::Prng<TW, G>) -> Self {
+        self.prng = Some(prng);
+        self
+    }
+
+    /// RNG used for NTRU encapsulation by `KeyedContext::encapsulate_random`,
+    /// see `Context::set_rng`.
+    pub fn rng<R>(mut self, rng: R) -> Self
+    where
+        R: prng::TbitsRng<TW> + Send + 'static,
+    {
+        self.rng = Some(Box::new(rng));
+        self
+    }
+
+    /// MSS private key used for signing.
+    pub fn secret_key(mut self, sk: &'k mut mss::PrivateKey<TW, P>) -> Self {
+        self.secret_key = Some(sk);
+        self
+    }
+
+    /// NTRU public key the secret is encapsulated with.
+    pub fn trusted_pubkey(mut self, pk: &'k ntru::PublicKey<TW, F>) -> Self {
+        self.trusted_pubkeys.push(pk);
+        self
+    }
+
+    pub fn build(self) -> Result<KeyedContext<'k, TW, F, G, P, OS>, Protobuf3Error> {
+        let stream = match self.stream {
+            Some(stream) => stream,
+            None => bail!("Output buffer is not set."),
+        };
+        ensure!(
+            self.trusted_pubkeys.is_empty() || self.prng.is_some() || self.rng.is_some(),
+            "PRNG or RNG is required to encapsulate secret with trusted public keys."
+        );
+        let mut ctx = Context::new(stream);
+        if let Some(spongos) = self.spongos {
+            ctx.spongos = spongos;
+        }
+        ctx.rng = self.rng;
+        Ok(KeyedContext {
+            ctx,
+            prng: self.prng,
+            secret_key: self.secret_key,
+            trusted_pubkeys: self.trusted_pubkeys,
+        })
+    }
+}
+
 /// Helper trait for wrapping (encoding/absorbing) trint3s.
 pub(crate) trait Wrap<TW> {
-    fn wrap3(&mut self, trint3: Trint3) -> Fallible<&mut Self>;
-    fn wrapn(&mut self, trits: TbitSlice<TW>) -> Fallible<&mut Self>;
+    fn wrap3(&mut self, trint3: Trint3) -> Result<&mut Self, Protobuf3Error>;
+    fn wrapn(&mut self, trits: TbitSlice<TW>) -> Result<&mut Self, Protobuf3Error>;
 }
 
 /// Helper function for wrapping (encoding/absorbing) size values.
 pub(crate) fn wrap_size<'a, TW, Ctx: Wrap<TW>>(
     ctx: &'a mut Ctx,
     size: Size,
-) -> Fallible<&'a mut Ctx> where
+) -> Result<&'a mut Ctx, Protobuf3Error> where
 {
     let d = size_trytes(size.0);
     ctx.wrap3(Trint3(d as i8))?;
@@ -64,6 +475,122 @@ pub(crate) fn wrap_size<'a, TW, Ctx: Wrap<TW>>(
     Ok(ctx)
 }
 
+/// Helper function for wrapping (encoding/absorbing) fixed-width `u64` values.
+pub(crate) fn wrap_u64<'a, TW, Ctx: Wrap<TW>>(
+    ctx: &'a mut Ctx,
+    u: u64,
+) -> Result<&'a mut Ctx, Protobuf3Error> {
+    let mut n = u;
+    for _ in 0..U64_TRYTES {
+        let (r, q) = trinary::mods3_u64(n);
+        ctx.wrap3(r)?;
+        n = q;
+    }
+    Ok(ctx)
+}
+
+/// Helper function for wrapping (encoding/absorbing) byte strings.
+/// Length is encoded as `size_t` followed by the bytes, each byte is encoded with `BYTE_TRYTES` trytes.
+pub(crate) fn wrap_bytes<'a, TW, Ctx: Wrap<TW>>(
+    ctx: &'a mut Ctx,
+    bytes: &[u8],
+) -> Result<&'a mut Ctx, Protobuf3Error> {
+    wrap_size(ctx, Size(bytes.len()))?;
+    wrap_bytes_body(ctx, bytes)
+}
+
+/// Helper function for wrapping (encoding/absorbing) bytes without the length.
+pub(crate) fn wrap_bytes_body<'a, TW, Ctx: Wrap<TW>>(
+    ctx: &'a mut Ctx,
+    bytes: &[u8],
+) -> Result<&'a mut Ctx, Protobuf3Error> {
+    for b in bytes {
+        let mut n = *b as usize;
+        for _ in 0..BYTE_TRYTES {
+            let (r, q) = trinary::mods3_usize(n);
+            ctx.wrap3(r)?;
+            n = q;
+        }
+    }
+    Ok(ctx)
+}
+
+/// Helper function for wrapping (encoding/absorbing) ratio values, the value is clamped into `[0, 1]`.
+pub(crate) fn wrap_ratio<'a, TW, Ctx: Wrap<TW>>(
+    ctx: &'a mut Ctx,
+    ratio: Ratio,
+) -> Result<&'a mut Ctx, Protobuf3Error> {
+    let mut n = ratio.to_fixed();
+    for _ in 0..SIZEOF_RATIO / 3 {
+        let (r, q) = trinary::mods3(n);
+        ctx.wrap3(r)?;
+        n = q;
+    }
+    Ok(ctx)
+}
+
+/// Helper function for wrapping (encoding/absorbing) interval values.
+pub(crate) fn wrap_interval<'a, TW, Ctx: Wrap<TW>>(
+    ctx: &'a mut Ctx,
+    interval: &Interval,
+) -> Result<&'a mut Ctx, Protobuf3Error> {
+    wrap_u64(ctx, interval.start_ms)?;
+    wrap_u64(ctx, interval.end_ms)
+}
+
+/// Helper function for wrapping (encoding/absorbing) socket addresses: `tryte` IP version
+/// `4` or `6` followed by the address octets and the big-endian port as byte strings
+/// without length. IPv6 addresses are followed by the big-endian flow info and scope id,
+/// so the address round trips losslessly.
+#[cfg(feature = "net")]
+pub(crate) fn wrap_socket_addr<'a, TW, Ctx: Wrap<TW>>(
+    ctx: &'a mut Ctx,
+    addr: &std::net::SocketAddr,
+) -> Result<&'a mut Ctx, Protobuf3Error> {
+    match addr {
+        std::net::SocketAddr::V4(addr) => {
+            ctx.wrap3(Trint3(4))?;
+            wrap_bytes_body(ctx, &addr.ip().octets())?;
+            wrap_bytes_body(ctx, &addr.port().to_be_bytes())
+        }
+        std::net::SocketAddr::V6(addr) => {
+            ctx.wrap3(Trint3(6))?;
+            wrap_bytes_body(ctx, &addr.ip().octets())?;
+            wrap_bytes_body(ctx, &addr.port().to_be_bytes())?;
+            wrap_bytes_body(ctx, &addr.flowinfo().to_be_bytes())?;
+            wrap_bytes_body(ctx, &addr.scope_id().to_be_bytes())
+        }
+    }
+}
+
+/// Helper function for wrapping (encoding/absorbing) the presence flag of `Option` fields.
+pub(crate) fn wrap_flag<'a, TW, Ctx: Wrap<TW>>(
+    ctx: &'a mut Ctx,
+    present: bool,
+) -> Result<&'a mut Ctx, Protobuf3Error>
+where
+    TW: trinary::TritWord,
+{
+    let mut flag = Tbits::<TW>::zero(SIZEOF_FLAG);
+    flag.slice_mut().put_trit(trinary::Trit(present as u8));
+    ctx.wrapn(flag.slice())
+}
+
+/// Helper function for binding (absorbing) schema identifier together with its domain label.
+pub(crate) fn wrap_schema<'a, TW, Ctx: Wrap<TW>>(
+    ctx: &'a mut Ctx,
+    schema_id: &[Trint3],
+) -> Result<&'a mut Ctx, Protobuf3Error> {
+    for t in SCHEMA_LABEL.iter() {
+        ctx.wrap3(*t)?;
+    }
+    wrap_size(ctx, Size(schema_id.len()))?;
+    for t in schema_id {
+        ctx.wrap3(*t)?;
+    }
+    Ok(ctx)
+}
+
 struct AbsorbContext<TW, F, OS> {
     ctx: Context<TW, F, OS>,
 }
@@ -87,16 +614,18 @@ where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn wrap3(&mut self, trint3: Trint3) -> Fallible<&mut Self> {
+    fn wrap3(&mut self, trint3: Trint3) -> Result<&mut Self, Protobuf3Error> {
         let mut slice = self.ctx.stream.try_advance(3)?;
         slice.put3(trint3);
         self.ctx.spongos.absorb(unsafe { slice.as_const() });
+        self.ctx.track(3, Region::Absorb);
         Ok(self)
     }
-    fn wrapn(&mut self, trits: TbitSlice<TW>) -> Fallible<&mut Self> {
+    fn wrapn(&mut self, trits: TbitSlice<TW>) -> Result<&mut Self, Protobuf3Error> {
         self.ctx.spongos.absorb(trits);
         let slice = self.ctx.stream.try_advance(trits.size())?;
         trits.copy(&slice);
+        self.ctx.track(trits.size(), Region::Absorb);
         Ok(self)
     }
 }
@@ -104,7 +633,7 @@ where
 fn wrap_absorb_trint3<'a, TW, F, OS: io::OStream<TW>>(
     ctx: &'a mut AbsorbContext<TW, F, OS>,
     trint3: Trint3,
-) -> Fallible<&'a mut AbsorbContext<TW, F, OS>>
+) -> Result<&'a mut AbsorbContext<TW, F, OS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
@@ -114,172 +643,983 @@ where
 fn wrap_absorb_size<'a, TW, F, OS: io::OStream<TW>>(
     ctx: &'a mut AbsorbContext<TW, F, OS>,
     size: Size,
-) -> Fallible<&'a mut AbsorbContext<TW, F, OS>>
+) -> Result<&'a mut AbsorbContext<TW, F, OS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
     wrap_size(ctx, size)
 }
-fn wrap_absorb_trits<'a, TW, F, OS: io::OStream<TW>>(
+fn wrap_absorb_ratio<'a, TW, F, OS: io::OStream<TW>>(
     ctx: &'a mut AbsorbContext<TW, F, OS>,
-    trits: TbitSlice<TW>,
-) -> Fallible<&'a mut AbsorbContext<TW, F, OS>>
+    ratio: Ratio,
+) -> Result<&'a mut AbsorbContext<TW, F, OS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    ctx.wrapn(trits)
+    wrap_ratio(ctx, ratio)
 }
-
-impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a Trint3> for Context<TW, F, OS>
+fn wrap_absorb_bytes<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut AbsorbContext<TW, F, OS>,
+    bytes: &[u8],
+) -> Result<&'a mut AbsorbContext<TW, F, OS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, trint3: &'a Trint3) -> Fallible<&mut Self> {
-        Ok(wrap_absorb_trint3(self.as_mut(), *trint3)?.as_mut())
-    }
+    wrap_bytes(ctx, bytes)
 }
-
-impl<TW, F, OS: io::OStream<TW>> Absorb<Trint3> for Context<TW, F, OS>
+#[cfg(any(feature = "net", feature = "ed25519-dalek"))]
+fn wrap_absorb_bytes_body<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut AbsorbContext<TW, F, OS>,
+    bytes: &[u8],
+) -> Result<&'a mut AbsorbContext<TW, F, OS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, trint3: Trint3) -> Fallible<&mut Self> {
-        self.absorb(&trint3)
-    }
+    wrap_bytes_body(ctx, bytes)
 }
-
-impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a Size> for Context<TW, F, OS>
+fn wrap_absorb_u64<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut AbsorbContext<TW, F, OS>,
+    u: u64,
+) -> Result<&'a mut AbsorbContext<TW, F, OS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, size: &'a Size) -> Fallible<&mut Self> {
-        Ok(wrap_absorb_size(self.as_mut(), *size)?.as_mut())
-    }
+    wrap_u64(ctx, u)
 }
-
-impl<TW, F, OS: io::OStream<TW>> Absorb<Size> for Context<TW, F, OS>
+#[cfg(feature = "net")]
+fn wrap_absorb_socket_addr<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut AbsorbContext<TW, F, OS>,
+    addr: &std::net::SocketAddr,
+) -> Result<&'a mut AbsorbContext<TW, F, OS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, size: Size) -> Fallible<&mut Self> {
-        self.absorb(&size)
-    }
+    wrap_socket_addr(ctx, addr)
 }
-
-impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a NTrytes<TW>> for Context<TW, F, OS>
+fn wrap_absorb_interval<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut AbsorbContext<TW, F, OS>,
+    interval: &Interval,
+) -> Result<&'a mut AbsorbContext<TW, F, OS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, ntrytes: &'a NTrytes<TW>) -> Fallible<&mut Self> {
-        Ok(wrap_absorb_trits(self.as_mut(), (ntrytes.0).slice())?.as_mut())
-    }
+    wrap_interval(ctx, interval)
 }
-
-impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a Trytes<TW>> for Context<TW, F, OS>
+fn wrap_absorb_flag<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut AbsorbContext<TW, F, OS>,
+    present: bool,
+) -> Result<&'a mut AbsorbContext<TW, F, OS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, trytes: &'a Trytes<TW>) -> Fallible<&mut Self> {
-        self.absorb(Size((trytes.0).size() / 3))?;
-        Ok(wrap_absorb_trits(self.as_mut(), (trytes.0).slice())?.as_mut())
-    }
+    wrap_flag(ctx, present)
 }
-
-impl<'a, TW, F, OS: io::OStream<TW>, P> Absorb<&'a mss::PublicKey<TW, P>> for Context<TW, F, OS>
+fn wrap_absorb_trits<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut AbsorbContext<TW, F, OS>,
+    trits: TbitSlice<TW>,
+) -> Result<&'a mut AbsorbContext<TW, F, OS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
-    P: mss::Parameters<TW>,
 {
-    fn absorb(&mut self, pk: &'a mss::PublicKey<TW, P>) -> Fallible<&mut Self> {
-        ensure!(pk.tbits().size() == P::PUBLIC_KEY_SIZE);
-        Ok(wrap_absorb_trits(self.as_mut(), pk.tbits().slice())?.as_mut())
-    }
+    ctx.wrapn(trits)
 }
 
-impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a ntru::PublicKey<TW, F>> for Context<TW, F, OS>
+impl<TW, F, OS: io::OStream<TW>> Context<TW, F, OS>
 where
-    TW: SpongosTbitWord + trinary::TritWord,
+    TW: SpongosTbitWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, pk: &'a ntru::PublicKey<TW, F>) -> Fallible<&mut Self> {
-        ensure!(pk.tbits().size() == ntru::PUBLIC_KEY_SIZE);
-        Ok(wrap_absorb_trits(self.as_mut(), pk.tbits().slice())?.as_mut())
+    /// Rewind the output stream to the position the context was created at and reset Spongos
+    /// to the initial zero state, so that the context can wrap another message into the same
+    /// buffer without reallocation. Tbits before that position are kept intact.
+    /// Dump sink and Spongos mode are kept, region tracking (see `track_regions`) is restarted if enabled.
+    pub fn reset(&mut self) -> Result<&mut Self, Protobuf3Error> {
+        let n = self.stream.position() - self.start;
+        self.stream.rewind(n)?;
+        self.spongos.wipe();
+        if self.regions.is_some() {
+            self.track_regions();
+        }
+        Ok(self)
     }
 }
 
-struct AbsorbExternalContext<TW, F, OS> {
-    ctx: Context<TW, F, OS>,
-}
-impl<TW, F, OS> AsMut<AbsorbExternalContext<TW, F, OS>> for Context<TW, F, OS> {
-    fn as_mut<'a>(&'a mut self) -> &'a mut AbsorbExternalContext<TW, F, OS> {
-        unsafe {
-            mem::transmute::<&'a mut Context<TW, F, OS>, &'a mut AbsorbExternalContext<TW, F, OS>>(
-                self,
-            )
-        }
+impl<TW, F, OS: io::OStream<TW>> Context<TW, F, OS> {
+    /// Offset in tbits of the next wrapped field relative to the position of the stream
+    /// when the context was created, ie. the size of the message wrapped so far.
+    pub fn position(&self) -> usize {
+        self.stream.position() - self.start
     }
 }
-impl<TW, F, OS> AsMut<Context<TW, F, OS>> for AbsorbExternalContext<TW, F, OS> {
-    fn as_mut<'a>(&'a mut self) -> &'a mut Context<TW, F, OS> {
-        unsafe {
-            mem::transmute::<&'a mut AbsorbExternalContext<TW, F, OS>, &'a mut Context<TW, F, OS>>(
-                self,
-            )
-        }
+
+impl<'b, TW, F> Context<TW, F, TbitSliceMut<'b, TW>>
+where
+    TW: BasicTbitWord,
+{
+    /// Rewind the output buffer to the placeholder field at range `at` of positions recorded
+    /// with `position` before and after the placeholder was written, overwrite it with `field`
+    /// and return to the current position.
+    ///
+    /// This only works for skipped fields: Spongos state is not touched, so patching
+    /// an absorbed or masked field would break the message. `field` must have the same
+    /// encoded size as the placeholder, eg. `Size` values should take the same number of trytes.
+    pub fn seek_and_absorb<T>(
+        &mut self,
+        at: Range<usize>,
+        field: T,
+    ) -> Result<&mut Self, Protobuf3Error>
+    where
+        T: Copy,
+        Self: Skip<T>,
+        sizeof::Context<TW, F>: Skip<T>,
+    {
+        let end = self.position();
+        ensure!(
+            at.start <= at.end && at.end <= end,
+            "Can't seek forward to range {:?}, current position is {}.",
+            at,
+            end
+        );
+        let n = sizeof::Context::<TW, F>::new().skip(field)?.get_size();
+        ensure!(
+            n == at.len(),
+            "Field of size {} doesn't match placeholder of size {}.",
+            n,
+            at.len()
+        );
+
+        unsafe { self.stream.pickup_mut(end - at.start) };
+        // The patched field is already covered by the recorded regions.
+        let regions = self.regions.take();
+        let r = self.skip(field).map(|_| ());
+        self.regions = regions;
+        let written = self.stream.dropped_size();
+        self.stream.advance(end - written);
+        r?;
+        Ok(self)
     }
 }
 
-impl<TW, F, OS: io::OStream<TW>> Wrap<TW> for AbsorbExternalContext<TW, F, OS>
+impl<TW, F, OS: io::OStream<TW>> Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn wrap3(&mut self, trint3: Trint3) -> Fallible<&mut Self> {
-        let mut buf = [BasicTbitWord::ZERO_WORD; 3];
-        let mut t3 = TbitSliceMut::<TW>::from_slice_mut(3, &mut buf);
-        t3.put3(trint3);
-        self.ctx.spongos.absorb(unsafe { t3.as_const() });
-        Ok(self)
+    /// Absorb message sequence counter encoded as a fixed-width `u64` field.
+    /// Counters are expected to strictly increase from message to message starting with 1,
+    /// see `unwrap::Context::check_counter`.
+    pub fn absorb_counter(&mut self, counter: u64) -> Result<&mut Self, Protobuf3Error> {
+        Ok(wrap_u64(AsMut::<AbsorbContext<TW, F, OS>>::as_mut(self), counter)?.as_mut())
     }
-    fn wrapn(&mut self, trits: TbitSlice<TW>) -> Fallible<&mut Self> {
-        self.ctx.spongos.absorb(trits);
+
+    /// Bind message to a schema version.
+    /// Schema identifier is absorbed as external (not encoded) data with a domain label,
+    /// so that unwrapping with a different schema identifier fails MAC check.
+    pub fn bind_schema(&mut self, schema_id: &[Trint3]) -> Result<&mut Self, Protobuf3Error> {
+        Ok(wrap_schema(
+            AsMut::<AbsorbExternalContext<TW, F, OS>>::as_mut(self),
+            schema_id,
+        )?
+        .as_mut())
+    }
+}
+
+impl<TW, F, OS: io::OStream<TW>> Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW> + Clone,
+{
+    /// Tolerant `oneof` field. Variant `tag` and `size` of its body (in trits) are absorbed,
+    /// the body is then wrapped by `cont` in a forked Spongos state (as with `Fork` command).
+    /// This allows readers unaware of the variant to skip the body and stay in sync.
+    /// The wrapped body is absorbed afterwards as raw trits so that the following commands
+    /// (eg. `Mac`) cover it for all readers. This requires an output stream that can be rewound.
+    /// `tag` must be less than `known_max`, the number of variants known to the writer,
+    /// and `size` must be equal to the size of the body wrapped by `cont`.
+    pub fn oneof_tolerant<C>(
+        &mut self,
+        tag: Size,
+        known_max: Size,
+        size: Size,
+        cont: C,
+    ) -> Result<&mut Self, Protobuf3Error>
+    where
+        C: for<'a> FnMut(&'a mut Self) -> Result<&'a mut Self, Protobuf3Error>,
+    {
+        ensure!(
+            tag < known_max,
+            Protobuf3Error::OutOfRange(format!(
+                "Oneof tag {} is not less than the number of known variants {}.",
+                tag.0, known_max.0
+            ))
+        );
+        self.absorb(&tag)?.absorb(&size)?;
+        let start = self.stream.position();
+        self.fork(cont)?;
+        let written = self.stream.position() - start;
+        ensure!(
+            written == size.0,
+            "Oneof body of {} trits doesn't match its encoded size {}.",
+            written,
+            size.0
+        );
+        self.stream.rewind(size.0)?;
+        let body = self.stream.try_advance(size.0)?;
+        self.spongos.absorb(unsafe { body.as_const() });
         Ok(self)
     }
 }
 
-fn wrap_absorb_external_trint3<'a, TW, F, OS: io::OStream<TW>>(
-    ctx: &'a mut AbsorbExternalContext<TW, F, OS>,
-    trint3: Trint3,
-) -> Fallible<&'a mut AbsorbExternalContext<TW, F, OS>>
+impl<TW, F, OS: io::OStream<TW>> Context<TW, F, OS>
+where
+    TW: IntTbitWord + SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW> + Clone,
+{
+    /// Encapsulate the same `secret` with each of the public `keys`. The number of
+    /// recipients is absorbed, then each capsule is wrapped in a forked Spongos state.
+    /// Recipients decapsulate the secret with `unwrap::Context::ntrukem_multi`.
+    pub fn ntrukem_multi<G>(
+        &mut self,
+        keys: &[&ntru::PublicKey<TW, F>],
+        prng: &prng::Prng<TW, G>,
+        nonce: &Tbits<TW>,
+        secret: &NTrytes<TW>,
+    ) -> Result<&mut Self, Protobuf3Error>
+    where
+        G: PRP<TW> + Clone + Default,
+    {
+        self.absorb(Size(keys.len()))?;
+        for pk in keys {
+            self.fork(|ctx| ctx.ntrukem((*pk, prng, nonce), secret))?;
+        }
+        Ok(self)
+    }
+}
+
+impl<TW, F, OS: io::OStream<TW>> Context<TW, F, OS>
+where
+    TW: IntTbitWord + SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    /// Finalize message: commit, sign the committed hash with `sk` (see `MssHashSig`),
+    /// commit again and squeeze message identifier `id` as an external (not encoded) value.
+    /// The same `id` is derived by `unwrap::Context::verify_signature_and_id`.
+    pub fn finalize_with_signature_and_id<P>(
+        &mut self,
+        sk: &mut mss::PrivateKey<TW, P>,
+        id: &mut NTrytes<TW>,
+    ) -> Result<&mut Self, Protobuf3Error>
+    where
+        P: mss::Parameters<TW>,
+    {
+        self.commit()?
+            .mssig(&mut *sk, MssHashSig)?
+            .commit()?
+            .squeeze(External(id))
+    }
+}
+
+impl<F, W> Context<trinary::Trit, F, io::WrapStream<W>>
+where
+    F: PRP<trinary::Trit>,
+    W: std::io::Write,
+{
+    /// Publish message written into `io::WrapStream`: `finalize_with_signature_and_id` with `sk`,
+    /// finish the stream and return the message identifier of `MSG_ID_SIZE` trits together
+    /// with the writer holding the packed message.
+    pub fn publish<P>(
+        mut self,
+        sk: &mut mss::PrivateKey<trinary::Trit, P>,
+    ) -> Result<(NTrytes<trinary::Trit>, W), Protobuf3Error>
+    where
+        P: mss::Parameters<trinary::Trit>,
+    {
+        let mut id = NTrytes::zero(MSG_ID_SIZE);
+        self.finalize_with_signature_and_id(sk, &mut id)?;
+        let writer = self.stream.finish()?;
+        Ok((id, writer))
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a Trint3> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, trint3: &'a Trint3) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Trint3", |ctx| {
+            Ok(wrap_absorb_trint3(ctx.as_mut(), *trint3)?.as_mut())
+        })
+    }
+}
+
+impl<TW, F, OS: io::OStream<TW>> Absorb<Trint3> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, trint3: Trint3) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Trint3", |ctx| { ctx.absorb(&trint3) })
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a Size> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, size: &'a Size) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Size", |ctx| {
+            Ok(wrap_absorb_size(ctx.as_mut(), *size)?.as_mut())
+        })
+    }
+}
+
+impl<TW, F, OS: io::OStream<TW>> Absorb<Size> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, size: Size) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Size", |ctx| { ctx.absorb(&size) })
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a Ratio> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, ratio: &'a Ratio) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Ratio", |ctx| {
+            Ok(wrap_absorb_ratio(ctx.as_mut(), *ratio)?.as_mut())
+        })
+    }
+}
+
+impl<TW, F, OS: io::OStream<TW>> Absorb<Ratio> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, ratio: Ratio) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Ratio", |ctx| { ctx.absorb(&ratio) })
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a [u8]> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, bytes: &'a [u8]) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "[u8]", |ctx| {
+            Ok(wrap_absorb_bytes(ctx.as_mut(), bytes)?.as_mut())
+        })
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a Interval> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, interval: &'a Interval) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Interval", |ctx| {
+            Ok(wrap_absorb_interval(ctx.as_mut(), interval)?.as_mut())
+        })
+    }
+}
+
+/// Presence flag followed by the inner field if present.
+impl<'a, TW, F, T: 'a, OS: io::OStream<TW>> Absorb<Option<&'a T>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+    Self: Absorb<&'a T>,
+{
+    fn absorb(&mut self, x: Option<&'a T>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Option<T>", |ctx| {
+            wrap_absorb_flag(ctx.as_mut(), x.is_some())?;
+            match x {
+                Some(x) => ctx.absorb(x),
+                None => Ok(ctx),
+            }
+        })
+    }
+}
+
+impl<TW, F, OS: io::OStream<TW>> Absorb<Interval> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, interval: Interval) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Interval", |ctx| { ctx.absorb(&interval) })
+    }
+}
+
+impl<TW, F, OS: io::OStream<TW>> Timestamp<u64> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb_timestamp(&mut self, ts: u64) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb_timestamp", "u64", |ctx| {
+            Ok(wrap_absorb_u64(ctx.as_mut(), ts)?.as_mut())
+        })
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a NTrytes<TW>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, ntrytes: &'a NTrytes<TW>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "NTrytes", |ctx| {
+            Ok(wrap_absorb_trits(ctx.as_mut(), (ntrytes.0).slice())?.as_mut())
+        })
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>, const N: usize> Absorb<&'a NTryte<TW, N>>
+    for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, ntryte: &'a NTryte<TW, N>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "NTryte", |ctx| {
+            ensure!(
+                (ntryte.0).size() == NTryte::<TW, N>::SIZE,
+                "Trit size of `ntryte` must be {}: {}.",
+                NTryte::<TW, N>::SIZE,
+                (ntryte.0).size()
+            );
+            Ok(wrap_absorb_trits(ctx.as_mut(), (ntryte.0).slice())?.as_mut())
+        })
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a Trytes<TW>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, trytes: &'a Trytes<TW>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Trytes", |ctx| {
+            ctx.absorb(Size((trytes.0).size() / 3))?;
+            Ok(wrap_absorb_trits(ctx.as_mut(), (trytes.0).slice())?.as_mut())
+        })
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a TlvStream<TW>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, tlvs: &'a TlvStream<TW>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "TlvStream", |ctx| {
+            ensure!(
+                tlvs.records.len() <= tlvs.max_records,
+                "Too many TLV records: {} > {}.",
+                tlvs.records.len(),
+                tlvs.max_records
+            );
+            ctx.absorb(Size(tlvs.records.len()))?;
+            for tlv in &tlvs.records {
+                ctx.absorb(&tlv.tlv_type)?.absorb(&tlv.value)?;
+            }
+            Ok(ctx)
+        })
+    }
+}
+
+impl<TW, F, OS: io::OStream<TW>> Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    /// Absorb `size_t` number of elements followed by the elements, each one with
+    /// its own `Absorb`. The encoding is the same as `absorb(Size(n))` followed by `repeated`.
+    pub fn absorb_many<'b, T>(&mut self, xs: &'b [T]) -> Result<&mut Self, Protobuf3Error>
+    where
+        Self: Absorb<&'b T>,
+    {
+        <Self as Absorb<Size>>::absorb(self, Size(xs.len()))?;
+        for x in xs {
+            self.absorb(x)?;
+        }
+        Ok(self)
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>, P> Absorb<&'a mss::PublicKey<TW, P>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+    P: mss::Parameters<TW>,
+{
+    fn absorb(&mut self, pk: &'a mss::PublicKey<TW, P>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "mss::PublicKey", |ctx| {
+            ensure!(pk.tbits().size() == P::PUBLIC_KEY_SIZE);
+            Ok(wrap_absorb_trits(ctx.as_mut(), pk.tbits().slice())?.as_mut())
+        })
+    }
+}
+
+#[cfg(feature = "net")]
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a std::net::Ipv4Addr> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, ip: &'a std::net::Ipv4Addr) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Ipv4Addr", |ctx| {
+            Ok(wrap_absorb_bytes_body(ctx.as_mut(), &ip.octets())?.as_mut())
+        })
+    }
+}
+
+#[cfg(feature = "net")]
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a std::net::Ipv6Addr> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, ip: &'a std::net::Ipv6Addr) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Ipv6Addr", |ctx| {
+            Ok(wrap_absorb_bytes_body(ctx.as_mut(), &ip.octets())?.as_mut())
+        })
+    }
+}
+
+#[cfg(feature = "net")]
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a std::net::SocketAddr> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, addr: &'a std::net::SocketAddr) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "SocketAddr", |ctx| {
+            Ok(wrap_absorb_socket_addr(ctx.as_mut(), addr)?.as_mut())
+        })
+    }
+}
+
+#[cfg(feature = "ed25519-dalek")]
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a Ed25519PublicKey> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, pk: &'a Ed25519PublicKey) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Ed25519PublicKey", |ctx| {
+            Ok(wrap_absorb_bytes_body(ctx.as_mut(), pk.as_bytes())?.as_mut())
+        })
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<&'a ntru::PublicKey<TW, F>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, pk: &'a ntru::PublicKey<TW, F>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "ntru::PublicKey", |ctx| {
+            ensure!(pk.tbits().size() == ntru::PUBLIC_KEY_SIZE);
+            Ok(wrap_absorb_trits(ctx.as_mut(), pk.tbits().slice())?.as_mut())
+        })
+    }
+}
+
+struct AbsorbExternalContext<TW, F, OS> {
+    ctx: Context<TW, F, OS>,
+}
+impl<TW, F, OS> AsMut<AbsorbExternalContext<TW, F, OS>> for Context<TW, F, OS> {
+    fn as_mut<'a>(&'a mut self) -> &'a mut AbsorbExternalContext<TW, F, OS> {
+        unsafe {
+            mem::transmute::<&'a mut Context<TW, F, OS>, &'a mut AbsorbExternalContext<TW, F, OS>>(
+                self,
+            )
+        }
+    }
+}
+impl<TW, F, OS> AsMut<Context<TW, F, OS>> for AbsorbExternalContext<TW, F, OS> {
+    fn as_mut<'a>(&'a mut self) -> &'a mut Context<TW, F, OS> {
+        unsafe {
+            mem::transmute::<&'a mut AbsorbExternalContext<TW, F, OS>, &'a mut Context<TW, F, OS>>(
+                self,
+            )
+        }
+    }
+}
+
+impl<TW, F, OS: io::OStream<TW>> Wrap<TW> for AbsorbExternalContext<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn wrap3(&mut self, trint3: Trint3) -> Result<&mut Self, Protobuf3Error> {
+        let mut buf = [BasicTbitWord::ZERO_WORD; 3];
+        let mut t3 = TbitSliceMut::<TW>::from_slice_mut(3, &mut buf);
+        t3.put3(trint3);
+        self.ctx.spongos.absorb(unsafe { t3.as_const() });
+        self.ctx.track(0, |_| {
+            Region::External(Tbits::from_slice(unsafe { t3.as_const() }))
+        });
+        Ok(self)
+    }
+    fn wrapn(&mut self, trits: TbitSlice<TW>) -> Result<&mut Self, Protobuf3Error> {
+        self.ctx.spongos.absorb(trits);
+        self.ctx
+            .track(0, |_| Region::External(Tbits::from_slice(trits)));
+        Ok(self)
+    }
+}
+
+fn wrap_absorb_external_trint3<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut AbsorbExternalContext<TW, F, OS>,
+    trint3: Trint3,
+) -> Result<&'a mut AbsorbExternalContext<TW, F, OS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    ctx.wrap3(trint3)
+}
+fn wrap_absorb_external_size<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut AbsorbExternalContext<TW, F, OS>,
+    size: Size,
+) -> Result<&'a mut AbsorbExternalContext<TW, F, OS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    wrap_size(ctx, size)
+}
+fn wrap_absorb_external_trits<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut AbsorbExternalContext<TW, F, OS>,
+    trits: TbitSlice<TW>,
+) -> Result<&'a mut AbsorbExternalContext<TW, F, OS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    ctx.wrapn(trits)
+}
+
+impl<'a, T: 'a, TW, F, OS: io::OStream<TW>> Absorb<&'a External<T>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+    Self: Absorb<External<&'a T>>,
+{
+    fn absorb(&mut self, external: &'a External<T>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<T>", |ctx| {
+            ctx.absorb(External(&external.0))
+        })
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<External<&'a Trint3>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, trint3: External<&'a Trint3>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<Trint3>", |ctx| {
+            Ok(wrap_absorb_external_trint3(ctx.as_mut(), *trint3.0)?.as_mut())
+        })
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<External<&'a Size>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, size: External<&'a Size>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<Size>", |ctx| {
+            Ok(wrap_absorb_external_size(ctx.as_mut(), *size.0)?.as_mut())
+        })
+    }
+}
+
+impl<TW, F, OS: io::OStream<TW>> Absorb<External<Size>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(&mut self, size: External<Size>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<Size>", |ctx| {
+            ctx.absorb(&size)
+        })
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<External<&'a NTrytes<TW>>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(
+        &mut self,
+        external_ntrytes: External<&'a NTrytes<TW>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<NTrytes>", |ctx| {
+            Ok(
+                wrap_absorb_external_trits(ctx.as_mut(), ((external_ntrytes.0).0).slice())?
+                    .as_mut(),
+            )
+        })
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>, P> Absorb<External<&'a mss::PublicKey<TW, P>>>
+    for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+    P: mss::Parameters<TW>,
+{
+    fn absorb(
+        &mut self,
+        pk: External<&'a mss::PublicKey<TW, P>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<mss::PublicKey>", |ctx| {
+            ensure!((pk.0).tbits().size() == P::PUBLIC_KEY_SIZE);
+            Ok(wrap_absorb_external_trits(ctx.as_mut(), (pk.0).tbits().slice())?.as_mut())
+        })
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>> Absorb<External<&'a ntru::PublicKey<TW, F>>>
+    for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn absorb(
+        &mut self,
+        pk: External<&'a ntru::PublicKey<TW, F>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<ntru::PublicKey>", |ctx| {
+            ensure!((pk.0).tbits().size() == ntru::PK_SIZE);
+            Ok(wrap_absorb_external_trits(ctx.as_mut(), (pk.0).tbits().slice())?.as_mut())
+        })
+    }
+}
+
+/// This is just an external tag or hash value to-be-signed.
+impl<'a, TW, F, OS> Squeeze<&'a mut External<NTrytes<TW>>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn squeeze(
+        &mut self,
+        external_ntrytes: &'a mut External<NTrytes<TW>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        self.spongos
+            .squeeze(&mut ((external_ntrytes.0).0).slice_mut());
+        let n = ((external_ntrytes.0).0).size();
+        self.track(0, |_| Region::SqueezeExternal(n));
+        Ok(self)
+    }
+}
+
+/// This is just an external tag or hash value to-be-signed.
+impl<'a, TW, F, OS> Squeeze<External<&'a mut NTrytes<TW>>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn squeeze(
+        &mut self,
+        external_ntrytes: External<&'a mut NTrytes<TW>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        self.spongos
+            .squeeze(&mut ((external_ntrytes.0).0).slice_mut());
+        let n = ((external_ntrytes.0).0).size();
+        self.track(0, |_| Region::SqueezeExternal(n));
+        Ok(self)
+    }
+}
+
+/// Squeeze `N` trytes into the caller's buffer, eg. a hash value to be signed externally.
+impl<'a, TW, F, OS, const N: usize> Squeeze<&'a mut External<NTryte<TW, N>>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn squeeze(
+        &mut self,
+        external_ntryte: &'a mut External<NTryte<TW, N>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        self.squeeze(External(&mut external_ntryte.0))
+    }
+}
+
+/// Squeeze `N` trytes into the caller's buffer, eg. a hash value to be signed externally.
+impl<'a, TW, F, OS, const N: usize> Squeeze<External<&'a mut NTryte<TW, N>>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn squeeze(
+        &mut self,
+        external_ntryte: External<&'a mut NTryte<TW, N>>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        let ntryte = external_ntryte.0;
+        if (ntryte.0).size() != NTryte::<TW, N>::SIZE {
+            ntryte.0 = Tbits::<TW>::zero(NTryte::<TW, N>::SIZE);
+        }
+        self.spongos.squeeze(&mut (ntryte.0).slice_mut());
+        self.track(0, |_| Region::SqueezeExternal(NTryte::<TW, N>::SIZE));
+        Ok(self)
+    }
+}
+
+/// External values are not encoded.
+impl<'a, TW, F, OS: io::OStream<TW>> Squeeze<&'a Mac> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn squeeze(&mut self, mac: &'a Mac) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "squeeze", "Mac", |ctx| {
+            ctx.spongos.squeeze(&mut ctx.stream.try_advance(mac.0)?);
+            ctx.track(mac.0, Region::Mac);
+            Ok(ctx)
+        })
+    }
+}
+
+struct MaskContext<TW, F, OS> {
+    ctx: Context<TW, F, OS>,
+}
+impl<TW, F, OS> AsMut<MaskContext<TW, F, OS>> for Context<TW, F, OS> {
+    fn as_mut<'a>(&'a mut self) -> &'a mut MaskContext<TW, F, OS> {
+        unsafe {
+            mem::transmute::<&'a mut Context<TW, F, OS>, &'a mut MaskContext<TW, F, OS>>(self)
+        }
+    }
+}
+impl<TW, F, OS> AsMut<Context<TW, F, OS>> for MaskContext<TW, F, OS> {
+    fn as_mut<'a>(&'a mut self) -> &'a mut Context<TW, F, OS> {
+        unsafe {
+            mem::transmute::<&'a mut MaskContext<TW, F, OS>, &'a mut Context<TW, F, OS>>(self)
+        }
+    }
+}
+
+impl<TW, F, OS: io::OStream<TW>> Wrap<TW> for MaskContext<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn wrap3(&mut self, trint3: Trint3) -> Result<&mut Self, Protobuf3Error> {
+        let mut slice = self.ctx.stream.try_advance(3)?;
+        slice.put3(trint3);
+        self.ctx.spongos.encrypt_mut(&mut slice);
+        self.ctx.track(3, Region::Mask);
+        Ok(self)
+    }
+    fn wrapn(&mut self, trits: TbitSlice<TW>) -> Result<&mut Self, Protobuf3Error> {
+        let mut slice = self.ctx.stream.try_advance(trits.size())?;
+        self.ctx.spongos.encrypt(trits, &mut slice);
+        self.ctx.track(trits.size(), Region::Mask);
+        Ok(self)
+    }
+}
+
+fn wrap_mask_trint3<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, OS>,
+    trint3: Trint3,
+) -> Result<&'a mut MaskContext<TW, F, OS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    ctx.wrap3(trint3)
+}
+fn wrap_mask_size<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, OS>,
+    size: Size,
+) -> Result<&'a mut MaskContext<TW, F, OS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    wrap_size(ctx, size)
+}
+fn wrap_mask_ratio<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, OS>,
+    ratio: Ratio,
+) -> Result<&'a mut MaskContext<TW, F, OS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    wrap_ratio(ctx, ratio)
+}
+fn wrap_mask_interval<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, OS>,
+    interval: &Interval,
+) -> Result<&'a mut MaskContext<TW, F, OS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    wrap_interval(ctx, interval)
+}
+fn wrap_mask_flag<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, OS>,
+    present: bool,
+) -> Result<&'a mut MaskContext<TW, F, OS>, Protobuf3Error>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    wrap_flag(ctx, present)
+}
+fn wrap_mask_bytes_body<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, OS>,
+    bytes: &[u8],
+) -> Result<&'a mut MaskContext<TW, F, OS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    ctx.wrap3(trint3)
+    wrap_bytes_body(ctx, bytes)
 }
-fn wrap_absorb_external_size<'a, TW, F, OS: io::OStream<TW>>(
-    ctx: &'a mut AbsorbExternalContext<TW, F, OS>,
-    size: Size,
-) -> Fallible<&'a mut AbsorbExternalContext<TW, F, OS>>
+#[cfg(feature = "net")]
+fn wrap_mask_socket_addr<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, OS>,
+    addr: &std::net::SocketAddr,
+) -> Result<&'a mut MaskContext<TW, F, OS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    wrap_size(ctx, size)
+    wrap_socket_addr(ctx, addr)
 }
-fn wrap_absorb_external_trits<'a, TW, F, OS: io::OStream<TW>>(
-    ctx: &'a mut AbsorbExternalContext<TW, F, OS>,
+fn wrap_mask_trits<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut MaskContext<TW, F, OS>,
     trits: TbitSlice<TW>,
-) -> Fallible<&'a mut AbsorbExternalContext<TW, F, OS>>
+) -> Result<&'a mut MaskContext<TW, F, OS>, Protobuf3Error>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
@@ -287,214 +1627,216 @@ where
     ctx.wrapn(trits)
 }
 
-impl<'a, T: 'a, TW, F, OS: io::OStream<TW>> Absorb<&'a External<T>> for Context<TW, F, OS>
+impl<'a, TW, F, OS: io::OStream<TW>> Mask<&'a Trint3> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
-    Self: Absorb<External<&'a T>>,
 {
-    fn absorb(&mut self, external: &'a External<T>) -> Fallible<&mut Self> {
-        self.absorb(External(&external.0))
+    fn mask(&mut self, trint3: &'a Trint3) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Trint3", |ctx| {
+            Ok(wrap_mask_trint3(ctx.as_mut(), *trint3)?.as_mut())
+        })
     }
 }
 
-impl<'a, TW, F, OS: io::OStream<TW>> Absorb<External<&'a Trint3>> for Context<TW, F, OS>
+impl<'a, TW, F, OS: io::OStream<TW>> Mask<&'a Size> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, trint3: External<&'a Trint3>) -> Fallible<&mut Self> {
-        Ok(wrap_absorb_external_trint3(self.as_mut(), *trint3.0)?.as_mut())
+    fn mask(&mut self, size: &'a Size) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Size", |ctx| {
+            Ok(wrap_mask_size(ctx.as_mut(), *size)?.as_mut())
+        })
     }
 }
 
-impl<'a, TW, F, OS: io::OStream<TW>> Absorb<External<&'a Size>> for Context<TW, F, OS>
+/// The length of the string in bytes is absorbed as `size_t` and is not hidden,
+/// UTF-8 bytes are masked, each byte is encoded with `BYTE_TRYTES` trytes.
+impl<'a, TW, F, OS: io::OStream<TW>> Mask<&'a str> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, size: External<&'a Size>) -> Fallible<&mut Self> {
-        Ok(wrap_absorb_external_size(self.as_mut(), *size.0)?.as_mut())
+    fn mask(&mut self, s: &'a str) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "str", |ctx| {
+            ctx.absorb(Size(s.len()))?;
+            Ok(wrap_mask_bytes_body(ctx.as_mut(), s.as_bytes())?.as_mut())
+        })
     }
 }
 
-impl<TW, F, OS: io::OStream<TW>> Absorb<External<Size>> for Context<TW, F, OS>
+/// Flag is encrypted as a single trit: `1` -- `true`, `0` -- `false`.
+impl<'a, TW, F, OS: io::OStream<TW>> Mask<&'a bool> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, size: External<Size>) -> Fallible<&mut Self> {
-        self.absorb(&size)
+    fn mask(&mut self, flag: &'a bool) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "bool", |ctx| {
+            Ok(wrap_mask_flag(ctx.as_mut(), *flag)?.as_mut())
+        })
     }
 }
 
-impl<'a, TW, F, OS: io::OStream<TW>> Absorb<External<&'a NTrytes<TW>>> for Context<TW, F, OS>
+impl<TW, F, OS: io::OStream<TW>> Mask<bool> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, external_ntrytes: External<&'a NTrytes<TW>>) -> Fallible<&mut Self> {
-        Ok(wrap_absorb_external_trits(self.as_mut(), ((external_ntrytes.0).0).slice())?.as_mut())
+    fn mask(&mut self, flag: bool) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "bool", |ctx| { ctx.mask(&flag) })
     }
 }
 
-impl<'a, TW, F, OS: io::OStream<TW>, P> Absorb<External<&'a mss::PublicKey<TW, P>>>
-    for Context<TW, F, OS>
+impl<'a, TW, F, OS: io::OStream<TW>> Mask<&'a trinary::Trit> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
-    P: mss::Parameters<TW>,
 {
-    fn absorb(&mut self, pk: External<&'a mss::PublicKey<TW, P>>) -> Fallible<&mut Self> {
-        ensure!((pk.0).tbits().size() == P::PUBLIC_KEY_SIZE);
-        Ok(wrap_absorb_external_trits(self.as_mut(), (pk.0).tbits().slice())?.as_mut())
+    fn mask(&mut self, trit: &'a trinary::Trit) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "trinary::Trit", |ctx| {
+            ensure!(trit.0 < 3, "Invalid trit: {}.", trit.0);
+            let mut t = Tbits::<TW>::zero(1);
+            t.slice_mut().put_trit(*trit);
+            Ok(wrap_mask_trits(ctx.as_mut(), t.slice())?.as_mut())
+        })
     }
 }
 
-impl<'a, TW, F, OS: io::OStream<TW>> Absorb<External<&'a ntru::PublicKey<TW, F>>>
-    for Context<TW, F, OS>
+impl<TW, F, OS: io::OStream<TW>> Mask<trinary::Trit> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn absorb(&mut self, pk: External<&'a ntru::PublicKey<TW, F>>) -> Fallible<&mut Self> {
-        ensure!((pk.0).tbits().size() == ntru::PK_SIZE);
-        Ok(wrap_absorb_external_trits(self.as_mut(), (pk.0).tbits().slice())?.as_mut())
+    fn mask(&mut self, trit: trinary::Trit) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "trinary::Trit", |ctx| { ctx.mask(&trit) })
     }
 }
 
-/// This is just an external tag or hash value to-be-signed.
-impl<'a, TW, F, OS> Squeeze<&'a mut External<NTrytes<TW>>> for Context<TW, F, OS>
+impl<'a, TW, F, OS: io::OStream<TW>> Mask<&'a Ratio> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn squeeze(&mut self, external_ntrytes: &'a mut External<NTrytes<TW>>) -> Fallible<&mut Self> {
-        self.spongos
-            .squeeze(&mut ((external_ntrytes.0).0).slice_mut());
-        Ok(self)
+    fn mask(&mut self, ratio: &'a Ratio) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Ratio", |ctx| {
+            Ok(wrap_mask_ratio(ctx.as_mut(), *ratio)?.as_mut())
+        })
     }
 }
 
-/// This is just an external tag or hash value to-be-signed.
-impl<'a, TW, F, OS> Squeeze<External<&'a mut NTrytes<TW>>> for Context<TW, F, OS>
+#[cfg(feature = "net")]
+impl<'a, TW, F, OS: io::OStream<TW>> Mask<&'a std::net::Ipv4Addr> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn squeeze(&mut self, external_ntrytes: External<&'a mut NTrytes<TW>>) -> Fallible<&mut Self> {
-        self.spongos
-            .squeeze(&mut ((external_ntrytes.0).0).slice_mut());
-        Ok(self)
+    fn mask(&mut self, ip: &'a std::net::Ipv4Addr) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Ipv4Addr", |ctx| {
+            Ok(wrap_mask_bytes_body(ctx.as_mut(), &ip.octets())?.as_mut())
+        })
     }
 }
 
-/// External values are not encoded.
-impl<'a, TW, F, OS: io::OStream<TW>> Squeeze<&'a Mac> for Context<TW, F, OS>
+#[cfg(feature = "net")]
+impl<'a, TW, F, OS: io::OStream<TW>> Mask<&'a std::net::Ipv6Addr> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn squeeze(&mut self, mac: &'a Mac) -> Fallible<&mut Self> {
-        self.spongos.squeeze(&mut self.stream.try_advance(mac.0)?);
-        Ok(self)
-    }
-}
-
-struct MaskContext<TW, F, OS> {
-    ctx: Context<TW, F, OS>,
-}
-impl<TW, F, OS> AsMut<MaskContext<TW, F, OS>> for Context<TW, F, OS> {
-    fn as_mut<'a>(&'a mut self) -> &'a mut MaskContext<TW, F, OS> {
-        unsafe {
-            mem::transmute::<&'a mut Context<TW, F, OS>, &'a mut MaskContext<TW, F, OS>>(self)
-        }
-    }
-}
-impl<TW, F, OS> AsMut<Context<TW, F, OS>> for MaskContext<TW, F, OS> {
-    fn as_mut<'a>(&'a mut self) -> &'a mut Context<TW, F, OS> {
-        unsafe {
-            mem::transmute::<&'a mut MaskContext<TW, F, OS>, &'a mut Context<TW, F, OS>>(self)
-        }
+    fn mask(&mut self, ip: &'a std::net::Ipv6Addr) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Ipv6Addr", |ctx| {
+            Ok(wrap_mask_bytes_body(ctx.as_mut(), &ip.octets())?.as_mut())
+        })
     }
 }
 
-impl<TW, F, OS: io::OStream<TW>> Wrap<TW> for MaskContext<TW, F, OS>
+#[cfg(feature = "net")]
+impl<'a, TW, F, OS: io::OStream<TW>> Mask<&'a std::net::SocketAddr> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn wrap3(&mut self, trint3: Trint3) -> Fallible<&mut Self> {
-        let mut slice = self.ctx.stream.try_advance(3)?;
-        slice.put3(trint3);
-        self.ctx.spongos.encrypt_mut(&mut slice);
-        Ok(self)
-    }
-    fn wrapn(&mut self, trits: TbitSlice<TW>) -> Fallible<&mut Self> {
-        let mut slice = self.ctx.stream.try_advance(trits.size())?;
-        self.ctx.spongos.encrypt(trits, &mut slice);
-        Ok(self)
+    fn mask(&mut self, addr: &'a std::net::SocketAddr) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "SocketAddr", |ctx| {
+            Ok(wrap_mask_socket_addr(ctx.as_mut(), addr)?.as_mut())
+        })
     }
 }
 
-fn wrap_mask_trint3<'a, TW, F, OS: io::OStream<TW>>(
-    ctx: &'a mut MaskContext<TW, F, OS>,
-    trint3: Trint3,
-) -> Fallible<&'a mut MaskContext<TW, F, OS>>
-where
-    TW: SpongosTbitWord + trinary::TritWord,
-    F: PRP<TW>,
-{
-    ctx.wrap3(trint3)
-}
-fn wrap_mask_size<'a, TW, F, OS: io::OStream<TW>>(
-    ctx: &'a mut MaskContext<TW, F, OS>,
-    size: Size,
-) -> Fallible<&'a mut MaskContext<TW, F, OS>>
+impl<'a, TW, F, OS: io::OStream<TW>> Mask<&'a Interval> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    wrap_size(ctx, size)
+    fn mask(&mut self, interval: &'a Interval) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Interval", |ctx| {
+            Ok(wrap_mask_interval(ctx.as_mut(), interval)?.as_mut())
+        })
+    }
 }
-fn wrap_mask_trits<'a, TW, F, OS: io::OStream<TW>>(
-    ctx: &'a mut MaskContext<TW, F, OS>,
-    trits: TbitSlice<TW>,
-) -> Fallible<&'a mut MaskContext<TW, F, OS>>
+
+/// Masked presence flag followed by the masked inner field if present.
+impl<'a, TW, F, T: 'a, OS: io::OStream<TW>> Mask<Option<&'a T>> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
+    Self: Mask<&'a T>,
 {
-    ctx.wrapn(trits)
+    fn mask(&mut self, x: Option<&'a T>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Option<T>", |ctx| {
+            wrap_mask_flag(ctx.as_mut(), x.is_some())?;
+            match x {
+                Some(x) => ctx.mask(x),
+                None => Ok(ctx),
+            }
+        })
+    }
 }
 
-impl<'a, TW, F, OS: io::OStream<TW>> Mask<&'a Trint3> for Context<TW, F, OS>
+impl<'a, TW, F, OS: io::OStream<TW>> Mask<&'a NTrytes<TW>> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn mask(&mut self, trint3: &'a Trint3) -> Fallible<&mut Self> {
-        Ok(wrap_mask_trint3(self.as_mut(), *trint3)?.as_mut())
+    fn mask(&mut self, ntrytes: &'a NTrytes<TW>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "NTrytes", |ctx| {
+            Ok(wrap_mask_trits(ctx.as_mut(), (ntrytes.0).slice())?.as_mut())
+        })
     }
 }
 
-impl<'a, TW, F, OS: io::OStream<TW>> Mask<&'a Size> for Context<TW, F, OS>
+impl<'a, TW, F, OS: io::OStream<TW>, const N: usize> Mask<&'a NTryte<TW, N>> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn mask(&mut self, size: &'a Size) -> Fallible<&mut Self> {
-        Ok(wrap_mask_size(self.as_mut(), *size)?.as_mut())
+    fn mask(&mut self, ntryte: &'a NTryte<TW, N>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "NTryte", |ctx| {
+            ensure!(
+                (ntryte.0).size() == NTryte::<TW, N>::SIZE,
+                "Trit size of `ntryte` must be {}: {}.",
+                NTryte::<TW, N>::SIZE,
+                (ntryte.0).size()
+            );
+            Ok(wrap_mask_trits(ctx.as_mut(), (ntryte.0).slice())?.as_mut())
+        })
     }
 }
 
-impl<'a, TW, F, OS: io::OStream<TW>> Mask<&'a NTrytes<TW>> for Context<TW, F, OS>
+/// Mask `N` elements of a fixed-size array one by one, the count is known and is not encoded.
+impl<'a, TW, F, T: 'a, OS: io::OStream<TW>, const N: usize> Mask<&'a [T; N]> for Context<TW, F, OS>
 where
-    TW: SpongosTbitWord + trinary::TritWord,
-    F: PRP<TW>,
+    T: MaskElement<Self>,
 {
-    fn mask(&mut self, ntrytes: &'a NTrytes<TW>) -> Fallible<&mut Self> {
-        Ok(wrap_mask_trits(self.as_mut(), (ntrytes.0).slice())?.as_mut())
+    fn mask(&mut self, xs: &'a [T; N]) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "[T; N]", |ctx| {
+            for x in xs.iter() {
+                x.mask_element(ctx)?;
+            }
+            Ok(ctx)
+        })
     }
 }
 
@@ -503,15 +1845,17 @@ where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn mask(&mut self, trytes: &'a Trytes<TW>) -> Fallible<&mut Self> {
-        ensure!(
-            (trytes.0).size() % 3 == 0,
-            "Trit size of `trytes` must be a multiple of 3: {}.",
-            (trytes.0).size()
-        );
-        let size = Size((trytes.0).size() / 3);
-        self.mask(&size)?;
-        Ok(wrap_mask_trits(self.as_mut(), (trytes.0).slice())?.as_mut())
+    fn mask(&mut self, trytes: &'a Trytes<TW>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "Trytes", |ctx| {
+            ensure!(
+                (trytes.0).size() % 3 == 0,
+                "Trit size of `trytes` must be a multiple of 3: {}.",
+                (trytes.0).size()
+            );
+            let size = Size((trytes.0).size() / 3);
+            ctx.mask(&size)?;
+            Ok(wrap_mask_trits(ctx.as_mut(), (trytes.0).slice())?.as_mut())
+        })
     }
 }
 
@@ -520,8 +1864,10 @@ where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn mask(&mut self, ntru_pk: &'a ntru::PublicKey<TW, F>) -> Fallible<&mut Self> {
-        Ok(wrap_mask_trits(self.as_mut(), ntru_pk.tbits().slice())?.as_mut())
+    fn mask(&mut self, ntru_pk: &'a ntru::PublicKey<TW, F>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "ntru::PublicKey", |ctx| {
+            Ok(wrap_mask_trits(ctx.as_mut(), ntru_pk.tbits().slice())?.as_mut())
+        })
     }
 }
 
@@ -531,8 +1877,10 @@ where
     F: PRP<TW>,
     P: mss::Parameters<TW>,
 {
-    fn mask(&mut self, mss_pk: &'a mss::PublicKey<TW, P>) -> Fallible<&mut Self> {
-        Ok(wrap_mask_trits(self.as_mut(), mss_pk.tbits().slice())?.as_mut())
+    fn mask(&mut self, mss_pk: &'a mss::PublicKey<TW, P>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mask", "mss::PublicKey", |ctx| {
+            Ok(wrap_mask_trits(ctx.as_mut(), mss_pk.tbits().slice())?.as_mut())
+        })
     }
 }
 
@@ -558,14 +1906,16 @@ impl<TW, F, OS: io::OStream<TW>> Wrap<TW> for SkipContext<TW, F, OS>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
-    fn wrap3(&mut self, trint3: Trint3) -> Fallible<&mut Self> {
+    fn wrap3(&mut self, trint3: Trint3) -> Result<&mut Self, Protobuf3Error> {
         let mut slice = self.ctx.stream.try_advance(3)?;
         slice.put3(trint3);
+        self.ctx.track(3, Region::Skip);
         Ok(self)
     }
-    fn wrapn(&mut self, trits: TbitSlice<TW>) -> Fallible<&mut Self> {
+    fn wrapn(&mut self, trits: TbitSlice<TW>) -> Result<&mut Self, Protobuf3Error> {
         let slice = self.ctx.stream.try_advance(trits.size())?;
         trits.copy(&slice);
+        self.ctx.track(trits.size(), Region::Skip);
         Ok(self)
     }
 }
@@ -573,7 +1923,7 @@ where
 fn wrap_skip_trint3<'a, TW, F, OS: io::OStream<TW>>(
     ctx: &'a mut SkipContext<TW, F, OS>,
     trint3: Trint3,
-) -> Fallible<&'a mut SkipContext<TW, F, OS>>
+) -> Result<&'a mut SkipContext<TW, F, OS>, Protobuf3Error>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
@@ -582,7 +1932,7 @@ where
 fn wrap_skip_size<'a, TW, F, OS: io::OStream<TW>>(
     ctx: &'a mut SkipContext<TW, F, OS>,
     size: Size,
-) -> Fallible<&'a mut SkipContext<TW, F, OS>>
+) -> Result<&'a mut SkipContext<TW, F, OS>, Protobuf3Error>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
@@ -591,18 +1941,37 @@ where
 fn wrap_skip_trits<'a, TW, F, OS: io::OStream<TW>>(
     ctx: &'a mut SkipContext<TW, F, OS>,
     trits: TbitSlice<TW>,
-) -> Fallible<&'a mut SkipContext<TW, F, OS>>
+) -> Result<&'a mut SkipContext<TW, F, OS>, Protobuf3Error>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
     ctx.wrapn(trits)
 }
+#[cfg(feature = "ed25519-dalek")]
+fn wrap_skip_bytes_body<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut SkipContext<TW, F, OS>,
+    bytes: &[u8],
+) -> Result<&'a mut SkipContext<TW, F, OS>, Protobuf3Error>
+where
+    TW: BasicTbitWord + trinary::TritWord,
+{
+    wrap_bytes_body(ctx, bytes)
+}
+fn wrap_skip_bytes<'a, TW, F, OS: io::OStream<TW>>(
+    ctx: &'a mut SkipContext<TW, F, OS>,
+    bytes: &[u8],
+) -> Result<&'a mut SkipContext<TW, F, OS>, Protobuf3Error>
+where
+    TW: BasicTbitWord + trinary::TritWord,
+{
+    wrap_bytes(ctx, bytes)
+}
 
 impl<'a, TW, F, OS: io::OStream<TW>> Skip<&'a Trint3> for Context<TW, F, OS>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
-    fn skip(&mut self, trint3: &'a Trint3) -> Fallible<&mut Self> {
+    fn skip(&mut self, trint3: &'a Trint3) -> Result<&mut Self, Protobuf3Error> {
         Ok(wrap_skip_trint3(self.as_mut(), *trint3)?.as_mut())
     }
 }
@@ -611,7 +1980,7 @@ impl<TW, F, OS: io::OStream<TW>> Skip<Trint3> for Context<TW, F, OS>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
-    fn skip(&mut self, val: Trint3) -> Fallible<&mut Self> {
+    fn skip(&mut self, val: Trint3) -> Result<&mut Self, Protobuf3Error> {
         self.skip(&val)
     }
 }
@@ -620,7 +1989,7 @@ impl<'a, TW, F, OS: io::OStream<TW>> Skip<&'a Size> for Context<TW, F, OS>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
-    fn skip(&mut self, size: &'a Size) -> Fallible<&mut Self> {
+    fn skip(&mut self, size: &'a Size) -> Result<&mut Self, Protobuf3Error> {
         Ok(wrap_skip_size(self.as_mut(), *size)?.as_mut())
     }
 }
@@ -629,7 +1998,7 @@ impl<TW, F, OS: io::OStream<TW>> Skip<Size> for Context<TW, F, OS>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
-    fn skip(&mut self, val: Size) -> Fallible<&mut Self> {
+    fn skip(&mut self, val: Size) -> Result<&mut Self, Protobuf3Error> {
         self.skip(&val)
     }
 }
@@ -638,7 +2007,7 @@ impl<'a, TW, F, OS: io::OStream<TW>> Skip<&'a NTrytes<TW>> for Context<TW, F, OS
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
-    fn skip(&mut self, ntrytes: &'a NTrytes<TW>) -> Fallible<&mut Self> {
+    fn skip(&mut self, ntrytes: &'a NTrytes<TW>) -> Result<&mut Self, Protobuf3Error> {
         Ok(wrap_skip_trits(self.as_mut(), (ntrytes.0).slice())?.as_mut())
     }
 }
@@ -647,24 +2016,57 @@ impl<'a, TW, F, OS: io::OStream<TW>> Skip<&'a Trytes<TW>> for Context<TW, F, OS>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
-    fn skip(&mut self, trytes: &'a Trytes<TW>) -> Fallible<&mut Self> {
+    fn skip(&mut self, trytes: &'a Trytes<TW>) -> Result<&mut Self, Protobuf3Error> {
         self.skip(Size((trytes.0).size() / 3))?;
         Ok(wrap_skip_trits(self.as_mut(), (trytes.0).slice())?.as_mut())
     }
 }
 
-/// Commit Spongos.
-impl<TW, F, OS> Commit for Context<TW, F, OS>
+/// Byte string is encoded the same way as in `Absorb<&[u8]>` but is not absorbed,
+/// eg. transport-level headers that must not affect the authenticated state.
+impl<'a, TW, F, OS: io::OStream<TW>> Skip<&'a [u8]> for Context<TW, F, OS>
+where
+    TW: BasicTbitWord + trinary::TritWord,
+{
+    fn skip(&mut self, bytes: &'a [u8]) -> Result<&mut Self, Protobuf3Error> {
+        Ok(wrap_skip_bytes(self.as_mut(), bytes)?.as_mut())
+    }
+}
+
+/// Commit Spongos and the output stream, eg. `io::WrapStream` writes out buffered trits.
+impl<TW, F, OS: io::OStream<TW>> Commit for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
 {
-    fn commit(&mut self) -> Fallible<&mut Self> {
+    fn commit(&mut self) -> Result<&mut Self, Protobuf3Error> {
         self.spongos.commit();
+        self.stream.commit();
+        self.track(0, |_| Region::Commit);
         Ok(self)
     }
 }
 
+impl<TW, F, OS> Context<TW, F, OS>
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW>,
+{
+    /// Current position of Spongos in the outer state block, `0 <= pos < Spongos::RATE`.
+    pub fn spongos_position(&self) -> usize {
+        self.spongos.pos()
+    }
+
+    /// Commit Spongos state as `commit` does and return the number of tbits
+    /// the incomplete outer state block is implicitly padded with, `0` if already committed.
+    pub fn commit_padding(&mut self) -> Result<usize, Protobuf3Error> {
+        let pos = self.spongos.pos();
+        self.spongos.commit();
+        self.track(0, |_| Region::Commit);
+        Ok(if pos == 0 { 0 } else { F::RATE - pos })
+    }
+}
+
 impl<'a, TW, F, OS: io::OStream<TW>, P> Mssig<&'a mss::PrivateKey<TW, P>, &'a External<NTrytes<TW>>>
     for Context<TW, F, OS>
 where
@@ -676,16 +2078,19 @@ where
         &mut self,
         sk: &'a mss::PrivateKey<TW, P>,
         hash: &'a External<NTrytes<TW>>,
-    ) -> Fallible<&mut Self> {
-        ensure!(
-            P::HASH_SIZE == ((hash.0).0).size(),
-            "Trit size of `external tryte hash[n]` to be signed with MSS must be equal {} trits.",
-            P::HASH_SIZE
-        );
-        ensure!(sk.private_keys_left() > 0, "All WOTS private keys in MSS Merkle tree have been exhausted, nothing to sign hash with.");
-        let sig_slice = self.stream.try_advance(P::signature_size(sk.height()))?;
-        sk.sign(((hash.0).0).slice(), sig_slice);
-        Ok(self)
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mssig", "mss::PrivateKey", |ctx| {
+            ensure!(
+                P::HASH_SIZE == ((hash.0).0).size(),
+                "Trit size of `external tryte hash[n]` to be signed with MSS must be equal {} trits.",
+                P::HASH_SIZE
+            );
+            ensure!(sk.private_keys_left() > 0, "All WOTS private keys in MSS Merkle tree have been exhausted, nothing to sign hash with.");
+            let sig_slice = ctx.stream.try_advance(P::signature_size(sk.height()))?;
+            sk.sign(((hash.0).0).slice(), sig_slice);
+            ctx.track(0, |_| Region::Opaque("mssig"));
+            Ok(ctx)
+        })
     }
 }
 
@@ -700,13 +2105,15 @@ where
         &mut self,
         sk: &'a mut mss::PrivateKey<TW, P>,
         hash: &'a External<NTrytes<TW>>,
-    ) -> Fallible<&mut Self> {
-        // Force convert to `&self` with a smaller life-time.
-        <Self as Mssig<&'_ mss::PrivateKey<TW, P>, &'_ External<NTrytes<TW>>>>::mssig(
-            self, sk, hash,
-        )?;
-        sk.next();
-        Ok(self)
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mssig", "mss::PrivateKey", |ctx| {
+            // Force convert to `&ctx` with a smaller life-time.
+            <Self as Mssig<&'_ mss::PrivateKey<TW, P>, &'_ External<NTrytes<TW>>>>::mssig(
+                ctx, sk, hash,
+            )?;
+            sk.next();
+            Ok(ctx)
+        })
     }
 }
 
@@ -717,9 +2124,15 @@ where
     F: PRP<TW>,
     P: mss::Parameters<TW>,
 {
-    fn mssig(&mut self, sk: &'a mss::PrivateKey<TW, P>, _hash: MssHashSig) -> Fallible<&mut Self> {
-        let mut hash = External(NTrytes(Tbits::<TW>::zero(P::HASH_SIZE)));
-        self.squeeze(&mut hash)?.commit()?.mssig(sk, &hash)
+    fn mssig(
+        &mut self,
+        sk: &'a mss::PrivateKey<TW, P>,
+        _hash: MssHashSig,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mssig", "mss::PrivateKey", |ctx| {
+            let mut hash = External(NTrytes(Tbits::<TW>::zero(P::HASH_SIZE)));
+            ctx.squeeze(&mut hash)?.commit()?.mssig(sk, &hash)
+        })
     }
 }
 
@@ -734,9 +2147,37 @@ where
         &mut self,
         sk: &'a mut mss::PrivateKey<TW, P>,
         _hash: MssHashSig,
-    ) -> Fallible<&mut Self> {
-        let mut hash = External(NTrytes(Tbits::<TW>::zero(P::HASH_SIZE)));
-        self.squeeze(&mut hash)?.commit()?.mssig(sk, &hash)
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "mssig", "mss::PrivateKey", |ctx| {
+            let mut hash = External(NTrytes(Tbits::<TW>::zero(P::HASH_SIZE)));
+            ctx.squeeze(&mut hash)?.commit()?.mssig(sk, &hash)
+        })
+    }
+}
+
+#[cfg(feature = "ed25519-dalek")]
+impl<'a, TW, F, OS: io::OStream<TW>> Ed25519Sig<&'a Ed25519Keypair, Ed25519HashSig>
+    for Context<TW, F, OS>
+where
+    TW: IntTbitWord + SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn ed25519sig(
+        &mut self,
+        key: &'a Ed25519Keypair,
+        _hash: Ed25519HashSig,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "ed25519sig", "Ed25519Keypair", |ctx| {
+            use ed25519_dalek::Signer;
+
+            let mut hash = [0_u8; ED25519_HASH_BYTES];
+            ctx.spongos.squeeze_bytes(&mut hash);
+            ctx.spongos.commit();
+            let signature = key.sign(&hash);
+            wrap_skip_bytes_body(ctx.as_mut(), &signature.to_bytes())?;
+            ctx.track(0, |_| Region::Opaque("ed25519sig"));
+            Ok(ctx)
+        })
     }
 }
 
@@ -762,18 +2203,46 @@ where
             &'a Tbits<TW>,
         ),
         secret: &'a NTrytes<TW>,
-    ) -> Fallible<&mut Self> {
-        //TODO: ensure!(ntru::KEY_SIZE == (secret.0).size(), "Trit size of `external tryte secret[n]` to be encapsulated with NTRU must be equal {} trits.", ntru::KEY_SIZE);
-
-        let ekey_slice = self.stream.try_advance(ntru::EKEY_SIZE)?;
-        (key.0).encrypt_with_spongos(
-            &mut self.spongos,
-            key.1,
-            (key.2).slice(),
-            (secret.0).slice(),
-            ekey_slice,
-        );
-        Ok(self)
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "ntrukem", "ntru::PublicKey", |ctx| {
+            //TODO: ensure!(ntru::KEY_SIZE == (secret.0).size(), "Trit size of `external tryte secret[n]` to be encapsulated with NTRU must be equal {} trits.", ntru::KEY_SIZE);
+
+            let ekey_slice = ctx.stream.try_advance(ntru::EKEY_SIZE)?;
+            (key.0).encrypt_with_spongos(
+                &mut ctx.spongos,
+                key.1,
+                (key.2).slice(),
+                (secret.0).slice(),
+                ekey_slice,
+            );
+            ctx.track(0, |_| Region::Opaque("ntrukem"));
+            Ok(ctx)
+        })
+    }
+}
+
+/// Randomness is drawn from the context RNG, see `set_rng`.
+impl<'a, TW, F, OS: io::OStream<TW>> Ntrukem<&'a ntru::PublicKey<TW, F>, &'a NTrytes<TW>>
+    for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+{
+    fn ntrukem(
+        &mut self,
+        key: &'a ntru::PublicKey<TW, F>,
+        secret: &'a NTrytes<TW>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "ntrukem", "ntru::PublicKey", |ctx| {
+            let rng = match &mut ctx.rng {
+                Some(rng) => rng,
+                None => bail!("RNG is not set."),
+            };
+            let ekey_slice = ctx.stream.try_advance(ntru::EKEY_SIZE)?;
+            key.encrypt_with_rng(&mut ctx.spongos, &mut **rng, (secret.0).slice(), ekey_slice);
+            ctx.track(0, |_| Region::Opaque("ntrukem"));
+            Ok(ctx)
+        })
     }
 }
 
@@ -781,12 +2250,161 @@ impl<C, TW, F, OS: io::OStream<TW>> Fork<C> for Context<TW, F, OS>
 where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW> + Clone,
-    C: for<'a> FnMut(&'a mut Self) -> Fallible<&'a mut Self>,
+    C: for<'a> FnMut(&'a mut Self) -> Result<&'a mut Self, Protobuf3Error>,
+{
+    fn fork(&mut self, mut cont: C) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "fork", |ctx| {
+            let saved_fork = ctx.spongos.fork();
+            ctx.track(0, |_| Region::Fork);
+            cont(ctx)?;
+            #[cfg(feature = "metrics")]
+            let permutations = ctx.spongos.permutation_count();
+            ctx.spongos = saved_fork;
+            #[cfg(feature = "metrics")]
+            ctx.spongos.set_permutation_count(permutations);
+            ctx.track(0, |_| Region::Unfork);
+            Ok(ctx)
+        })
+    }
+}
+
+impl<TW, F, OS: io::OStream<TW>> Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW> + Clone,
 {
-    fn fork(&mut self, mut cont: C) -> Fallible<&mut Self> {
+    /// Fork Spongos state and continue processing `cont` commands as `fork` does,
+    /// but return the resulting forked Spongos state instead of discarding it,
+    /// eg. to squeeze a per-fork commitment. The current Spongos state is not affected.
+    pub fn fork_capture<C>(&mut self, mut cont: C) -> Result<Spongos<TW, F>, Protobuf3Error>
+    where
+        C: for<'a> FnMut(&'a mut Self) -> Result<&'a mut Self, Protobuf3Error>,
+    {
         let saved_fork = self.spongos.fork();
+        self.track(0, |_| Region::Fork);
         cont(self)?;
-        self.spongos = saved_fork;
+        self.track(0, |_| Region::Unfork);
+        #[cfg(feature = "metrics")]
+        let permutations = self.spongos.permutation_count();
+        let forked = mem::replace(&mut self.spongos, saved_fork);
+        #[cfg(feature = "metrics")]
+        self.spongos.set_permutation_count(permutations);
+        Ok(forked)
+    }
+}
+
+impl<TW, F, OS: io::OStream<TW>> Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW> + Clone,
+{
+    /// Process items with `value_handle` as `repeated` does, but the handler can pause
+    /// processing after an item. In that case the returned `ResumeToken` captures Spongos
+    /// state and the number of remaining items and `None` is returned once all items are done.
+    ///
+    /// To continue pass the same `values_iter` (its processed items are skipped) and the token,
+    /// Spongos state is restored from the token, so the context can be a new one,
+    /// eg. with a different output buffer.
+    pub fn repeated_resumable<I, C>(
+        &mut self,
+        values_iter: I,
+        resume: Option<ResumeToken<TW, F>>,
+        mut value_handle: C,
+    ) -> Result<Option<ResumeToken<TW, F>>, Protobuf3Error>
+    where
+        I: iter::ExactSizeIterator,
+        C: for<'a> FnMut(
+            &'a mut Self,
+            <I as iter::Iterator>::Item,
+        ) -> Result<RepeatedStep, Protobuf3Error>,
+    {
+        let total = values_iter.len();
+        let mut processed = 0;
+        if let Some(token) = resume {
+            ensure!(
+                token.processed + token.remaining == total,
+                "Resumed {} + {} items, but {} items are given.",
+                token.processed,
+                token.remaining,
+                total
+            );
+            self.spongos = token.spongos;
+            processed = token.processed;
+            self.track(0, |_| Region::Opaque("repeated_resumable"));
+        }
+
+        for item in values_iter.skip(processed) {
+            let step = value_handle(self, item)?;
+            processed += 1;
+            if step == RepeatedStep::Pause && processed < total {
+                return Ok(Some(ResumeToken {
+                    spongos: self.spongos.fork(),
+                    processed,
+                    remaining: total - processed,
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<TW, F, OS: io::OStream<TW>> Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord + Send + Sync,
+    F: PRP<TW> + Clone + Send + Sync,
+{
+    /// Parallel version of
+    /// `repeated(items.iter(), |ctx, item| ctx.fork(|ctx| { item.wrap_forked(ctx)?; Ok(ctx) }))`.
+    ///
+    /// Forked Spongos states are discarded, so each item is wrapped into its own buffer
+    /// starting from a copy of the current state on the rayon thread pool. The buffers are
+    /// then put into the stream in item order, the output is identical to the sequential one.
+    pub fn repeated_fork_par<T>(&mut self, items: &[T]) -> Result<&mut Self, Protobuf3Error>
+    where
+        T: ForkedItem<TW, F> + Sync,
+    {
+        use rayon::prelude::*;
+
+        let start = &self.spongos;
+        let bufs = items
+            .par_iter()
+            .map(|item| -> Result<(Tbits<TW>, u64), Protobuf3Error> {
+                let mut sizeof_ctx = sizeof::Context::<TW, F>::new();
+                item.sizeof_forked(&mut sizeof_ctx)?;
+                let mut buf = Tbits::zero(sizeof_ctx.get_size());
+                // Permutations performed in the fork, counted as in the sequential version.
+                let permutations = {
+                    let mut ctx = Context {
+                        spongos: start.fork(),
+                        stream: buf.slice_mut(),
+                        start: 0,
+                        dump_sink: DumpSink::default(),
+                        regions: None,
+                        rng: None,
+                    };
+                    item.wrap_forked(&mut ctx)?;
+                    ensure!(ctx.stream.is_empty(), "Forked item size mismatch.");
+                    #[cfg(feature = "metrics")]
+                    let permutations = ctx.spongos.permutation_count() - start.permutation_count();
+                    #[cfg(not(feature = "metrics"))]
+                    let permutations = 0_u64;
+                    permutations
+                };
+                Ok((buf, permutations))
+            })
+            .collect::<Result<Vec<_>, Protobuf3Error>>()?;
+
+        for (buf, _) in bufs.iter() {
+            buf.slice().copy(&self.stream.try_advance(buf.size())?);
+        }
+        #[cfg(feature = "metrics")]
+        {
+            let forked: u64 = bufs.iter().map(|(_, n)| n).sum();
+            self.spongos
+                .set_permutation_count(self.spongos.permutation_count() + forked);
+        }
+        self.track(0, |_| Region::Opaque("repeated_fork_par"));
         Ok(self)
     }
 }
@@ -796,37 +2414,65 @@ where
     TW: SpongosTbitWord + trinary::TritWord,
     F: PRP<TW>,
     I: iter::Iterator,
-    C: for<'a> FnMut(&'a mut Self, <I as iter::Iterator>::Item) -> Fallible<&'a mut Self>,
+    C: for<'a> FnMut(
+        &'a mut Self,
+        <I as iter::Iterator>::Item,
+    ) -> Result<&'a mut Self, Protobuf3Error>,
 {
-    fn repeated(&mut self, values_iter: I, mut value_handle: C) -> Fallible<&mut Self> {
-        values_iter.fold(Ok(self), |rctx, item| -> Fallible<&mut Self> {
-            match rctx {
-                Ok(ctx) => value_handle(ctx, item),
-                Err(e) => Err(e),
-            }
-        })
+    fn repeated(
+        &mut self,
+        values_iter: I,
+        mut value_handle: C,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        values_iter.fold(
+            Ok(self),
+            |rctx, item| -> Result<&mut Self, Protobuf3Error> {
+                match rctx {
+                    Ok(ctx) => value_handle(ctx, item),
+                    Err(e) => Err(e),
+                }
+            },
+        )
+    }
+}
+impl<C, TW, F, OS: io::OStream<TW>> RepeatedN<C> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+    C: for<'a> FnMut(&'a mut Self, usize) -> Result<&'a mut Self, Protobuf3Error>,
+{
+    fn repeated_n(&mut self, n: usize, mut value_handle: C) -> Result<&mut Self, Protobuf3Error> {
+        for i in 0..n {
+            value_handle(self, i)?;
+        }
+        Ok(self)
     }
 }
+
 impl<'a, TW, F, T: 'a + AbsorbFallback<TW, F>, OS: io::OStream<TW>> Absorb<&'a Fallback<T>>
     for Context<TW, F, OS>
 {
-    fn absorb(&mut self, val: &'a Fallback<T>) -> Fallible<&mut Self> {
-        (val.0).wrap_absorb(self)?;
-        Ok(self)
+    fn absorb(&mut self, val: &'a Fallback<T>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "Fallback<T>", |ctx| {
+            (val.0).wrap_absorb(ctx)?;
+            Ok(ctx)
+        })
     }
 }
 impl<'a, TW, F, T: 'a + AbsorbExternalFallback<TW, F>, OS: io::OStream<TW>>
     Absorb<External<Fallback<&'a T>>> for Context<TW, F, OS>
 {
-    fn absorb(&mut self, val: External<Fallback<&'a T>>) -> Fallible<&mut Self> {
-        ((val.0).0).wrap_absorb_external(self)?;
-        Ok(self)
+    fn absorb(&mut self, val: External<Fallback<&'a T>>) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "absorb", "External<Fallback<T>>", |ctx| {
+            ((val.0).0).wrap_absorb_external(ctx)?;
+            Ok(ctx)
+        })
     }
 }
 impl<'a, TW, F, T: 'a + SkipFallback<TW, F>, OS: io::OStream<TW>> Skip<&'a Fallback<T>>
     for Context<TW, F, OS>
 {
-    fn skip(&mut self, val: &'a Fallback<T>) -> Fallible<&mut Self> {
+    fn skip(&mut self, val: &'a Fallback<T>) -> Result<&mut Self, Protobuf3Error> {
         (val.0).wrap_skip(self)?;
         Ok(self)
     }
@@ -838,12 +2484,38 @@ where
     TW: SpongosTbitWord,
     F: PRP<TW>,
 {
-    fn join(&mut self, store: &'a S, link: &'a L) -> Fallible<&mut Self> {
-        //TODO: Return and use info.
-        let (mut s, _i) = store.lookup(link)?;
-        link.wrap_skip(self)?;
-        self.spongos.join(&mut s);
-        Ok(self)
+    fn join(&mut self, store: &'a S, link: &'a L) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "join", std::any::type_name::<L>(), |ctx| {
+            //TODO: Return and use info.
+            let (mut s, _i) = store.lookup(link)?;
+            link.wrap_skip(ctx)?;
+            ctx.spongos.join(&mut s);
+            ctx.track(0, |_| Region::Opaque("join"));
+            Ok(ctx)
+        })
+    }
+}
+
+impl<'a, TW, F, L: SkipFallback<TW, F>, S: LinkStore<TW, F, L>, OS: io::OStream<TW>>
+    JoinVerified<&'a L, &'a S, &'a NTrytes<TW>> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW> + Clone,
+{
+    fn join_verified(
+        &mut self,
+        store: &'a S,
+        link: &'a L,
+        expected_mac: &'a NTrytes<TW>,
+    ) -> Result<&mut Self, Protobuf3Error> {
+        traced!(self, "join_verified", std::any::type_name::<L>(), |ctx| {
+            let (mut s, _i) = store.lookup(link)?;
+            check_link_commitment(&s, expected_mac)?;
+            link.wrap_skip(ctx)?;
+            ctx.spongos.join(&mut s);
+            ctx.track(0, |_| Region::Opaque("join"));
+            Ok(ctx)
+        })
     }
 }
 
@@ -851,7 +2523,7 @@ where
 impl<'a, L, S: LinkStore<L>, TW, F, OS: io::OStream<TW>> Join<&'a L, &'a S> for Context<TW, F, OS> where
     Self: Skip<&'a L>
 {
-    fn join(&mut self, store: &'a S, link: &'a L) -> Fallible<&mut Self> {
+    fn join(&mut self, store: &'a S, link: &'a L) -> Result<&mut Self, Protobuf3Error> {
         let (mut s, i) = store.lookup(link)?;
         self.skip(link)?;
         self.spongos.join(&mut s);
@@ -860,17 +2532,53 @@ impl<'a, L, S: LinkStore<L>, TW, F, OS: io::OStream<TW>> Join<&'a L, &'a S> for
 }
  */
 
+/// Wrapped values are trusted, guards are not checked.
+impl<TW, F, OS> Guard for Context<TW, F, OS> {
+    fn guard(&mut self, _cond: bool, _err: &'static str) -> Result<&mut Self, Protobuf3Error> {
+        Ok(self)
+    }
+}
+
+impl<'a, TW, F, OS: io::OStream<TW>, T: 'a + ?Sized> SealedMask<&'a T> for Context<TW, F, OS>
+where
+    TW: SpongosTbitWord + trinary::TritWord,
+    F: PRP<TW>,
+    Self: Mask<&'a T>,
+{
+    fn sealed_mask(&mut self, field: &'a T) -> Result<&mut Self, Protobuf3Error> {
+        self.mask(field)?
+            .commit()?
+            .squeeze(&Mac(SEALED_MASK_TAG_SIZE))
+    }
+}
+
+impl<TW, F, OS: io::OStream<TW>> PadTo for Context<TW, F, OS>
+where
+    TW: BasicTbitWord,
+{
+    fn pad_to(&mut self, total: usize) -> Result<&mut Self, Protobuf3Error> {
+        let position = self.position();
+        ensure!(
+            position <= total,
+            Protobuf3Error::PadOverflow { position, total }
+        );
+        self.stream.try_advance(total - position)?.set_zero();
+        self.track(total - position, Region::Skip);
+        Ok(self)
+    }
+}
+
 impl<TW, F, OS: io::OStream<TW>> Dump for Context<TW, F, OS>
 where
     TW: BasicTbitWord + trinary::TritWord,
 {
-    fn dump<'a>(&mut self, args: std::fmt::Arguments<'a>) -> Fallible<&mut Self> {
-        println!(
+    fn dump<'a>(&mut self, args: std::fmt::Arguments<'a>) -> Result<&mut Self, Protobuf3Error> {
+        self.dump_sink.dump(format_args!(
             "{}: ostream=[{}] spongos=[{:?}]",
             args,
             self.stream.dump(),
             self.spongos
-        );
+        ));
         Ok(self)
     }
 }
@@ -0,0 +1,160 @@
+use thiserror::Error;
+
+/// Errors returned by commands.
+///
+/// Errors that callers may want to handle specifically have their own variants,
+/// the rest (eg. malformed arguments of the `sizeof` and `wrap` commands or errors
+/// returned by user closures) are kept as `Other` messages.
+#[derive(Clone, PartialEq, Eq, Debug, Error)]
+pub enum Protobuf3Error {
+    /// Input stream is exhausted before all the requested trits are read.
+    #[error("Input is too short: {requested} trits requested, {available} trits available.")]
+    TruncatedInput { requested: usize, available: usize },
+
+    /// Decoded value (eg. size, length, counter) failed a range check.
+    #[error("{0}")]
+    OutOfRange(String),
+
+    /// Length prefix of `field` brings the total size claimed by length prefixes to `claimed`
+    /// trits which exceeds the `max` size, see `unwrap::Context::set_max_message_size`.
+    #[error("Message is too large: `{field}` claims {claimed} trits in total, maximum is {max}.")]
+    MessageTooLarge {
+        field: &'static str,
+        claimed: usize,
+        max: usize,
+    },
+
+    /// Message of `position` trits can't be padded to `total` trits, see `command::PadTo`.
+    #[error("Message of {position} trits is longer than padded size {total}.")]
+    PadOverflow { position: usize, total: usize },
+
+    /// `size_t` is encoded with a zero most significant trint3, ie. padded with zero trits.
+    /// Each value has a unique encoding, so padded encodings are rejected to keep messages
+    /// non-malleable, eg. for content-addressed links.
+    #[error("Non-canonical `size_t` encoding.")]
+    NonCanonicalSizeT,
+
+    /// Decoded string is not valid UTF-8, eg. a masked string decrypted with a wrong key.
+    #[error("Decoded string is not valid UTF-8.")]
+    InvalidUtf8,
+
+    /// Tag of a `oneof` variant is not known, see `Oneof::known`.
+    #[error("Unknown oneof tag: {0}.")]
+    UnknownOneof(usize),
+
+    /// Squeezed MAC does not match.
+    #[error("Integrity is violated, bad MAC.")]
+    BadMac,
+
+    /// Recovered MSS public key does not match.
+    #[error("Authenticity is violated, bad signature.")]
+    MssVerify,
+
+    /// Ed25519 signature does not verify with the public key.
+    #[error("Authenticity is violated, bad Ed25519 signature.")]
+    Ed25519Verify,
+
+    /// Signature `index` of `Mssig::mssig_batch` failed for `reason`.
+    #[error("Signature {index} in batch failed: {reason}")]
+    MssigBatch { index: usize, reason: String },
+
+    /// NTRU key decapsulation failed: the capsule is well-formed but is not encapsulated
+    /// with the private key (or is corrupted undetectably), ie. "not a recipient".
+    #[error("Failed to decapsulate secret.")]
+    NtruDecapsulation,
+
+    /// NTRU capsule is not a valid encoding and can't be decapsulated with any private key,
+    /// see `unwrap::Context::try_ntrukem`.
+    #[error("Malformed NTRU capsule.")]
+    NtruMalformedCapsule,
+
+    /// Linked Spongos state retrieved from the link store doesn't match the expected
+    /// commitment, see `JoinVerified`.
+    #[error("Integrity is violated, bad linked state commitment.")]
+    BadLinkCommitment,
+
+    /// Link is not found in the link store.
+    #[error("Link not found.")]
+    UnknownLink,
+
+    /// Link has been evicted from a bounded link store, see `LruLinkStore`,
+    /// the linked message should be fetched again.
+    #[error("Link expired.")]
+    LinkExpired,
+
+    /// Link is not found in any of the named stores of a `MultiLinkStore`.
+    #[error("Link not found in stores {tried:?}.")]
+    UnknownLinkIn { tried: Vec<String> },
+
+    /// Tracked region of `command` (eg. a signature or a join) depends on the key material
+    /// and can't be re-masked in place, see `wrap::remask`.
+    #[error("Command `{0}` can't be remasked.")]
+    NotRemaskable(&'static str),
+
+    /// Error of the `std::io` writer or reader underlying a stream, see `io::WrapStream`.
+    #[error("IO error: {message}")]
+    Io {
+        kind: std::io::ErrorKind,
+        message: String,
+    },
+
+    /// Any other failure.
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<std::io::Error> for Protobuf3Error {
+    fn from(e: std::io::Error) -> Self {
+        Protobuf3Error::Io {
+            kind: e.kind(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<&str> for Protobuf3Error {
+    fn from(msg: &str) -> Self {
+        Protobuf3Error::Other(msg.to_string())
+    }
+}
+
+impl From<String> for Protobuf3Error {
+    fn from(msg: String) -> Self {
+        Protobuf3Error::Other(msg)
+    }
+}
+
+/// Return early with a `Protobuf3Error`: either a typed error
+/// `bail!(Protobuf3Error::BadMac)` or a message `bail!("Bad size: {}.", n)`.
+/// The error is converted with `into()`, so it can be used in functions returning
+/// other error types convertible from `Protobuf3Error`.
+macro_rules! bail {
+    ($e:expr) => {
+        return Err($crate::error::Protobuf3Error::from($e).into())
+    };
+    ($fmt:expr, $($arg:tt)*) => {
+        return Err($crate::error::Protobuf3Error::Other(format!($fmt, $($arg)*)).into())
+    };
+}
+
+/// Return early with a `Protobuf3Error` unless the condition holds, see `bail`.
+macro_rules! ensure {
+    ($cond:expr) => {
+        if $cond {
+        } else {
+            bail!(concat!("Condition failed: `", stringify!($cond), "`"));
+        }
+    };
+    ($cond:expr, $e:expr) => {
+        if $cond {
+        } else {
+            bail!($e);
+        }
+    };
+    ($cond:expr, $fmt:expr, $($arg:tt)*) => {
+        if $cond {
+        } else {
+            bail!($fmt, $($arg)*);
+        }
+    };
+}
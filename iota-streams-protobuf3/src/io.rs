@@ -1,8 +1,17 @@
 //! Lightweight abstraction, a trinary equivalent of `Write` trait allowing access to trinary slices.
 
-use failure::{bail, ensure, Fallible};
 use iota_streams_core::tbits::{word::BasicTbitWord, TbitSlice, TbitSliceMut};
 
+use crate::error::Protobuf3Error;
+
+mod stream;
+pub use stream::*;
+
+#[cfg(feature = "tokio")]
+mod async_stream;
+#[cfg(feature = "tokio")]
+pub use async_stream::*;
+
 /// Write
 pub trait OStream<TW> {
     /// Try advance and panic in case of error.
@@ -13,11 +22,25 @@ pub trait OStream<TW> {
     }
 
     /// Try put n tbits into the stream, returning a slice to the buffer.
-    fn try_advance<'a>(&'a mut self, n: usize) -> Fallible<TbitSliceMut<'a, TW>>;
+    fn try_advance<'a>(&'a mut self, n: usize) -> Result<TbitSliceMut<'a, TW>, Protobuf3Error>;
 
     /// Commit advanced buffers to the internal sink.
     fn commit(&mut self);
 
+    /// Offset of the next advanced slice in tbits. Only differences of positions are meaningful:
+    /// a slice counts tbits dropped before it was used as a stream, see `wrap::Context::position`
+    /// for the offset within a message. Streams that don't track the position return 0,
+    /// commands depending on the position (eg. `PadTo`) can't be used with them.
+    fn position(&self) -> usize {
+        0
+    }
+
+    /// Step back over the last `n` advanced tbits so that they can be written again.
+    /// Streams writing into an external sink can't be rewound.
+    fn rewind(&mut self, _n: usize) -> Result<(), Protobuf3Error> {
+        bail!("Output stream can't be rewound.")
+    }
+
     /// Dump stream debug info.
     fn dump(&self) -> String {
         String::new()
@@ -34,11 +57,22 @@ pub trait IStream<TW> {
     }
 
     /// Try get n tbits from the stream, returning a slice to the buffer.
-    fn try_advance<'a>(&'a mut self, n: usize) -> Fallible<TbitSlice<'a, TW>>;
+    fn try_advance<'a>(&'a mut self, n: usize) -> Result<TbitSlice<'a, TW>, Protobuf3Error>;
 
     /// Commit advanced buffers from the internal sources.
     fn commit(&mut self);
 
+    /// Offset of the next advanced slice in tbits, see `OStream::position`.
+    fn position(&self) -> usize {
+        0
+    }
+
+    /// Step back over the last `n` advanced tbits so that they can be read again.
+    /// Streams reading from an external source can't be rewound.
+    fn rewind(&mut self, _n: usize) -> Result<(), Protobuf3Error> {
+        bail!("Input stream can't be rewound.")
+    }
+
     /// Dump stream debug info.
     fn dump(&self) -> String {
         String::new()
@@ -49,11 +83,22 @@ impl<'b, TW> OStream<TW> for TbitSliceMut<'b, TW>
 where
     TW: BasicTbitWord,
 {
-    fn try_advance<'a>(&'a mut self, n: usize) -> Fallible<TbitSliceMut<'a, TW>> {
+    fn try_advance<'a>(&'a mut self, n: usize) -> Result<TbitSliceMut<'a, TW>, Protobuf3Error> {
         ensure!(n <= self.size(), "Output slice too short.");
         Ok(self.advance(n))
     }
     fn commit(&mut self) {}
+    fn position(&self) -> usize {
+        self.dropped_size()
+    }
+    fn rewind(&mut self, n: usize) -> Result<(), Protobuf3Error> {
+        ensure!(
+            n <= self.dropped_size(),
+            "Can't rewind past the start of the slice."
+        );
+        unsafe { self.pickup_mut(n) };
+        Ok(())
+    }
     fn dump(&self) -> String {
         format!("{:?}", self)
     }
@@ -63,11 +108,28 @@ impl<'b, TW> IStream<TW> for TbitSlice<'b, TW>
 where
     TW: BasicTbitWord,
 {
-    fn try_advance<'a>(&'a mut self, n: usize) -> Fallible<TbitSlice<'a, TW>> {
-        ensure!(n <= self.size(), "Input slice too short.");
+    fn try_advance<'a>(&'a mut self, n: usize) -> Result<TbitSlice<'a, TW>, Protobuf3Error> {
+        ensure!(
+            n <= self.size(),
+            Protobuf3Error::TruncatedInput {
+                requested: n,
+                available: self.size(),
+            }
+        );
         Ok(self.advance(n))
     }
     fn commit(&mut self) {}
+    fn position(&self) -> usize {
+        self.dropped_size()
+    }
+    fn rewind(&mut self, n: usize) -> Result<(), Protobuf3Error> {
+        ensure!(
+            n <= self.dropped_size(),
+            "Can't rewind past the start of the slice."
+        );
+        unsafe { self.pickup_mut(n) };
+        Ok(())
+    }
     fn dump(&self) -> String {
         format!("{:?}", self)
     }
@@ -80,10 +142,13 @@ impl<TW> OStream<TW> for NoOStream {
         assert!(false, "Advance can't be implemented for NoOStream");
         self.try_advance(n).unwrap()
     }
-    fn try_advance<'a>(&'a mut self, _n: usize) -> Fallible<TbitSliceMut<'a, TW>> {
+    fn try_advance<'a>(&'a mut self, _n: usize) -> Result<TbitSliceMut<'a, TW>, Protobuf3Error> {
         bail!("Advance can't be implemented for NoOStream")
     }
     fn commit(&mut self) {}
+    fn rewind(&mut self, _n: usize) -> Result<(), Protobuf3Error> {
+        Ok(())
+    }
 }
 
 pub struct NoIStream;
@@ -93,58 +158,67 @@ impl<TW> IStream<TW> for NoIStream {
         assert!(false, "Advance can't be implemented for NoIStream");
         self.try_advance(n).unwrap()
     }
-    fn try_advance<'a>(&'a mut self, _n: usize) -> Fallible<TbitSlice<'a, TW>> {
+    fn try_advance<'a>(&'a mut self, _n: usize) -> Result<TbitSlice<'a, TW>, Protobuf3Error> {
         bail!("Advance can't be implemented for NoIStream")
     }
     fn commit(&mut self) {}
+    fn rewind(&mut self, _n: usize) -> Result<(), Protobuf3Error> {
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use iota_streams_core::sponge::spongos::Spongos;
-    use iota_streams_core::tbits::{TbitSlice, TbitSliceMut, Tbits};
+    use iota_streams_core::sponge::{prp::troika::Troika, spongos::Spongos};
+    use iota_streams_core::tbits::{trinary::Trit, TbitSlice, TbitSliceMut, Tbits};
     use std::str::FromStr;
 
-    fn wrap_absorb_tbits<OS: OStream>(x: TbitSlice, s: &mut Spongos, os: &mut OS) -> () {
+    fn wrap_absorb_tbits<OS: OStream<Trit>>(
+        x: TbitSlice<Trit>,
+        s: &mut Spongos<Trit, Troika>,
+        os: &mut OS,
+    ) {
         let n = x.size();
         let t = os.advance(n);
-        x.copy(t);
+        x.copy(&t);
         s.absorb(x);
     }
 
-    fn unwrap_absorb_tbits<IS: IStream>(
-        x: TbitSliceMut,
-        s: &mut Spongos,
+    fn unwrap_absorb_tbits<IS: IStream<Trit>>(
+        x: TbitSliceMut<Trit>,
+        s: &mut Spongos<Trit, Troika>,
         is: &mut IS,
-    ) -> Fallible<()> {
+    ) -> Result<(), Protobuf3Error> {
         let n = x.size();
         let t = is.try_advance(n)?;
-        t.copy(x);
-        s.absorb(x.as_const());
+        t.copy(&x);
+        s.absorb(unsafe { x.as_const() });
         Ok(())
     }
 
     #[test]
     pub fn wrap_unwrap() {
-        let x = Tbits::from_str("ABC").unwrap();
-        let mut y = Tbits::zero(x.size());
+        let x = Tbits::<Trit>::from_str("ABC").unwrap();
+        let mut y = Tbits::<Trit>::zero(x.size());
 
-        let mut buf = Tbits::zero(x.size());
+        let mut buf = Tbits::<Trit>::zero(x.size());
 
         let tag = {
-            let mut s = Spongos::init();
+            let mut s = Spongos::<Trit, Troika>::init();
             let mut b = buf.slice_mut();
             wrap_absorb_tbits(x.slice(), &mut s, &mut b);
-            s.squeeze_tbits(81);
+            s.commit();
+            s.squeeze_tbits(81)
         };
 
         let tag2 = {
-            let mut s = Spongos::init();
+            let mut s = Spongos::<Trit, Troika>::init();
             let mut b = buf.slice();
             let r = unwrap_absorb_tbits(y.slice_mut(), &mut s, &mut b);
             assert!(r.is_ok());
-            s.squeeze_tbits(81);
+            s.commit();
+            s.squeeze_tbits(81)
         };
 
         assert_eq!(x, y);
@@ -0,0 +1,144 @@
+//! Streaming adapters of `OStream` and `IStream` over tokio writers and readers.
+//!
+//! Commands are synchronous and never block on IO here: the adapters keep the packed trits
+//! in memory and the IO is done by their async methods in between commands.
+//! `AsyncWrapStream` buffers the output of commands until `flush` or `finish` is awaited,
+//! `AsyncUnwrapStream` serves commands from trits prefetched by `fill` or `fill_to_end`
+//! and fails with `TruncatedInput` when a command needs more trits than were prefetched.
+//! The methods just await the IO, so the adapters work on any runtime, including
+//! the current-thread one, and the writer's backpressure suspends the wrapping task.
+//!
+//! Trits are packed the same way as by `WrapStream` and `UnwrapStream`.
+
+use std::collections::VecDeque;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use iota_streams_core::tbits::{trinary::Trit, TbitSlice, TbitSliceMut};
+
+use super::{IStream, OStream, UnwrapStream, WrapStream, READ_CHUNK_SIZE, TRITS_PER_BYTE};
+use crate::error::Protobuf3Error;
+
+/// Output stream writing packed trits into `tokio::io::AsyncWrite`, see `WrapStream`.
+///
+/// The output of commands is buffered until `flush` is awaited, flush after each command
+/// (or a few) to bound the buffer. Call `finish` after the last command, a stream dropped
+/// without `finish` discards the buffered output.
+pub struct AsyncWrapStream<W> {
+    inner: WrapStream<Vec<u8>>,
+    writer: W,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrapStream<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            inner: WrapStream::new(Vec::new()),
+            writer,
+        }
+    }
+
+    /// Write out the output of commands so far and flush, incomplete last byte is kept
+    /// until the following commands complete it.
+    pub async fn flush(&mut self) -> Result<(), Protobuf3Error> {
+        let bytes = self.inner.writer_mut()?;
+        self.writer.write_all(bytes).await?;
+        bytes.clear();
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    /// Write out all buffered trits padding the last byte with zero trits, flush and return the writer.
+    pub async fn finish(mut self) -> Result<W, Protobuf3Error> {
+        let bytes = self.inner.finish()?;
+        self.writer.write_all(&bytes).await?;
+        self.writer.flush().await?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: AsyncWrite + Unpin> OStream<Trit> for AsyncWrapStream<W> {
+    fn try_advance<'a>(&'a mut self, n: usize) -> Result<TbitSliceMut<'a, Trit>, Protobuf3Error> {
+        self.inner.try_advance(n)
+    }
+
+    fn commit(&mut self) {
+        self.inner.commit()
+    }
+
+    fn position(&self) -> usize {
+        self.inner.position()
+    }
+
+    fn dump(&self) -> String {
+        self.inner.dump()
+    }
+}
+
+/// Input stream reading packed trits from `tokio::io::AsyncRead`, see `UnwrapStream`.
+///
+/// Commands only consume trits prefetched with `fill` or `fill_to_end`,
+/// the sizes of fields to be unwrapped can be computed with `sizeof` context.
+pub struct AsyncUnwrapStream<R> {
+    inner: UnwrapStream<VecDeque<u8>>,
+    reader: R,
+    /// Scratch space for read bytes.
+    bytes: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncUnwrapStream<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            inner: UnwrapStream::new(VecDeque::new()),
+            reader,
+            bytes: vec![0; READ_CHUNK_SIZE],
+        }
+    }
+
+    /// Number of prefetched trits not yet consumed by commands.
+    pub fn available(&self) -> usize {
+        self.inner.tail_size() + self.inner.reader().len() * TRITS_PER_BYTE
+    }
+
+    /// Read until at least `n` trits are prefetched or the end of stream is reached,
+    /// in the latter case the next command fails with `TruncatedInput`.
+    pub async fn fill(&mut self, n: usize) -> Result<(), Protobuf3Error> {
+        while self.available() < n {
+            let left = n - self.available();
+            let m = std::cmp::min(left.div_ceil(TRITS_PER_BYTE), READ_CHUNK_SIZE);
+            let k = self.reader.read(&mut self.bytes[..m]).await?;
+            if k == 0 {
+                break;
+            }
+            self.inner.reader_mut().extend(&self.bytes[..k]);
+        }
+        Ok(())
+    }
+
+    /// Read until the end of stream.
+    pub async fn fill_to_end(&mut self) -> Result<(), Protobuf3Error> {
+        let mut bytes = Vec::new();
+        self.reader.read_to_end(&mut bytes).await?;
+        self.inner.reader_mut().extend(bytes);
+        Ok(())
+    }
+
+    /// Return the reader, prefetched trits are discarded.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: AsyncRead + Unpin> IStream<Trit> for AsyncUnwrapStream<R> {
+    fn try_advance<'a>(&'a mut self, n: usize) -> Result<TbitSlice<'a, Trit>, Protobuf3Error> {
+        self.inner.try_advance(n)
+    }
+
+    fn commit(&mut self) {}
+
+    fn position(&self) -> usize {
+        self.inner.position()
+    }
+
+    fn dump(&self) -> String {
+        self.inner.dump()
+    }
+}
@@ -0,0 +1,295 @@
+//! Streaming adapters of `OStream` and `IStream` over `std::io` writers and readers.
+//!
+//! Trits are packed 5 per byte, the same way as `B1T5` word: byte value is `t0 + 3*t1 + ... + 81*t4`.
+
+use std::io::{Read, Write};
+
+use iota_streams_core::tbits::{trinary::Trit, TbitSlice, TbitSliceMut, Tbits};
+
+use super::{IStream, OStream};
+use crate::error::Protobuf3Error;
+
+/// Number of trits packed into a byte.
+pub const TRITS_PER_BYTE: usize = 5;
+
+/// Max size of a single write to the underlying writer in bytes.
+pub const WRITE_CHUNK_SIZE: usize = 4096;
+
+/// Max size of a single read from the underlying reader in bytes.
+pub const READ_CHUNK_SIZE: usize = 4096;
+
+fn pack_trits(ts: &[Trit]) -> u8 {
+    ts.iter().rev().fold(0_u8, |b, t| b * 3 + t.0)
+}
+
+fn unpack_trits(mut b: u8, ts: &mut [Trit]) -> Result<(), Protobuf3Error> {
+    ensure!(b < 243, "Invalid packed trits byte: {}.", b);
+    for t in ts.iter_mut() {
+        *t = Trit(b % 3);
+        b /= 3;
+    }
+    Ok(())
+}
+
+/// Output stream writing packed trits into `std::io::Write` incrementally.
+///
+/// The slice advanced by a command is buffered and written out on the next advance
+/// or on `commit` (the wrap `Commit` command commits the stream), so memory is bounded
+/// by the largest field rather than by the whole message. Buffers larger than a write
+/// chunk are released once written.
+/// Call `finish` after the last command to write out the trailing partial byte and to get
+/// write errors, a stream dropped without `finish` writes out buffered trits ignoring errors.
+pub struct WrapStream<W: Write> {
+    /// The writer, taken out by `finish`.
+    writer: Option<W>,
+    /// Buffer of the last advanced slice.
+    buf: Tbits<Trit>,
+    /// Size of the last advanced slice, not yet written.
+    pending: usize,
+    /// Number of trits advanced so far.
+    position: usize,
+    /// Trits of the incomplete last byte.
+    tail: Vec<Trit>,
+    /// Scratch space for trits to be packed.
+    trits: Vec<Trit>,
+    /// Scratch space for packed bytes.
+    bytes: Vec<u8>,
+    /// Write error of `commit`, returned by the next advance or `finish`.
+    error: Option<Protobuf3Error>,
+}
+
+impl<W: Write> WrapStream<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Some(writer),
+            buf: Tbits::zero(0),
+            pending: 0,
+            position: 0,
+            tail: Vec::with_capacity(TRITS_PER_BYTE),
+            trits: vec![Trit(0); TRITS_PER_BYTE * WRITE_CHUNK_SIZE],
+            bytes: Vec::with_capacity(WRITE_CHUNK_SIZE),
+            error: None,
+        }
+    }
+
+    /// Write out the last advanced slice, incomplete byte is kept in `tail`.
+    fn write_pending(&mut self) -> Result<(), Protobuf3Error> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => bail!("Output stream is finished."),
+        };
+        let mut slice = self.buf.slice().take(self.pending);
+        self.pending = 0;
+        while !slice.is_empty() {
+            let k = self.tail.len();
+            let m = std::cmp::min(slice.size(), self.trits.len() - k);
+            self.trits[..k].copy_from_slice(&self.tail);
+            slice.take(m).get_tbits(&mut self.trits[k..k + m]);
+            slice = slice.drop(m);
+
+            let n = (k + m) / TRITS_PER_BYTE * TRITS_PER_BYTE;
+            self.bytes.clear();
+            self.bytes
+                .extend(self.trits[..n].chunks(TRITS_PER_BYTE).map(pack_trits));
+            writer.write_all(&self.bytes)?;
+            self.tail.clear();
+            self.tail.extend_from_slice(&self.trits[n..k + m]);
+        }
+        if self.trits.len() < self.buf.size() {
+            self.buf = Tbits::zero(0);
+        }
+        Ok(())
+    }
+
+    /// Write out all buffered trits padding the last byte with zero trits and flush.
+    fn write_all(&mut self) -> Result<(), Protobuf3Error> {
+        self.write_pending()?;
+        if let Some(writer) = &mut self.writer {
+            if !self.tail.is_empty() {
+                self.tail.resize(TRITS_PER_BYTE, Trit(0));
+                writer.write_all(&[pack_trits(&self.tail)])?;
+                self.tail.clear();
+            }
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write out the last advanced slice and return the writer, incomplete byte is kept in `tail`.
+    pub(super) fn writer_mut(&mut self) -> Result<&mut W, Protobuf3Error> {
+        self.write_pending()?;
+        match &mut self.writer {
+            Some(writer) => Ok(writer),
+            None => bail!("Output stream is finished."),
+        }
+    }
+
+    /// Write out all buffered trits padding the last byte with zero trits, flush and return the writer.
+    pub fn finish(mut self) -> Result<W, Protobuf3Error> {
+        self.write_all()?;
+        match self.writer.take() {
+            Some(writer) => Ok(writer),
+            None => bail!("Output stream is finished."),
+        }
+    }
+}
+
+impl<W: Write> Drop for WrapStream<W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.write_all();
+        }
+    }
+}
+
+impl<W: Write> OStream<Trit> for WrapStream<W> {
+    fn try_advance<'a>(&'a mut self, n: usize) -> Result<TbitSliceMut<'a, Trit>, Protobuf3Error> {
+        self.write_pending()?;
+        if self.buf.size() < n {
+            self.buf = Tbits::zero(n);
+        }
+        self.pending = n;
+        self.position += n;
+        Ok(self.buf.slice_mut().take(n))
+    }
+
+    /// Write out the last advanced slice, the error is returned by the next advance.
+    fn commit(&mut self) {
+        if let Err(e) = self.write_pending() {
+            self.error = Some(e);
+        }
+    }
+
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    fn dump(&self) -> String {
+        format!("pending={} tail={}", self.pending, self.tail.len())
+    }
+}
+
+/// Input stream reading packed trits from `std::io::Read` on demand.
+///
+/// Each advance reads just enough bytes to cover the requested trits,
+/// the remaining trits of the last read byte are kept for the next advance.
+/// Advanced size usually comes from the input itself, so the buffer grows with
+/// the trits actually read rather than being allocated for the requested size upfront.
+pub struct UnwrapStream<R> {
+    reader: R,
+    /// Buffer of the last advanced slice.
+    buf: Tbits<Trit>,
+    /// Trits read so far by the current advance.
+    read: Vec<Trit>,
+    /// Unused trits of the last read byte.
+    tail: Vec<Trit>,
+    /// Number of trits advanced so far.
+    position: usize,
+    /// Scratch space for read bytes.
+    bytes: Vec<u8>,
+    /// Scratch space for unpacked trits.
+    trits: Vec<Trit>,
+}
+
+impl<R: Read> UnwrapStream<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Tbits::zero(0),
+            read: Vec::new(),
+            tail: Vec::with_capacity(TRITS_PER_BYTE),
+            position: 0,
+            bytes: vec![0; READ_CHUNK_SIZE],
+            trits: vec![Trit(0); TRITS_PER_BYTE * READ_CHUNK_SIZE],
+        }
+    }
+
+    /// Return the reader, unused trits of the last read byte are discarded.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    pub(super) fn reader(&self) -> &R {
+        &self.reader
+    }
+
+    pub(super) fn reader_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Number of unused trits of the last read byte.
+    pub(super) fn tail_size(&self) -> usize {
+        self.tail.len()
+    }
+
+    /// Read up to `bytes.len()` bytes, fewer bytes are read only on premature end of stream.
+    /// Return the number of bytes read.
+    fn read_exact(reader: &mut R, bytes: &mut [u8]) -> Result<usize, Protobuf3Error> {
+        let mut k = 0;
+        while k < bytes.len() {
+            match reader.read(&mut bytes[k..]) {
+                Ok(0) => break,
+                Ok(l) => k += l,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(k)
+    }
+}
+
+impl<R: Read> IStream<Trit> for UnwrapStream<R> {
+    fn try_advance<'a>(&'a mut self, n: usize) -> Result<TbitSlice<'a, Trit>, Protobuf3Error> {
+        let k = std::cmp::min(n, self.tail.len());
+        self.read.clear();
+        self.read.extend_from_slice(&self.tail[..k]);
+        self.tail.drain(..k);
+
+        while self.read.len() < n {
+            let left = n - self.read.len();
+            let m = std::cmp::min(
+                (left + TRITS_PER_BYTE - 1) / TRITS_PER_BYTE,
+                READ_CHUNK_SIZE,
+            );
+            let k = Self::read_exact(&mut self.reader, &mut self.bytes[..m])?;
+            ensure!(
+                k == m,
+                Protobuf3Error::TruncatedInput {
+                    requested: n,
+                    available: self.read.len() + std::cmp::min(left, k * TRITS_PER_BYTE),
+                }
+            );
+            for (b, ts) in self.bytes[..m]
+                .iter()
+                .zip(self.trits.chunks_mut(TRITS_PER_BYTE))
+            {
+                unpack_trits(*b, ts)?;
+            }
+
+            let l = std::cmp::min(left, m * TRITS_PER_BYTE);
+            self.read.extend_from_slice(&self.trits[..l]);
+            self.tail
+                .extend_from_slice(&self.trits[l..m * TRITS_PER_BYTE]);
+        }
+
+        if self.buf.size() < n {
+            self.buf = Tbits::zero(n);
+        }
+        self.buf.slice_mut().take(n).put_tbits(&self.read);
+        self.position += n;
+        Ok(self.buf.slice().take(n))
+    }
+
+    fn commit(&mut self) {}
+
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    fn dump(&self) -> String {
+        format!("tail={}", self.tail.len())
+    }
+}
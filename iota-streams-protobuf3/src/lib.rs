@@ -1,11 +1,25 @@
 //! Protobuf3 is trinary data description language extended with commands for cryptographic processing.
 //! Protobuf3 is implemented as a EDSL in rust.
 
+/// Protobuf3 error kinds.
+#[macro_use]
+pub mod error;
+
 /// Protobuf3 command traits.
 pub mod command;
 
-/// Abstractions for input/output buffers. It does not support the actual IO.
+/// Abstractions for input/output buffers and streaming adapters over `std::io`.
 pub mod io;
 
+/// Round-trip harness for message formats, enabled with `testkit` feature.
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
+
+/// Known-answer test vectors for Spongos and message encoding.
+pub mod test_vectors;
+
+/// Variable-length `size_t` encoding for trit slices.
+pub mod trit_varint;
+
 /// Protobuf3 specific types.
 pub mod types;
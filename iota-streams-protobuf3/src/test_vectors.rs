@@ -0,0 +1,162 @@
+//! Known-answer test vectors for Troika Spongos and the trinary encoding of messages.
+//!
+//! Trits are given as ASCII tryte strings. The vectors are recomputed by the tests of
+//! this module, any divergence means a breaking change of the Spongos permutation, the
+//! duplex construction or the encoding of fields. Reimplementations can validate against
+//! the same vectors.
+
+use std::str::FromStr;
+
+use iota_streams_core::{
+    sponge::{prp::troika::Troika, spongos::Spongos},
+    tbits::{trinary::Trit, Tbits},
+};
+
+use crate::command::{sizeof, unwrap, wrap, Absorb, Commit, Mask, Squeeze};
+use crate::error::Protobuf3Error;
+use crate::types::{Mac, Size, Trytes};
+
+/// Absorb `input`, commit and squeeze `squeezed`.
+pub struct SpongosVector {
+    pub input: &'static str,
+    pub squeezed: &'static str,
+}
+
+/// Absorb `key`, commit and encrypt `plaintext` into `ciphertext`.
+pub struct EncryptVector {
+    pub key: &'static str,
+    pub plaintext: &'static str,
+    pub ciphertext: &'static str,
+}
+
+/// Message `absorb tryte payload[]; mask size_t n; commit; squeeze tryte mac[27];` wrapped into `wrapped`.
+pub struct MessageVector {
+    pub payload: &'static str,
+    pub n: usize,
+    pub wrapped: &'static str,
+}
+
+pub const SPONGOS_VECTORS: &[SpongosVector] = &[
+    SpongosVector {
+        input: "A",
+        squeezed: "ZSSPTIFYDFAMVGQVZJSWNLURGKY",
+    },
+    SpongosVector {
+        input: "THEQUICKBROWNFOXJUMPSOVERTHELAZYDOG9THEQUICKBROWNFOXJUMPSOVERTHELAZYDOG9THEQUICKBROWNFOXJUMPSOVERTHELAZYDOG9THEQUICKBROWNFOXJUMPSOVERTHELAZYDOG9THEQUICKBROWNFOXJUMPSOVERTHELAZYDOG9THEQUICKBROWNFOXJUMP",
+        squeezed: "PPSSRTPRVFLXROJDUVBYGZXSMVCLAUTLVOHOOQHFJMXU9UKXVOFYCDOANCCBDWMGRMRAUJIQISGHWAEEK",
+    },
+];
+
+pub const ENCRYPT_VECTORS: &[EncryptVector] = &[EncryptVector {
+    key: "SPONGOSKEY",
+    plaintext: "PLAINTEXTOFTWOHUNDREDTRYTES9PLAINTEXTOFTWOHUNDREDTRYTES9PLAINTEXTOFTWOHUNDREDTRYTES9PLAINTEXTOFTWOHUNDREDTRYTES9PLAINTEXTOFTWOHUNDREDTRYTES9PLAINTEXTOFTWOHUNDREDTRYTES9PLAINTEXTOFTWOHUNDREDTRYTES9PLAI",
+    ciphertext: "UBTQBUH9TRBHBOPYMZKUGAKENYDAZMPYFPSOPYNPENCENTFLIPODVLFPGHIOHD9PTWKBLODL9GMWOMCHEXJXSQN9RQXFZENMRX9CSUYUQOPZRJNEARJGSZKSPJGGWTSSE9QANDCWUBSWRNFERUJUPMQUERWLFAZLYKYLIWIOJDRZWLQGHVQMXWYIGXFTUK9ZJHINXYWQ",
+}];
+
+pub const MESSAGE_VECTORS: &[MessageVector] = &[MessageVector {
+    payload: "PAYLOAD",
+    n: 1234,
+    wrapped: "AGPAYLOADCSSBHJISRGWDZVGUPMCNUSOTTHXZUWH",
+}];
+
+fn trytes(s: &str) -> Result<Tbits<Trit>, Protobuf3Error> {
+    Tbits::from_str(s).map_err(|()| Protobuf3Error::Other(format!("Bad tryte string: `{}`.", s)))
+}
+
+/// Recompute squeezed trytes of `v`.
+pub fn spongos_squeeze(v: &SpongosVector) -> Result<String, Protobuf3Error> {
+    let mut s = Spongos::<Trit, Troika>::init();
+    s.absorb_tbits(&trytes(v.input)?);
+    s.commit();
+    Ok(s.squeeze_tbits(3 * v.squeezed.len()).to_string())
+}
+
+/// Recompute ciphertext of `v` and check it decrypts back to the plaintext.
+pub fn spongos_encrypt(v: &EncryptVector) -> Result<String, Protobuf3Error> {
+    let key = trytes(v.key)?;
+    let mut s = Spongos::<Trit, Troika>::init();
+    s.absorb_tbits(&key);
+    s.commit();
+    let ciphertext = s.encrypt_tbits(&trytes(v.plaintext)?);
+
+    let mut s = Spongos::<Trit, Troika>::init();
+    s.absorb_tbits(&key);
+    s.commit();
+    let plaintext = s.decrypt_tbits(&ciphertext).to_string();
+    ensure!(
+        plaintext == v.plaintext,
+        "Decrypted `{}` instead of `{}`.",
+        plaintext,
+        v.plaintext
+    );
+    Ok(ciphertext.to_string())
+}
+
+/// Recompute wrapped message of `v` and check it unwraps back to the fields.
+pub fn wrap_message(v: &MessageVector) -> Result<String, Protobuf3Error> {
+    let payload = Trytes(trytes(v.payload)?);
+    let n = Size(v.n);
+    let mac = Mac(81);
+
+    let buf_size = sizeof::Context::<Trit, Troika>::new()
+        .absorb(&payload)?
+        .mask(&n)?
+        .commit()?
+        .squeeze(&mac)?
+        .get_size();
+    let mut buf = Tbits::<Trit>::zero(buf_size);
+    wrap::Context::<Trit, Troika, _>::new(buf.slice_mut())
+        .absorb(&payload)?
+        .mask(&n)?
+        .commit()?
+        .squeeze(&mac)?;
+
+    let mut payload2 = Trytes::default();
+    let mut n2 = Size(0);
+    unwrap::Context::<Trit, Troika, _>::new(buf.slice())
+        .absorb(&mut payload2)?
+        .mask(&mut n2)?
+        .commit()?
+        .squeeze(&mac)?;
+    ensure!(
+        payload2 == payload && n2 == n,
+        "Unwrapped fields differ from the original."
+    );
+    Ok(buf.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spongos_vectors() {
+        for v in SPONGOS_VECTORS {
+            assert_eq!(
+                v.squeezed,
+                spongos_squeeze(v).unwrap(),
+                "input `{}`",
+                v.input
+            );
+        }
+    }
+
+    #[test]
+    fn encrypt_vectors() {
+        for v in ENCRYPT_VECTORS {
+            assert_eq!(v.ciphertext, spongos_encrypt(v).unwrap(), "key `{}`", v.key);
+        }
+    }
+
+    #[test]
+    fn message_vectors() {
+        for v in MESSAGE_VECTORS {
+            assert_eq!(
+                v.wrapped,
+                wrap_message(v).unwrap(),
+                "payload `{}`",
+                v.payload
+            );
+        }
+    }
+}
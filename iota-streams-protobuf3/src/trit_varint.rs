@@ -0,0 +1,106 @@
+//! Variable-length encoding of unsigned integers, the same as used by `size_t` fields.
+//!
+//! A value `n` is encoded as a trint3 `d = size_trytes(n)` followed by `d` trint3s
+//! of `n` in balanced little-endian representation. The most significant trint3 must be
+//! positive, so the encoding of each value is unique. The largest encodable value is
+//! `SIZE_MAX`, it takes 13 trytes.
+//!
+//! These functions allow custom commands to read and write `size_t`-compatible length
+//! prefixes directly in trit slices.
+
+use iota_streams_core::tbits::{
+    trinary::{self, Trint3, TritWord},
+    TbitSlice, TbitSliceMut,
+};
+
+use crate::error::Protobuf3Error;
+use crate::types::{size_trytes, sizeof_sizet, SIZE_MAX};
+
+/// Size of encoding of `value` in trits.
+///
+/// Panics if `value` exceeds `SIZE_MAX`.
+pub fn sizeof(value: u64) -> usize {
+    assert!(value <= SIZE_MAX as u64);
+    sizeof_sizet(value as usize)
+}
+
+/// Encode `value` into the front of `out` and advance `out` past the encoding.
+/// Returns the number of trits written.
+///
+/// Panics if `value` exceeds `SIZE_MAX` or `out` is too short.
+pub fn encode<TW>(value: u64, out: &mut TbitSliceMut<TW>) -> usize
+where
+    TW: TritWord,
+{
+    let n = sizeof(value);
+    assert!(
+        out.size() >= n,
+        "Output is too short for `size_t` encoding."
+    );
+
+    let d = size_trytes(value as usize);
+    out.advance(3).put3(Trint3(d as i8));
+    let mut m = value;
+    for _ in 0..d {
+        let (r, q) = trinary::mods3_u64(m);
+        out.advance(3).put3(r);
+        m = q;
+    }
+    n
+}
+
+/// Decode a value from the front of `x`.
+/// Returns the value and the number of trits consumed.
+///
+/// Fails with `Protobuf3Error::TruncatedInput` if `x` ends before the encoding does,
+/// with `Protobuf3Error::NonCanonicalSizeT` if the encoding is padded with zero trits and
+/// with `Protobuf3Error::OutOfRange` if the encoding is otherwise invalid.
+pub fn decode<TW>(x: &TbitSlice<TW>) -> Result<(u64, usize), Protobuf3Error>
+where
+    TW: TritWord,
+{
+    let mut x = *x;
+    let available = x.size();
+    let truncated = |requested| Protobuf3Error::TruncatedInput {
+        requested,
+        available,
+    };
+
+    ensure!(3 <= available, truncated(3));
+    let d = x.advance(3).get3();
+    ensure!(
+        Trint3(0) <= d && d <= Trint3(13),
+        Protobuf3Error::OutOfRange(format!("Invalid size of `size_t`: {}.", d))
+    );
+    let n = 3 * (d.0 as usize + 1);
+    ensure!(n <= available, truncated(n));
+
+    let mut m: i64 = 0;
+    let mut r: i64 = 1;
+    let mut t = Trint3(0);
+    for i in 0..d.0 {
+        t = x.advance(3).get3();
+        if 0 < i {
+            r *= 27;
+        }
+        m += r * t.0 as i64;
+    }
+
+    ensure!(
+        d.0 == 0 || t != Trint3(0),
+        Protobuf3Error::NonCanonicalSizeT
+    );
+    ensure!(
+        d.0 == 0 || Trint3(0) < t,
+        Protobuf3Error::OutOfRange(format!(
+            "The last most significant trint3 of `size_t` can't be negative: {}.",
+            t
+        ))
+    );
+    ensure!(
+        m as usize <= SIZE_MAX,
+        Protobuf3Error::OutOfRange(format!("`size_t` value is overflown: {}.", m))
+    );
+
+    Ok((m as u64, n))
+}
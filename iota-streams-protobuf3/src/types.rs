@@ -1,5 +1,7 @@
-use failure::{bail, Fallible};
-use std::convert::{AsMut, AsRef};
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::convert::{AsMut, AsRef, TryFrom};
 use std::fmt;
 use std::hash;
 use std::string::ToString;
@@ -7,11 +9,13 @@ use std::string::ToString;
 use iota_streams_core::{
     sponge::{prp::PRP, spongos::Spongos},
     tbits::{
-        word::{BasicTbitWord, SpongosTbitWord, StringTbitWord},
+        word::{BasicTbitWord, PackedTbitWord, SpongosTbitWord, StringTbitWord},
         Tbits,
     },
 };
 
+use crate::command::{sizeof, unwrap, wrap};
+use crate::error::Protobuf3Error;
 use crate::io;
 
 /// PB3 integer type `tryte` is signed and is represented with `Trint3`, not `Tryte` which is unsigned.
@@ -44,6 +48,25 @@ where
 }
 impl<TW> Eq for NTrytes<TW> where TW: BasicTbitWord {}
 
+/// NTrytes are ordered as the inner tbits, see `Ord for Tbits`.
+impl<TW> PartialOrd for NTrytes<TW>
+where
+    TW: PackedTbitWord,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<TW> Ord for NTrytes<TW>
+where
+    TW: PackedTbitWord,
+{
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
 impl<TW> NTrytes<TW>
 where
     TW: BasicTbitWord,
@@ -80,6 +103,87 @@ where
     }
 }
 
+/// Fixed-size array of `N` trytes, eg. `NTryte<TW, 81>` for hashes and keys.
+/// Unlike `NTrytes` the size is a part of the type, the inner buffer has `N * 3` trits
+/// which is checked by `new`, so the buffer is not public.
+#[derive(Clone)]
+pub struct NTryte<TW, const N: usize>(pub(crate) Tbits<TW>);
+
+impl<TW, const N: usize> NTryte<TW, N> {
+    /// Size of the array in trits.
+    pub const SIZE: usize = N * 3;
+
+    /// Inner buffer of `N * 3` trits.
+    pub fn tbits(&self) -> &Tbits<TW> {
+        &self.0
+    }
+}
+
+impl<TW, const N: usize> NTryte<TW, N>
+where
+    TW: BasicTbitWord,
+{
+    pub fn zero() -> Self {
+        Self(Tbits::<TW>::zero(Self::SIZE))
+    }
+
+    /// Wrap `tbits`, fails unless there are exactly `N * 3` trits.
+    pub fn new(tbits: Tbits<TW>) -> Result<Self, Protobuf3Error> {
+        ensure!(
+            tbits.size() == Self::SIZE,
+            "Trit size of `ntryte` must be {}: {}.",
+            Self::SIZE,
+            tbits.size()
+        );
+        Ok(Self(tbits))
+    }
+}
+
+impl<TW, const N: usize> TryFrom<Tbits<TW>> for NTryte<TW, N>
+where
+    TW: BasicTbitWord,
+{
+    type Error = Protobuf3Error;
+    fn try_from(tbits: Tbits<TW>) -> Result<Self, Protobuf3Error> {
+        Self::new(tbits)
+    }
+}
+
+impl<TW, const N: usize> From<NTryte<TW, N>> for Tbits<TW> {
+    fn from(ntryte: NTryte<TW, N>) -> Self {
+        ntryte.0
+    }
+}
+
+impl<TW, const N: usize> Default for NTryte<TW, N>
+where
+    TW: BasicTbitWord,
+{
+    fn default() -> Self {
+        Self::zero()
+    }
+}
+
+impl<TW, const N: usize> fmt::Debug for NTryte<TW, N>
+where
+    TW: BasicTbitWord,
+    TW::Tbit: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl<TW, const N: usize> PartialEq for NTryte<TW, N>
+where
+    TW: BasicTbitWord,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+impl<TW, const N: usize> Eq for NTryte<TW, N> where TW: BasicTbitWord {}
+
 /// Variable-size array of trytes, the size is not known at compile time and is encoded in trinary representation.
 /// The inner buffer size (in trits) must be multiple of 3.
 //TODO: PartialEq, Eq, Clone, Debug
@@ -132,6 +236,139 @@ where
     }
 }
 
+/// Values that can be wiped in place so that the writes are not optimized away,
+/// eg. plaintext unmasked by `SealedMask` with a bad tag.
+pub trait Wipe {
+    fn wipe(&mut self);
+}
+
+impl<TW> Wipe for NTrytes<TW>
+where
+    TW: SpongosTbitWord,
+{
+    fn wipe(&mut self) {
+        self.0.wipe();
+    }
+}
+
+impl<TW, const N: usize> Wipe for NTryte<TW, N>
+where
+    TW: SpongosTbitWord,
+{
+    fn wipe(&mut self) {
+        self.0.wipe();
+    }
+}
+
+impl<TW> Wipe for Trytes<TW>
+where
+    TW: SpongosTbitWord,
+{
+    fn wipe(&mut self) {
+        self.0.wipe();
+    }
+}
+
+impl Wipe for String {
+    fn wipe(&mut self) {
+        // Zero bytes are valid UTF-8.
+        for b in unsafe { self.as_bytes_mut() } {
+            unsafe { std::ptr::write_volatile(b, 0) };
+        }
+    }
+}
+
+/// Fraction in range `[0, 1]` encoded as fixed-point value `round(ratio * RATIO_SCALE)` with 3 trytes.
+/// Wrap operation clamps the value into `[0, 1]`, unwrap operation rejects encoded values out of range.
+#[derive(PartialEq, PartialOrd, Copy, Clone, Debug, Default)]
+pub struct Ratio(pub f64);
+
+/// Fixed-point scale of `Ratio`: `(27^3 - 1) / 2`.
+pub const RATIO_SCALE: i32 = 9841;
+
+/// Size of encoded `Ratio` in trits.
+pub const SIZEOF_RATIO: usize = 9;
+
+impl Ratio {
+    /// Fixed-point representation of the value clamped into `[0, 1]`, NaN is treated as 0.
+    pub fn to_fixed(self) -> i32 {
+        (self.0.max(0.0).min(1.0) * RATIO_SCALE as f64).round() as i32
+    }
+
+    /// Value of the fixed-point representation `r`.
+    pub fn from_fixed(r: i32) -> Self {
+        Self(r as f64 / RATIO_SCALE as f64)
+    }
+}
+
+/// Type-length-value record, the length is the size of `value` in trytes.
+#[derive(Clone)]
+pub struct Tlv<TW> {
+    pub tlv_type: Size,
+    pub value: Trytes<TW>,
+}
+
+impl<TW> PartialEq for Tlv<TW>
+where
+    TW: BasicTbitWord,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.tlv_type == other.tlv_type && self.value == other.value
+    }
+}
+impl<TW> Eq for Tlv<TW> where TW: BasicTbitWord {}
+
+/// Default limit of the TLV record value size in trytes, see `TlvStream::with_max_value_size`.
+pub const TLV_MAX_VALUE_SIZE: usize = 2187;
+
+/// Extensible sequence of TLV records, encoded as the number of records followed
+/// by `type`, `length` and `value` of each record.
+///
+/// Unwrap operation keeps records of `known` types only. Records of unknown types
+/// are still absorbed (the length tells how many trytes to consume) and only counted in `skipped`.
+/// The number of records is limited by `max_records`, the length of each record
+/// is limited by `max_value_size` trytes and is checked before the value is allocated.
+#[derive(Clone)]
+pub struct TlvStream<TW> {
+    pub records: Vec<Tlv<TW>>,
+    pub known: Vec<Size>,
+    pub max_records: usize,
+    pub max_value_size: usize,
+    pub skipped: usize,
+}
+
+impl<TW> TlvStream<TW> {
+    /// TLV stream to be wrapped.
+    pub fn new(records: Vec<Tlv<TW>>) -> Self {
+        let max_records = records.len();
+        Self {
+            records,
+            known: Vec::new(),
+            max_records,
+            max_value_size: usize::MAX,
+            skipped: 0,
+        }
+    }
+
+    /// Empty TLV stream to be unwrapped, it accepts at most `max_records` records
+    /// of at most `TLV_MAX_VALUE_SIZE` trytes and keeps records of `known` types.
+    pub fn with_known(known: Vec<Size>, max_records: usize) -> Self {
+        Self {
+            records: Vec::new(),
+            known,
+            max_records,
+            max_value_size: TLV_MAX_VALUE_SIZE,
+            skipped: 0,
+        }
+    }
+
+    /// Accept records with values of at most `max_value_size` trytes.
+    pub fn with_max_value_size(mut self, max_value_size: usize) -> Self {
+        self.max_value_size = max_value_size;
+        self
+    }
+}
+
 /// The value wrapped in Mac is just the size of message authentication tag (MAC) in trits.
 /// The actual trits are not important. The requested amount of trits is squeezed
 /// from Spongos and encoded in the trinary stream during Wrap operation.
@@ -145,6 +382,68 @@ pub struct Mac(pub usize);
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub struct MssHashSig;
 
+/// Ed25519 public key, encoded as `byte [32]`.
+#[cfg(feature = "ed25519-dalek")]
+pub type Ed25519PublicKey = ed25519_dalek::PublicKey;
+
+/// Ed25519 signature, encoded as `byte [64]`.
+#[cfg(feature = "ed25519-dalek")]
+pub type Ed25519Signature = ed25519_dalek::Signature;
+
+/// Ed25519 signing key pair.
+#[cfg(feature = "ed25519-dalek")]
+pub type Ed25519Keypair = ed25519_dalek::Keypair;
+
+/// Size of encoded Ed25519 public key in trits.
+#[cfg(feature = "ed25519-dalek")]
+pub const SIZEOF_ED25519_PUBLIC_KEY: usize = 3 * BYTE_TRYTES * ed25519_dalek::PUBLIC_KEY_LENGTH;
+
+/// Size of encoded Ed25519 signature in trits.
+#[cfg(feature = "ed25519-dalek")]
+pub const SIZEOF_ED25519_SIGNATURE: usize = 3 * BYTE_TRYTES * ed25519_dalek::SIGNATURE_LENGTH;
+
+/// Number of bytes squeezed from Spongos and signed by `Ed25519Sig` command.
+#[cfg(feature = "ed25519-dalek")]
+pub const ED25519_HASH_BYTES: usize = 32;
+
+/// Ed25519sig command modifier, it instructs Context to squeeze `ED25519_HASH_BYTES`
+/// hash bytes, commit spongos state, sign the hash and encode (without absorbing!)
+/// the signature, as `MssHashSig` does for `Mssig`.
+#[cfg(feature = "ed25519-dalek")]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Ed25519HashSig;
+
+/// Value returned by `repeated_resumable` handler after processing an item.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum RepeatedStep {
+    /// Continue with the next item.
+    Continue,
+    /// Stop after the current item and return `ResumeToken`.
+    Pause,
+}
+
+/// State of interrupted `repeated_resumable`: Spongos state after the last processed item
+/// and the number of processed and remaining items. Passing the token to a later call
+/// of `repeated_resumable` with the same items continues exactly where it stopped.
+#[derive(Clone)]
+pub struct ResumeToken<TW, F> {
+    pub(crate) spongos: Spongos<TW, F>,
+    pub(crate) processed: usize,
+    pub(crate) remaining: usize,
+}
+
+impl<TW, F> ResumeToken<TW, F> {
+    /// Number of items processed before pause.
+    pub fn processed(&self) -> usize {
+        self.processed
+    }
+
+    /// Number of items left to process.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+}
+
 /*
 impl Default for Mac {
     fn default() -> Self {
@@ -187,6 +486,131 @@ pub fn sizeof_sizet(n: usize) -> usize {
     3 * (size_trytes(n) + 1)
 }
 
+/// Number of trytes in fixed-width encoding of `u64` values: `(27^14 - 1) / 2 > u64::MAX`.
+pub const U64_TRYTES: usize = 14;
+
+/// Size of fixed-width encoding of `u64` values in trits.
+pub const SIZEOF_U64: usize = 3 * U64_TRYTES;
+
+/// Size of the message identifier returned by `wrap::Context::publish` in trits.
+pub const MSG_ID_SIZE: usize = 81;
+
+/// Number of trytes encoding a single byte of a byte string.
+///
+/// The byte value is encoded as an unsigned integer with balanced trytes, the least
+/// significant tryte first: 2 trytes hold values up to `(27^2 - 1) / 2 = 364 > 255`.
+/// This is the PB3 byte string encoding, it's not related to `spongos::BYTE_TBITS`
+/// which is the number of tbits per byte in `Spongos::absorb_bytes` for any tbit word.
+pub const BYTE_TRYTES: usize = 2;
+
+/// Size of encoded byte string of `n` bytes in trits: `size_t` length followed by the bytes.
+pub fn sizeof_bytes(n: usize) -> usize {
+    sizeof_sizet(n) + 3 * BYTE_TRYTES * n
+}
+
+/// Domain label `SCHEMA` absorbed before schema identifier, see `bind_schema`.
+pub const SCHEMA_LABEL: [Trint3; 6] = [
+    Trint3(-8),
+    Trint3(3),
+    Trint3(8),
+    Trint3(5),
+    Trint3(13),
+    Trint3(1),
+];
+
+/// Time interval from `start_ms` to `end_ms` (in milliseconds).
+/// Both bounds are encoded as fixed-width `u64` fields, so the encoded size does not depend on the value.
+/// Unwrap operation rejects intervals with `end_ms < start_ms`.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub struct Interval {
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+/// Size of encoded `Interval` in trits.
+pub const SIZEOF_INTERVAL: usize = 2 * SIZEOF_U64;
+
+/// Size of encoded `Ipv4Addr` in trits, the address is encoded as `byte [4]`.
+#[cfg(feature = "net")]
+pub const SIZEOF_IPV4_ADDR: usize = 3 * BYTE_TRYTES * 4;
+
+/// Size of encoded `Ipv6Addr` in trits, the address is encoded as `byte [16]`.
+#[cfg(feature = "net")]
+pub const SIZEOF_IPV6_ADDR: usize = 3 * BYTE_TRYTES * 16;
+
+/// Size of encoded port of `SocketAddr` in trits, the port is encoded as big-endian `byte [2]`.
+#[cfg(feature = "net")]
+pub const SIZEOF_PORT: usize = 3 * BYTE_TRYTES * 2;
+
+/// Size of encoded flow info and scope id of `SocketAddrV6` in trits,
+/// each is encoded as big-endian `byte [4]`.
+#[cfg(feature = "net")]
+pub const SIZEOF_IPV6_FLOW_SCOPE: usize = 3 * BYTE_TRYTES * 2 * 4;
+
+/// Size of encoded `SocketAddr` in trits: `tryte` IP version `4` or `6`,
+/// the IP address, the port and for IPv6 the flow info and scope id.
+#[cfg(feature = "net")]
+pub fn sizeof_socket_addr(addr: &std::net::SocketAddr) -> usize {
+    let ip = match addr {
+        std::net::SocketAddr::V4(_) => SIZEOF_IPV4_ADDR,
+        std::net::SocketAddr::V6(_) => SIZEOF_IPV6_ADDR + SIZEOF_IPV6_FLOW_SCOPE,
+    };
+    3 + ip + SIZEOF_PORT
+}
+
+/// Size of the presence flag of `Option` fields in trits.
+/// The flag is followed by the inner field when present: `1` -- `Some`, `0` -- `None`.
+pub const SIZEOF_FLAG: usize = 1;
+
+/// Variant of a tolerant `oneof` field, see `unwrap::Context::oneof_tolerant`.
+#[derive(Clone)]
+pub enum Oneof<TW> {
+    /// Variant tag is known and its body has been unwrapped.
+    Known(Size),
+    /// Variant tag is unknown (eg. introduced by a later protocol version),
+    /// its body is skipped and kept as raw trits.
+    Unknown(Size, Tbits<TW>),
+}
+
+impl<TW> Oneof<TW> {
+    /// Tag of a known variant, `Protobuf3Error::UnknownOneof` if the variant has been skipped.
+    pub fn known(&self) -> Result<Size, Protobuf3Error> {
+        match self {
+            Oneof::Known(tag) => Ok(*tag),
+            Oneof::Unknown(tag, _) => Err(Protobuf3Error::UnknownOneof(tag.0)),
+        }
+    }
+}
+
+impl<TW> fmt::Debug for Oneof<TW>
+where
+    TW: BasicTbitWord,
+    TW::Tbit: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Oneof::Known(tag) => write!(f, "Known({:?})", tag),
+            Oneof::Unknown(tag, body) => write!(f, "Unknown({:?}, {:?})", tag, body),
+        }
+    }
+}
+
+impl<TW> PartialEq for Oneof<TW>
+where
+    TW: BasicTbitWord,
+{
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Oneof::Known(tag), Oneof::Known(tag2)) => tag == tag2,
+            (Oneof::Unknown(tag, body), Oneof::Unknown(tag2, body2)) => {
+                tag == tag2 && body == body2
+            }
+            _ => false,
+        }
+    }
+}
+impl<TW> Eq for Oneof<TW> where TW: BasicTbitWord {}
+
 /// PB3 `external` modifier, it changes behaviour of commands in the following way.
 /// The external field is not encoded in trinary representation and the value is stored in the environment implicitly.
 #[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
@@ -202,8 +626,8 @@ pub trait LinkStore<TW, F, Link> {
     type Info;
 
     /// Lookup link in the store and return spongos state and associated info.
-    fn lookup(&self, _link: &Link) -> Fallible<(Spongos<TW, F>, Self::Info)> {
-        bail!("Link not found.");
+    fn lookup(&self, _link: &Link) -> Result<(Spongos<TW, F>, Self::Info), Protobuf3Error> {
+        bail!(Protobuf3Error::UnknownLink);
     }
 
     /// Put link into the store together with spongos state and associated info.
@@ -215,12 +639,72 @@ pub trait LinkStore<TW, F, Link> {
     /// Overwriting the spongos state means "forgetting the old and accepting the new".
     ///
     /// Not updating the spongos state means immutability -- "the first one makes the history".
-    fn update(&mut self, link: &Link, spongos: Spongos<TW, F>, info: Self::Info) -> Fallible<()>;
+    fn update(
+        &mut self,
+        link: &Link,
+        spongos: Spongos<TW, F>,
+        info: Self::Info,
+    ) -> Result<(), Protobuf3Error>;
 
     /// Remove link and associated info from the store.
     fn erase(&mut self, _link: &Link) {}
 }
 
+/// Commitment to a linked Spongos state checked by `JoinVerified`: `n` trits squeezed
+/// from a fork of `spongos`, `n` must be at least `Spongos::MAC_SIZE`. It is usually calculated when the linked message is unwrapped
+/// and kept apart from the link store, eg. in the application state.
+///
+/// Link stores may keep only the inner part of the state (see `Spongos::to_inner`),
+/// so the commitment is calculated from the inner part, the outer state is reset.
+pub fn link_commitment<TW, F>(spongos: &Spongos<TW, F>, n: usize) -> NTrytes<TW>
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW> + Clone,
+{
+    NTrytes(commitment_spongos(spongos).squeeze_tbits(n))
+}
+
+/// Compare the commitment to `spongos` with `expected` in constant time.
+/// Commitments shorter than `Spongos::MAC_SIZE` (eg. empty ones) are rejected as they
+/// can be easily forged.
+pub(crate) fn check_link_commitment<TW, F>(
+    spongos: &Spongos<TW, F>,
+    expected: &NTrytes<TW>,
+) -> Result<(), Protobuf3Error>
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW> + Clone,
+{
+    ensure!(
+        Spongos::<TW, F>::MAC_SIZE <= (expected.0).size(),
+        Protobuf3Error::OutOfRange(format!(
+            "Link commitment of {} trits is shorter than {}.",
+            (expected.0).size(),
+            Spongos::<TW, F>::MAC_SIZE
+        ))
+    );
+    ensure!(
+        commitment_spongos(spongos).squeeze_eq((expected.0).slice()),
+        Protobuf3Error::BadLinkCommitment
+    );
+    Ok(())
+}
+
+/// Spongos the link commitment is squeezed from. The outer state of a restored inner
+/// state is zero, a single tbit is absorbed to transform the inner state before squeeze.
+fn commitment_spongos<TW, F>(spongos: &Spongos<TW, F>) -> Spongos<TW, F>
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW> + Clone,
+{
+    let mut s = spongos.fork();
+    s.commit();
+    let mut s = Spongos::<TW, F>::from_inner(s.to_inner());
+    s.absorb_tbits(&Tbits::zero(1));
+    s.commit();
+    s
+}
+
 /// Empty "dummy" link store that stores no links.
 #[derive(Copy, Clone, Debug)]
 pub struct EmptyLinkStore<TW, F, Link, Info>(std::marker::PhantomData<(TW, F, Link, Info)>);
@@ -238,7 +722,7 @@ impl<TW, F, Link, Info> LinkStore<TW, F, Link> for EmptyLinkStore<TW, F, Link, I
         _link: &Link,
         _spongos: Spongos<TW, F>,
         _info: Self::Info,
-    ) -> Fallible<()> {
+    ) -> Result<(), Protobuf3Error> {
         Ok(())
     }
 }
@@ -270,17 +754,22 @@ where
     Info: Clone,
 {
     type Info = Info;
-    fn lookup(&self, link: &Link) -> Fallible<(Spongos<TW, F>, Self::Info)> {
+    fn lookup(&self, link: &Link) -> Result<(Spongos<TW, F>, Self::Info), Protobuf3Error> {
         if self.link == *link {
             Ok((
                 Spongos::<TW, F>::from_inner(self.spongos.clone()),
                 self.info.clone(),
             ))
         } else {
-            bail!("Link not found.");
+            bail!(Protobuf3Error::UnknownLink);
         }
     }
-    fn update(&mut self, link: &Link, spongos: Spongos<TW, F>, info: Self::Info) -> Fallible<()> {
+    fn update(
+        &mut self,
+        link: &Link,
+        spongos: Spongos<TW, F>,
+        info: Self::Info,
+    ) -> Result<(), Protobuf3Error> {
         let inner = spongos.to_inner();
         self.link = link.clone();
         self.spongos = inner;
@@ -292,8 +781,6 @@ where
     }
 }
 
-use std::collections::HashMap;
-
 pub struct DefaultLinkStore<TW, F, Link, Info>
 where
     F: PRP<TW>,
@@ -324,16 +811,21 @@ where
     type Info = Info;
 
     /// Add info for the link.
-    fn lookup(&self, link: &Link) -> Fallible<(Spongos<TW, F>, Info)> {
+    fn lookup(&self, link: &Link) -> Result<(Spongos<TW, F>, Info), Protobuf3Error> {
         if let Some((inner, info)) = self.map.get(link).cloned() {
             Ok((Spongos::from_inner(inner), info))
         } else {
-            bail!("Link not found")
+            Err(Protobuf3Error::UnknownLink)
         }
     }
 
     /// Try to retrieve info for the link.
-    fn update(&mut self, link: &Link, spongos: Spongos<TW, F>, info: Info) -> Fallible<()> {
+    fn update(
+        &mut self,
+        link: &Link,
+        spongos: Spongos<TW, F>,
+        info: Info,
+    ) -> Result<(), Protobuf3Error> {
         let inner = spongos.to_inner();
         self.map.insert(link.clone(), (inner, info));
         Ok(())
@@ -345,7 +837,227 @@ where
     }
 }
 
-use crate::command::{sizeof, unwrap, wrap};
+/// Link store holding at most `capacity` links, the least recently used link
+/// (looked up or updated) is evicted when a new link is put into the full store.
+///
+/// Up to `capacity` last evicted links are remembered and their lookup fails with
+/// `Protobuf3Error::LinkExpired` instead of `Protobuf3Error::UnknownLink`.
+pub struct LruLinkStore<TW, F, Link, Info>
+where
+    F: PRP<TW>,
+{
+    capacity: usize,
+    /// Entries with their last use time.
+    map: HashMap<Link, (F::Inner, Info, Cell<u64>)>,
+    /// Links ordered by their last use time.
+    order: RefCell<BTreeMap<u64, Link>>,
+    /// Use time counter.
+    clock: Cell<u64>,
+    evicted: HashSet<Link>,
+    evicted_order: VecDeque<Link>,
+}
+
+impl<TW, F, Link, Info> LruLinkStore<TW, F, Link, Info>
+where
+    F: PRP<TW>,
+    Link: Eq + hash::Hash + Clone,
+{
+    /// Create store holding at most `capacity` links, `capacity` must be positive.
+    pub fn new(capacity: usize) -> Self {
+        assert!(0 < capacity, "LruLinkStore capacity must be positive.");
+        Self {
+            capacity,
+            map: HashMap::new(),
+            order: RefCell::new(BTreeMap::new()),
+            clock: Cell::new(0),
+            evicted: HashSet::new(),
+            evicted_order: VecDeque::new(),
+        }
+    }
+
+    /// The number of links in the store.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Whether the store holds no links.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Mark the link used at `time` as used now.
+    fn touch(&self, link: &Link, time: &Cell<u64>) {
+        let now = self.clock.get() + 1;
+        self.clock.set(now);
+        let mut order = self.order.borrow_mut();
+        order.remove(&time.get());
+        order.insert(now, link.clone());
+        time.set(now);
+    }
+
+    fn forget_evicted(&mut self, link: &Link) {
+        if self.evicted.remove(link) {
+            self.evicted_order.retain(|l| l != link);
+        }
+    }
+
+    fn evict_lru(&mut self) {
+        let lru = self
+            .order
+            .borrow()
+            .iter()
+            .next()
+            .map(|(t, l)| (*t, l.clone()));
+        if let Some((t, link)) = lru {
+            self.order.borrow_mut().remove(&t);
+            self.map.remove(&link);
+            if self.evicted_order.len() == self.capacity {
+                if let Some(oldest) = self.evicted_order.pop_front() {
+                    self.evicted.remove(&oldest);
+                }
+            }
+            self.evicted.insert(link.clone());
+            self.evicted_order.push_back(link);
+        }
+    }
+}
+
+impl<TW, F, Link, Info> LinkStore<TW, F, Link> for LruLinkStore<TW, F, Link, Info>
+where
+    TW: SpongosTbitWord,
+    F: PRP<TW> + Clone,
+    F::Inner: Clone,
+    Link: Eq + hash::Hash + Clone,
+    Info: Clone,
+{
+    type Info = Info;
+
+    fn lookup(&self, link: &Link) -> Result<(Spongos<TW, F>, Info), Protobuf3Error> {
+        if let Some((inner, info, time)) = self.map.get(link) {
+            self.touch(link, time);
+            Ok((Spongos::from_inner(inner.clone()), info.clone()))
+        } else if self.evicted.contains(link) {
+            Err(Protobuf3Error::LinkExpired)
+        } else {
+            Err(Protobuf3Error::UnknownLink)
+        }
+    }
+
+    /// Put or replace link, the least recently used link is evicted if the store is full.
+    fn update(
+        &mut self,
+        link: &Link,
+        spongos: Spongos<TW, F>,
+        info: Info,
+    ) -> Result<(), Protobuf3Error> {
+        let inner = spongos.to_inner();
+        self.forget_evicted(link);
+        let time = match self.map.remove(link) {
+            Some((_, _, time)) => time,
+            None => {
+                if self.map.len() == self.capacity {
+                    self.evict_lru();
+                }
+                // Not in `order` yet, time 0 is never used.
+                Cell::new(0)
+            }
+        };
+        self.touch(link, &time);
+        self.map.insert(link.clone(), (inner, info, time));
+        Ok(())
+    }
+
+    /// Remove link, erased links are not reported as expired.
+    fn erase(&mut self, link: &Link) {
+        if let Some((_, _, time)) = self.map.remove(link) {
+            self.order.borrow_mut().remove(&time.get());
+        }
+        self.forget_evicted(link);
+    }
+}
+
+/// Link store that resolves links across several named stores, eg. one per transport.
+///
+/// Stores are looked up in the order they were added and the first one that knows
+/// the link wins. New links are put into the first (primary) store.
+pub struct MultiLinkStore<S> {
+    stores: Vec<(String, S)>,
+}
+
+impl<S> Default for MultiLinkStore<S> {
+    fn default() -> Self {
+        Self { stores: Vec::new() }
+    }
+}
+
+impl<S> MultiLinkStore<S> {
+    /// Add `store` named `name` after the already added stores.
+    pub fn add(&mut self, name: &str, store: S) {
+        self.stores.push((name.to_string(), store));
+    }
+
+    /// Builder-style `add`.
+    pub fn with(mut self, name: &str, store: S) -> Self {
+        self.add(name, store);
+        self
+    }
+
+    /// Access store by name.
+    pub fn store(&self, name: &str) -> Option<&S> {
+        self.stores.iter().find(|(n, _)| n == name).map(|(_, s)| s)
+    }
+
+    /// Access store by name.
+    pub fn store_mut(&mut self, name: &str) -> Option<&mut S> {
+        self.stores
+            .iter_mut()
+            .find(|(n, _)| n == name)
+            .map(|(_, s)| s)
+    }
+}
+
+impl<TW, F, Link, S> LinkStore<TW, F, Link> for MultiLinkStore<S>
+where
+    S: LinkStore<TW, F, Link>,
+{
+    type Info = S::Info;
+
+    /// Try each store in turn, `Protobuf3Error::UnknownLinkIn` lists the stores tried.
+    /// Only "not found" errors move on to the next store, other errors (eg. a failed backend
+    /// or an expired link) are returned as is.
+    fn lookup(&self, link: &Link) -> Result<(Spongos<TW, F>, Self::Info), Protobuf3Error> {
+        for (_, store) in self.stores.iter() {
+            match store.lookup(link) {
+                Ok(r) => return Ok(r),
+                Err(Protobuf3Error::UnknownLink) | Err(Protobuf3Error::UnknownLinkIn { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Err(Protobuf3Error::UnknownLinkIn {
+            tried: self.stores.iter().map(|(n, _)| n.clone()).collect(),
+        })
+    }
+
+    /// Put link into the primary store.
+    fn update(
+        &mut self,
+        link: &Link,
+        spongos: Spongos<TW, F>,
+        info: Self::Info,
+    ) -> Result<(), Protobuf3Error> {
+        match self.stores.first_mut() {
+            Some((_, store)) => store.update(link, spongos, info),
+            None => bail!("MultiLinkStore is empty."),
+        }
+    }
+
+    /// Remove link from all the stores.
+    fn erase(&mut self, link: &Link) {
+        for (_, store) in self.stores.iter_mut() {
+            store.erase(link);
+        }
+    }
+}
 
 pub struct Fallback<T>(pub T);
 
@@ -387,14 +1099,27 @@ impl<T> AsMut<T> for Fallback<T> {
     }
 }
 
+/// Item of `wrap::Context::repeated_fork_par`, each item is wrapped in its own fork
+/// of the Spongos state, independently of the other items.
+pub trait ForkedItem<TW, F> {
+    fn sizeof_forked(&self, ctx: &mut sizeof::Context<TW, F>) -> Result<(), Protobuf3Error>;
+    fn wrap_forked<OS: io::OStream<TW>>(
+        &self,
+        ctx: &mut wrap::Context<TW, F, OS>,
+    ) -> Result<(), Protobuf3Error>;
+}
+
 /// Trait allows for custom (non-standard Protobuf3) types to be Absorb.
 pub trait AbsorbFallback<TW, F> {
-    fn sizeof_absorb(&self, ctx: &mut sizeof::Context<TW, F>) -> Fallible<()>;
-    fn wrap_absorb<OS: io::OStream<TW>>(&self, ctx: &mut wrap::Context<TW, F, OS>) -> Fallible<()>;
+    fn sizeof_absorb(&self, ctx: &mut sizeof::Context<TW, F>) -> Result<(), Protobuf3Error>;
+    fn wrap_absorb<OS: io::OStream<TW>>(
+        &self,
+        ctx: &mut wrap::Context<TW, F, OS>,
+    ) -> Result<(), Protobuf3Error>;
     fn unwrap_absorb<IS: io::IStream<TW>>(
         &mut self,
         ctx: &mut unwrap::Context<TW, F, IS>,
-    ) -> Fallible<()>;
+    ) -> Result<(), Protobuf3Error>;
 }
 
 /// Trait allows for custom (non-standard Protobuf3) types to be AbsorbExternal.
@@ -403,15 +1128,18 @@ pub trait AbsorbFallback<TW, F> {
 ///
 /// Note, that "absolute" links are absorbed in the message header.
 pub trait AbsorbExternalFallback<TW, F> {
-    fn sizeof_absorb_external(&self, ctx: &mut sizeof::Context<TW, F>) -> Fallible<()>;
+    fn sizeof_absorb_external(
+        &self,
+        ctx: &mut sizeof::Context<TW, F>,
+    ) -> Result<(), Protobuf3Error>;
     fn wrap_absorb_external<OS: io::OStream<TW>>(
         &self,
         ctx: &mut wrap::Context<TW, F, OS>,
-    ) -> Fallible<()>;
+    ) -> Result<(), Protobuf3Error>;
     fn unwrap_absorb_external<IS: io::IStream<TW>>(
         &self,
         ctx: &mut unwrap::Context<TW, F, IS>,
-    ) -> Fallible<()>;
+    ) -> Result<(), Protobuf3Error>;
 }
 
 /// Trait allows for custom (non-standard Protobuf3) types to be Absorb.
@@ -420,10 +1148,13 @@ pub trait AbsorbExternalFallback<TW, F> {
 ///
 /// Note, that "relative" links are usually skipped and joined in the message content.
 pub trait SkipFallback<TW, F> {
-    fn sizeof_skip(&self, ctx: &mut sizeof::Context<TW, F>) -> Fallible<()>;
-    fn wrap_skip<OS: io::OStream<TW>>(&self, ctx: &mut wrap::Context<TW, F, OS>) -> Fallible<()>;
+    fn sizeof_skip(&self, ctx: &mut sizeof::Context<TW, F>) -> Result<(), Protobuf3Error>;
+    fn wrap_skip<OS: io::OStream<TW>>(
+        &self,
+        ctx: &mut wrap::Context<TW, F, OS>,
+    ) -> Result<(), Protobuf3Error>;
     fn unwrap_skip<IS: io::IStream<TW>>(
         &mut self,
         ctx: &mut unwrap::Context<TW, F, IS>,
-    ) -> Fallible<()>;
+    ) -> Result<(), Protobuf3Error>;
 }